@@ -0,0 +1,69 @@
+// Answers selection requests from the native messaging host. Keep the message
+// format in sync with src/browser.rs.
+
+const HOST = 'com.get_selected_text.host';
+const RECONNECT_DELAY_MS = 5000;
+
+// Runs inside every frame of the active tab, cross-origin iframes included.
+function readSelection() {
+    // Characters of a field's value kept on each side of the selection, as
+    // the crate's default context size in src/context.rs.
+    const CONTEXT_CHARS = 150;
+    // Autofill tokens of fields whose value is never handed over.
+    const SECRET_AUTOCOMPLETE = /^(cc-.*|current-password|new-password|one-time-code)$/;
+
+    const active = document.activeElement;
+    // Inputs and textareas keep their selection out of document.getSelection().
+    if (active && (active.tagName === 'TEXTAREA' || active.tagName === 'INPUT') &&
+        typeof active.selectionStart === 'number' && active.selectionStart !== active.selectionEnd) {
+        const autocomplete = (active.getAttribute('autocomplete') || '').toLowerCase().split(/\s+/);
+        if (active.type === 'password' || autocomplete.some(token => SECRET_AUTOCOMPLETE.test(token)))
+            return null;
+        const {value, selectionStart: start, selectionEnd: end} = active;
+        return {
+            text: value.substring(start, end),
+            context: value.substring(Math.max(0, start - CONTEXT_CHARS), end + CONTEXT_CHARS),
+            frameUrl: location.href,
+        };
+    }
+
+    const selection = document.getSelection();
+    const text = selection ? selection.toString() : '';
+    if (!text)
+        return null;
+
+    let block = selection.getRangeAt(0).commonAncestorContainer;
+    if (block.nodeType !== Node.ELEMENT_NODE)
+        block = block.parentElement;
+    while (block && block.parentElement && getComputedStyle(block).display.startsWith('inline'))
+        block = block.parentElement;
+    return {text, context: block ? block.innerText : null, frameUrl: location.href};
+}
+
+async function activeSelection() {
+    const [tab] = await chrome.tabs.query({active: true, lastFocusedWindow: true});
+    if (!tab)
+        return {text: ''};
+    const results = await chrome.scripting.executeScript({
+        target: {tabId: tab.id, allFrames: true},
+        func: readSelection,
+    });
+    const hit = results.map(frame => frame.result).find(result => result && result.text);
+    return {text: '', ...hit, url: tab.url};
+}
+
+function connect() {
+    const port = chrome.runtime.connectNative(HOST);
+    port.onMessage.addListener(async message => {
+        if (message.type !== 'getSelection')
+            return;
+        try {
+            port.postMessage({id: message.id, ...await activeSelection()});
+        } catch (error) {
+            port.postMessage({id: message.id, error: String(error)});
+        }
+    });
+    port.onDisconnect.addListener(() => setTimeout(connect, RECONNECT_DELAY_MS));
+}
+
+connect();
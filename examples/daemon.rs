@@ -0,0 +1,6 @@
+//! The binary started by the units in `dbus/`. Install it as
+//! /usr/bin/get-selected-text-daemon, or adjust their paths.
+
+fn main() -> Result<(), get_selected_text::GetTextError> {
+    get_selected_text::run_daemon()
+}
@@ -0,0 +1,6 @@
+//! The native messaging host for the extension in `browser-extension/`.
+//! Build it, then point the `path` of the host manifest at the binary.
+
+fn main() -> std::io::Result<()> {
+    get_selected_text::run_native_host()
+}
@@ -0,0 +1,16 @@
+//! Prints the frontmost app's accessibility tree as a test fixture, after a
+//! three second delay to switch to the app and select some text:
+//!
+//! cargo run --example record_ax_tree --features unstable-raw > tests/fixtures/ax/my_app.json
+
+#[cfg(target_os = "macos")]
+fn main() -> Result<(), get_selected_text::GetTextError> {
+    std::thread::sleep(std::time::Duration::from_secs(3));
+    println!("{}", get_selected_text::raw::record_focused_app_tree()?);
+    Ok(())
+}
+
+#[cfg(not(target_os = "macos"))]
+fn main() {
+    eprintln!("Recording accessibility trees is only supported on macOS.");
+}
@@ -0,0 +1,6 @@
+//! Serves captures on ws://127.0.0.1:7878; the protocol and the token clients
+//! must present are described in the README.
+
+fn main() -> std::io::Result<()> {
+    get_selected_text::serve(7878, get_selected_text::ServerOptions::default())
+}
@@ -0,0 +1,52 @@
+// Exposes the focused window and the PRIMARY selection on gnome-shell's own
+// session bus connection (bus name org.gnome.Shell) for the get-selected-text
+// crate. Keep the interface in sync with src/gnome.rs.
+
+import Gio from 'gi://Gio';
+import GLib from 'gi://GLib';
+import St from 'gi://St';
+import {Extension} from 'resource:///org/gnome/shell/extensions/extension.js';
+
+const OBJECT_PATH = '/org/gnome/Shell/Extensions/GetSelectedText';
+
+const INTERFACE = `
+<node>
+  <interface name="org.gnome.Shell.Extensions.GetSelectedText">
+    <method name="FocusedWindow">
+      <arg type="s" direction="out" name="app_id"/>
+      <arg type="u" direction="out" name="pid"/>
+      <arg type="s" direction="out" name="window_id"/>
+    </method>
+    <method name="PrimarySelection">
+      <arg type="s" direction="out" name="text"/>
+    </method>
+  </interface>
+</node>`;
+
+class Service {
+    FocusedWindow() {
+        const window = global.display.get_focus_window();
+        if (!window)
+            return ['', 0, ''];
+        const appId = window.get_gtk_application_id() ?? window.get_wm_class() ?? '';
+        return [appId, window.get_pid(), String(window.get_id())];
+    }
+
+    PrimarySelectionAsync(_params, invocation) {
+        St.Clipboard.get_default().get_text(St.ClipboardType.PRIMARY, (_clipboard, text) => {
+            invocation.return_value(new GLib.Variant('(s)', [text ?? '']));
+        });
+    }
+}
+
+export default class GetSelectedTextExtension extends Extension {
+    enable() {
+        this._exported = Gio.DBusExportedObject.wrapJSObject(INTERFACE, new Service());
+        this._exported.export(Gio.DBus.session, OBJECT_PATH);
+    }
+
+    disable() {
+        this._exported.unexport();
+        this._exported = null;
+    }
+}
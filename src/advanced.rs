@@ -0,0 +1,49 @@
+//! The context machinery on its own, for apps that already know the selected
+//! text (from their own hooks, a browser extension or an earlier capture)
+//! and only need the text around it.
+//!
+//! These skip the strategy chain, the context memo and the app quirks that
+//! decide between them; [`crate::get_selected_text_with_context`] is the
+//! right call otherwise. Both return `Ok(None)` when no context was found.
+
+use crate::{GetTextError, GetTextOptions};
+
+/// Context for `selected_text` from UI Automation: the TextPattern or value
+/// of the focused element or its nearest ancestor that has one, cut to
+/// `options.context_size`. Reads the UI only; nothing is typed or copied.
+///
+/// # Errors
+///
+/// Returns `GetTextError::Uia` if UI Automation can't be initialised or
+/// nothing has the focus.
+#[cfg(target_os = "windows")]
+pub fn get_context_via_uia(selected_text: &str, options: &GetTextOptions) -> Result<Option<String>, GetTextError> {
+    if selected_text.is_empty() {
+        return Ok(None);
+    }
+    let size = options.context_size.as_ref().unwrap_or(&crate::context::DEFAULT_SIZE);
+    crate::windows::get_context_via_uia(selected_text, size)
+}
+
+/// Context for `selected_text` from simulating Select All + Copy in the
+/// focused app, cut to `options.context_size`. The clipboard is put back, and
+/// so is the selection when `options.restore_selection` is set and AX/UIA
+/// can save it.
+///
+/// On Windows, editable controls are skipped unless
+/// `options.allow_select_all_in_editable` is set, as are documents longer
+/// than `options.max_document_chars`.
+///
+/// # Errors
+///
+/// Returns `GetTextError::NotInContext` if `selected_text` isn't in the copied
+/// text, `GetTextError::Timeout` if the sequence took longer than
+/// `options.select_all_timeout`, and `GetTextError::FocusChanged` if the
+/// focus moved during it.
+pub fn get_context_via_select_all(
+    selected_text: &str,
+    options: &GetTextOptions,
+) -> Result<Option<String>, GetTextError> {
+    crate::utils::with_enigo(|enigo| crate::utils::get_context_via_select_all(enigo, selected_text, options))?
+        .map_err(GetTextError::from)
+}
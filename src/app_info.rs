@@ -0,0 +1,356 @@
+//! The executable, version and icon of the app a selection came from
+//! (`app-info` feature), for popups that show where the text was taken.
+
+use std::num::NonZeroUsize;
+use std::path::PathBuf;
+
+use lru::LruCache;
+use parking_lot::Mutex;
+
+const MAX_CACHED_APPS: usize = 16;
+
+/// Details about the focused app, as reported in [`crate::Capture`] and
+/// [`crate::SelectionResult`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct AppDetails {
+    pub executable: Option<PathBuf>,
+    /// The version the app advertises to users, e.g. "126.0.6478.127": the
+    /// product version on Windows, `CFBundleShortVersionString` on macOS.
+    /// Always `None` on Linux, where executables carry no version.
+    pub version: Option<String>,
+    pub icon: Option<AppIcon>,
+}
+
+/// An app's icon.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum AppIcon {
+    /// Non-premultiplied RGBA pixels, row by row from the top (Windows).
+    Rgba { width: u32, height: u32, pixels: Vec<u8> },
+    /// An image file: the bundle's `.icns` on macOS, the `.png` or `.svg`
+    /// named by the app's desktop entry on Linux.
+    Path(PathBuf),
+}
+
+static CACHE: Mutex<Option<LruCache<u64, AppDetails>>> = Mutex::new(None);
+
+/// The details of the process `process_id`, cached per process since
+/// extracting an icon takes a few milliseconds.
+pub(crate) fn app_details(process_id: u64) -> Option<AppDetails> {
+    let executable = crate::framework::executable_path(process_id);
+    let mut cache = CACHE.lock();
+    let cache = cache.get_or_insert_with(|| LruCache::new(NonZeroUsize::new(MAX_CACHED_APPS).unwrap()));
+    if let Some(details) = cache.get(&process_id) {
+        if details.executable == executable {
+            return Some(details.clone());
+        }
+    }
+    let details = platform::details(process_id, executable)?;
+    cache.put(process_id, details.clone());
+    Some(details)
+}
+
+#[cfg(target_os = "windows")]
+mod platform {
+    use std::ffi::c_void;
+    use std::os::windows::ffi::OsStrExt;
+    use std::path::{Path, PathBuf};
+    use std::ptr;
+
+    use windows_sys::Win32::Graphics::Gdi::{
+        DeleteObject, GetDC, GetDIBits, GetObjectW, ReleaseDC, BITMAP, BITMAPINFO, BITMAPINFOHEADER, BI_RGB,
+        DIB_RGB_COLORS,
+    };
+    use windows_sys::Win32::Storage::FileSystem::{
+        GetFileVersionInfoSizeW, GetFileVersionInfoW, VerQueryValueW, VS_FIXEDFILEINFO,
+    };
+    use windows_sys::Win32::UI::Shell::ExtractIconExW;
+    use windows_sys::Win32::UI::WindowsAndMessaging::{DestroyIcon, GetIconInfo, HICON, ICONINFO};
+
+    use super::{AppDetails, AppIcon};
+
+    fn wide(path: &Path) -> Vec<u16> {
+        path.as_os_str().encode_wide().chain(Some(0)).collect()
+    }
+
+    fn version(path: &[u16]) -> Option<String> {
+        let mut handle = 0;
+        let size = unsafe { GetFileVersionInfoSizeW(path.as_ptr(), &mut handle) };
+        if size == 0 {
+            return None;
+        }
+        let mut data = vec![0u8; size as usize];
+        if unsafe { GetFileVersionInfoW(path.as_ptr(), 0, size, data.as_mut_ptr().cast()) } == 0 {
+            return None;
+        }
+        let root: Vec<u16> = "\\".encode_utf16().chain(Some(0)).collect();
+        let mut info: *mut c_void = ptr::null_mut();
+        let mut len = 0;
+        if unsafe { VerQueryValueW(data.as_ptr().cast(), root.as_ptr(), &mut info, &mut len) } == 0
+            || info.is_null()
+            || (len as usize) < std::mem::size_of::<VS_FIXEDFILEINFO>()
+        {
+            return None;
+        }
+        let info = unsafe { &*(info as *const VS_FIXEDFILEINFO) };
+        Some(format!(
+            "{}.{}.{}.{}",
+            info.dwProductVersionMS >> 16,
+            info.dwProductVersionMS & 0xffff,
+            info.dwProductVersionLS >> 16,
+            info.dwProductVersionLS & 0xffff
+        ))
+    }
+
+    /// The pixels of `icon`'s color bitmap.
+    unsafe fn rgba(icon: HICON) -> Option<AppIcon> {
+        let mut info: ICONINFO = std::mem::zeroed();
+        if GetIconInfo(icon, &mut info) == 0 {
+            return None;
+        }
+        let pixels = (|| {
+            if info.hbmColor == 0 {
+                return None;
+            }
+            let mut bitmap: BITMAP = std::mem::zeroed();
+            let size = std::mem::size_of::<BITMAP>() as i32;
+            if GetObjectW(info.hbmColor, size, &mut bitmap as *mut BITMAP as *mut c_void) == 0 {
+                return None;
+            }
+            let (width, height) = (bitmap.bmWidth, bitmap.bmHeight);
+            let mut header: BITMAPINFO = std::mem::zeroed();
+            header.bmiHeader = BITMAPINFOHEADER {
+                biSize: std::mem::size_of::<BITMAPINFOHEADER>() as u32,
+                biWidth: width,
+                // Negative for rows from the top.
+                biHeight: -height,
+                biPlanes: 1,
+                biBitCount: 32,
+                biCompression: BI_RGB,
+                ..std::mem::zeroed()
+            };
+            let mut pixels = vec![0u8; (width * height * 4) as usize];
+            let dc = GetDC(0);
+            let lines = GetDIBits(
+                dc,
+                info.hbmColor,
+                0,
+                height as u32,
+                pixels.as_mut_ptr().cast(),
+                &mut header,
+                DIB_RGB_COLORS,
+            );
+            ReleaseDC(0, dc);
+            if lines == 0 {
+                return None;
+            }
+            for pixel in pixels.chunks_exact_mut(4) {
+                pixel.swap(0, 2);
+            }
+            // Icons from before alpha channels leave it all zero and mask
+            // through `hbmMask` instead.
+            if pixels.chunks_exact(4).all(|pixel| pixel[3] == 0) {
+                pixels.chunks_exact_mut(4).for_each(|pixel| pixel[3] = 255);
+            }
+            Some(AppIcon::Rgba {
+                width: width as u32,
+                height: height as u32,
+                pixels,
+            })
+        })();
+        DeleteObject(info.hbmColor);
+        DeleteObject(info.hbmMask);
+        pixels
+    }
+
+    fn icon(path: &[u16]) -> Option<AppIcon> {
+        let mut large: HICON = 0;
+        if unsafe { ExtractIconExW(path.as_ptr(), 0, &mut large, ptr::null_mut(), 1) } == 0 || large == 0 {
+            return None;
+        }
+        let icon = unsafe { rgba(large) };
+        unsafe { DestroyIcon(large) };
+        icon
+    }
+
+    pub(super) fn details(_process_id: u64, executable: Option<PathBuf>) -> Option<AppDetails> {
+        let path = wide(executable.as_deref()?);
+        Some(AppDetails {
+            version: version(&path),
+            icon: icon(&path),
+            executable,
+        })
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use std::ffi::CStr;
+    use std::os::raw::c_char;
+    use std::path::PathBuf;
+
+    use cocoa::base::{id, nil};
+    use cocoa::foundation::{NSAutoreleasePool, NSString};
+    use objc::{class, msg_send, sel, sel_impl};
+
+    use super::{AppDetails, AppIcon};
+
+    unsafe fn to_string(string: id) -> Option<String> {
+        if string == nil {
+            return None;
+        }
+        let utf8: *const c_char = msg_send![string, UTF8String];
+        (!utf8.is_null()).then(|| CStr::from_ptr(utf8).to_string_lossy().into_owned())
+    }
+
+    unsafe fn info_value(bundle: id, key: &str) -> id {
+        let key = NSString::alloc(nil).init_str(key).autorelease();
+        msg_send![bundle, objectForInfoDictionaryKey: key]
+    }
+
+    pub(super) fn details(process_id: u64, executable: Option<PathBuf>) -> Option<AppDetails> {
+        unsafe {
+            let pool = NSAutoreleasePool::new(nil);
+            let pid = process_id as i32;
+            let app: id = msg_send![class!(NSRunningApplication), runningApplicationWithProcessIdentifier: pid];
+            let bundle: id = if app == nil {
+                nil
+            } else {
+                let url: id = msg_send![app, bundleURL];
+                if url == nil {
+                    nil
+                } else {
+                    msg_send![class!(NSBundle), bundleWithURL: url]
+                }
+            };
+            let (version, icon) = if bundle == nil {
+                (None, None)
+            } else {
+                let version = to_string(info_value(bundle, "CFBundleShortVersionString"));
+                let icon_name = info_value(bundle, "CFBundleIconFile");
+                let icon_path: id = if icon_name == nil {
+                    nil
+                } else {
+                    msg_send![bundle, pathForImageResource: icon_name]
+                };
+                (version, to_string(icon_path).map(|path| AppIcon::Path(PathBuf::from(path))))
+            };
+            pool.drain();
+            if executable.is_none() && version.is_none() && icon.is_none() {
+                return None;
+            }
+            Some(AppDetails {
+                executable,
+                version,
+                icon,
+            })
+        }
+    }
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+mod platform {
+    use std::path::{Path, PathBuf};
+
+    use super::{AppDetails, AppIcon};
+
+    /// Icon theme sizes to look in, largest first.
+    const ICON_SIZES: &[&str] = &["256x256", "128x128", "96x96", "64x64", "48x48", "scalable"];
+
+    fn data_dirs() -> Vec<PathBuf> {
+        let home = std::env::var_os("XDG_DATA_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".local/share")));
+        let system = std::env::var("XDG_DATA_DIRS")
+            .ok()
+            .filter(|dirs| !dirs.is_empty())
+            .unwrap_or_else(|| "/usr/local/share:/usr/share".to_string());
+        home.into_iter().chain(system.split(':').map(PathBuf::from)).collect()
+    }
+
+    /// The `Icon` of a desktop entry whose `Exec` runs `program`, or whose
+    /// file is named after it.
+    pub(super) fn desktop_icon(entry: &str, entry_name: &str, program: &str) -> Option<String> {
+        let mut icon = None;
+        let mut runs_program = entry_name == program;
+        let mut in_main_group = false;
+        for line in entry.lines() {
+            let line = line.trim();
+            if line.starts_with('[') {
+                in_main_group = line == "[Desktop Entry]";
+                continue;
+            }
+            if !in_main_group {
+                continue;
+            }
+            if let Some(value) = line.strip_prefix("Icon=") {
+                icon = Some(value.trim().to_string());
+            } else if let Some(value) = line.strip_prefix("Exec=") {
+                let command = value.split_whitespace().find(|word| *word != "env" && !word.contains('='));
+                let command = command.unwrap_or_default();
+                runs_program |= Path::new(command.trim_matches('"')).file_name().is_some_and(|name| name == program);
+            } else if let Some(value) = line.strip_prefix("StartupWMClass=") {
+                runs_program |= value.trim().eq_ignore_ascii_case(program);
+            }
+        }
+        icon.filter(|_| runs_program)
+    }
+
+    fn resolve_icon(name: &str, dirs: &[PathBuf]) -> Option<PathBuf> {
+        if Path::new(name).is_absolute() {
+            return Some(PathBuf::from(name)).filter(|path| path.is_file());
+        }
+        dirs.iter()
+            .flat_map(|dir| {
+                ICON_SIZES
+                    .iter()
+                    .map(move |size| dir.join("icons/hicolor").join(size).join("apps"))
+                    .chain(Some(dir.join("pixmaps")))
+            })
+            .flat_map(|dir| ["png", "svg"].map(|extension| dir.join(format!("{}.{}", name, extension))))
+            .find(|path| path.is_file())
+    }
+
+    fn icon(executable: &Path) -> Option<AppIcon> {
+        let program = executable.file_name()?.to_str()?;
+        let dirs = data_dirs();
+        let name = dirs
+            .iter()
+            .filter_map(|dir| std::fs::read_dir(dir.join("applications")).ok())
+            .flatten()
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|extension| extension == "desktop"))
+            .find_map(|path| {
+                let entry = std::fs::read_to_string(&path).ok()?;
+                let entry_name = path.file_stem()?.to_str()?.to_string();
+                desktop_icon(&entry, &entry_name, program)
+            })?;
+        resolve_icon(&name, &dirs).map(AppIcon::Path)
+    }
+
+    pub(super) fn details(_process_id: u64, executable: Option<PathBuf>) -> Option<AppDetails> {
+        let executable = executable?;
+        Some(AppDetails {
+            icon: icon(&executable),
+            version: None,
+            executable: Some(executable),
+        })
+    }
+}
+
+#[cfg(all(test, not(any(target_os = "windows", target_os = "macos"))))]
+mod tests {
+    use super::platform::desktop_icon;
+
+    #[test]
+    fn finds_the_icon_of_the_entry_that_runs_the_program() {
+        let entry = "[Desktop Entry]\nName=Firefox\nExec=env MOZ_ENABLE_WAYLAND=1 /usr/lib/firefox/firefox %u\n\
+                     Icon=firefox\n\n[Desktop Action new-window]\nExec=firefox --new-window\nIcon=other\n";
+        assert_eq!(desktop_icon(entry, "org.mozilla.firefox", "firefox").as_deref(), Some("firefox"));
+        assert_eq!(desktop_icon(entry, "org.mozilla.firefox", "code"), None);
+        let named = "[Desktop Entry]\nExec=/opt/launcher\nIcon=/opt/code/code.png\n";
+        assert_eq!(desktop_icon(named, "code", "code").as_deref(), Some("/opt/code/code.png"));
+    }
+}
@@ -0,0 +1,154 @@
+//! Apps that need special handling, and the kind of each app, recognized by
+//! the focused app's name.
+
+/// IntelliJ-platform IDEs. Their accessibility tree comes from the JDK's
+/// bridge, which they only switch on with "Support screen readers"; without
+/// it AX and UIA see a bare window, and walking it costs seconds.
+const JETBRAINS: [&str; 16] = [
+    "intellij", "idea64", "pycharm", "webstorm", "phpstorm", "rubymine", "clion", "goland", "rustrover",
+    "rider", "datagrip", "dataspell", "appcode", "aqua", "android studio", "studio64",
+];
+
+pub(crate) const JETBRAINS_HINT: &str = "Turn on Settings > Appearance & Behavior > Appearance > \
+     Support screen readers in the IDE, then restart it.";
+
+pub(crate) fn is_jetbrains(app_name: &str) -> bool {
+    let app_name = app_name.to_lowercase();
+    JETBRAINS.iter().any(|name| app_name.contains(name))
+}
+
+/// VS Code and its forks. Monaco keeps only the selection in the hidden
+/// textarea that accessibility APIs see, unless `editor.accessibilitySupport`
+/// is on. Compared whole, since "code" alone is a common substring.
+pub(crate) const VSCODE: [&str; 6] = ["code", "code - insiders", "visual studio code", "vscodium", "cursor", "windsurf"];
+
+pub(crate) const VSCODE_HINT: &str = "Set \"editor.accessibilitySupport\" to \"on\" in the editor's settings, \
+     or run \"Toggle Screen Reader Accessibility Mode\" from the command palette.";
+
+/// Adobe Acrobat and Reader. Their UIA tree exposes the text per page, not
+/// on the focused element.
+pub(crate) const ACROBAT: [&str; 6] = [
+    "acrobat", "acrord32", "adobe acrobat", "adobe acrobat reader", "adobe acrobat reader dc", "adobe acrobat dc",
+];
+
+/// LibreOffice. Its documents reach accessibility clients only through
+/// IAccessible2 (Windows) and AT-SPI (Linux), as one object per paragraph or
+/// cell.
+pub(crate) const LIBREOFFICE: [&str; 8] = [
+    "soffice", "soffice.bin", "libreoffice", "libreoffice writer", "libreoffice calc", "libreoffice impress",
+    "libreoffice-writer", "libreoffice-calc",
+];
+
+pub(crate) fn is_libreoffice(app_name: &str) -> bool {
+    let app_name = app_name.to_lowercase();
+    let app_name = app_name.strip_suffix(".exe").unwrap_or(&app_name);
+    LIBREOFFICE.contains(&app_name)
+}
+
+pub(crate) fn is_acrobat(app_name: &str) -> bool {
+    let app_name = app_name.to_lowercase();
+    let app_name = app_name.strip_suffix(".exe").unwrap_or(&app_name);
+    ACROBAT.contains(&app_name)
+}
+
+pub(crate) fn is_vscode(app_name: &str) -> bool {
+    let app_name = app_name.to_lowercase();
+    let app_name = app_name.strip_suffix(".exe").unwrap_or(&app_name);
+    VSCODE.contains(&app_name)
+}
+
+/// What kind of app a selection comes from, so that callers can adjust to it
+/// (format code, skip translating terminal output) without keeping their own
+/// app lists.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum SourceKind {
+    Browser,
+    CodeEditor,
+    Terminal,
+    PdfViewer,
+    /// Word processors, spreadsheets, presentations and note-taking apps.
+    OfficeDoc,
+    Chat,
+    #[default]
+    Other,
+}
+
+// Each kind lists names compared whole, then distinctive parts of names.
+const CODE_EDITORS: (&[&str], &[&str]) = (
+    &["zed", "nova", "atom", "fleet", "kate", "gedit", "notepad"],
+    &["xcode", "sublime", "bbedit", "textmate", "notepad++", "neovide", "nvim", "vim", "emacs", "visual studio"],
+);
+
+const TERMINALS: (&[&str], &[&str]) = (
+    &["foot", "hyper", "warp", "cmd", "st", "windowsterminal"],
+    &[
+        "terminal", "iterm", "alacritty", "kitty", "wezterm", "ghostty", "konsole", "xterm", "tilix", "terminator",
+        "ptyxis", "conhost", "powershell", "tabby",
+    ],
+);
+
+const BROWSERS: (&[&str], &[&str]) = (
+    &["arc", "zen", "orion", "edge", "msedge", "opera"],
+    &["safari", "chrome", "chromium", "firefox", "microsoft edge", "brave", "vivaldi", "librewolf", "waterfox"],
+);
+
+const PDF_VIEWERS: (&[&str], &[&str]) = (
+    &["preview", "skim", "evince", "okular", "zathura", "papers"],
+    &["pdf", "sumatra", "foxit"],
+);
+
+const OFFICE_DOCS: (&[&str], &[&str]) = (
+    &["word", "winword", "excel", "powerpnt", "pages", "numbers", "keynote", "soffice", "notes", "onenote"],
+    &["microsoft word", "microsoft excel", "powerpoint", "libreoffice", "wps", "obsidian", "notion", "bear"],
+);
+
+const CHAT_CLIENTS: (&[&str], &[&str]) = (
+    &["qq", "line", "signal", "element", "messages", "teams", "ms-teams", "lark", "skype"],
+    &[
+        "wechat", "weixin", "微信", "slack", "discord", "telegram", "whatsapp", "microsoft teams", "feishu", "飞书",
+        "dingtalk", "钉钉",
+    ],
+);
+
+/// Classifies an app by the name the OS reports for it.
+pub(crate) fn source_kind(app_name: &str) -> SourceKind {
+    let app_name = app_name.to_lowercase();
+    let app_name = app_name.strip_suffix(".exe").unwrap_or(&app_name);
+    let is = |(whole, parts): (&[&str], &[&str])| {
+        whole.contains(&app_name) || parts.iter().any(|part| app_name.contains(part))
+    };
+    // Editors first: VS Code and its forks are browsers underneath, and
+    // terminals run editors.
+    if is_vscode(app_name) || is_jetbrains(app_name) || is(CODE_EDITORS) {
+        SourceKind::CodeEditor
+    } else if is(TERMINALS) {
+        SourceKind::Terminal
+    } else if is_acrobat(app_name) || is(PDF_VIEWERS) {
+        SourceKind::PdfViewer
+    } else if is(BROWSERS) {
+        SourceKind::Browser
+    } else if is(OFFICE_DOCS) {
+        SourceKind::OfficeDoc
+    } else if is(CHAT_CLIENTS) {
+        SourceKind::Chat
+    } else {
+        SourceKind::Other
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifies_by_app_name() {
+        assert_eq!(source_kind("Code.exe"), SourceKind::CodeEditor);
+        assert_eq!(source_kind("Windows Terminal"), SourceKind::Terminal);
+        assert_eq!(source_kind("Google Chrome"), SourceKind::Browser);
+        assert_eq!(source_kind("AcroRd32.exe"), SourceKind::PdfViewer);
+        assert_eq!(source_kind("WINWORD.EXE"), SourceKind::OfficeDoc);
+        assert_eq!(source_kind("1Password"), SourceKind::Other);
+        assert_eq!(source_kind("WeChat"), SourceKind::Chat);
+    }
+}
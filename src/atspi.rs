@@ -0,0 +1,301 @@
+//! AT-SPI, the accessibility bus of Linux desktops (`atspi` feature), which
+//! plays the part of AX and UI Automation: GTK, Qt, Firefox, Chromium and
+//! LibreOffice report the selection of the focused text object and its
+//! offsets there. Apps register their accessible objects on a bus of their
+//! own, whose address the session bus hands out; the crate looks up the
+//! focused app's objects there by process ID.
+//!
+//! The bus connection, the apps' bus names and the focused object found
+//! last are kept between calls, so a capture that reads the selection and
+//! then its context only searches for the focus once.
+
+use std::collections::HashMap;
+
+use parking_lot::Mutex;
+use zbus::blocking::{connection, Connection};
+use zbus::zvariant::{DynamicDeserialize, OwnedObjectPath, OwnedValue};
+
+use crate::GetTextError;
+
+const REGISTRY: &str = "org.a11y.atspi.Registry";
+const ROOT_PATH: &str = "/org/a11y/atspi/accessible/root";
+const ACCESSIBLE: &str = "org.a11y.atspi.Accessible";
+const COLLECTION: &str = "org.a11y.atspi.Collection";
+const TEXT: &str = "org.a11y.atspi.Text";
+
+/// `ATSPI_STATE_FOCUSED`, a bit in the first word of a state set.
+const STATE_FOCUSED: i32 = 12;
+/// `ATSPI_STATE_SHOWING`. Hidden subtrees can't hold the focus.
+const STATE_SHOWING: i32 = 25;
+/// Objects visited looking for the focus in apps without the Collection
+/// interface, before giving up.
+const MAX_WALKED_OBJECTS: usize = 2_000;
+/// `ATSPI_Collection_MATCH_ALL`; an empty criterion matches everything.
+const MATCH_ALL: i32 = 1;
+/// `ATSPI_Collection_SORT_ORDER_CANONICAL`.
+const SORT_CANONICAL: u32 = 1;
+
+/// States, attributes, roles and interfaces, each with its match type, and
+/// whether to invert the rule.
+type MatchRule = (Vec<i32>, i32, HashMap<String, String>, i32, Vec<i32>, i32, Vec<String>, i32, bool);
+
+fn dbus_error(error: impl std::fmt::Display) -> GetTextError {
+    GetTextError::Os(format!("AT-SPI: {}", error))
+}
+
+fn reply<R>(reply: zbus::Result<zbus::Message>) -> Result<R, GetTextError>
+where
+    R: for<'d> DynamicDeserialize<'d>,
+{
+    reply.map_err(dbus_error)?.body().deserialize::<R>().map_err(dbus_error)
+}
+
+static BUS: Mutex<Option<Connection>> = Mutex::new(None);
+/// The bus names of the registered apps, by process ID.
+static APPS: Mutex<Option<HashMap<u64, String>>> = Mutex::new(None);
+/// The focused text object found last, with its app's process ID.
+static LAST_FOCUSED: Mutex<Option<(u64, Text)>> = Mutex::new(None);
+
+fn connect() -> Result<Connection, GetTextError> {
+    let session = Connection::session().map_err(dbus_error)?;
+    let address: String =
+        reply(session.call_method(Some("org.a11y.Bus"), "/org/a11y/bus", Some("org.a11y.Bus"), "GetAddress", &()))?;
+    connection::Builder::address(address.as_str())
+        .map_err(dbus_error)?
+        .build()
+        .map_err(dbus_error)
+}
+
+/// The a11y bus, connected on first use.
+fn bus() -> Result<Connection, GetTextError> {
+    let mut bus = BUS.lock();
+    if let Some(connection) = bus.as_ref() {
+        return Ok(connection.clone());
+    }
+    let connection = connect()?;
+    *bus = Some(connection.clone());
+    Ok(connection)
+}
+
+/// Drops everything kept about the bus, after a call on it failed: the bus
+/// may have restarted.
+fn forget_bus() {
+    BUS.lock().take();
+    APPS.lock().take();
+    LAST_FOCUSED.lock().take();
+}
+
+fn process_of(bus: &Connection, name: &str) -> Option<u64> {
+    let pid = reply::<u32>(bus.call_method(
+        Some("org.freedesktop.DBus"),
+        "/org/freedesktop/DBus",
+        Some("org.freedesktop.DBus"),
+        "GetConnectionUnixProcessID",
+        &(name,),
+    ));
+    pid.ok().map(u64::from)
+}
+
+/// The bus name under which the app with `process_id` registered its
+/// objects. The registered apps are only listed again when the known name
+/// is gone or the app is new.
+fn application(bus: &Connection, process_id: u64) -> Result<Option<String>, GetTextError> {
+    let known = APPS.lock().as_ref().and_then(|apps| apps.get(&process_id).cloned());
+    if let Some(name) = known.filter(|name| process_of(bus, name) == Some(process_id)) {
+        return Ok(Some(name));
+    }
+    let apps: Vec<(String, OwnedObjectPath)> =
+        reply(bus.call_method(Some(REGISTRY), ROOT_PATH, Some(ACCESSIBLE), "GetChildren", &()))?;
+    let apps: HashMap<u64, String> =
+        apps.into_iter().filter_map(|(name, _)| Some((process_of(bus, &name)?, name))).collect();
+    let name = apps.get(&process_id).cloned();
+    *APPS.lock() = Some(apps);
+    Ok(name)
+}
+
+/// The Text interface of one accessible object.
+#[derive(Clone)]
+pub(crate) struct Text {
+    bus: Connection,
+    name: String,
+    path: OwnedObjectPath,
+}
+
+impl crate::libreoffice::TextObject for Text {
+    fn selection_count(&self) -> usize {
+        let count = reply::<i32>(self.bus.call_method(
+            Some(self.name.as_str()),
+            self.path.as_str(),
+            Some(TEXT),
+            "GetNSelections",
+            &(),
+        ));
+        count.map_or(0, |count| count.max(0) as usize)
+    }
+
+    fn selection(&self, index: usize) -> Option<(usize, usize)> {
+        let (start, end) = reply::<(i32, i32)>(self.bus.call_method(
+            Some(self.name.as_str()),
+            self.path.as_str(),
+            Some(TEXT),
+            "GetSelection",
+            &(index as i32,),
+        ))
+        .ok()?;
+        (start >= 0 && end >= start).then_some((start as usize, end as usize))
+    }
+
+    fn char_count(&self) -> Option<usize> {
+        let value = reply::<OwnedValue>(self.bus.call_method(
+            Some(self.name.as_str()),
+            self.path.as_str(),
+            Some("org.freedesktop.DBus.Properties"),
+            "Get",
+            &(TEXT, "CharacterCount"),
+        ))
+        .ok()?;
+        i32::try_from(value).ok().filter(|count| *count >= 0).map(|count| count as usize)
+    }
+
+    fn text(&self, start: usize, end: usize) -> Option<String> {
+        reply::<String>(self.bus.call_method(
+            Some(self.name.as_str()),
+            self.path.as_str(),
+            Some(TEXT),
+            "GetText",
+            &(start as i32, end as i32),
+        ))
+        .ok()
+    }
+}
+
+/// The Text interface of the focused object of the app with `process_id`.
+/// `None` if accessibility is off, the app isn't on the bus or nothing
+/// focused there has text.
+pub(crate) fn focused_text(process_id: u64) -> Option<Text> {
+    let last = LAST_FOCUSED.lock().clone();
+    if let Some((last_process, text)) = last {
+        if last_process == process_id && is_focused(&text.bus, &text.name, &text.path) {
+            return Some(text);
+        }
+    }
+    let bus = bus().ok()?;
+    let app = match application(&bus, process_id) {
+        Ok(app) => app?,
+        Err(_) => {
+            forget_bus();
+            return None;
+        }
+    };
+    let rule: MatchRule = (
+        vec![1 << STATE_FOCUSED, 0],
+        MATCH_ALL,
+        HashMap::new(),
+        MATCH_ALL,
+        Vec::new(),
+        MATCH_ALL,
+        Vec::new(),
+        MATCH_ALL,
+        false,
+    );
+    let matches: Result<Vec<(String, OwnedObjectPath)>, _> = reply(bus.call_method(
+        Some(app.as_str()),
+        ROOT_PATH,
+        Some(COLLECTION),
+        "GetMatches",
+        &(rule, SORT_CANONICAL, 1i32, true),
+    ));
+    let (name, path) = match matches {
+        Ok(matches) => matches.into_iter().next()?,
+        // Qt doesn't implement Collection.
+        Err(_) => walk_to_focus(&bus, &app)?,
+    };
+    let text = Text { bus, name, path };
+    *LAST_FOCUSED.lock() = Some((process_id, text.clone()));
+    Some(text)
+}
+
+fn state(bus: &Connection, name: &str, path: &OwnedObjectPath) -> Option<u32> {
+    let words: Vec<u32> = reply(bus.call_method(Some(name), path.as_str(), Some(ACCESSIBLE), "GetState", &())).ok()?;
+    words.first().copied()
+}
+
+fn is_focused(bus: &Connection, name: &str, path: &OwnedObjectPath) -> bool {
+    state(bus, name, path).is_some_and(|state| state & (1 << STATE_FOCUSED) != 0)
+}
+
+/// Finds the focused object by walking down the app's tree, skipping hidden
+/// subtrees.
+fn walk_to_focus(bus: &Connection, app: &str) -> Option<(String, OwnedObjectPath)> {
+    let mut pending = vec![(app.to_string(), OwnedObjectPath::try_from(ROOT_PATH).ok()?)];
+    let mut visited = 0;
+    while let Some((name, path)) = pending.pop() {
+        visited += 1;
+        if visited > MAX_WALKED_OBJECTS {
+            return None;
+        }
+        let Some(state) = state(bus, &name, &path) else {
+            continue;
+        };
+        if state & (1 << STATE_FOCUSED) != 0 {
+            return Some((name, path));
+        }
+        // The application root itself reports no states.
+        if path.as_str() != ROOT_PATH && state & (1 << STATE_SHOWING) == 0 {
+            continue;
+        }
+        let children: Vec<(String, OwnedObjectPath)> =
+            reply(bus.call_method(Some(name.as_str()), path.as_str(), Some(ACCESSIBLE), "GetChildren", &()))
+                .unwrap_or_default();
+        pending.extend(children.into_iter().rev());
+    }
+    None
+}
+
+/// `Strategy::Accessibility` on Linux: the selections of the focused text
+/// object, one per line.
+pub(crate) fn selected_text(process_id: Option<u64>) -> Result<String, GetTextError> {
+    use crate::libreoffice::TextObject;
+
+    let text = process_id
+        .and_then(focused_text)
+        .ok_or_else(|| GetTextError::Other("The focused object exposes no AT-SPI text".into()))?;
+    let selections: Vec<String> = (0..text.selection_count())
+        .filter_map(|index| text.selection(index))
+        .filter(|(start, end)| start < end)
+        .filter_map(|(start, end)| text.text(start, end))
+        .collect();
+    Ok(selections.join("\n"))
+}
+
+/// The context of `selected_text` in the focused text object of the app
+/// with `process_id`: cut around the object's selection when that is
+/// `selected_text`, otherwise around its first occurrence. `None` for
+/// objects longer than `max_chars`.
+pub(crate) fn context(
+    process_id: u64,
+    selected_text: &str,
+    size: &crate::ContextSize,
+    max_chars: usize,
+) -> Option<String> {
+    use crate::context::{set_confidence, ContextConfidence};
+    use crate::libreoffice::TextObject;
+
+    let text = focused_text(process_id)?;
+    let length = text.char_count()?;
+    if length > max_chars {
+        return None;
+    }
+    let full_text = text.text(0, length)?;
+    let at_selection = (text.selection_count() > 0)
+        .then(|| text.selection(0))
+        .flatten()
+        .and_then(|(start, end)| crate::context::around_range(&full_text, selected_text, start, end, size));
+    if let Some(context) = at_selection {
+        set_confidence(ContextConfidence::High);
+        return Some(context);
+    }
+    let context = crate::context::around(&full_text, selected_text, size)?;
+    set_confidence(ContextConfidence::Medium);
+    Some(context)
+}
@@ -0,0 +1,92 @@
+use std::fmt;
+use std::time::Duration;
+
+use crate::{GetTextError, Strategy};
+
+/// Why a single strategy did not produce the selection.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum FailureReason {
+    /// The strategy needs a permission the process doesn't have.
+    PermissionDenied,
+    /// The strategy ran but found no selected text.
+    Empty,
+    /// The strategy did not finish in time.
+    Timeout,
+    /// The app exposes its text to accessibility APIs only with its own
+    /// screen-reader support turned on, and it is off.
+    AccessibilityModeOff,
+    /// The strategy doesn't exist on this platform.
+    Unsupported,
+    /// Any other error, with its message.
+    Error(String),
+}
+
+impl FailureReason {
+    pub(crate) fn from_error(error: &(dyn std::error::Error + 'static)) -> Self {
+        match error.downcast_ref::<GetTextError>() {
+            Some(GetTextError::PermissionDenied(_)) => FailureReason::PermissionDenied,
+            Some(GetTextError::Timeout) => FailureReason::Timeout,
+            Some(GetTextError::NoSelection) => FailureReason::Empty,
+            Some(GetTextError::AccessibilityModeDisabled { .. }) => FailureReason::AccessibilityModeOff,
+            _ => FailureReason::Error(error.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for FailureReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FailureReason::PermissionDenied => write!(f, "permission denied"),
+            FailureReason::Empty => write!(f, "empty"),
+            FailureReason::Timeout => write!(f, "timeout"),
+            FailureReason::AccessibilityModeOff => write!(f, "the app's screen-reader support is off"),
+            FailureReason::Unsupported => write!(f, "not available on this platform"),
+            FailureReason::Error(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+/// One failed strategy attempt.
+#[derive(Debug, Clone)]
+pub struct Attempt {
+    pub strategy: Strategy,
+    pub reason: FailureReason,
+    pub elapsed: Duration,
+}
+
+/// The strategies tried during one call, in the order they ran.
+#[derive(Debug, Clone, Default)]
+pub struct AttemptLog(pub Vec<Attempt>);
+
+impl AttemptLog {
+    pub(crate) fn push(&mut self, strategy: Strategy, reason: FailureReason, elapsed: Duration) {
+        self.0.push(Attempt {
+            strategy,
+            reason,
+            elapsed,
+        });
+    }
+
+    pub fn attempts(&self) -> &[Attempt] {
+        &self.0
+    }
+}
+
+impl fmt::Display for AttemptLog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, attempt) in self.0.iter().enumerate() {
+            if i > 0 {
+                write!(f, "; ")?;
+            }
+            write!(
+                f,
+                "{:?}: {} ({} ms)",
+                attempt.strategy,
+                attempt.reason,
+                attempt.elapsed.as_millis()
+            )?;
+        }
+        Ok(())
+    }
+}
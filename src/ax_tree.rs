@@ -0,0 +1,370 @@
+//! The AX tree traversal and text/context extraction, written against the
+//! [`AxNode`] trait so they run on live `AXUIElement`s (macOS) and on trees
+//! recorded into fixture files alike.
+
+use serde_json::{json, Map, Value};
+
+/// Traversal stops this deep below the starting element.
+pub(crate) const MAX_DEPTH: usize = 6;
+/// Only this many children of each element are visited.
+pub(crate) const MAX_CHILDREN_PER_LEVEL: usize = 15;
+
+/// Longer values, in UTF-16 units, aren't read whole: apps build the string
+/// on demand, and for a log view or a long web page that takes seconds.
+pub(crate) const MAX_VALUE_CHARS: usize = 50_000;
+/// UTF-16 units read either side of the selection when the value is too long
+/// to read whole.
+const RANGED_CONTEXT_CHARS: usize = 2_000;
+
+const SELECTED_TEXT: &str = "AXSelectedText";
+const WEB_AREA: &str = "AXWebArea";
+/// Roles whose `AXValue`, when they have one, is built from every row or
+/// item they hold rather than being text a selection sits in.
+const CONTAINER_ROLES: [&str; 5] = ["AXTable", "AXOutline", "AXList", "AXBrowser", "AXGrid"];
+
+/// The attributes [`record`] keeps, which are all the traversal reads.
+const RECORDED_ATTRIBUTES: [&str; 6] = ["AXRole", "AXSubrole", "AXTitle", "AXDescription", "AXValue", SELECTED_TEXT];
+
+/// An element of an accessibility tree.
+pub(crate) trait AxNode: Sized {
+    /// The attribute's value if it is a string.
+    fn string_attribute(&self, name: &str) -> Option<String>;
+    fn child_count(&self) -> usize;
+    fn child(&self, index: usize) -> Option<Self>;
+
+    fn role(&self) -> Option<String> {
+        self.string_attribute("AXRole")
+    }
+
+    /// `AXNumberOfCharacters`, the length of `AXValue` in UTF-16 units.
+    fn char_count(&self) -> Option<usize> {
+        None
+    }
+
+    /// `AXSelectedTextRange` as a UTF-16 location and length.
+    fn selected_range(&self) -> Option<(usize, usize)> {
+        None
+    }
+
+    /// `AXStringForRange`: the UTF-16 units `location..location + length` of
+    /// `AXValue`, without building the whole value.
+    fn string_for_range(&self, _location: usize, _length: usize) -> Option<String> {
+        None
+    }
+}
+
+pub(crate) fn has_selection(node: &impl AxNode) -> bool {
+    node.string_attribute(SELECTED_TEXT).is_some_and(|text| !text.is_empty())
+}
+
+/// The first element at or below `node` with a non-empty selection, within
+/// the traversal limits.
+pub(crate) fn find_selection<N: AxNode + Clone>(node: &N, depth: usize) -> Option<N> {
+    if depth > MAX_DEPTH {
+        return None;
+    }
+    if has_selection(node) {
+        return Some(node.clone());
+    }
+    (0..node.child_count().min(MAX_CHILDREN_PER_LEVEL))
+        .filter_map(|index| node.child(index))
+        .find_map(|child| find_selection(&child, depth + 1))
+}
+
+/// The selected text of the first element at or below `node` that
+/// [`extract`] accepts, and its context.
+pub(crate) fn traverse(node: &impl AxNode, depth: usize) -> Option<(String, Option<String>)> {
+    if depth > MAX_DEPTH {
+        return None;
+    }
+    if let Some(found) = extract(node) {
+        return Some(found);
+    }
+    (0..node.child_count().min(MAX_CHILDREN_PER_LEVEL))
+        .filter_map(|index| node.child(index))
+        .find_map(|child| traverse(&child, depth + 1))
+}
+
+/// The element's selected text and context. `None` without a selection, and
+/// for web areas without an `AXValue`: their title or description isn't
+/// context, so the copy-based fallbacks should take over.
+pub(crate) fn extract(node: &impl AxNode) -> Option<(String, Option<String>)> {
+    let selected_text = node.string_attribute(SELECTED_TEXT).filter(|text| !text.is_empty())?;
+    let context = context(node, &selected_text);
+    if node.role().as_deref() == Some(WEB_AREA) && context.is_none() {
+        return None;
+    }
+    Some((selected_text, context))
+}
+
+fn context(node: &impl AxNode, selected_text: &str) -> Option<String> {
+    use crate::context::{set_confidence, ContextConfidence};
+
+    let role = node.role();
+    let value = value(node, role.as_deref());
+    if let Some((value, Some(offset))) = &value {
+        if let Some(start) = chars_in_utf16(value, *offset) {
+            crate::context::note_position(value, selected_text, start);
+        }
+    }
+    let value = value.map(|(value, _)| value);
+    if role.as_deref() == Some(WEB_AREA) {
+        let value = value.filter(|value| !value.is_empty());
+        if value.is_some() {
+            set_confidence(ContextConfidence::High);
+        }
+        return value;
+    }
+    // Anything of ten bytes or less is a label, not context.
+    if let Some(value) = value.filter(|value| value.len() > 10) {
+        set_confidence(ContextConfidence::High);
+        return Some(value);
+    }
+    let label = ["AXDescription", "AXTitle"]
+        .iter()
+        .filter_map(|attribute| node.string_attribute(attribute))
+        .find(|text| text.len() > 10);
+    if label.is_some() {
+        set_confidence(ContextConfidence::Low);
+    }
+    label
+}
+
+/// `AXValue`, or the part of it around the selection when it is longer than
+/// [`MAX_VALUE_CHARS`], with where the selection starts in it in UTF-16
+/// units when the element reports its range. `None` for container roles, and
+/// for long values the element can't read by range.
+fn value(node: &impl AxNode, role: Option<&str>) -> Option<(String, Option<usize>)> {
+    if role.is_some_and(|role| CONTAINER_ROLES.contains(&role)) {
+        return None;
+    }
+    match node.char_count() {
+        Some(count) if count > MAX_VALUE_CHARS => {
+            let (location, length) = node.selected_range()?;
+            let start = location.saturating_sub(RANGED_CONTEXT_CHARS);
+            let end = (location + length + RANGED_CONTEXT_CHARS).min(count);
+            let value = node.string_for_range(start, end.saturating_sub(start))?;
+            Some((value, Some(location - start)))
+        }
+        _ => {
+            let value = node.string_attribute("AXValue")?;
+            Some((value, node.selected_range().map(|(location, _)| location)))
+        }
+    }
+}
+
+/// The number of characters in the first `units` UTF-16 units of `text`.
+/// `None` if `text` is shorter or the offset splits a surrogate pair.
+fn chars_in_utf16(text: &str, units: usize) -> Option<usize> {
+    let mut seen = 0;
+    for (chars, c) in text.chars().enumerate() {
+        if seen == units {
+            return Some(chars);
+        }
+        seen += c.len_utf16();
+        if seen > units {
+            return None;
+        }
+    }
+    (seen == units).then(|| text.chars().count())
+}
+
+/// Records `node` and its descendants as a fixture: `{"attributes": {...},
+/// "children": [...]}`. Goes two levels and five children past the traversal
+/// limits, so fixtures also show what the limits cut off.
+#[cfg_attr(not(all(feature = "unstable-raw", target_os = "macos")), allow(dead_code))]
+pub(crate) fn record(node: &impl AxNode, depth: usize) -> Value {
+    let attributes: Map<String, Value> = RECORDED_ATTRIBUTES
+        .iter()
+        .filter_map(|name| Some((name.to_string(), Value::String(node.string_attribute(name)?))))
+        .collect();
+    let children: Vec<Value> = if depth < MAX_DEPTH + 2 {
+        (0..node.child_count().min(MAX_CHILDREN_PER_LEVEL + 5))
+            .filter_map(|index| node.child(index))
+            .map(|child| record(&child, depth + 1))
+            .collect()
+    } else {
+        Vec::new()
+    };
+    json!({ "attributes": attributes, "children": children })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    /// A node replayed from a fixture file.
+    #[derive(Debug, Clone)]
+    struct RecordedNode {
+        attributes: HashMap<String, String>,
+        children: Vec<RecordedNode>,
+    }
+
+    impl RecordedNode {
+        fn from_json(value: &Value) -> Self {
+            let attributes = value["attributes"]
+                .as_object()
+                .map(|attributes| {
+                    attributes
+                        .iter()
+                        .filter_map(|(name, value)| Some((name.clone(), value.as_str()?.to_string())))
+                        .collect()
+                })
+                .unwrap_or_default();
+            let children = value["children"]
+                .as_array()
+                .map(|children| children.iter().map(RecordedNode::from_json).collect())
+                .unwrap_or_default();
+            RecordedNode { attributes, children }
+        }
+    }
+
+    impl AxNode for RecordedNode {
+        fn string_attribute(&self, name: &str) -> Option<String> {
+            self.attributes.get(name).cloned()
+        }
+
+        fn child_count(&self) -> usize {
+            self.children.len()
+        }
+
+        fn child(&self, index: usize) -> Option<Self> {
+            self.children.get(index).cloned()
+        }
+
+        fn char_count(&self) -> Option<usize> {
+            self.attributes.get("AXNumberOfCharacters")?.parse().ok()
+        }
+
+        fn selected_range(&self) -> Option<(usize, usize)> {
+            let (location, length) = self.attributes.get("AXSelectedTextRange")?.split_once(',')?;
+            Some((location.parse().ok()?, length.parse().ok()?))
+        }
+
+        fn string_for_range(&self, location: usize, length: usize) -> Option<String> {
+            let units = self.attributes.get("AXValue")?.encode_utf16().collect::<Vec<_>>();
+            Some(String::from_utf16_lossy(units.get(location..location + length)?))
+        }
+    }
+
+    fn fixture(json: &str) -> RecordedNode {
+        RecordedNode::from_json(&serde_json::from_str(json).expect("fixture is valid JSON"))
+    }
+
+    macro_rules! load {
+        ($name:literal) => {
+            fixture(include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/ax/", $name)))
+        };
+    }
+
+    #[test]
+    fn text_area_selection_with_value_as_context() {
+        let tree = load!("text_area.json");
+        assert_eq!(
+            traverse(&tree, 0),
+            Some(("quick brown".to_string(), Some("The quick brown fox jumps over the lazy dog.".to_string())))
+        );
+    }
+
+    #[test]
+    fn short_value_falls_back_to_description() {
+        let tree = load!("short_value.json");
+        let (_, context) = traverse(&tree, 0).expect("selection found");
+        assert_eq!(context.as_deref(), Some("Search the documentation index"));
+    }
+
+    #[test]
+    fn web_area_without_value_is_left_to_the_fallbacks() {
+        let tree = load!("web_area_without_value.json");
+        assert_eq!(traverse(&tree, 0), None);
+        // The element is still found for range operations.
+        assert!(find_selection(&tree, 0).is_some());
+    }
+
+    #[test]
+    fn web_area_uses_only_its_value() {
+        let tree = load!("web_area_with_value.json");
+        assert_eq!(
+            traverse(&tree, 0),
+            Some(("selected".to_string(), Some("Some selected paragraph text".to_string())))
+        );
+    }
+
+    #[test]
+    fn selected_range_marks_the_occurrence_selected() {
+        let node = fixture(
+            &json!({ "attributes": {
+                "AXRole": "AXTextArea",
+                "AXSelectedText": "cat",
+                "AXValue": "😀 one cat, two cat",
+                "AXSelectedTextRange": "16,3",
+            }})
+            .to_string(),
+        );
+        let (found, noted) = crate::context::with_split(|| traverse(&node, 0));
+        let (_, context) = found.expect("selection found");
+        let parts = crate::context::split(&context.unwrap(), "cat", noted).unwrap();
+        assert_eq!(parts.before, "😀 one cat, two ");
+    }
+
+    #[test]
+    fn depth_limit() {
+        assert!(traverse(&load!("depth_6.json"), 0).is_some());
+        assert!(traverse(&load!("depth_7.json"), 0).is_none());
+        assert!(find_selection(&load!("depth_7.json"), 0).is_none());
+    }
+
+    #[test]
+    fn children_limit() {
+        assert!(traverse(&load!("child_14.json"), 0).is_some());
+        assert!(traverse(&load!("child_15.json"), 0).is_none());
+    }
+
+    #[test]
+    fn long_values_are_read_around_the_selection() {
+        let value = format!("{}needle{}", "a".repeat(MAX_VALUE_CHARS), "b".repeat(MAX_VALUE_CHARS));
+        let node = |attributes: serde_json::Value| fixture(&json!({ "attributes": attributes }).to_string());
+
+        let ranged = node(json!({
+            "AXRole": "AXTextArea",
+            "AXSelectedText": "needle",
+            "AXValue": value,
+            "AXNumberOfCharacters": (2 * MAX_VALUE_CHARS + 6).to_string(),
+            "AXSelectedTextRange": format!("{},6", MAX_VALUE_CHARS),
+        }));
+        let (_, context) = traverse(&ranged, 0).expect("selection found");
+        let expected = format!("{}needle{}", "a".repeat(RANGED_CONTEXT_CHARS), "b".repeat(RANGED_CONTEXT_CHARS));
+        assert_eq!(context, Some(expected));
+
+        // Without a selected range the value isn't read at all.
+        let unranged = node(json!({
+            "AXRole": "AXTextArea",
+            "AXSelectedText": "needle",
+            "AXValue": value,
+            "AXNumberOfCharacters": (2 * MAX_VALUE_CHARS + 6).to_string(),
+            "AXDescription": "Console output of the build",
+        }));
+        let (_, context) = traverse(&unranged, 0).expect("selection found");
+        assert_eq!(context.as_deref(), Some("Console output of the build"));
+    }
+
+    #[test]
+    fn container_values_are_skipped() {
+        let table = fixture(
+            r#"{"attributes": {"AXRole": "AXTable", "AXSelectedText": "row", "AXValue": "every row of the table"}}"#,
+        );
+        assert_eq!(traverse(&table, 0), Some(("row".to_string(), None)));
+    }
+
+    #[test]
+    fn record_round_trips() {
+        let json: Value = serde_json::from_str(include_str!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/fixtures/ax/text_area.json"
+        )))
+        .unwrap();
+        assert_eq!(record(&RecordedNode::from_json(&json), 0), json);
+    }
+}
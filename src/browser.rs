@@ -0,0 +1,212 @@
+//! Browser selections through the companion WebExtension in
+//! `browser-extension/` (`browser-bridge` feature).
+//!
+//! The browser starts the native messaging host, a small binary calling
+//! [`run_native_host`], and keeps it connected to the extension. The host
+//! listens on a loopback port recorded, with a random token, in a file only
+//! the user can read; captures in the app connect to it and present the
+//! token, and the host relays the request to the extension, which reads the
+//! selection from every frame of the active tab.
+
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::Duration;
+
+use parking_lot::Mutex;
+use serde_json::{json, Value};
+
+use crate::runtime_dir::{random_token, runtime_dir, token_matches, write_private_file};
+use crate::GetTextError;
+
+const PORT_FILE: &str = "get-selected-text-browser.port";
+/// How long the host waits for the extension to answer.
+const REPLY_TIMEOUT: Duration = Duration::from_secs(1);
+const CONNECT_TIMEOUT: Duration = Duration::from_millis(200);
+
+/// Browsers the extension can be installed in, matched against the focused
+/// app's name.
+const BROWSERS: [&str; 8] = [
+    "chrome", "chromium", "firefox", "msedge", "edge", "brave", "vivaldi", "opera",
+];
+
+/// A selection read by the browser extension.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct BrowserSelection {
+    pub text: String,
+    /// The text of the block element holding the selection, or the whole
+    /// value of an input or textarea.
+    pub context: Option<String>,
+    /// The URL of the tab.
+    pub url: Option<String>,
+    /// The URL of the frame holding the selection, which differs from `url`
+    /// for selections inside iframes.
+    pub frame_url: Option<String>,
+}
+
+fn port_file() -> io::Result<PathBuf> {
+    Ok(runtime_dir()?.join(PORT_FILE))
+}
+
+/// Writes `port` and `token` to the port file, readable by this user only.
+fn write_port_file(port: u16, token: &str) -> io::Result<PathBuf> {
+    write_private_file(PORT_FILE, &format!("{}\n{}\n", port, token))
+}
+
+/// The port and token the running host wrote.
+fn read_port_file() -> io::Result<(u16, String)> {
+    let contents = std::fs::read_to_string(port_file()?)?;
+    let mut lines = contents.lines();
+    let malformed = || io::Error::new(io::ErrorKind::InvalidData, "malformed port file");
+    let port = lines.next().and_then(|line| line.trim().parse().ok()).ok_or_else(malformed)?;
+    let token = lines.next().map(str::trim).filter(|token| !token.is_empty()).ok_or_else(malformed)?;
+    Ok((port, token.to_string()))
+}
+
+/// Whether `client` starts with the line `token`. Anything else, or nothing
+/// within [`CONNECT_TIMEOUT`], is refused.
+fn authenticate(client: &TcpStream, token: &str) -> bool {
+    if client.set_nonblocking(false).is_err() || client.set_read_timeout(Some(CONNECT_TIMEOUT)).is_err() {
+        return false;
+    }
+    let mut line = String::new();
+    let mut reader = BufReader::new(client.take(token.len() as u64 + 2));
+    reader.read_line(&mut line).is_ok() && token_matches(line.trim_end(), token)
+}
+
+fn read_native_message(input: &mut impl Read) -> io::Result<Option<Value>> {
+    let mut len = [0u8; 4];
+    match input.read_exact(&mut len) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    }
+    let mut message = vec![0u8; u32::from_ne_bytes(len) as usize];
+    input.read_exact(&mut message)?;
+    Ok(Some(serde_json::from_slice(&message)?))
+}
+
+fn write_native_message(output: &mut impl Write, message: &Value) -> io::Result<()> {
+    let bytes = serde_json::to_vec(message)?;
+    output.write_all(&(bytes.len() as u32).to_ne_bytes())?;
+    output.write_all(&bytes)?;
+    output.flush()
+}
+
+type Pending = Arc<Mutex<HashMap<u64, mpsc::Sender<Value>>>>;
+
+/// Runs the native messaging host until the browser disconnects.
+///
+/// Call this from the binary registered in the host manifest (see
+/// `browser-extension/com.get_selected_text.host.json`). Stdin and stdout
+/// belong to the browser while it runs, so nothing else may print to stdout.
+pub fn run_native_host() -> io::Result<()> {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))?;
+    listener.set_nonblocking(true)?;
+    let token = random_token()?;
+    let port_file = write_port_file(listener.local_addr()?.port(), &token)?;
+
+    let pending: Pending = Arc::default();
+    let closed = Arc::new(AtomicBool::new(false));
+    {
+        let pending = pending.clone();
+        let closed = closed.clone();
+        thread::spawn(move || {
+            let mut stdin = io::stdin().lock();
+            while let Ok(Some(reply)) = read_native_message(&mut stdin) {
+                let Some(id) = reply.get("id").and_then(Value::as_u64) else {
+                    continue;
+                };
+                if let Some(waiter) = pending.lock().remove(&id) {
+                    let _ = waiter.send(reply);
+                }
+            }
+            closed.store(true, Ordering::SeqCst);
+        });
+    }
+
+    let next_id = AtomicU64::new(0);
+    while !closed.load(Ordering::SeqCst) {
+        let client = match listener.accept() {
+            Ok((client, _)) => client,
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                thread::sleep(Duration::from_millis(20));
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+        if !authenticate(&client, &token) {
+            continue;
+        }
+        let id = next_id.fetch_add(1, Ordering::Relaxed);
+        let (sender, receiver) = mpsc::channel();
+        pending.lock().insert(id, sender);
+        write_native_message(&mut io::stdout().lock(), &json!({ "type": "getSelection", "id": id }))?;
+        let reply = receiver
+            .recv_timeout(REPLY_TIMEOUT)
+            .unwrap_or_else(|_| json!({ "error": "the browser extension did not answer" }));
+        pending.lock().remove(&id);
+        let _ = answer(client, &reply);
+    }
+
+    let _ = std::fs::remove_file(port_file);
+    Ok(())
+}
+
+fn answer(mut client: TcpStream, reply: &Value) -> io::Result<()> {
+    serde_json::to_writer(&mut client, reply)?;
+    client.write_all(b"\n")
+}
+
+/// Asks the browser extension for the selection in the active tab.
+///
+/// # Errors
+///
+/// `GetTextError::Other` if no host is running (the extension isn't
+/// installed, or the browser isn't open) or the extension reported an error.
+pub fn get_browser_selection() -> Result<BrowserSelection, GetTextError> {
+    let not_connected = |detail: String| GetTextError::Other(format!("browser extension not connected: {}", detail));
+    let (port, token) = read_port_file().map_err(|e| not_connected(e.to_string()))?;
+    let address = SocketAddr::from((Ipv4Addr::LOCALHOST, port));
+    let mut stream = TcpStream::connect_timeout(&address, CONNECT_TIMEOUT).map_err(|e| not_connected(e.to_string()))?;
+    stream
+        .write_all(format!("{}\n", token).as_bytes())
+        .map_err(|e| not_connected(e.to_string()))?;
+    stream
+        .set_read_timeout(Some(REPLY_TIMEOUT + CONNECT_TIMEOUT))
+        .map_err(|e| GetTextError::Os(e.to_string()))?;
+
+    let mut line = String::new();
+    BufReader::new(stream).read_line(&mut line).map_err(|e| match e.kind() {
+        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut => GetTextError::Timeout,
+        _ => GetTextError::Os(e.to_string()),
+    })?;
+    let reply: Value = serde_json::from_str(&line).map_err(|e| GetTextError::Other(e.to_string()))?;
+    if let Some(error) = reply.get("error").and_then(Value::as_str) {
+        return Err(GetTextError::Other(error.to_string()));
+    }
+
+    let field = |name: &str| reply.get(name).and_then(Value::as_str).map(str::to_string);
+    Ok(BrowserSelection {
+        text: field("text").unwrap_or_default(),
+        context: field("context"),
+        url: field("url"),
+        frame_url: field("frameUrl"),
+    })
+}
+
+/// The extension's selection, if the focused app is a browser and the
+/// extension answered with a non-empty selection.
+pub(crate) fn selection_for_focused_app() -> Option<BrowserSelection> {
+    let focus = crate::utils::focus_snapshot()?;
+    let app_name = focus.app_name().to_lowercase();
+    if !BROWSERS.iter().any(|browser| app_name.contains(browser)) {
+        return None;
+    }
+    get_browser_selection().ok().filter(|selection| !selection.text.is_empty())
+}
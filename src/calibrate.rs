@@ -0,0 +1,108 @@
+//! Clipboard timings tuned to this machine by [`calibrate`], instead of the
+//! fixed delays that suit a fast laptop and are too short on a loaded VM.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::GetTextError;
+
+const DEFAULT_SETTLE_MS: u64 = 50;
+const DEFAULT_COPY_WAIT_MS: u64 = 250;
+const CYCLES: u32 = 5;
+/// A write not visible after this long counts as this long.
+const MAX_LATENCY: Duration = Duration::from_secs(1);
+
+static SETTLE_MS: AtomicU64 = AtomicU64::new(DEFAULT_SETTLE_MS);
+static COPY_WAIT_MS: AtomicU64 = AtomicU64::new(DEFAULT_COPY_WAIT_MS);
+
+/// What [`calibrate`] measured and the timings it set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Calibration {
+    /// The slowest of the write/read-back cycles.
+    pub clipboard_latency: Duration,
+    /// Wait after the crate's own clipboard write before simulating a copy.
+    pub settle_delay: Duration,
+    /// Wait after the simulated copy before reading the clipboard.
+    pub copy_wait: Duration,
+}
+
+/// Measures how long this machine's clipboard takes to show a copy, and
+/// tunes the delays of later captures to it.
+///
+/// Copies a marker a few times, timing how long each takes to read back,
+/// then puts the previous contents back. On Windows the copy is made the way
+/// an app answers Ctrl+C, by a hidden edit control of this process handling
+/// `WM_COPY`; elsewhere the marker is written to the clipboard directly. No
+/// keys are pressed, so the user's app isn't involved. The waits are only
+/// ever raised from the built-in timings, and the copy wait keeps a 100 ms
+/// margin for the target app to answer the shortcut, which can't be
+/// measured without one. Call it once at startup, or from a "captures are
+/// unreliable" troubleshooting action.
+///
+/// # Errors
+///
+/// Returns `GetTextError::Clipboard` or `ClipboardBusy` if the clipboard
+/// can't be written, and what starting an input sequence returns (e.g.
+/// `InputBusy`). The timings are left unchanged in that case.
+pub fn calibrate() -> Result<Calibration, GetTextError> {
+    let _sequence = crate::sequence::begin()?;
+    let mut clipboard = crate::utils::open_clipboard()?;
+    let saved = crate::utils::SavedClipboard::save(&mut clipboard);
+    crate::side_effects::clipboard_modified();
+
+    let mut slowest = Duration::ZERO;
+    let mut result = Ok(());
+    for cycle in 0..CYCLES {
+        let marker = format!("get-selected-text calibration {} {}", std::process::id(), cycle);
+        let started = Instant::now();
+        if let Err(e) = copy_marker(&mut clipboard, &marker) {
+            result = Err(e);
+            break;
+        }
+        while crate::utils::read_clipboard_text().ok().as_deref() != Some(marker.as_str())
+            && started.elapsed() < MAX_LATENCY
+        {
+            thread::sleep(Duration::from_millis(1));
+        }
+        slowest = slowest.max(started.elapsed());
+    }
+    saved.restore(&mut clipboard)?;
+    result?;
+
+    let latency_ms = slowest.as_millis() as u64;
+    let settle_ms = (latency_ms * 2).clamp(DEFAULT_SETTLE_MS, 200);
+    let copy_wait_ms = (latency_ms * 4 + 100).clamp(DEFAULT_COPY_WAIT_MS, 1000);
+    SETTLE_MS.store(settle_ms, Ordering::Relaxed);
+    COPY_WAIT_MS.store(copy_wait_ms, Ordering::Relaxed);
+    Ok(Calibration {
+        clipboard_latency: slowest,
+        settle_delay: Duration::from_millis(settle_ms),
+        copy_wait: Duration::from_millis(copy_wait_ms),
+    })
+}
+
+#[cfg(target_os = "windows")]
+fn copy_marker(_clipboard: &mut arboard::Clipboard, marker: &str) -> Result<(), GetTextError> {
+    crate::windows::copy_from_hidden_edit(marker)
+}
+
+#[cfg(not(target_os = "windows"))]
+fn copy_marker(clipboard: &mut arboard::Clipboard, marker: &str) -> Result<(), GetTextError> {
+    crate::utils::write_temporary_text(clipboard, marker)
+}
+
+/// Goes back to the built-in timings (50 ms settle, 250 ms copy wait).
+pub fn reset_calibration() {
+    SETTLE_MS.store(DEFAULT_SETTLE_MS, Ordering::Relaxed);
+    COPY_WAIT_MS.store(DEFAULT_COPY_WAIT_MS, Ordering::Relaxed);
+}
+
+pub(crate) fn settle_delay() -> Duration {
+    Duration::from_millis(SETTLE_MS.load(Ordering::Relaxed))
+}
+
+pub(crate) fn copy_wait() -> Duration {
+    Duration::from_millis(COPY_WAIT_MS.load(Ordering::Relaxed))
+}
@@ -0,0 +1,191 @@
+//! Everything known about the selection, gathered in one pass.
+
+use std::time::Duration;
+
+use crate::{AppInfo, ContextConfidence, SourceKind, Strategy};
+
+/// Where the selection is on screen, in screen coordinates (points on macOS,
+/// pixels on Windows). For a selection spanning several lines, the rectangle
+/// enclosing all of them.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[non_exhaustive]
+pub struct SelectionBounds {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+impl SelectionBounds {
+    /// The smallest rectangle enclosing all of `rects` (`x`, `y`, `width`,
+    /// `height`), or `None` if there are none.
+    #[cfg_attr(not(target_os = "windows"), allow(dead_code))]
+    pub(crate) fn enclosing(rects: impl IntoIterator<Item = (f64, f64, f64, f64)>) -> Option<Self> {
+        rects
+            .into_iter()
+            .map(|(x, y, width, height)| (x, y, x + width, y + height))
+            .reduce(|a, b| (a.0.min(b.0), a.1.min(b.1), a.2.max(b.2), a.3.max(b.3)))
+            .map(|(left, top, right, bottom)| SelectionBounds {
+                x: left,
+                y: top,
+                width: right - left,
+                height: bottom - top,
+            })
+    }
+}
+
+/// Where the selection sits in its document, counted from 1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct SelectionPosition {
+    /// The line the selection starts on. Lines end at line breaks, except
+    /// where only the line index of a wrapped text view is known (macOS
+    /// controls that don't expose their value), which counts visual lines.
+    pub first_line: usize,
+    /// The line the selection ends on.
+    pub last_line: usize,
+    /// The paragraph the selection starts in. Paragraphs are separated by
+    /// blank lines. `None` when the document's text can't be read.
+    pub paragraph: Option<usize>,
+}
+
+impl SelectionPosition {
+    /// The position of `selected` when the document reads `prefix` before it.
+    #[cfg_attr(not(any(target_os = "macos", target_os = "windows")), allow(dead_code))]
+    pub(crate) fn in_text(prefix: &str, selected: &str) -> Self {
+        let first_line = prefix.matches('\n').count() + 1;
+        // A selection of whole lines ends with the line break of its last one.
+        let selected = selected.strip_suffix('\n').unwrap_or(selected);
+        let mut paragraph = 1;
+        let mut seen_text = false;
+        let mut after_blank = false;
+        // The last piece is the start of the selection's own line.
+        for line in prefix.split('\n') {
+            if line.trim().is_empty() {
+                after_blank = seen_text;
+            } else {
+                if after_blank {
+                    paragraph += 1;
+                    after_blank = false;
+                }
+                seen_text = true;
+            }
+        }
+        if after_blank {
+            paragraph += 1;
+        }
+        SelectionPosition {
+            first_line,
+            last_line: first_line + selected.matches('\n').count(),
+            paragraph: Some(paragraph),
+        }
+    }
+}
+
+/// The result of [`crate::capture`].
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct Capture {
+    pub text: String,
+    /// Sized like the context of `get_selected_text_with_context()`.
+    pub context: Option<String>,
+    /// How `context` was read, and so how far it can be trusted to surround
+    /// this very selection. `None` without a context.
+    pub context_confidence: Option<ContextConfidence>,
+    /// The app that was focused when the capture started.
+    pub app: Option<AppInfo>,
+    /// The executable, version and icon of `app` (`app-info` feature).
+    #[cfg(feature = "app-info")]
+    pub app_details: Option<crate::AppDetails>,
+    /// What kind of app `app` is; `Other` when it is unknown.
+    pub source: SourceKind,
+    /// `None` where AX or UI Automation can't locate the selection (web areas
+    /// in some browsers, apps read through a copy only, Linux).
+    pub bounds: Option<SelectionBounds>,
+    /// The selection's lines and paragraph, where AX or UI Automation can
+    /// locate it in its document.
+    pub position: Option<SelectionPosition>,
+}
+
+/// The result of [`crate::get_selection`]: the selected text and where it
+/// came from.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct SelectionResult {
+    pub text: String,
+    /// The app that was focused when the capture started.
+    pub app: Option<AppInfo>,
+    /// The bundle identifier of `app`, e.g. "com.apple.Safari" (macOS).
+    pub bundle_id: Option<String>,
+    /// The executable, version and icon of `app` (`app-info` feature).
+    #[cfg(feature = "app-info")]
+    pub app_details: Option<crate::AppDetails>,
+    /// What kind of app `app` is; `Other` when it is unknown.
+    pub source: SourceKind,
+    /// The title of the focused window. `None` where it can't be read (GNOME
+    /// on Wayland, and windows without one).
+    pub window_title: Option<String>,
+    /// The strategy that read the selection. `None` when an extractor, the
+    /// browser bridge or a terminal integration answered instead, or when
+    /// nothing was selected.
+    pub strategy: Option<Strategy>,
+    /// How long the capture took, including the wait for its turn.
+    pub elapsed: Duration,
+}
+
+/// The selection's lines and paragraph, reading at most `limit` characters
+/// of the document before it.
+pub(crate) fn selection_position(limit: usize) -> Option<SelectionPosition> {
+    #[cfg(target_os = "macos")]
+    {
+        crate::macos::selection_position(limit)
+    }
+    #[cfg(target_os = "windows")]
+    {
+        crate::windows::selection_position(limit)
+    }
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    {
+        let _ = limit;
+        None
+    }
+}
+
+/// The selection's bounds, read before anything is copied: a copy may cancel
+/// or move the selection.
+pub(crate) fn selection_bounds() -> Option<SelectionBounds> {
+    #[cfg(target_os = "macos")]
+    {
+        crate::macos::selection_bounds()
+    }
+    #[cfg(target_os = "windows")]
+    {
+        crate::windows::selection_bounds()
+    }
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_lines_and_paragraphs_before_the_selection() {
+        let prefix = "Title\n\nFirst paragraph,\nstill first.\n\n\nSecond ";
+        let position = SelectionPosition::in_text(prefix, "para\ngraph\n");
+        assert_eq!(position.first_line, 7);
+        assert_eq!(position.last_line, 8);
+        assert_eq!(position.paragraph, Some(3));
+    }
+
+    #[test]
+    fn selection_starting_a_paragraph_belongs_to_it() {
+        let position = SelectionPosition::in_text("One\r\n\r\n", "Two");
+        assert_eq!((position.first_line, position.last_line, position.paragraph), (3, 3, Some(2)));
+        let position = SelectionPosition::in_text("", "One");
+        assert_eq!((position.first_line, position.paragraph), (1, Some(1)));
+    }
+}
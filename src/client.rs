@@ -0,0 +1,523 @@
+use std::sync::{Arc, OnceLock};
+use std::time::Instant;
+
+use crate::memo::ContextMemo;
+use crate::strategy::{Strategy, StrategyCache};
+use crate::{GetTextError, GetTextOptions};
+
+/// A handle for capturing the selection with its own configuration.
+///
+/// Each client carries its own default options and its own per-app strategy
+/// statistics, so two differently configured consumers in one process don't
+/// influence each other. Platform resources pre-warmed by [`crate::init`]
+/// (input simulator, UI Automation instance) are shared by all clients.
+///
+/// The free functions of this crate are thin wrappers over a process-wide
+/// default client.
+#[derive(Debug)]
+pub struct SelectedTextClient {
+    options: GetTextOptions,
+    // Shared with the per-call views made by `with_call_options`.
+    pub(crate) strategies: Arc<StrategyCache>,
+    pub(crate) context_memo: Arc<ContextMemo>,
+}
+
+impl SelectedTextClient {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a client whose calls use `options` unless overridden per call.
+    pub fn with_options(options: GetTextOptions) -> Self {
+        Self {
+            options: crate::env_overrides::apply(&options).into_owned(),
+            strategies: Arc::new(StrategyCache::new()),
+            context_memo: Arc::default(),
+        }
+    }
+
+    /// A client using `options` that shares this one's strategy statistics
+    /// and context memo, for a call with options of its own.
+    fn with_call_options(&self, options: GetTextOptions) -> Self {
+        Self {
+            options: crate::env_overrides::apply(&options).into_owned(),
+            strategies: self.strategies.clone(),
+            context_memo: self.context_memo.clone(),
+        }
+    }
+
+    pub fn options(&self) -> &GetTextOptions {
+        &self.options
+    }
+
+    /// Gets the selected text using this client's options.
+    ///
+    /// # Errors
+    ///
+    /// See [`crate::get_selected_text`].
+    pub fn get_selected_text(&self) -> Result<String, GetTextError> {
+        self.get_selected_text_with_options(&self.options)
+    }
+
+    /// Gets the selected text using `options` instead of this client's own.
+    ///
+    /// # Errors
+    ///
+    /// See [`crate::get_selected_text`].
+    pub fn get_selected_text_with_options(
+        &self,
+        options: &GetTextOptions,
+    ) -> Result<String, GetTextError> {
+        let options = &*crate::env_overrides::apply(options);
+        let _capture = crate::suppress::enter_capture()?;
+        if options.coalesce_requests {
+            let key = format!("{:?}", options);
+            return crate::queue::coalesce(key, options.queue_timeout, || self.queued_capture_text(options));
+        }
+        self.queued_capture_text(options)
+    }
+
+    fn queued_capture_text(&self, options: &GetTextOptions) -> Result<String, GetTextError> {
+        let _turn = crate::queue::wait_turn(options.queue_timeout)?;
+        let started = Instant::now();
+        let (result, strategy) = crate::strategy::with_succeeded(|| {
+            crate::quirks::with_copy_wait(options.copy_wait, || self.capture_text(options))
+        });
+        crate::env_overrides::gst_log!("Capture in {:?}: {}", started.elapsed(), crate::env_overrides::describe(&result));
+        let result = result.map_err(GetTextError::from);
+        crate::metrics::record_capture(result.is_err(), started.elapsed());
+        if let Ok(text) = &result {
+            cancel_selection_after(options, text, strategy);
+        }
+        renote_succeeded(strategy);
+        result
+    }
+
+    /// [`SelectedTextClient::get_selected_text_with_options`] for backends
+    /// that capture the selection as part of a larger capture.
+    pub(crate) fn capture_text(&self, options: &GetTextOptions) -> Result<String, Box<dyn std::error::Error>> {
+        if let Some(selection) = crate::extractor::run(false) {
+            return Ok(selection.text);
+        }
+        refuse_capture(options)?;
+        #[cfg(feature = "browser-bridge")]
+        if let Some(selection) = crate::browser::selection_for_focused_app() {
+            return Ok(selection.text);
+        }
+        #[cfg(unix)]
+        if let Some(selection) = crate::terminal::selection_for_focused_app() {
+            return Ok(selection.text);
+        }
+        #[cfg(any(target_os = "windows", all(target_os = "linux", feature = "atspi")))]
+        if let Some(selection) = crate::libreoffice::selection_for_focused_app() {
+            return Ok(selection.text);
+        }
+        #[cfg(target_os = "windows")]
+        {
+            crate::windows::get_selected_text_with_options(options)
+        }
+        #[cfg(target_os = "macos")]
+        {
+            crate::macos::get_selected_text_with_options(self, options)
+        }
+        #[cfg(target_os = "linux")]
+        {
+            crate::linux::get_selected_text_with_options(options)
+        }
+        #[cfg(not(any(target_os = "windows", target_os = "macos", target_os = "linux")))]
+        {
+            let _ = options;
+            Err(Box::new(GetTextError::Unimplemented) as Box<dyn std::error::Error>)
+        }
+    }
+
+    /// Gets the selected text from sources that answer at once, without
+    /// simulating input or touching the clipboard.
+    ///
+    /// # Errors
+    ///
+    /// See [`crate::try_get_selected_text`].
+    pub fn try_get_selected_text(&self) -> Result<String, GetTextError> {
+        #[cfg(target_os = "linux")]
+        {
+            let process_id = crate::utils::focus_snapshot().map(|focus| focus.process_id());
+            #[cfg(feature = "atspi")]
+            if let Ok(text) = crate::atspi::selected_text(process_id) {
+                if !text.is_empty() {
+                    return Ok(text);
+                }
+            }
+            match crate::linux::peek_primary(process_id) {
+                Ok(text) if !text.is_empty() => Ok(text),
+                Ok(_) | Err(GetTextError::NoSelection) => Err(GetTextError::NoSelection),
+                // PRIMARY is out of reach or its owner is slow; only a copy can tell.
+                Err(_) => Err(GetTextError::WouldBlock),
+            }
+        }
+        #[cfg(any(target_os = "macos", target_os = "windows"))]
+        {
+            use crate::utils::SelectionProbe;
+
+            #[cfg(target_os = "macos")]
+            let (peeked, probe) = (crate::macos::peek_selection(), crate::macos::probe_selection());
+            #[cfg(target_os = "windows")]
+            let (peeked, probe) = (crate::windows::peek_selection(), crate::windows::probe_selection());
+            match peeked {
+                Some(text) if !text.is_empty() => Ok(text),
+                _ if probe == SelectionProbe::Absent => Err(GetTextError::NoSelection),
+                _ => Err(GetTextError::WouldBlock),
+            }
+        }
+        #[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+        {
+            Err(GetTextError::Unimplemented)
+        }
+    }
+
+    /// Gets the selected text trying exactly `strategies`, in this order.
+    ///
+    /// # Errors
+    ///
+    /// See [`crate::get_selected_text_with_strategies`].
+    pub fn get_selected_text_with_strategies(
+        &self,
+        strategies: &[crate::Strategy],
+    ) -> Result<String, GetTextError> {
+        self.get_selected_text_with_options(&self.options.clone().strategies(strategies))
+    }
+
+    /// Writes the selected text to `writer` as UTF-8 and returns the number of
+    /// bytes written.
+    ///
+    /// With the `native-clipboard` feature on Windows, the text is converted
+    /// from the clipboard in chunks, so a selection of hundreds of kilobytes
+    /// never exists as one `String` in this process. Elsewhere, or when the
+    /// client's options don't allow simulating the copy, it is captured as
+    /// usual and then written.
+    ///
+    /// # Errors
+    ///
+    /// See [`crate::get_selected_text`]. Errors from `writer` are returned as
+    /// `GetTextError::Os`.
+    pub fn get_selected_text_streaming<W: std::io::Write>(&self, mut writer: W) -> Result<u64, GetTextError> {
+        // Streaming copies; where the options rule that out, capturing as
+        // usual fails the same way every other entry point does.
+        #[cfg(all(target_os = "windows", feature = "native-clipboard"))]
+        if self.options.allows_input()
+            && self.options.strategies.as_ref().map_or(true, |strategies| strategies.contains(&Strategy::Clipboard))
+            && !self.options.disabled_strategies.contains(&Strategy::Clipboard)
+        {
+            let _capture = crate::suppress::enter_capture()?;
+            let _turn = crate::queue::wait_turn(self.options.queue_timeout)?;
+            let started = Instant::now();
+            let result = self.stream_text(&mut writer);
+            crate::metrics::record_capture(result.is_err(), started.elapsed());
+            return result;
+        }
+        let text = self.get_selected_text()?;
+        writer.write_all(text.as_bytes())?;
+        writer.flush()?;
+        Ok(text.len() as u64)
+    }
+
+    #[cfg(all(target_os = "windows", feature = "native-clipboard"))]
+    fn stream_text(&self, writer: &mut dyn std::io::Write) -> Result<u64, GetTextError> {
+        refuse_capture(&self.options)?;
+        if self.options.precheck_selection
+            && crate::windows::probe_selection() == crate::utils::SelectionProbe::Absent
+        {
+            return Err(GetTextError::NoSelection);
+        }
+        let written = crate::windows::stream_selected_text(writer, self.options.restore_clipboard)?;
+        writer.flush()?;
+        Ok(written)
+    }
+
+    /// Gets the selected text and its surrounding context using this client's options.
+    ///
+    /// # Errors
+    ///
+    /// See [`crate::get_selected_text_with_context`].
+    pub fn get_selected_text_with_context(&self) -> Result<(String, Option<String>), GetTextError> {
+        let _capture = crate::suppress::enter_capture()?;
+        let _turn = crate::queue::wait_turn(self.options.queue_timeout)?;
+        let started = Instant::now();
+        let result = self.capture_with_context();
+        crate::metrics::record_capture(result.is_err(), started.elapsed());
+        result
+    }
+
+    /// Gets the selected text and its surrounding context using `options`
+    /// instead of this client's own, keeping what the client learned about
+    /// each app.
+    ///
+    /// # Errors
+    ///
+    /// See [`crate::get_selected_text_with_context`].
+    pub fn get_selected_text_with_context_opts(
+        &self,
+        options: &GetTextOptions,
+    ) -> Result<(String, Option<String>), GetTextError> {
+        self.with_call_options(options.clone()).get_selected_text_with_context()
+    }
+
+    /// Gets the selected text and its context split at the selection, using
+    /// this client's options.
+    ///
+    /// # Errors
+    ///
+    /// See [`crate::get_selected_text_with_context`].
+    pub fn get_selected_text_with_context_parts(&self) -> Result<(String, Option<crate::Context>), GetTextError> {
+        let (result, noted) = crate::context::with_split(|| self.get_selected_text_with_context());
+        let (text, context) = result?;
+        let parts = context.and_then(|context| crate::context::split(&context, &text, noted));
+        Ok((text, parts))
+    }
+
+    /// Captures the selection, its context, the focused app and the
+    /// selection's screen bounds in one pass, saving and restoring the
+    /// clipboard once even when finding the context takes a second copy.
+    ///
+    /// # Errors
+    ///
+    /// See [`crate::get_selected_text_with_context`].
+    pub fn capture(&self) -> Result<crate::Capture, GetTextError> {
+        let _capture = crate::suppress::enter_capture()?;
+        let _turn = crate::queue::wait_turn(self.options.queue_timeout)?;
+        let started = Instant::now();
+        let app = crate::utils::focus_snapshot().map(|focus| crate::AppInfo {
+            name: focus.app_name().to_string(),
+            process_id: focus.process_id(),
+        });
+        #[cfg(feature = "app-info")]
+        let app_details = app.as_ref().and_then(|app| crate::app_info::app_details(app.process_id));
+        let bounds = crate::capture::selection_bounds();
+        let position = crate::capture::selection_position(self.options.max_document_chars);
+        let (result, confidence) = crate::context::with_confidence(|| {
+            let _session = if self.options.restore_clipboard {
+                crate::utils::ClipboardSession::begin()?
+            } else {
+                None
+            };
+            self.capture_with_context()
+        });
+        crate::metrics::record_capture(result.is_err(), started.elapsed());
+        let (text, context) = result?;
+        // Extractors, the browser bridge and terminals hand over a context the
+        // crate didn't read itself.
+        let context_confidence = context
+            .as_ref()
+            .map(|_| confidence.unwrap_or(crate::ContextConfidence::Medium));
+        let source = app.as_ref().map(|app| crate::apps::source_kind(&app.name)).unwrap_or_default();
+        Ok(crate::Capture {
+            text,
+            context,
+            context_confidence,
+            app,
+            #[cfg(feature = "app-info")]
+            app_details,
+            source,
+            bounds,
+            position,
+        })
+    }
+
+    /// Gets the selected text along with the focused app and window, the
+    /// strategy that read it and how long that took.
+    ///
+    /// # Errors
+    ///
+    /// See [`crate::get_selected_text`].
+    pub fn get_selection(&self) -> Result<crate::SelectionResult, GetTextError> {
+        let started = Instant::now();
+        let focus = crate::utils::focus_snapshot();
+        #[cfg(target_os = "macos")]
+        let bundle_id = focus.as_ref().and_then(|focus| crate::macos::bundle_identifier(focus.process_id()));
+        #[cfg(not(target_os = "macos"))]
+        let bundle_id = None;
+        #[cfg(feature = "app-info")]
+        let app_details = focus.as_ref().and_then(|focus| crate::app_info::app_details(focus.process_id()));
+        let (result, strategy) = crate::strategy::with_succeeded(|| self.get_selected_text());
+        Ok(crate::SelectionResult {
+            text: result?,
+            app: focus.as_ref().map(|focus| crate::AppInfo {
+                name: focus.app_name().to_string(),
+                process_id: focus.process_id(),
+            }),
+            bundle_id,
+            #[cfg(feature = "app-info")]
+            app_details,
+            source: focus.as_ref().map(|focus| crate::apps::source_kind(focus.app_name())).unwrap_or_default(),
+            window_title: focus.as_ref().and_then(|focus| focus.title()).map(str::to_string),
+            strategy,
+            elapsed: started.elapsed(),
+        })
+    }
+
+    /// Reads the whole focused document, independent of any selection, using
+    /// this client's options.
+    ///
+    /// # Errors
+    ///
+    /// See [`crate::get_document_text`].
+    pub fn get_document_text(&self) -> Result<String, GetTextError> {
+        let _capture = crate::suppress::enter_capture()?;
+        let _turn = crate::queue::wait_turn(self.options.queue_timeout)?;
+        if !self.options.allow_own_window && crate::utils::own_window_focused() {
+            return Err(GetTextError::OwnWindowFocused);
+        }
+        crate::session::ensure_active()?;
+        let limit = self.options.max_document_chars;
+        #[cfg(target_os = "windows")]
+        let text = crate::windows::document_text(limit);
+        #[cfg(target_os = "macos")]
+        let text = crate::macos::document_text(limit);
+        #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+        let text: Option<String> = None;
+        if let Some(text) = text {
+            return Ok(text);
+        }
+        if let Some(app) = crate::utils::exclusive_fullscreen_app() {
+            return Err(GetTextError::UnsupportedForegroundApp { app });
+        }
+        // Last resort: Select All + Copy, which is skipped where it could
+        // clobber an edit or stall the app, or input may not be simulated.
+        let select_all = || {
+            crate::utils::with_enigo(|enigo| crate::utils::get_text_via_select_all(enigo, &self.options))
+        };
+        let text = if self.options.allows_input() {
+            crate::quirks::with_copy_wait(self.options.copy_wait, select_all)??
+        } else {
+            None
+        };
+        text.ok_or_else(|| {
+            GetTextError::Other("The focused document exposes no text and Select All is off for it".into())
+        })
+    }
+
+    /// Reads the whole focused document using `options` instead of this
+    /// client's own.
+    ///
+    /// # Errors
+    ///
+    /// See [`crate::get_document_text`].
+    pub fn get_document_text_with_options(&self, options: &GetTextOptions) -> Result<String, GetTextError> {
+        self.with_call_options(options.clone()).get_document_text()
+    }
+
+    fn capture_with_context(&self) -> Result<(String, Option<String>), GetTextError> {
+        let (result, strategy) = crate::strategy::with_succeeded(|| {
+            crate::quirks::with_copy_wait(self.options.copy_wait, || self.get_selected_text_with_context_os())
+        });
+        renote_succeeded(strategy);
+        let (selected_text, context) = result?;
+        cancel_selection_after(&self.options, &selected_text, strategy);
+        // Some backends return a whole paragraph or element value; apply the
+        // requested sizing to those too.
+        let context = match (&self.options.context_size, context) {
+            (Some(size), Some(context)) => Some(crate::context::trim_around(&context, &selected_text, size)),
+            (_, context) => context,
+        };
+        Ok((selected_text, context))
+    }
+
+    fn get_selected_text_with_context_os(
+        &self,
+    ) -> Result<(String, Option<String>), Box<dyn std::error::Error>> {
+        if let Some(selection) = crate::extractor::run(true) {
+            return Ok((selection.text, selection.context));
+        }
+        refuse_capture(&self.options)?;
+        #[cfg(feature = "browser-bridge")]
+        if let Some(selection) = crate::browser::selection_for_focused_app() {
+            return Ok((selection.text, selection.context));
+        }
+        #[cfg(unix)]
+        if let Some(selection) = crate::terminal::selection_for_focused_app() {
+            return Ok((selection.text, selection.context));
+        }
+        #[cfg(any(target_os = "windows", all(target_os = "linux", feature = "atspi")))]
+        if let Some(selection) = crate::libreoffice::selection_for_focused_app() {
+            return Ok((selection.text, selection.context));
+        }
+        #[cfg(target_os = "windows")]
+        {
+            crate::windows::get_selected_text_with_context_for(self)
+                .map_err(|e| Box::new(e) as Box<dyn std::error::Error>)
+        }
+        #[cfg(target_os = "macos")]
+        {
+            crate::macos::get_selected_text_with_context(self)
+        }
+        #[cfg(target_os = "linux")]
+        {
+            crate::linux::get_selected_text_with_context_for(self)
+        }
+        #[cfg(not(any(target_os = "windows", target_os = "macos", target_os = "linux")))]
+        {
+            Err(Box::new(GetTextError::Unimplemented))
+        }
+    }
+}
+
+impl Default for SelectedTextClient {
+    fn default() -> Self {
+        Self::with_options(GetTextOptions::default())
+    }
+}
+
+static DEFAULT_CLIENT: OnceLock<SelectedTextClient> = OnceLock::new();
+
+/// Refuses a capture while this process's own window is focused (unless
+/// allowed), the session is locked or an app runs in exclusive fullscreen.
+fn refuse_capture(options: &GetTextOptions) -> Result<(), GetTextError> {
+    if !options.allow_own_window && crate::utils::own_window_focused() {
+        return Err(GetTextError::OwnWindowFocused);
+    }
+    crate::session::ensure_active()?;
+    if let Some(app) = crate::utils::exclusive_fullscreen_app() {
+        return Err(GetTextError::UnsupportedForegroundApp { app });
+    }
+    Ok(())
+}
+
+/// Applies `GetTextOptions::cancel_select` once a capture found text. Only
+/// when `strategy` read it from the focused app's own selection: after an
+/// extractor, a terminal integration, LibreOffice or the browser bridge, the
+/// key could land somewhere the selection isn't. Nor when the call may not
+/// send input. If the key can't be sent the selection just stays, which
+/// doesn't fail the capture.
+fn cancel_selection_after(options: &GetTextOptions, text: &str, strategy: Option<Strategy>) {
+    if options.cancel_select && options.allows_input() && !text.is_empty() && strategy.is_some() {
+        if let Err(e) = crate::utils::cancel_selection() {
+            crate::env_overrides::gst_log!("Cancelling the selection failed: {}", e);
+        }
+    }
+}
+
+/// Notes `strategy` again for an enclosing `with_succeeded`, such as
+/// `get_selection`'s, after a nested one took it.
+fn renote_succeeded(strategy: Option<Strategy>) {
+    if let Some(strategy) = strategy {
+        crate::strategy::note_succeeded(strategy);
+    }
+}
+
+/// The client behind the crate's free functions.
+pub(crate) fn default_client() -> &'static SelectedTextClient {
+    DEFAULT_CLIENT.get_or_init(SelectedTextClient::new)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cancel_select_sends_no_key_when_input_is_not_allowed() {
+        let options = GetTextOptions::new().strategies(&[Strategy::Accessibility]).cancel_select(true);
+        let ((), effects) =
+            crate::track_side_effects(|| cancel_selection_after(&options, "hello", Some(Strategy::Accessibility)));
+        assert!(!effects.keystrokes_sent);
+        assert!(!effects.selection_changed);
+    }
+}
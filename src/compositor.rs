@@ -0,0 +1,135 @@
+//! Focused-window lookup through Wayland compositor IPC, where
+//! `active_win_pos_rs` (X11 only) has nothing to query.
+//!
+//! Supports Hyprland and Sway over their sockets, KWin through `kdotool`,
+//! which drives KWin's scripting interface, and GNOME through the companion
+//! extension (`gnome` feature).
+
+use std::io::{Read, Write};
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use serde_json::Value;
+
+const IPC_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// The focused window as reported by the compositor.
+pub(crate) struct FocusedWindow {
+    pub(crate) app_name: String,
+    pub(crate) process_id: u64,
+    pub(crate) window_id: String,
+    pub(crate) title: Option<String>,
+}
+
+/// Asks whichever supported compositor is running. `None` if there is none,
+/// or nothing is focused.
+pub(crate) fn focused_window() -> Option<FocusedWindow> {
+    if let Some(signature) = std::env::var_os("HYPRLAND_INSTANCE_SIGNATURE") {
+        return hyprland(&signature.to_string_lossy());
+    }
+    if let Some(socket) = std::env::var_os("SWAYSOCK") {
+        return sway(PathBuf::from(socket));
+    }
+    if std::env::var("XDG_CURRENT_DESKTOP").is_ok_and(|desktop| desktop.contains("KDE")) {
+        return kwin();
+    }
+    #[cfg(feature = "gnome")]
+    if crate::gnome::is_gnome() {
+        return crate::gnome::focused_window();
+    }
+    None
+}
+
+fn connect(path: &PathBuf) -> Option<UnixStream> {
+    let stream = UnixStream::connect(path).ok()?;
+    stream.set_read_timeout(Some(IPC_TIMEOUT)).ok()?;
+    stream.set_write_timeout(Some(IPC_TIMEOUT)).ok()?;
+    Some(stream)
+}
+
+fn hyprland(signature: &str) -> Option<FocusedWindow> {
+    // Hyprland moved its sockets from /tmp/hypr to $XDG_RUNTIME_DIR/hypr in 0.40.
+    let candidates = std::env::var_os("XDG_RUNTIME_DIR")
+        .map(|dir| PathBuf::from(dir).join("hypr"))
+        .into_iter()
+        .chain(std::iter::once(PathBuf::from("/tmp/hypr")))
+        .map(|dir| dir.join(signature).join(".socket.sock"));
+    let mut stream = candidates.filter_map(|path| connect(&path)).next()?;
+    stream.write_all(b"j/activewindow").ok()?;
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).ok()?;
+
+    let window: Value = serde_json::from_slice(&response).ok()?;
+    Some(FocusedWindow {
+        app_name: window.get("class")?.as_str()?.to_string(),
+        process_id: window.get("pid")?.as_u64()?,
+        window_id: window.get("address")?.as_str()?.to_string(),
+        title: window.get("title").and_then(Value::as_str).map(str::to_string),
+    })
+}
+
+const I3_MAGIC: &[u8] = b"i3-ipc";
+const I3_GET_TREE: u32 = 4;
+
+fn sway(socket: PathBuf) -> Option<FocusedWindow> {
+    let mut stream = connect(&socket)?;
+    let mut request = I3_MAGIC.to_vec();
+    request.extend_from_slice(&0u32.to_ne_bytes());
+    request.extend_from_slice(&I3_GET_TREE.to_ne_bytes());
+    stream.write_all(&request).ok()?;
+
+    let mut header = [0u8; 14];
+    stream.read_exact(&mut header).ok()?;
+    if &header[..6] != I3_MAGIC {
+        return None;
+    }
+    let len = u32::from_ne_bytes(header[6..10].try_into().ok()?) as usize;
+    let mut payload = vec![0u8; len];
+    stream.read_exact(&mut payload).ok()?;
+
+    let tree: Value = serde_json::from_slice(&payload).ok()?;
+    let node = find_focused(&tree)?;
+    // Native Wayland clients have an app_id, XWayland ones a window class.
+    let app_name = node
+        .get("app_id")
+        .and_then(Value::as_str)
+        .or_else(|| node.pointer("/window_properties/class").and_then(Value::as_str))?;
+    Some(FocusedWindow {
+        app_name: app_name.to_string(),
+        process_id: node.get("pid")?.as_u64()?,
+        window_id: node.get("id")?.as_u64()?.to_string(),
+        title: node.get("name").and_then(Value::as_str).map(str::to_string),
+    })
+}
+
+fn find_focused(node: &Value) -> Option<&Value> {
+    if node.get("focused").and_then(Value::as_bool) == Some(true) {
+        return Some(node);
+    }
+    ["nodes", "floating_nodes"]
+        .iter()
+        .filter_map(|key| node.get(key)?.as_array())
+        .flatten()
+        .find_map(find_focused)
+}
+
+fn kwin() -> Option<FocusedWindow> {
+    let run = |args: &[&str]| -> Option<String> {
+        let output = std::process::Command::new("kdotool").args(args).output().ok()?;
+        output
+            .status
+            .success()
+            .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+    };
+    let window_id = run(&["getactivewindow"])?;
+    let process_id = run(&["getwindowpid", &window_id])?.parse().ok()?;
+    let app_name = run(&["getwindowclassname", &window_id])?;
+    let title = run(&["getwindowname", &window_id]);
+    Some(FocusedWindow {
+        app_name,
+        process_id,
+        window_id,
+        title,
+    })
+}
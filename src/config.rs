@@ -0,0 +1,206 @@
+//! Defaults read from a TOML file (`config` feature), so end users of an app
+//! can tune the crate without the app exposing every option.
+//!
+//! ```toml
+//! context_chars = 300
+//! restore_clipboard = true
+//! disabled_strategies = ["AppleScript"]
+//! input_cooldown_ms = 50
+//!
+//! [apps."Windows Terminal"]
+//! copy_shortcut = "CtrlInsert"
+//! context = "AccessibilityOnly"
+//! ```
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use parking_lot::RwLock;
+use serde::Deserialize;
+
+use crate::{Boundary, ContextSize, ContextSupport, CopyShortcut, GetTextError, GetTextOptions, Strategy};
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ConfigFile {
+    context_chars: Option<usize>,
+    context_tokens: Option<usize>,
+    context_sentences: Option<usize>,
+    context_paragraphs: Option<usize>,
+    context_snap: Option<String>,
+    context_memo_ttl_ms: Option<u64>,
+    max_document_chars: Option<usize>,
+    allow_select_all_in_editable: Option<bool>,
+    precheck_selection: Option<bool>,
+    restore_clipboard: Option<bool>,
+    disabled_strategies: Vec<String>,
+    input_cooldown_ms: Option<u64>,
+    clipboard_restore_delay_ms: Option<u64>,
+    apps: HashMap<String, AppConfig>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct AppConfig {
+    strategies: Option<Vec<String>>,
+    copy_shortcut: Option<String>,
+    context: Option<String>,
+    copy_wait_ms: Option<u64>,
+}
+
+static DEFAULTS: RwLock<Option<GetTextOptions>> = RwLock::new(None);
+
+/// The options loaded from the configuration file, if any.
+pub(crate) fn default_options() -> Option<GetTextOptions> {
+    DEFAULTS.read().clone()
+}
+
+/// `$XDG_CONFIG_HOME/get-selected-text/config.toml`, falling back to
+/// `~/.config` (`%USERPROFILE%\.config` on Windows).
+pub fn default_config_path() -> Option<PathBuf> {
+    let base = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| {
+            let home = if cfg!(target_os = "windows") { "USERPROFILE" } else { "HOME" };
+            std::env::var_os(home).map(|home| PathBuf::from(home).join(".config"))
+        })?;
+    Some(base.join("get-selected-text").join("config.toml"))
+}
+
+/// Loads the file at [`default_config_path`] if it exists. Called by
+/// [`crate::init`].
+pub(crate) fn load_default() -> Result<(), GetTextError> {
+    match default_config_path() {
+        Some(path) if path.is_file() => load_config(&path),
+        _ => Ok(()),
+    }
+}
+
+/// Reads a configuration file and applies it to the whole process: the
+/// options become what `GetTextOptions::default()` returns, the cool-downs
+/// are set, and each `[apps."Name"]` table replaces that app's quirks.
+///
+/// Settings missing from the file keep the crate's defaults, and per-app
+/// tables only replace the keys they set.
+///
+/// # Errors
+///
+/// Returns `GetTextError::Config` if the file can't be read, isn't valid
+/// TOML, or uses an unknown key or name. Nothing is applied in that case.
+pub fn load_config(path: &Path) -> Result<(), GetTextError> {
+    let source = std::fs::read_to_string(path)
+        .map_err(|e| GetTextError::Config(format!("{}: {}", path.display(), e)))?;
+    let file: ConfigFile =
+        toml::from_str(&source).map_err(|e| GetTextError::Config(format!("{}: {}", path.display(), e)))?;
+
+    let options = file.options()?;
+    let mut quirks = Vec::with_capacity(file.apps.len());
+    for (app_name, app) in &file.apps {
+        quirks.push((app_name.as_str(), app.quirks(app_name)?));
+    }
+
+    // Everything parsed; apply.
+    if let Some(ms) = file.input_cooldown_ms {
+        crate::set_input_cooldown(Duration::from_millis(ms));
+    }
+    if let Some(ms) = file.clipboard_restore_delay_ms {
+        crate::set_clipboard_restore_delay(Duration::from_millis(ms));
+    }
+    for (app_name, app_quirks) in quirks {
+        crate::set_app_quirks(app_name, app_quirks);
+    }
+    *DEFAULTS.write() = Some(options);
+    Ok(())
+}
+
+impl ConfigFile {
+    fn options(&self) -> Result<GetTextOptions, GetTextError> {
+        let mut options = GetTextOptions::builtin();
+        let sizes = [
+            self.context_chars.map(ContextSize::Chars),
+            self.context_tokens.map(ContextSize::tokens),
+            self.context_sentences.map(ContextSize::Sentences),
+            self.context_paragraphs.map(ContextSize::Paragraphs),
+        ];
+        let mut sizes = sizes.into_iter().flatten();
+        if let Some(size) = sizes.next() {
+            options.context_size = Some(size);
+        }
+        if sizes.next().is_some() {
+            return Err(GetTextError::Config(
+                "Only one of context_chars, context_tokens, context_sentences and context_paragraphs may be set"
+                    .to_string(),
+            ));
+        }
+        if let Some(name) = &self.context_snap {
+            let boundary = match name.to_ascii_lowercase().as_str() {
+                "word" => Boundary::Word,
+                "sentence" => Boundary::Sentence,
+                _ => return Err(unknown("context boundary", name)),
+            };
+            let size = options.context_size.take().unwrap_or(crate::context::DEFAULT_SIZE);
+            options.context_size = Some(size.snap_to(boundary));
+        }
+        if let Some(ms) = self.context_memo_ttl_ms {
+            options.context_memo_ttl = Duration::from_millis(ms);
+        }
+        if let Some(limit) = self.max_document_chars {
+            options.max_document_chars = limit;
+        }
+        if let Some(allow) = self.allow_select_all_in_editable {
+            options.allow_select_all_in_editable = allow;
+        }
+        if let Some(precheck) = self.precheck_selection {
+            options.precheck_selection = precheck;
+        }
+        if let Some(restore) = self.restore_clipboard {
+            options.restore_clipboard = restore;
+        }
+        for name in &self.disabled_strategies {
+            options = options.disable_strategy(parse_strategy(name)?);
+        }
+        Ok(options)
+    }
+}
+
+impl AppConfig {
+    fn quirks(&self, app_name: &str) -> Result<crate::AppQuirks, GetTextError> {
+        let mut quirks = crate::app_quirks(app_name);
+        if let Some(names) = &self.strategies {
+            let strategies = names.iter().map(|name| parse_strategy(name)).collect::<Result<Vec<_>, _>>()?;
+            quirks = quirks.strategies(&strategies);
+        }
+        if let Some(name) = &self.copy_shortcut {
+            let shortcut = match name.to_ascii_lowercase().as_str() {
+                "ctrlc" | "ctrl+c" => CopyShortcut::CtrlC,
+                "ctrlshiftc" | "ctrl+shift+c" => CopyShortcut::CtrlShiftC,
+                "ctrlinsert" | "ctrl+insert" => CopyShortcut::CtrlInsert,
+                _ => return Err(unknown("copy shortcut", name)),
+            };
+            quirks = quirks.copy_shortcut(shortcut);
+        }
+        if let Some(name) = &self.context {
+            let context = match name.to_ascii_lowercase().as_str() {
+                "full" => ContextSupport::Full,
+                "accessibilityonly" => ContextSupport::AccessibilityOnly,
+                "pointer" => ContextSupport::Pointer,
+                _ => return Err(unknown("context support", name)),
+            };
+            quirks = quirks.context(context);
+        }
+        if let Some(ms) = self.copy_wait_ms {
+            quirks = quirks.copy_wait(Duration::from_millis(ms));
+        }
+        Ok(quirks)
+    }
+}
+
+fn parse_strategy(name: &str) -> Result<Strategy, GetTextError> {
+    Strategy::from_name(name).ok_or_else(|| unknown("strategy", name))
+}
+
+fn unknown(what: &str, name: &str) -> GetTextError {
+    GetTextError::Config(format!("Unknown {} \"{}\"", what, name))
+}
@@ -0,0 +1,643 @@
+use std::cell::{Cell, RefCell};
+use std::fmt;
+use std::sync::Arc;
+
+/// How much text around the selection to return as context.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub enum ContextSize {
+    /// Up to this many characters on each side of the selection.
+    Chars(usize),
+    /// Up to `budget` tokens in total, the selection included. The budget left
+    /// after the selection is split evenly before and after it, and whatever
+    /// one side can't use goes to the other.
+    ///
+    /// If the selection alone exceeds the budget, the context is just the
+    /// selection.
+    Tokens { budget: usize, counter: TokenCounter },
+    /// The sentences the selection starts and ends in, and this many whole
+    /// sentences before and after them. Sentences are found by the Unicode
+    /// sentence boundary rules (UAX #29) with the `segmentation` feature,
+    /// and at `.`, `!`, `?`, `…` followed by whitespace, full-width sentence
+    /// ends and line breaks without it.
+    Sentences(usize),
+    /// The paragraphs the selection starts and ends in, and this many
+    /// paragraphs before and after them. A paragraph ends at a line break,
+    /// as in UI Automation and word processors; blank lines don't count as
+    /// paragraphs.
+    Paragraphs(usize),
+    /// `size`, with each side cut back to the nearest `boundary` so the
+    /// context doesn't start or end in the middle of a word or sentence.
+    /// Built with [`ContextSize::snap_to`].
+    Snapped { size: Box<ContextSize>, boundary: Boundary },
+}
+
+impl ContextSize {
+    /// A token budget counted with the default characters-per-token estimate.
+    pub fn tokens(budget: usize) -> Self {
+        ContextSize::Tokens {
+            budget,
+            counter: TokenCounter::default(),
+        }
+    }
+
+    /// This size, snapped inward to `boundary` on both sides. Snapping only
+    /// ever drops text, so a token budget still holds.
+    pub fn snap_to(self, boundary: Boundary) -> Self {
+        let size = match self {
+            ContextSize::Snapped { size, .. } => size,
+            size => Box::new(size),
+        };
+        ContextSize::Snapped { size, boundary }
+    }
+}
+
+/// Where [`ContextSize::snap_to`] may cut the context.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Boundary {
+    /// Between words: partial words at either end are dropped. A side with
+    /// no whitespace in it, such as CJK text, is kept as cut.
+    Word,
+    /// Between sentences: partial sentences at either end are dropped. A side
+    /// with no sentence end in it is snapped to words instead, rather than
+    /// left empty.
+    Sentence,
+}
+
+/// Counts tokens for [`ContextSize::Tokens`].
+#[derive(Clone)]
+#[non_exhaustive]
+pub enum TokenCounter {
+    /// Estimates the token count as the number of characters divided by this
+    /// ratio.
+    CharsPerToken(f32),
+    /// Counts with a real tokenizer. It is called a logarithmic number of times
+    /// per side of the selection, on candidate slices of the surrounding text.
+    Custom(Arc<dyn Fn(&str) -> usize + Send + Sync>),
+}
+
+impl TokenCounter {
+    pub fn custom(count: impl Fn(&str) -> usize + Send + Sync + 'static) -> Self {
+        TokenCounter::Custom(Arc::new(count))
+    }
+
+    pub fn count(&self, text: &str) -> usize {
+        match self {
+            TokenCounter::CharsPerToken(ratio) => {
+                (text.chars().count() as f32 / ratio.max(f32::EPSILON)).ceil() as usize
+            }
+            TokenCounter::Custom(count) => count(text),
+        }
+    }
+}
+
+impl Default for TokenCounter {
+    /// Four characters per token, a common estimate for English text.
+    fn default() -> Self {
+        TokenCounter::CharsPerToken(4.0)
+    }
+}
+
+impl fmt::Debug for TokenCounter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TokenCounter::CharsPerToken(ratio) => f.debug_tuple("CharsPerToken").field(ratio).finish(),
+            TokenCounter::Custom(_) => f.write_str("Custom(..)"),
+        }
+    }
+}
+
+/// How far a context can be trusted to surround the very text selected,
+/// judged by how it was read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[non_exhaustive]
+pub enum ContextConfidence {
+    /// A label of the element holding the selection (its title or
+    /// description, or the selection itself), which may not surround it.
+    Low,
+    /// The selection was searched for in a larger text: everything copied
+    /// with Select All, a document, a page or a control's value. When that
+    /// text repeats the selection, its first occurrence is taken, which may
+    /// not be the one selected. Contexts from extractors count as this too.
+    Medium,
+    /// Read at the selection's own position through AX or UI Automation: the
+    /// element's value or the selection's paragraph.
+    High,
+}
+
+/// A context split at the selection.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub struct Context {
+    /// The context up to the selection.
+    pub before: String,
+    /// The selection as it appears in the context. It can differ from the
+    /// copied text in whitespace, where the app copies differently from what
+    /// AX or UI Automation report.
+    pub selected: String,
+    /// The context after the selection.
+    pub after: String,
+}
+
+impl fmt::Display for Context {
+    /// The flat context, as `get_selected_text_with_context()` returns it.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}{}", self.before, self.selected, self.after)
+    }
+}
+
+impl Context {
+    /// `context` split at its first occurrence of `selected_text`. `None` if
+    /// it doesn't occur.
+    fn split_at_first(context: &str, selected_text: &str) -> Option<Self> {
+        let start = context.find(selected_text)?;
+        let end = start + selected_text.len();
+        Some(Context {
+            before: context[..start].to_string(),
+            selected: selected_text.to_string(),
+            after: context[end..].to_string(),
+        })
+    }
+}
+
+thread_local! {
+    static SPLIT: RefCell<Option<Context>> = const { RefCell::new(None) };
+    static CONFIDENCE: Cell<Option<ContextConfidence>> = const { Cell::new(None) };
+}
+
+/// Notes how the context about to be returned on this thread was read.
+pub(crate) fn set_confidence(confidence: ContextConfidence) {
+    CONFIDENCE.with(|current| current.set(Some(confidence)));
+}
+
+/// The confidence last noted on this thread.
+pub(crate) fn confidence() -> Option<ContextConfidence> {
+    CONFIDENCE.with(Cell::get)
+}
+
+/// Runs `f` and returns the confidence of the context it read.
+pub(crate) fn with_confidence<R>(f: impl FnOnce() -> R) -> (R, Option<ContextConfidence>) {
+    let outer = CONFIDENCE.with(|current| current.replace(None));
+    let result = f();
+    (result, CONFIDENCE.with(|current| current.replace(outer)))
+}
+
+/// Notes where the selection is in the context about to be returned on this
+/// thread. The windows cut below note themselves.
+fn note_split(context: &Context) {
+    SPLIT.with(|current| *current.borrow_mut() = Some(context.clone()));
+}
+
+/// Notes that the selection starts at character `start` of `full_text`, a
+/// context returned whole, for a backend that reports where the selection
+/// is. Nothing is noted if those characters aren't `selected_text`.
+pub(crate) fn note_position(full_text: &str, selected_text: &str, start: usize) {
+    let end = start + selected_text.chars().count();
+    if char_slice(full_text, start, end) == selected_text {
+        note_split(&Context {
+            before: char_slice(full_text, 0, start).to_string(),
+            selected: selected_text.to_string(),
+            after: char_slice(full_text, end, usize::MAX).to_string(),
+        });
+    }
+}
+
+/// The split last noted on this thread, if it is one of `context`.
+fn noted_split_of(context: &str) -> Option<Context> {
+    SPLIT.with(|current| current.borrow().clone()).filter(|split| split.to_string() == context)
+}
+
+/// Runs `f` and returns the split last noted while it ran.
+pub(crate) fn with_split<R>(f: impl FnOnce() -> R) -> (R, Option<Context>) {
+    let outer = SPLIT.with(|current| current.replace(None));
+    let result = f();
+    (result, SPLIT.with(|current| current.replace(outer)))
+}
+
+/// `context` split at the selection: where `noted` says it is, else at the
+/// first occurrence of `selected_text`. `None` if it can't be found.
+pub(crate) fn split(context: &str, selected_text: &str, noted: Option<Context>) -> Option<Context> {
+    noted
+        .filter(|split| split.to_string() == context)
+        .or_else(|| Context::split_at_first(context, selected_text))
+}
+
+/// Sizing used by the Select All and UIA document fallbacks when the caller
+/// didn't ask for one.
+pub(crate) const DEFAULT_SIZE: ContextSize = ContextSize::Chars(150);
+
+/// The characters `from..to` of `text`, clamped to its length. All context
+/// slicing goes through here, so no cut can land inside a character.
+fn char_slice(text: &str, from: usize, to: usize) -> &str {
+    let mut boundaries = text.char_indices().map(|(index, _)| index).chain(std::iter::once(text.len()));
+    let start = boundaries.nth(from).unwrap_or(text.len());
+    let end = match to.checked_sub(from + 1) {
+        Some(skip) => boundaries.nth(skip).unwrap_or(text.len()),
+        None => start,
+    };
+    &text[start..end]
+}
+
+fn first_chars(text: &str, n: usize) -> &str {
+    char_slice(text, 0, n)
+}
+
+fn last_chars(text: &str, n: usize) -> &str {
+    let count = text.chars().count();
+    char_slice(text, count.saturating_sub(n), count)
+}
+
+/// The largest number of characters `take(text, n)` can return while staying
+/// within `allowance` tokens.
+fn fit(text: &str, allowance: usize, counter: &TokenCounter, take: fn(&str, usize) -> &str) -> usize {
+    let (mut low, mut high) = (0, text.chars().count());
+    while low < high {
+        let mid = low + (high - low + 1) / 2;
+        if counter.count(take(text, mid)) <= allowance {
+            low = mid;
+        } else {
+            high = mid - 1;
+        }
+    }
+    low
+}
+
+/// Characters that end a sentence when followed by whitespace. The
+/// full-width ones end it on their own.
+const SENTENCE_ENDS: &[char] = &['.', '!', '?', '\u{2026}'];
+const FULL_WIDTH_SENTENCE_ENDS: &[char] = &['\u{3002}', '\u{ff01}', '\u{ff1f}'];
+
+/// Byte offsets in `text` right after each `boundary` inside it, i.e. where
+/// a snapped piece of context may start or end.
+fn boundaries(text: &str, boundary: Boundary) -> Vec<usize> {
+    let mut chars = text.char_indices().peekable();
+    let mut offsets = Vec::new();
+    while let Some((index, c)) = chars.next() {
+        let next = chars.peek().map(|&(_, next)| next);
+        let is_boundary = match boundary {
+            Boundary::Word => c.is_whitespace() && !next.is_some_and(char::is_whitespace),
+            Boundary::Sentence => {
+                c == '\n'
+                    || FULL_WIDTH_SENTENCE_ENDS.contains(&c)
+                    || (SENTENCE_ENDS.contains(&c) && next.is_some_and(char::is_whitespace))
+            }
+        };
+        if is_boundary && next.is_some() {
+            offsets.push(index + c.len_utf8());
+        }
+    }
+    offsets
+}
+
+/// The part of `kept`, the end of `before`, that starts on a `boundary`.
+fn snap_start<'a>(before: &str, kept: &'a str, boundary: Boundary) -> &'a str {
+    let dropped = &before[..before.len() - kept.len()];
+    let cut_on_boundary = match boundary {
+        Boundary::Word => dropped.ends_with(char::is_whitespace) || kept.starts_with(char::is_whitespace),
+        Boundary::Sentence => {
+            let ends = boundaries(before, boundary);
+            ends.contains(&dropped.len()) || ends.contains(&dropped.trim_end().len())
+        }
+    };
+    if dropped.is_empty() || cut_on_boundary {
+        return kept;
+    }
+    match boundaries(kept, boundary).first() {
+        Some(&offset) => kept[offset..].trim_start(),
+        None if boundary == Boundary::Sentence => snap_start(before, kept, Boundary::Word),
+        None => kept,
+    }
+}
+
+/// The part of `kept`, the start of `after`, that ends on a `boundary`.
+fn snap_end<'a>(after: &str, kept: &'a str, boundary: Boundary) -> &'a str {
+    let dropped = &after[kept.len()..];
+    let cut_on_boundary = match boundary {
+        Boundary::Word => dropped.starts_with(char::is_whitespace) || kept.ends_with(char::is_whitespace),
+        Boundary::Sentence => boundaries(after, boundary).contains(&kept.len()),
+    };
+    if dropped.is_empty() || cut_on_boundary {
+        return kept;
+    }
+    match boundaries(kept, boundary).last() {
+        Some(&offset) => kept[..offset].trim_end(),
+        None if boundary == Boundary::Sentence => snap_end(after, kept, Boundary::Word),
+        None => kept,
+    }
+}
+
+/// Byte offsets where the sentences of `text` start, from 0 to `text.len()`.
+fn sentence_starts(text: &str) -> Vec<usize> {
+    #[cfg(feature = "segmentation")]
+    let inner = {
+        use unicode_segmentation::UnicodeSegmentation;
+        text.split_sentence_bound_indices().map(|(start, _)| start).collect::<Vec<_>>()
+    };
+    // A sentence starts after the whitespace following the end of the last.
+    #[cfg(not(feature = "segmentation"))]
+    let inner = boundaries(text, Boundary::Sentence)
+        .into_iter()
+        .map(|end| text.len() - text[end..].trim_start().len())
+        .collect::<Vec<_>>();
+    let mut starts = vec![0];
+    starts.extend(inner.into_iter().filter(|&start| start > 0 && start < text.len()));
+    starts.push(text.len());
+    starts.dedup();
+    starts
+}
+
+/// Byte offsets where the paragraphs of `text` start, from 0 to
+/// `text.len()`: after each line break that a non-blank line follows. Word
+/// ends paragraphs with a bare `\r`.
+fn paragraph_starts(text: &str) -> Vec<usize> {
+    const LINE_BREAKS: [char; 2] = ['\r', '\n'];
+    let mut starts = vec![0];
+    starts.extend(
+        text.match_indices(LINE_BREAKS)
+            .map(|(index, _)| index + 1)
+            .filter(|&start| text[start..].split(LINE_BREAKS).next().is_some_and(|line| !line.trim().is_empty())),
+    );
+    starts.push(text.len());
+    starts.dedup();
+    starts
+}
+
+/// How many characters before and after the selection at bytes
+/// `start..end` of `full_text` make up the units (sentences, paragraphs)
+/// it is in and `n` more on each side, the units starting at `starts`.
+/// Trailing whitespace of the last unit is left out.
+fn unit_sides(full_text: &str, starts: &[usize], start: usize, end: usize, n: usize) -> (usize, usize) {
+    let first = starts.iter().rposition(|&offset| offset <= start).unwrap_or(0);
+    let last = starts.iter().position(|&offset| offset >= end).unwrap_or(starts.len() - 1);
+    let from = starts[first.saturating_sub(n)];
+    let to = starts[(last + n).min(starts.len() - 1)];
+    (
+        full_text[from..start].chars().count(),
+        full_text[end..to].trim_end().chars().count(),
+    )
+}
+
+/// How many characters of `before` and `after` the window described by
+/// `size` keeps, before any snapping.
+fn side_chars(before: &str, selection: &str, after: &str, size: &ContextSize) -> (usize, usize) {
+    match size {
+        ContextSize::Chars(n) => (*n, *n),
+        ContextSize::Tokens { budget, counter } => {
+            let remaining = budget.saturating_sub(counter.count(selection));
+            let mut before_chars = fit(before, remaining / 2, counter, last_chars);
+            let before_tokens = counter.count(last_chars(before, before_chars));
+            let after_chars = fit(after, remaining.saturating_sub(before_tokens), counter, first_chars);
+            let after_tokens = counter.count(first_chars(after, after_chars));
+            if after_tokens < remaining.saturating_sub(before_tokens) {
+                before_chars = fit(before, remaining.saturating_sub(after_tokens), counter, last_chars);
+            }
+            (before_chars, after_chars)
+        }
+        ContextSize::Sentences(n) => {
+            let full_text = [before, selection, after].concat();
+            let starts = sentence_starts(&full_text);
+            unit_sides(&full_text, &starts, before.len(), before.len() + selection.len(), *n)
+        }
+        ContextSize::Paragraphs(n) => {
+            let full_text = [before, selection, after].concat();
+            let starts = paragraph_starts(&full_text);
+            unit_sides(&full_text, &starts, before.len(), before.len() + selection.len(), *n)
+        }
+        ContextSize::Snapped { size, .. } => side_chars(before, selection, after, size),
+    }
+}
+
+/// Cuts the window described by `size` out of `full_text`, around the
+/// selection made of its characters `start..end`.
+fn window(full_text: &str, start: usize, end: usize, size: &ContextSize) -> String {
+    window_parts(full_text, start, end, size).to_string()
+}
+
+/// [`window`], split at the selection and noted for this thread.
+fn window_parts(full_text: &str, start: usize, end: usize, size: &ContextSize) -> Context {
+    let (before, selection, after) = (
+        char_slice(full_text, 0, start),
+        char_slice(full_text, start, end),
+        char_slice(full_text, end, usize::MAX),
+    );
+    let (before_chars, after_chars) = side_chars(before, selection, after, size);
+    let (mut kept_before, mut kept_after) = (last_chars(before, before_chars), first_chars(after, after_chars));
+    if let ContextSize::Snapped { boundary, .. } = size {
+        kept_before = snap_start(before, kept_before, *boundary);
+        kept_after = snap_end(after, kept_after, *boundary);
+    }
+    let context = Context {
+        before: kept_before.to_string(),
+        selected: selection.to_string(),
+        after: kept_after.to_string(),
+    };
+    note_split(&context);
+    context
+}
+
+/// The window described by `size` around the first occurrence of
+/// `selected_text` in `full_text`. `None` if it doesn't occur.
+pub(crate) fn around(full_text: &str, selected_text: &str, size: &ContextSize) -> Option<String> {
+    let start_byte = full_text.find(selected_text)?;
+    let start = full_text[..start_byte].chars().count();
+    Some(window(full_text, start, start + selected_text.chars().count(), size))
+}
+
+/// The window described by `size` around the characters `start..end` of
+/// `full_text`, for a backend that reports where the selection is. `None`
+/// if those characters aren't `selected_text`.
+pub(crate) fn around_range(
+    full_text: &str,
+    selected_text: &str,
+    start: usize,
+    end: usize,
+    size: &ContextSize,
+) -> Option<String> {
+    (char_slice(full_text, start, end) == selected_text).then(|| window(full_text, start, end, size))
+}
+
+/// Applies `size` to a context that was captured whole, around the selection
+/// noted for it, else around the first occurrence of `selected_text`.
+/// Returns the context unchanged if the selection can't be found in it.
+pub(crate) fn trim_around(context: &str, selected_text: &str, size: &ContextSize) -> String {
+    if let Some(split) = noted_split_of(context) {
+        let start = split.before.chars().count();
+        return window(context, start, start + split.selected.chars().count(), size);
+    }
+    around(context, selected_text, size).unwrap_or_else(|| context.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    /// Text mixing one- to four-byte characters, combining marks and a ZWJ
+    /// emoji sequence, so window edges land on every kind of boundary.
+    fn mixed_text(max_len: usize) -> impl Strategy<Value = String> {
+        let pieces = prop_oneof![
+            Just("a"),
+            Just(" "),
+            Just("é"),
+            Just("e\u{301}"),
+            Just("中"),
+            Just("文"),
+            Just("😀"),
+            Just("👩\u{200d}💻"),
+            Just("\u{308}"),
+        ];
+        prop::collection::vec(pieces, 0..max_len).prop_map(|pieces| pieces.concat())
+    }
+
+    fn chars(text: &str) -> Vec<char> {
+        text.chars().collect()
+    }
+
+    proptest! {
+        #[test]
+        fn char_slice_matches_char_iteration(text in mixed_text(30), from in 0usize..40, to in 0usize..40) {
+            let expected: String = text.chars().skip(from).take(to.saturating_sub(from)).collect();
+            prop_assert_eq!(char_slice(&text, from, to), expected);
+        }
+
+        #[test]
+        fn chars_window_takes_n_characters_each_side(
+            before in mixed_text(20),
+            selected in mixed_text(10),
+            after in mixed_text(20),
+            n in 0usize..25,
+        ) {
+            let full = format!("{}{}{}", before, selected, after);
+            let start = before.chars().count();
+            let end = start + selected.chars().count();
+            let before_chars = chars(&before);
+            let expected: String = before_chars[before_chars.len().saturating_sub(n)..]
+                .iter()
+                .chain(selected.chars().collect::<Vec<_>>().iter())
+                .chain(after.chars().take(n).collect::<Vec<_>>().iter())
+                .collect();
+            prop_assert_eq!(window(&full, start, end, &ContextSize::Chars(n)), expected);
+        }
+
+        #[test]
+        fn around_returns_a_substring_holding_the_selection(
+            before in mixed_text(20),
+            selected in mixed_text(10),
+            after in mixed_text(20),
+            n in 0usize..25,
+        ) {
+            let full = format!("{}{}{}", before, selected, after);
+            let context = around(&full, &selected, &ContextSize::Chars(n)).expect("selection occurs");
+            prop_assert!(full.contains(&context));
+            prop_assert!(context.contains(&selected));
+            prop_assert!(context.chars().count() <= selected.chars().count() + 2 * n);
+        }
+
+        #[test]
+        fn token_window_stays_within_budget(
+            before in mixed_text(20),
+            selected in mixed_text(10),
+            after in mixed_text(20),
+            budget in 0usize..60,
+        ) {
+            let full = format!("{}{}{}", before, selected, after);
+            let start = before.chars().count();
+            let end = start + selected.chars().count();
+            let counter = TokenCounter::CharsPerToken(1.0);
+            let size = ContextSize::Tokens { budget, counter: counter.clone() };
+            let context = window(&full, start, end, &size);
+            prop_assert!(context.contains(&selected));
+            prop_assert!(counter.count(&context) <= budget.max(counter.count(&selected)));
+        }
+
+        #[test]
+        fn snapping_only_drops_text(
+            before in mixed_text(20),
+            selected in mixed_text(10),
+            after in mixed_text(20),
+            n in 0usize..25,
+            sentence in any::<bool>(),
+        ) {
+            let full = format!("{}{}{}", before, selected, after);
+            let start = before.chars().count();
+            let end = start + selected.chars().count();
+            let boundary = if sentence { Boundary::Sentence } else { Boundary::Word };
+            let cut = window(&full, start, end, &ContextSize::Chars(n));
+            let snapped = window(&full, start, end, &ContextSize::Chars(n).snap_to(boundary));
+            prop_assert!(cut.contains(&snapped));
+            prop_assert!(snapped.contains(&selected));
+        }
+    }
+
+    #[test]
+    fn word_snapping_drops_partial_words() {
+        let full = "the quick brown fox jumps over the lazy dog";
+        let size = ContextSize::Chars(8).snap_to(Boundary::Word);
+        assert_eq!(around(full, "fox", &size).unwrap(), "brown fox jumps");
+        assert_eq!(around(full, "fox", &ContextSize::Chars(8)).unwrap(), "k brown fox jumps o");
+    }
+
+    #[test]
+    fn sentence_snapping_keeps_whole_sentences() {
+        let full = "It rained. The match was called off at noon. Fans went home. Nobody minded.";
+        let size = ContextSize::Chars(20).snap_to(Boundary::Sentence);
+        assert_eq!(around(full, "called off", &size).unwrap(), "The match was called off at noon.");
+        // Too short for a whole sentence on either side: falls back to words.
+        let size = ContextSize::Chars(6).snap_to(Boundary::Sentence);
+        assert_eq!(around(full, "called off", &size).unwrap(), "was called off at");
+    }
+
+    #[test]
+    fn trimming_keeps_the_noted_occurrence() {
+        let full = "one cat, two cat, three cat";
+        let ((), noted) = with_split(|| note_position(full, "cat", 13));
+        let parts = split(full, "cat", noted).unwrap();
+        assert_eq!((parts.before.as_str(), parts.after.as_str()), ("one cat, two ", ", three cat"));
+        note_position(full, "cat", 13);
+        assert_eq!(split(full, "cat", None).unwrap().before, "one ");
+        assert_eq!(trim_around(full, "cat", &ContextSize::Chars(4)), "two cat, th");
+    }
+
+    #[test]
+    fn sentences_mode_keeps_whole_sentences_around_the_selection() {
+        let full = "It rained. The match was called off at noon. Fans went home. Nobody minded. The end.";
+        let around_call = |n| around(full, "called off", &ContextSize::Sentences(n)).unwrap();
+        assert_eq!(around_call(0), "The match was called off at noon.");
+        assert_eq!(around_call(1), "It rained. The match was called off at noon. Fans went home.");
+        assert_eq!(around_call(9), full);
+        // A selection spanning two sentences keeps both.
+        let spanning = around(full, "home. Nobody", &ContextSize::Sentences(0)).unwrap();
+        assert_eq!(spanning, "Fans went home. Nobody minded.");
+    }
+
+    #[test]
+    fn paragraphs_mode_keeps_whole_paragraphs_around_the_selection() {
+        let full = "Title\n\nFirst paragraph.\nSecond one, selected.\n\nThird.\nFourth.\n";
+        let around_selected = |n| around(full, "selected", &ContextSize::Paragraphs(n)).unwrap();
+        assert_eq!(around_selected(0), "Second one, selected.");
+        // The blank line isn't a paragraph of its own.
+        assert_eq!(around_selected(1), "First paragraph.\nSecond one, selected.\n\nThird.");
+        assert_eq!(around_selected(2), full.trim_end());
+    }
+
+    #[test]
+    fn token_window_survives_a_counter_with_overhead() {
+        // Charges 5 tokens even for an empty string, so the counter can use
+        // up more than the budget left over after the selection.
+        let counter = TokenCounter::custom(|text| text.chars().count() + 5);
+        let size = ContextSize::Tokens { budget: 12, counter };
+        assert_eq!(around("one two three", "two", &size).unwrap(), "two");
+        let size = ContextSize::Tokens { budget: 0, counter: TokenCounter::custom(|_| 100) };
+        assert_eq!(around("one two three", "two", &size).unwrap(), "two");
+    }
+
+    #[test]
+    fn range_picks_the_selected_occurrence() {
+        let full = "one cat, two cat, three cat";
+        let size = ContextSize::Chars(4);
+        assert_eq!(around_range(full, "cat", 13, 16, &size).unwrap(), "two cat, th");
+        assert_eq!(around_range(full, "dog", 13, 16, &size), None);
+    }
+}
@@ -0,0 +1,340 @@
+//! Passive capture (`copy-hook` feature): hears the user press Ctrl+C or
+//! Cmd+C themselves and reports what landed on the clipboard, with the app it
+//! came from. Nothing is typed, selected or written to the clipboard, so it
+//! suits deployments where simulated input isn't acceptable.
+//!
+//! A low-level keyboard hook on Windows and a listen-only event tap on macOS
+//! watch for the shortcut. X11 has no global key hook without extra
+//! extensions, so on Linux every new CLIPBOARD owner that isn't the crate
+//! counts as a user copy, whichever way it was made.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::{AppInfo, GetTextError};
+
+/// How long the focused app gets to answer the shortcut.
+const COPY_TIMEOUT: Duration = Duration::from_millis(500);
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+static RUNNING: AtomicBool = AtomicBool::new(false);
+
+/// Text the user copied, reported by [`watch_user_copies`].
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct CopyEvent {
+    pub text: String,
+    /// The app that had the focus when the shortcut was pressed.
+    pub app: Option<AppInfo>,
+}
+
+/// Waits for the clipboard to move on from change count `before`.
+fn wait_for_change(before: u64) -> bool {
+    let started = Instant::now();
+    while started.elapsed() < COPY_TIMEOUT {
+        if crate::suppress::change_count().is_some_and(|count| count != before) {
+            return true;
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+    false
+}
+
+/// Each message is a copy: the clipboard's change count when the shortcut
+/// was pressed, or `None` when the clipboard has already changed.
+fn run_worker(copies: Receiver<Option<u64>>, on_copy: impl Fn(CopyEvent)) {
+    let token = crate::suppress_token();
+    while let Ok(before) = copies.recv() {
+        // The crate's own copies, including ones posted by another process
+        // (System Events for the macOS AppleScript strategy).
+        if token.is_own_change() {
+            continue;
+        }
+        let app = crate::utils::focus_snapshot().map(|focus| AppInfo {
+            name: focus.app_name().to_string(),
+            process_id: focus.process_id(),
+        });
+        // A shortcut the app ignored (nothing selected) leaves the clipboard alone.
+        if before.is_some_and(|before| !wait_for_change(before)) || token.is_own_change() {
+            continue;
+        }
+        match crate::utils::read_clipboard_text() {
+            Ok(text) if !text.is_empty() => on_copy(CopyEvent { text, app }),
+            Ok(_) => {}
+            Err(e) => crate::env_overrides::gst_log!("Reading a user copy failed: {}", e),
+        }
+    }
+}
+
+/// Watches for user copies until dropped. Returned by [`watch_user_copies`].
+pub struct CopyWatch {
+    stop: Arc<AtomicBool>,
+    source: Option<platform::Source>,
+}
+
+impl Drop for CopyWatch {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        if let Some(source) = self.source.take() {
+            source.stop();
+        }
+        RUNNING.store(false, Ordering::SeqCst);
+    }
+}
+
+/// Calls `on_copy` with the text and source app of every copy the user makes
+/// with Ctrl+C, Ctrl+Insert or Cmd+C, without sending any input itself. The
+/// crate's own simulated copies are ignored. The callback runs on a
+/// background thread, one copy at a time.
+///
+/// macOS needs the accessibility permission for the event tap. On Linux
+/// copies are detected from CLIPBOARD ownership on X11, so copies made from
+/// menus are reported too, and Wayland sessions are unsupported.
+///
+/// # Errors
+///
+/// Returns `GetTextError::Other` if a watch is already running,
+/// `GetTextError::Os` if the hook can't be installed and
+/// `GetTextError::Unimplemented` under Wayland.
+pub fn watch_user_copies(on_copy: impl Fn(CopyEvent) + Send + 'static) -> Result<CopyWatch, GetTextError> {
+    if RUNNING.swap(true, Ordering::SeqCst) {
+        return Err(GetTextError::Other("A copy watch is already running".into()));
+    }
+    let stop = Arc::new(AtomicBool::new(false));
+    let (sender, receiver) = mpsc::channel();
+    let source = match platform::Source::start(sender, stop.clone()) {
+        Ok(source) => source,
+        Err(e) => {
+            RUNNING.store(false, Ordering::SeqCst);
+            return Err(e);
+        }
+    };
+    // Ends by itself once the source is gone and its sender with it.
+    thread::spawn(move || run_worker(receiver, on_copy));
+    Ok(CopyWatch {
+        stop,
+        source: Some(source),
+    })
+}
+
+#[cfg(any(target_os = "windows", target_os = "macos"))]
+fn hook_thread_exited() -> GetTextError {
+    GetTextError::Os("The keyboard hook thread exited".into())
+}
+
+#[cfg(target_os = "windows")]
+mod platform {
+    use super::*;
+
+    use parking_lot::Mutex;
+    use windows_sys::Win32::Foundation::{LPARAM, LRESULT, WPARAM};
+    use windows_sys::Win32::System::LibraryLoader::GetModuleHandleW;
+    use windows_sys::Win32::System::Threading::GetCurrentThreadId;
+    use windows_sys::Win32::UI::WindowsAndMessaging::{
+        CallNextHookEx, GetMessageW, PostThreadMessageW, SetWindowsHookExW, UnhookWindowsHookEx, HC_ACTION,
+        KBDLLHOOKSTRUCT, LLKHF_INJECTED, MSG, WH_KEYBOARD_LL, WM_KEYDOWN, WM_KEYUP, WM_QUIT, WM_SYSKEYDOWN,
+        WM_SYSKEYUP,
+    };
+
+    const VK_CONTROL: u32 = 0x11;
+    const VK_LCONTROL: u32 = 0xA2;
+    const VK_RCONTROL: u32 = 0xA3;
+    const VK_INSERT: u32 = 0x2D;
+    const VK_C: u32 = 0x43;
+
+    /// The hook procedure has no user data, so its state lives here.
+    static COPIES: Mutex<Option<Sender<Option<u64>>>> = Mutex::new(None);
+    static CONTROL_DOWN: AtomicBool = AtomicBool::new(false);
+
+    unsafe extern "system" fn keyboard_proc(code: i32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+        if code == HC_ACTION as i32 {
+            let info = &*(lparam as *const KBDLLHOOKSTRUCT);
+            // The crate's simulated Ctrl+C is injected; only real key presses count.
+            if info.flags & LLKHF_INJECTED == 0 {
+                let down = matches!(wparam as u32, WM_KEYDOWN | WM_SYSKEYDOWN);
+                let up = matches!(wparam as u32, WM_KEYUP | WM_SYSKEYUP);
+                match info.vkCode {
+                    VK_CONTROL | VK_LCONTROL | VK_RCONTROL if down || up => CONTROL_DOWN.store(down, Ordering::SeqCst),
+                    VK_C | VK_INSERT if down && CONTROL_DOWN.load(Ordering::SeqCst) => {
+                        if let Some(copies) = COPIES.lock().as_ref() {
+                            let _ = copies.send(crate::suppress::change_count());
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+        CallNextHookEx(0, code, wparam, lparam)
+    }
+
+    pub(super) struct Source {
+        thread: JoinHandle<()>,
+        thread_id: u32,
+    }
+
+    impl Source {
+        pub(super) fn start(copies: Sender<Option<u64>>, _stop: Arc<AtomicBool>) -> Result<Self, GetTextError> {
+            *COPIES.lock() = Some(copies);
+            CONTROL_DOWN.store(false, Ordering::SeqCst);
+            let (ready, started) = mpsc::channel();
+            // The hook is called on the thread that installed it, which must
+            // keep pumping messages; a slow pump lags every key press.
+            let thread = thread::spawn(move || {
+                let module = unsafe { GetModuleHandleW(std::ptr::null()) };
+                let hook = unsafe { SetWindowsHookExW(WH_KEYBOARD_LL, Some(keyboard_proc), module, 0) };
+                if hook == 0 {
+                    let error = std::io::Error::last_os_error();
+                    let _ = ready.send(Err(GetTextError::Os(format!("SetWindowsHookExW failed: {}", error))));
+                    return;
+                }
+                let _ = ready.send(Ok(unsafe { GetCurrentThreadId() }));
+                let mut message: MSG = unsafe { std::mem::zeroed() };
+                while unsafe { GetMessageW(&mut message, 0, 0, 0) } > 0 {}
+                unsafe { UnhookWindowsHookEx(hook) };
+            });
+            let started = started.recv().unwrap_or_else(|_| Err(hook_thread_exited()));
+            match started {
+                Ok(thread_id) => Ok(Source { thread, thread_id }),
+                Err(e) => {
+                    COPIES.lock().take();
+                    Err(e)
+                }
+            }
+        }
+
+        pub(super) fn stop(self) {
+            unsafe { PostThreadMessageW(self.thread_id, WM_QUIT, 0, 0) };
+            let _ = self.thread.join();
+            COPIES.lock().take();
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use super::*;
+
+    use core_foundation::runloop::{kCFRunLoopCommonModes, kCFRunLoopDefaultMode, CFRunLoop};
+    use core_graphics::event::{
+        CGEventFlags, CGEventTap, CGEventTapLocation, CGEventTapOptions, CGEventTapPlacement, CGEventType,
+        EventField,
+    };
+
+    /// `kVK_ANSI_C`.
+    const KEY_C: i64 = 8;
+    /// How often the tap's run loop checks whether it should stop.
+    const STOP_POLL: Duration = Duration::from_millis(200);
+
+    pub(super) struct Source {
+        thread: JoinHandle<()>,
+    }
+
+    impl Source {
+        pub(super) fn start(copies: Sender<Option<u64>>, stop: Arc<AtomicBool>) -> Result<Self, GetTextError> {
+            let (ready, started) = mpsc::channel();
+            let thread = thread::spawn(move || {
+                let own_pid = std::process::id() as i64;
+                let other_modifiers = CGEventFlags::CGEventFlagControl
+                    | CGEventFlags::CGEventFlagAlternate
+                    | CGEventFlags::CGEventFlagShift;
+                let tap = CGEventTap::new(
+                    CGEventTapLocation::Session,
+                    CGEventTapPlacement::HeadInsertEventTap,
+                    CGEventTapOptions::ListenOnly,
+                    vec![CGEventType::KeyDown],
+                    |_proxy, _event_type, event| {
+                        // The crate's simulated Cmd+C is posted from this process;
+                        // the worker skips the ones System Events posts for it.
+                        if event.get_integer_value_field(EventField::EVENT_SOURCE_UNIX_PROCESS_ID) == own_pid {
+                            return None;
+                        }
+                        let flags = event.get_flags();
+                        let is_copy = event.get_integer_value_field(EventField::KEYBOARD_EVENT_KEYCODE) == KEY_C
+                            && flags.contains(CGEventFlags::CGEventFlagCommand)
+                            && !flags.intersects(other_modifiers);
+                        if is_copy {
+                            let _ = copies.send(crate::suppress::change_count());
+                        }
+                        None
+                    },
+                );
+                let Ok(tap) = tap else {
+                    let message = "CGEventTapCreate failed; is the accessibility permission granted?";
+                    let _ = ready.send(Err(GetTextError::Os(message.into())));
+                    return;
+                };
+                let Ok(source) = tap.mach_port.create_runloop_source(0) else {
+                    let _ = ready.send(Err(GetTextError::Os("Couldn't add the event tap to a run loop".into())));
+                    return;
+                };
+                CFRunLoop::get_current().add_source(&source, unsafe { kCFRunLoopCommonModes });
+                tap.enable();
+                let _ = ready.send(Ok(()));
+                while !stop.load(Ordering::SeqCst) {
+                    CFRunLoop::run_in_mode(unsafe { kCFRunLoopDefaultMode }, STOP_POLL, false);
+                }
+            });
+            started.recv().unwrap_or_else(|_| Err(hook_thread_exited()))?;
+            Ok(Source { thread })
+        }
+
+        pub(super) fn stop(self) {
+            let _ = self.thread.join();
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use super::*;
+
+    /// How often the ownership wait checks whether it should stop.
+    const STOP_POLL: Duration = Duration::from_millis(200);
+
+    pub(super) struct Source {
+        thread: JoinHandle<()>,
+    }
+
+    impl Source {
+        pub(super) fn start(copies: Sender<Option<u64>>, stop: Arc<AtomicBool>) -> Result<Self, GetTextError> {
+            // XWayland would only show copies made in X11 apps.
+            if crate::xfixes::wayland_session() {
+                return Err(GetTextError::Unimplemented);
+            }
+            let waiter = crate::xfixes::OwnerWaiter::clipboard()?;
+            let thread = thread::spawn(move || {
+                while !stop.load(Ordering::SeqCst) {
+                    if !waiter.wait(STOP_POLL) {
+                        continue;
+                    }
+                    if copies.send(None).is_err() {
+                        break;
+                    }
+                }
+            });
+            Ok(Source { thread })
+        }
+
+        pub(super) fn stop(self) {
+            let _ = self.thread.join();
+        }
+    }
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos", target_os = "linux")))]
+mod platform {
+    use super::*;
+
+    pub(super) struct Source;
+
+    impl Source {
+        pub(super) fn start(_copies: Sender<Option<u64>>, _stop: Arc<AtomicBool>) -> Result<Self, GetTextError> {
+            Err(GetTextError::Unimplemented)
+        }
+
+        pub(super) fn stop(self) {}
+    }
+}
@@ -0,0 +1,62 @@
+//! The `org.getselectedtext.Daemon` DBus service (`daemon` feature), so
+//! desktop apps can capture selections over the session bus instead of
+//! bundling the accessibility code. `dbus/` holds the activation file and the
+//! systemd user unit that start a binary calling [`run_daemon`].
+
+use zbus::blocking::connection::Builder;
+use zbus::object_server::SignalContext;
+
+use crate::GetTextError;
+
+const BUS_NAME: &str = "org.getselectedtext.Daemon";
+const OBJECT_PATH: &str = "/org/getselectedtext/Daemon";
+
+struct Daemon;
+
+#[zbus::interface(name = "org.getselectedtext.Daemon")]
+impl Daemon {
+    // Captures block zbus's executor thread while they run; the sequence
+    // guard serializes them anyway.
+    fn get_selection(&self) -> zbus::fdo::Result<String> {
+        crate::get_selected_text().map_err(|e| zbus::fdo::Error::Failed(e.to_string()))
+    }
+
+    /// The selection and its context, which is empty when none was found.
+    fn get_selection_with_context(&self) -> zbus::fdo::Result<(String, String)> {
+        crate::get_selected_text_with_context()
+            .map(|(text, context)| (text, context.unwrap_or_default()))
+            .map_err(|e| zbus::fdo::Error::Failed(e.to_string()))
+    }
+
+    #[zbus(signal)]
+    async fn selection_changed(context: &SignalContext<'_>, text: &str) -> zbus::Result<()>;
+}
+
+/// Owns `org.getselectedtext.Daemon` on the session bus and serves it
+/// forever, emitting `SelectionChanged` whenever the PRIMARY selection
+/// changes.
+///
+/// # Errors
+///
+/// `GetTextError::Os` if the session bus can't be reached or the name is
+/// already owned.
+pub fn run_daemon() -> Result<(), GetTextError> {
+    let dbus_error = |e: zbus::Error| GetTextError::Os(format!("DBus: {}", e));
+    let connection = Builder::session()
+        .and_then(|builder| builder.name(BUS_NAME))
+        .and_then(|builder| builder.serve_at(OBJECT_PATH, Daemon))
+        .and_then(|builder| builder.build())
+        .map_err(dbus_error)?;
+    let daemon = connection
+        .object_server()
+        .interface::<_, Daemon>(OBJECT_PATH)
+        .map_err(dbus_error)?;
+
+    crate::watcher::run(
+        || true,
+        |text| {
+            let _ = zbus::block_on(Daemon::selection_changed(daemon.signal_context(), text));
+        },
+    );
+    Ok(())
+}
@@ -0,0 +1,414 @@
+//! Captures a selection as soon as the user finishes making it with the
+//! mouse (`drag-hook` feature), so a host can show its popup right after the
+//! selection without installing a global mouse hook of its own.
+//!
+//! A low-level hook (a `WH_MOUSE_LL` hook on Windows, a listen-only event tap
+//! on macOS, pointer polling on X11) reports left-button presses and
+//! releases. A release that ends a drag, or a double or triple click, counts
+//! as a finished selection unless the press landed on window chrome, and
+//! triggers [`crate::get_selected_text`].
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::GetTextError;
+
+/// How far the pointer must move between press and release to count as a
+/// drag rather than a click.
+const DRAG_THRESHOLD: f64 = 4.0;
+/// Presses closer together than this, near the same spot, extend a multi-click.
+const MULTI_CLICK_INTERVAL: Duration = Duration::from_millis(500);
+/// Lets the app finish updating its selection after the release.
+const SETTLE_DELAY: Duration = Duration::from_millis(40);
+
+static RUNNING: AtomicBool = AtomicBool::new(false);
+
+type Point = (f64, f64);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum MouseEvent {
+    Down(Point),
+    Up(Point),
+}
+
+fn distance(a: Point, b: Point) -> f64 {
+    ((a.0 - b.0).powi(2) + (a.1 - b.1).powi(2)).sqrt()
+}
+
+/// Tells selecting gestures apart from plain clicks.
+#[derive(Debug, Default)]
+struct Gesture {
+    pressed: Option<Point>,
+    last_release: Option<(Point, Instant)>,
+    clicks: u32,
+}
+
+impl Gesture {
+    fn press(&mut self, at: Point, now: Instant) {
+        let repeated = self.last_release.is_some_and(|(released, when)| {
+            now.duration_since(when) < MULTI_CLICK_INTERVAL && distance(released, at) < DRAG_THRESHOLD
+        });
+        self.clicks = if repeated { self.clicks + 1 } else { 1 };
+        self.pressed = Some(at);
+    }
+
+    /// Where the gesture started if the release finishes a selection: the
+    /// end of a drag, or of a double or triple click.
+    fn release(&mut self, at: Point, now: Instant) -> Option<Point> {
+        let start = self.pressed.take()?;
+        self.last_release = Some((at, now));
+        (distance(start, at) >= DRAG_THRESHOLD || self.clicks >= 2).then_some(start)
+    }
+}
+
+fn is_chrome_at(at: Point) -> Option<bool> {
+    #[cfg(target_os = "windows")]
+    return crate::windows::is_chrome_at(at.0 as i32, at.1 as i32);
+    #[cfg(target_os = "macos")]
+    return crate::macos::is_chrome_at(at.0, at.1);
+    #[allow(unreachable_code)]
+    {
+        let _ = at;
+        None
+    }
+}
+
+fn run_worker(events: Receiver<MouseEvent>, on_selection: impl Fn(String)) {
+    let mut gesture = Gesture::default();
+    while let Ok(event) = events.recv() {
+        let start = match event {
+            MouseEvent::Down(at) => {
+                gesture.press(at, Instant::now());
+                continue;
+            }
+            MouseEvent::Up(at) => gesture.release(at, Instant::now()),
+        };
+        let Some(start) = start else {
+            continue;
+        };
+        if is_chrome_at(start) == Some(true) {
+            continue;
+        }
+        thread::sleep(SETTLE_DELAY);
+        match crate::get_selected_text() {
+            Ok(text) if !text.is_empty() => on_selection(text),
+            Ok(_) => {}
+            Err(e) => crate::env_overrides::gst_log!("Capture after a drag failed: {}", e),
+        }
+        // Whatever the pointer did during the capture belongs to no gesture.
+        while events.try_recv().is_ok() {}
+        gesture = Gesture::default();
+    }
+}
+
+/// Watches for finished mouse selections until dropped. Returned by
+/// [`watch_selection_drags`].
+pub struct DragWatch {
+    stop: Arc<AtomicBool>,
+    source: Option<platform::Source>,
+}
+
+impl Drop for DragWatch {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        if let Some(source) = self.source.take() {
+            source.stop();
+        }
+        RUNNING.store(false, Ordering::SeqCst);
+    }
+}
+
+/// Calls `on_selection` with the selected text each time the user finishes
+/// selecting with the mouse: a drag ends, or a double or triple click
+/// selects a word or line. Drags that start on title bars, scroll bars and
+/// other window chrome are ignored, as are empty captures. The callback runs
+/// on a background thread, one capture at a time.
+///
+/// macOS needs the accessibility permission for the event tap. On Linux
+/// this watches the X11 pointer, so under Wayland it only sees XWayland
+/// windows.
+///
+/// # Errors
+///
+/// Returns `GetTextError::Other` if a watch is already running and
+/// `GetTextError::Os` if the hook can't be installed.
+pub fn watch_selection_drags(on_selection: impl Fn(String) + Send + 'static) -> Result<DragWatch, GetTextError> {
+    if RUNNING.swap(true, Ordering::SeqCst) {
+        return Err(GetTextError::Other("A drag watch is already running".into()));
+    }
+    let stop = Arc::new(AtomicBool::new(false));
+    let (sender, receiver) = mpsc::channel();
+    let source = match platform::Source::start(sender, stop.clone()) {
+        Ok(source) => source,
+        Err(e) => {
+            RUNNING.store(false, Ordering::SeqCst);
+            return Err(e);
+        }
+    };
+    // Ends by itself once the source is gone and its sender with it.
+    thread::spawn(move || run_worker(receiver, on_selection));
+    Ok(DragWatch {
+        stop,
+        source: Some(source),
+    })
+}
+
+#[cfg(any(target_os = "windows", target_os = "macos"))]
+fn hook_thread_exited() -> GetTextError {
+    GetTextError::Os("The mouse hook thread exited".into())
+}
+
+#[cfg(target_os = "windows")]
+mod platform {
+    use super::*;
+
+    use parking_lot::Mutex;
+    use windows_sys::Win32::Foundation::{LPARAM, LRESULT, WPARAM};
+    use windows_sys::Win32::System::LibraryLoader::GetModuleHandleW;
+    use windows_sys::Win32::System::Threading::GetCurrentThreadId;
+    use windows_sys::Win32::UI::WindowsAndMessaging::{
+        CallNextHookEx, GetMessageW, PostThreadMessageW, SetWindowsHookExW, UnhookWindowsHookEx, HC_ACTION,
+        LLMHF_INJECTED, MSG, MSLLHOOKSTRUCT, WH_MOUSE_LL, WM_LBUTTONDOWN, WM_LBUTTONUP, WM_QUIT,
+    };
+
+    /// The hook procedure has no user data, so its channel lives here.
+    static EVENTS: Mutex<Option<Sender<MouseEvent>>> = Mutex::new(None);
+
+    unsafe extern "system" fn mouse_proc(code: i32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+        if code == HC_ACTION as i32 {
+            let info = &*(lparam as *const MSLLHOOKSTRUCT);
+            // Clicks synthesized by the crate (or anyone else) aren't the user's.
+            if info.flags & LLMHF_INJECTED == 0 {
+                let at = (info.pt.x as f64, info.pt.y as f64);
+                let event = match wparam as u32 {
+                    WM_LBUTTONDOWN => Some(MouseEvent::Down(at)),
+                    WM_LBUTTONUP => Some(MouseEvent::Up(at)),
+                    _ => None,
+                };
+                if let (Some(event), Some(events)) = (event, EVENTS.lock().as_ref()) {
+                    let _ = events.send(event);
+                }
+            }
+        }
+        CallNextHookEx(0, code, wparam, lparam)
+    }
+
+    pub(super) struct Source {
+        thread: JoinHandle<()>,
+        thread_id: u32,
+    }
+
+    impl Source {
+        pub(super) fn start(events: Sender<MouseEvent>, _stop: Arc<AtomicBool>) -> Result<Self, GetTextError> {
+            *EVENTS.lock() = Some(events);
+            let (ready, started) = mpsc::channel();
+            // The hook is called on the thread that installed it, which must
+            // keep pumping messages; a slow pump lags the whole system's mouse.
+            let thread = thread::spawn(move || {
+                let module = unsafe { GetModuleHandleW(std::ptr::null()) };
+                let hook = unsafe { SetWindowsHookExW(WH_MOUSE_LL, Some(mouse_proc), module, 0) };
+                if hook == 0 {
+                    let error = std::io::Error::last_os_error();
+                    let _ = ready.send(Err(GetTextError::Os(format!("SetWindowsHookExW failed: {}", error))));
+                    return;
+                }
+                let _ = ready.send(Ok(unsafe { GetCurrentThreadId() }));
+                let mut message: MSG = unsafe { std::mem::zeroed() };
+                while unsafe { GetMessageW(&mut message, 0, 0, 0) } > 0 {}
+                unsafe { UnhookWindowsHookEx(hook) };
+            });
+            let started = started.recv().unwrap_or_else(|_| Err(hook_thread_exited()));
+            match started {
+                Ok(thread_id) => Ok(Source { thread, thread_id }),
+                Err(e) => {
+                    EVENTS.lock().take();
+                    Err(e)
+                }
+            }
+        }
+
+        pub(super) fn stop(self) {
+            unsafe { PostThreadMessageW(self.thread_id, WM_QUIT, 0, 0) };
+            let _ = self.thread.join();
+            EVENTS.lock().take();
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use super::*;
+
+    use core_foundation::runloop::{kCFRunLoopCommonModes, kCFRunLoopDefaultMode, CFRunLoop};
+    use core_graphics::event::{
+        CGEventTap, CGEventTapLocation, CGEventTapOptions, CGEventTapPlacement, CGEventType, EventField,
+    };
+
+    /// How often the tap's run loop checks whether it should stop.
+    const STOP_POLL: Duration = Duration::from_millis(200);
+
+    pub(super) struct Source {
+        thread: JoinHandle<()>,
+    }
+
+    impl Source {
+        pub(super) fn start(events: Sender<MouseEvent>, stop: Arc<AtomicBool>) -> Result<Self, GetTextError> {
+            let (ready, started) = mpsc::channel();
+            let thread = thread::spawn(move || {
+                let own_pid = std::process::id() as i64;
+                let tap = CGEventTap::new(
+                    CGEventTapLocation::Session,
+                    CGEventTapPlacement::HeadInsertEventTap,
+                    CGEventTapOptions::ListenOnly,
+                    vec![CGEventType::LeftMouseDown, CGEventType::LeftMouseUp],
+                    |_proxy, event_type, event| {
+                        // Clicks posted by this process (the mouse fallback) aren't the user's.
+                        if event.get_integer_value_field(EventField::EVENT_SOURCE_UNIX_PROCESS_ID) == own_pid {
+                            return None;
+                        }
+                        let location = event.location();
+                        let at = (location.x, location.y);
+                        let event = match event_type {
+                            CGEventType::LeftMouseDown => MouseEvent::Down(at),
+                            CGEventType::LeftMouseUp => MouseEvent::Up(at),
+                            _ => return None,
+                        };
+                        let _ = events.send(event);
+                        None
+                    },
+                );
+                let Ok(tap) = tap else {
+                    let message = "CGEventTapCreate failed; is the accessibility permission granted?";
+                    let _ = ready.send(Err(GetTextError::Os(message.into())));
+                    return;
+                };
+                let Ok(source) = tap.mach_port.create_runloop_source(0) else {
+                    let _ = ready.send(Err(GetTextError::Os("Couldn't add the event tap to a run loop".into())));
+                    return;
+                };
+                CFRunLoop::get_current().add_source(&source, unsafe { kCFRunLoopCommonModes });
+                tap.enable();
+                let _ = ready.send(Ok(()));
+                while !stop.load(Ordering::SeqCst) {
+                    CFRunLoop::run_in_mode(unsafe { kCFRunLoopDefaultMode }, STOP_POLL, false);
+                }
+            });
+            started.recv().unwrap_or_else(|_| Err(hook_thread_exited()))?;
+            Ok(Source { thread })
+        }
+
+        pub(super) fn stop(self) {
+            let _ = self.thread.join();
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use super::*;
+
+    use x11rb::connection::Connection;
+    use x11rb::protocol::xproto::{ConnectionExt as _, KeyButMask};
+
+    /// X11 has no global mouse hook without XInput2 or XRecord; polling the
+    /// pointer this often catches every press a person makes.
+    const POLL_INTERVAL: Duration = Duration::from_millis(15);
+
+    pub(super) struct Source {
+        thread: JoinHandle<()>,
+    }
+
+    impl Source {
+        pub(super) fn start(events: Sender<MouseEvent>, stop: Arc<AtomicBool>) -> Result<Self, GetTextError> {
+            let (connection, screen) =
+                x11rb::connect(None).map_err(|e| GetTextError::Os(format!("X11: {}", e)))?;
+            let root = connection.setup().roots[screen].root;
+            let thread = thread::spawn(move || {
+                let mut pressed = false;
+                while !stop.load(Ordering::SeqCst) {
+                    thread::sleep(POLL_INTERVAL);
+                    let pointer = connection.query_pointer(root).ok().and_then(|cookie| cookie.reply().ok());
+                    let Some(pointer) = pointer else {
+                        break;
+                    };
+                    let down = pointer.mask.contains(KeyButMask::BUTTON1);
+                    if down == pressed {
+                        continue;
+                    }
+                    pressed = down;
+                    let at = (pointer.root_x as f64, pointer.root_y as f64);
+                    let event = if down { MouseEvent::Down(at) } else { MouseEvent::Up(at) };
+                    if events.send(event).is_err() {
+                        break;
+                    }
+                }
+            });
+            Ok(Source { thread })
+        }
+
+        pub(super) fn stop(self) {
+            let _ = self.thread.join();
+        }
+    }
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos", target_os = "linux")))]
+mod platform {
+    use super::*;
+
+    pub(super) struct Source;
+
+    impl Source {
+        pub(super) fn start(_events: Sender<MouseEvent>, _stop: Arc<AtomicBool>) -> Result<Self, GetTextError> {
+            Err(GetTextError::Unimplemented)
+        }
+
+        pub(super) fn stop(self) {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_click_is_not_a_selection() {
+        let mut gesture = Gesture::default();
+        let now = Instant::now();
+        gesture.press((10.0, 10.0), now);
+        assert_eq!(gesture.release((11.0, 10.0), now), None);
+    }
+
+    #[test]
+    fn a_drag_is_a_selection_from_its_start() {
+        let mut gesture = Gesture::default();
+        let now = Instant::now();
+        gesture.press((10.0, 10.0), now);
+        assert_eq!(gesture.release((80.0, 12.0), now), Some((10.0, 10.0)));
+    }
+
+    #[test]
+    fn a_double_click_is_a_selection_but_two_slow_clicks_are_not() {
+        let mut gesture = Gesture::default();
+        let now = Instant::now();
+        gesture.press((10.0, 10.0), now);
+        gesture.release((10.0, 10.0), now);
+        gesture.press((10.0, 10.0), now + Duration::from_millis(200));
+        assert!(gesture.release((10.0, 10.0), now + Duration::from_millis(250)).is_some());
+
+        let mut gesture = Gesture::default();
+        gesture.press((10.0, 10.0), now);
+        gesture.release((10.0, 10.0), now);
+        gesture.press((10.0, 10.0), now + Duration::from_secs(1));
+        assert_eq!(gesture.release((10.0, 10.0), now + Duration::from_secs(1)), None);
+    }
+
+    #[test]
+    fn a_release_without_a_press_is_ignored() {
+        let mut gesture = Gesture::default();
+        assert_eq!(gesture.release((10.0, 10.0), Instant::now()), None);
+    }
+}
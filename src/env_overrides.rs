@@ -0,0 +1,101 @@
+//! Overrides read from `GST_*` environment variables, for debugging a capture
+//! problem on a user's machine without rebuilding the host app:
+//!
+//! - `GST_STRATEGY=clipboard,uia` tries exactly these strategies, in this
+//!   order, whatever the options and app quirks say.
+//! - `GST_TIMEOUT_MS=800` waits this long for the app to answer a simulated
+//!   copy instead of 250 ms (or what `calibrate()` measured).
+//! - `GST_DISABLE_SELECT_ALL=1` never reads context by selecting everything.
+//! - `GST_LOG=1` prints each strategy attempt and why steps were skipped to
+//!   stderr.
+//!
+//! The variables are read once, on first use.
+
+use std::borrow::Cow;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use crate::{GetTextOptions, Strategy};
+
+#[derive(Debug, Default)]
+struct Overrides {
+    strategies: Option<Vec<Strategy>>,
+    copy_timeout: Option<Duration>,
+    disable_select_all: bool,
+}
+
+fn flag(name: &str) -> bool {
+    std::env::var(name).is_ok_and(|value| !matches!(value.trim(), "" | "0" | "false" | "off"))
+}
+
+fn overrides() -> &'static Overrides {
+    static OVERRIDES: OnceLock<Overrides> = OnceLock::new();
+    OVERRIDES.get_or_init(|| {
+        let strategies = std::env::var("GST_STRATEGY").ok().map(|names| {
+            names
+                .split(',')
+                .map(str::trim)
+                .filter(|name| !name.is_empty())
+                .filter_map(|name| {
+                    let strategy = Strategy::from_name(name);
+                    if strategy.is_none() {
+                        crate::env_overrides::gst_log!("GST_STRATEGY: ignoring unknown strategy \"{}\"", name);
+                    }
+                    strategy
+                })
+                .collect::<Vec<_>>()
+        });
+        let copy_timeout = std::env::var("GST_TIMEOUT_MS")
+            .ok()
+            .and_then(|ms| ms.trim().parse().ok())
+            .map(Duration::from_millis);
+        Overrides {
+            strategies,
+            copy_timeout,
+            disable_select_all: flag("GST_DISABLE_SELECT_ALL"),
+        }
+    })
+}
+
+/// `options` with `GST_STRATEGY` applied.
+pub(crate) fn apply(options: &GetTextOptions) -> Cow<'_, GetTextOptions> {
+    match &overrides().strategies {
+        Some(strategies) => Cow::Owned(options.clone().strategies(strategies)),
+        None => Cow::Borrowed(options),
+    }
+}
+
+/// How long to wait for the app to answer a simulated copy.
+pub(crate) fn copy_timeout(default: Duration) -> Duration {
+    overrides().copy_timeout.unwrap_or(default)
+}
+
+pub(crate) fn select_all_disabled() -> bool {
+    overrides().disable_select_all
+}
+
+// Apart from the other overrides, which log while they are read.
+pub(crate) fn log_enabled() -> bool {
+    static LOG: OnceLock<bool> = OnceLock::new();
+    *LOG.get_or_init(|| flag("GST_LOG"))
+}
+
+/// Prints to stderr when `GST_LOG` is set, in release builds too.
+macro_rules! gst_log {
+    ($($arg:tt)*) => {
+        if $crate::env_overrides::log_enabled() {
+            eprintln!("[get-selected-text] {}", format_args!($($arg)*));
+        }
+    };
+}
+
+pub(crate) use gst_log;
+
+/// A strategy's outcome for `gst_log!`, without the selected text itself.
+pub(crate) fn describe(result: &Result<String, Box<dyn std::error::Error>>) -> String {
+    match result {
+        Ok(text) if text.is_empty() => "empty".to_string(),
+        Ok(text) => format!("{} chars", text.chars().count()),
+        Err(e) => format!("failed: {}", e),
+    }
+}
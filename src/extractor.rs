@@ -0,0 +1,97 @@
+//! Application-provided extractors, for apps the crate has no integration
+//! for (an in-house tool's automation API, a plugin socket, ...).
+
+use std::sync::Arc;
+
+use parking_lot::RwLock;
+
+use crate::GetTextError;
+
+/// The focused application, as passed to [`SelectionExtractor::matches`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct AppInfo {
+    /// The name the OS reports for the app, e.g. "Safari" or "Code.exe".
+    pub name: String,
+    pub process_id: u64,
+}
+
+/// What the caller asked for.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct ExtractContext {
+    pub app: AppInfo,
+    /// Whether the call wants context around the selection. Extractors may
+    /// return it regardless; it is dropped when not wanted.
+    pub wants_context: bool,
+}
+
+/// A selection returned by an extractor.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Selection {
+    pub text: String,
+    pub context: Option<String>,
+}
+
+impl Selection {
+    pub fn new(text: impl Into<String>) -> Self {
+        Selection {
+            text: text.into(),
+            context: None,
+        }
+    }
+
+    pub fn with_context(mut self, context: impl Into<String>) -> Self {
+        self.context = Some(context.into());
+        self
+    }
+}
+
+/// A source of selections for specific apps, registered with
+/// [`register_extractor`].
+pub trait SelectionExtractor: Send + Sync {
+    /// Whether this extractor handles `app`. Called on every capture, so keep
+    /// it cheap.
+    fn matches(&self, app: &AppInfo) -> bool;
+
+    /// Reads the selection. An error, or an empty text, hands the call on to
+    /// the next matching extractor and then to the built-in strategies.
+    fn extract(&self, context: &ExtractContext) -> Result<Selection, GetTextError>;
+}
+
+static EXTRACTORS: RwLock<Vec<Arc<dyn SelectionExtractor>>> = RwLock::new(Vec::new());
+
+/// Adds `extractor` to every capture in this process. Registered extractors
+/// run before the built-in integrations and strategies, in registration
+/// order.
+pub fn register_extractor(extractor: impl SelectionExtractor + 'static) {
+    EXTRACTORS.write().push(Arc::new(extractor));
+}
+
+/// Removes every registered extractor.
+pub fn clear_extractors() {
+    EXTRACTORS.write().clear();
+}
+
+/// The first non-empty selection from a matching extractor.
+pub(crate) fn run(wants_context: bool) -> Option<Selection> {
+    // Cloned so extractors can register others without deadlocking.
+    let extractors = EXTRACTORS.read().clone();
+    if extractors.is_empty() {
+        return None;
+    }
+    let focus = crate::utils::focus_snapshot()?;
+    let context = ExtractContext {
+        app: AppInfo {
+            name: focus.app_name().to_string(),
+            process_id: focus.process_id(),
+        },
+        wants_context,
+    };
+    extractors
+        .iter()
+        .filter(|extractor| extractor.matches(&context.app))
+        .filter_map(|extractor| extractor.extract(&context).ok())
+        .find(|selection| !selection.text.is_empty())
+}
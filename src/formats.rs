@@ -0,0 +1,30 @@
+/// A kind of data the source application can put on the clipboard when its
+/// selection is copied.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[non_exhaustive]
+pub enum ClipboardFormat {
+    PlainText,
+    Html,
+    Rtf,
+    Image,
+    Files,
+}
+
+/// The selection in each text format the source app offered, read from one
+/// simulated copy by `get_selection_formats_bundle()`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct FormatsBundle {
+    pub text: Option<String>,
+    pub html: Option<crate::HtmlFragment>,
+    pub rtf: Option<String>,
+}
+
+/// Sorts `formats` and drops duplicates, for platforms that report several
+/// native types mapping to the same format.
+#[cfg_attr(not(any(target_os = "macos", target_os = "linux")), allow(dead_code))]
+pub(crate) fn normalize(mut formats: Vec<ClipboardFormat>) -> Vec<ClipboardFormat> {
+    formats.sort();
+    formats.dedup();
+    formats
+}
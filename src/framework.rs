@@ -0,0 +1,236 @@
+//! The UI framework behind an app, recognized from the libraries it loads
+//! and how its bundle is laid out, so that apps nobody wrote a quirk for
+//! still get a strategy order that suits them.
+
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+
+use lru::LruCache;
+use parking_lot::Mutex;
+
+use crate::Strategy;
+
+const MAX_TRACKED_PROCESSES: usize = 100;
+
+/// The UI framework an app is built with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Framework {
+    /// Chromium with Node.js: VS Code, Slack, Discord, Obsidian...
+    Electron,
+    /// Qt 5 or 6.
+    Qt,
+    /// Swing, AWT or JavaFX on a JVM.
+    Java,
+    /// An iPad app built for macOS with Mac Catalyst.
+    Catalyst,
+    /// Windows Presentation Foundation.
+    Wpf,
+    /// The platform's own toolkit (AppKit, Win32/WinUI, GTK), or none of
+    /// the above.
+    Native,
+}
+
+impl Framework {
+    /// The strategies to start from for apps of this framework, when the app
+    /// has no quirk of its own. `None` keeps the platform's default order.
+    pub(crate) fn strategies(self) -> Option<&'static [Strategy]> {
+        match self {
+            // Chromium builds its AX tree only once an assistive app has
+            // asked for it, so the first AX reads come back empty.
+            #[cfg(target_os = "macos")]
+            Framework::Electron => Some(&[Strategy::AppleScript, Strategy::Accessibility]),
+            // The Java accessibility bridge is often off, leaving AX a bare
+            // window that is slow to walk.
+            #[cfg(target_os = "macos")]
+            Framework::Java => Some(&[Strategy::AppleScript, Strategy::Accessibility]),
+            // WPF text controls implement TextPattern fully.
+            #[cfg(target_os = "windows")]
+            Framework::Wpf => Some(&[Strategy::Uia, Strategy::Clipboard]),
+            _ => None,
+        }
+    }
+}
+
+static CACHE: Mutex<Option<LruCache<u64, (PathBuf, Framework)>>> = Mutex::new(None);
+
+/// The framework of the process `process_id`, or `Framework::Native` if
+/// nothing else is recognized (including when the process can't be
+/// inspected, e.g. one running as another user).
+///
+/// Results are cached per process, as long as its executable stays the same.
+pub fn detect_framework(process_id: u64) -> Framework {
+    let Some(executable) = executable_path(process_id) else {
+        return Framework::Native;
+    };
+    let mut cache = CACHE.lock();
+    let cache = cache.get_or_insert_with(|| LruCache::new(NonZeroUsize::new(MAX_TRACKED_PROCESSES).unwrap()));
+    if let Some((cached_executable, framework)) = cache.get(&process_id) {
+        if *cached_executable == executable {
+            return *framework;
+        }
+    }
+    let framework = detect(process_id, &executable);
+    crate::env_overrides::gst_log!("{} is a {:?} app", executable.display(), framework);
+    cache.put(process_id, (executable, framework));
+    framework
+}
+
+/// The framework of the focused app.
+pub fn focused_framework() -> Framework {
+    crate::utils::focus_snapshot()
+        .map(|focus| detect_framework(focus.process_id()))
+        .unwrap_or(Framework::Native)
+}
+
+/// The strategies to start from in the focused app: its quirk, else its
+/// framework's order, else `default_order`.
+pub(crate) fn default_order(app_name: &str, process_id: Option<u64>, default_order: &[Strategy]) -> Vec<Strategy> {
+    crate::quirks::app_quirks(app_name)
+        .strategies
+        .or_else(|| Some(detect_framework(process_id?).strategies()?.to_vec()))
+        .unwrap_or_else(|| default_order.to_vec())
+}
+
+/// Electron apps ship their code as `resources/app.asar` (or unpacked in
+/// `resources/app`) next to the executable, or inside the bundle on macOS.
+fn has_electron_resources(resources: &Path) -> bool {
+    resources.join("app.asar").is_file() || resources.join("app").is_dir()
+}
+
+#[cfg(target_os = "macos")]
+pub(crate) fn executable_path(process_id: u64) -> Option<PathBuf> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let mut buffer = vec![0u8; libc::PROC_PIDPATHINFO_MAXSIZE as usize];
+    let len = unsafe { libc::proc_pidpath(process_id as i32, buffer.as_mut_ptr().cast(), buffer.len() as u32) };
+    if len <= 0 {
+        return None;
+    }
+    Some(PathBuf::from(std::ffi::OsStr::from_bytes(&buffer[..len as usize])))
+}
+
+#[cfg(target_os = "macos")]
+fn detect(_process_id: u64, executable: &Path) -> Framework {
+    // .../Foo.app/Contents/MacOS/Foo
+    let Some(contents) = executable
+        .ancestors()
+        .find(|dir| dir.extension().is_some_and(|ext| ext == "app"))
+        .map(|bundle| bundle.join("Contents"))
+    else {
+        return Framework::Native;
+    };
+    let frameworks = contents.join("Frameworks");
+    if frameworks.join("Electron Framework.framework").is_dir() || has_electron_resources(&contents.join("Resources"))
+    {
+        return Framework::Electron;
+    }
+    if frameworks.join("QtCore.framework").is_dir() {
+        return Framework::Qt;
+    }
+    // Bundled runtimes (jpackage, JetBrains Runtime) or a launcher for the
+    // system JVM.
+    if ["runtime", "jbr", "Java", "PlugIns/jdk", "PlugIns/jre"]
+        .iter()
+        .any(|dir| contents.join(dir).is_dir())
+    {
+        return Framework::Java;
+    }
+    // Binary plists keep keys as plain ASCII too.
+    if std::fs::read(contents.join("Info.plist"))
+        .is_ok_and(|plist| plist.windows(b"UIDeviceFamily".len()).any(|key| key == b"UIDeviceFamily"))
+    {
+        return Framework::Catalyst;
+    }
+    Framework::Native
+}
+
+#[cfg(target_os = "windows")]
+pub(crate) fn executable_path(process_id: u64) -> Option<PathBuf> {
+    use std::os::windows::ffi::OsStringExt;
+    use windows_sys::Win32::Foundation::CloseHandle;
+    use windows_sys::Win32::System::Threading::{
+        OpenProcess, QueryFullProcessImageNameW, PROCESS_NAME_WIN32, PROCESS_QUERY_LIMITED_INFORMATION,
+    };
+
+    // Unlike a module snapshot, this is cheap enough to run on every capture.
+    let process = unsafe { OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, process_id as u32) };
+    if process == 0 {
+        return None;
+    }
+    let mut buffer = vec![0u16; 32_768];
+    let mut len = buffer.len() as u32;
+    let ok = unsafe { QueryFullProcessImageNameW(process, PROCESS_NAME_WIN32, buffer.as_mut_ptr(), &mut len) } != 0;
+    unsafe { CloseHandle(process) };
+    ok.then(|| PathBuf::from(std::ffi::OsString::from_wide(&buffer[..len as usize])))
+}
+
+#[cfg(target_os = "windows")]
+fn loaded_modules(process_id: u64) -> Option<Vec<PathBuf>> {
+    use std::os::windows::ffi::OsStringExt;
+    use windows_sys::Win32::Foundation::{CloseHandle, INVALID_HANDLE_VALUE};
+    use windows_sys::Win32::System::Diagnostics::ToolHelp::{
+        CreateToolhelp32Snapshot, Module32FirstW, Module32NextW, MODULEENTRY32W, TH32CS_SNAPMODULE,
+        TH32CS_SNAPMODULE32,
+    };
+
+    let snapshot = unsafe { CreateToolhelp32Snapshot(TH32CS_SNAPMODULE | TH32CS_SNAPMODULE32, process_id as u32) };
+    if snapshot == INVALID_HANDLE_VALUE {
+        return None;
+    }
+    let mut modules = Vec::new();
+    let mut entry: MODULEENTRY32W = unsafe { std::mem::zeroed() };
+    entry.dwSize = std::mem::size_of::<MODULEENTRY32W>() as u32;
+    let mut more = unsafe { Module32FirstW(snapshot, &mut entry) } != 0;
+    while more {
+        let len = entry.szExePath.iter().position(|&c| c == 0).unwrap_or(entry.szExePath.len());
+        modules.push(PathBuf::from(std::ffi::OsString::from_wide(&entry.szExePath[..len])));
+        more = unsafe { Module32NextW(snapshot, &mut entry) } != 0;
+    }
+    unsafe { CloseHandle(snapshot) };
+    Some(modules)
+}
+
+/// Only called when the cache has no answer: the module snapshot is slow.
+#[cfg(target_os = "windows")]
+fn detect(process_id: u64, executable: &Path) -> Framework {
+    if executable.parent().is_some_and(|dir| has_electron_resources(&dir.join("resources"))) {
+        return Framework::Electron;
+    }
+    let modules = loaded_modules(process_id).unwrap_or_default();
+    let loaded = |prefixes: &[&str]| {
+        modules.iter().any(|module| {
+            let name = module.file_name().map(|name| name.to_string_lossy().to_lowercase()).unwrap_or_default();
+            prefixes.iter().any(|prefix| name.starts_with(prefix))
+        })
+    };
+    if loaded(&["qt5core", "qt6core"]) {
+        Framework::Qt
+    } else if loaded(&["jvm.dll"]) {
+        Framework::Java
+    } else if loaded(&["wpfgfx_", "presentationcore"]) {
+        Framework::Wpf
+    } else {
+        Framework::Native
+    }
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+pub(crate) fn executable_path(process_id: u64) -> Option<PathBuf> {
+    std::fs::read_link(format!("/proc/{}/exe", process_id)).ok()
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+fn detect(process_id: u64, executable: &Path) -> Framework {
+    if executable.parent().is_some_and(|dir| has_electron_resources(&dir.join("resources"))) {
+        return Framework::Electron;
+    }
+    let maps = std::fs::read_to_string(format!("/proc/{}/maps", process_id)).unwrap_or_default();
+    if maps.contains("libQt5Core") || maps.contains("libQt6Core") {
+        Framework::Qt
+    } else if maps.contains("libjvm.so") {
+        Framework::Java
+    } else {
+        Framework::Native
+    }
+}
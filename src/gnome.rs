@@ -0,0 +1,46 @@
+//! Client for the companion GNOME Shell extension in `gnome-extension/`
+//! (`gnome` feature). GNOME's Wayland session lets no other client see the
+//! focused window or read the PRIMARY selection without focus.
+
+use zbus::blocking::Connection;
+
+use crate::compositor::FocusedWindow;
+use crate::GetTextError;
+
+// The extension exports its object on gnome-shell's own bus connection.
+const DESTINATION: &str = "org.gnome.Shell";
+const OBJECT_PATH: &str = "/org/gnome/Shell/Extensions/GetSelectedText";
+const INTERFACE: &str = "org.gnome.Shell.Extensions.GetSelectedText";
+
+fn call<R>(method: &str) -> Result<R, GetTextError>
+where
+    R: for<'d> zbus::zvariant::DynamicDeserialize<'d>,
+{
+    let connection = Connection::session().map_err(|e| GetTextError::Os(format!("DBus session bus: {}", e)))?;
+    let reply = connection
+        .call_method(Some(DESTINATION), OBJECT_PATH, Some(INTERFACE), method, &())
+        .map_err(|e| GetTextError::Os(format!("GNOME extension call {} failed: {}", method, e)))?;
+    reply
+        .body()
+        .deserialize::<R>()
+        .map_err(|e| GetTextError::Os(format!("GNOME extension reply to {}: {}", method, e)))
+}
+
+pub(crate) fn is_gnome() -> bool {
+    std::env::var("XDG_CURRENT_DESKTOP").is_ok_and(|desktop| desktop.contains("GNOME"))
+}
+
+pub(crate) fn focused_window() -> Option<FocusedWindow> {
+    let (app_name, process_id, window_id) = call::<(String, u32, String)>("FocusedWindow").ok()?;
+    (!app_name.is_empty()).then(|| FocusedWindow {
+        app_name,
+        process_id: process_id as u64,
+        window_id,
+        // The extension doesn't report titles.
+        title: None,
+    })
+}
+
+pub(crate) fn primary_selection() -> Result<String, GetTextError> {
+    call::<(String,)>("PrimarySelection").map(|(text,)| text)
+}
@@ -0,0 +1,53 @@
+/// HTML copied from the selection.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HtmlFragment {
+    /// The selected markup, without any clipboard envelope.
+    pub html: String,
+    /// The document the selection was copied from, when the source reported it.
+    pub source_url: Option<String>,
+}
+
+const START_MARKER: &str = "<!--StartFragment-->";
+const END_MARKER: &str = "<!--EndFragment-->";
+
+/// Parses a Windows `HTML Format` (CF_HTML) payload: an ASCII `Key:Value`
+/// header followed by the document, where `StartFragment`/`EndFragment` are
+/// byte offsets of the selected markup.
+#[cfg_attr(not(all(windows, feature = "native-clipboard")), allow(dead_code))]
+pub(crate) fn parse_cf_html(raw: &[u8]) -> Option<HtmlFragment> {
+    let header_end = raw.iter().position(|&b| b == b'<').unwrap_or(raw.len());
+    let header = std::str::from_utf8(&raw[..header_end]).ok()?;
+
+    let mut start = None;
+    let mut end = None;
+    let mut source_url = None;
+    for line in header.lines() {
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        match key.trim() {
+            "StartFragment" => start = value.trim().parse::<usize>().ok(),
+            "EndFragment" => end = value.trim().parse::<usize>().ok(),
+            "SourceURL" => source_url = Some(value.trim().to_string()).filter(|url| !url.is_empty()),
+            _ => {}
+        }
+    }
+
+    let html = match (start, end) {
+        (Some(start), Some(end)) if start <= end && end <= raw.len() => {
+            String::from_utf8_lossy(&raw[start..end]).into_owned()
+        }
+        // Some producers write wrong offsets; fall back to the fragment comments.
+        _ => {
+            let document = String::from_utf8_lossy(&raw[header_end..]);
+            let start = document.find(START_MARKER)? + START_MARKER.len();
+            let end = document[start..].find(END_MARKER)? + start;
+            document[start..end].to_string()
+        }
+    };
+
+    Some(HtmlFragment {
+        html: html.trim().to_string(),
+        source_url,
+    })
+}
@@ -0,0 +1,211 @@
+//! IAccessible2 text of the focused object (Windows). Apps built on their own
+//! toolkits, like LibreOffice, expose documents this way rather than through
+//! UI Automation's TextPattern, and the UIA proxy for them drops the text
+//! offsets.
+//!
+//! The interfaces are declared here by hand, as far as the calls used go:
+//! neither `windows-sys` nor `uiautomation` ships IAccessible2.
+
+use std::ffi::c_void;
+use std::ptr;
+
+use windows_sys::core::{BSTR, GUID, HRESULT};
+use windows_sys::Win32::Foundation::{SysFreeString, SysStringLen};
+use windows_sys::Win32::System::Com::{CoInitializeEx, CoUninitialize, COINIT_APARTMENTTHREADED};
+use windows_sys::Win32::UI::Accessibility::AccessibleObjectFromWindow;
+use windows_sys::Win32::UI::WindowsAndMessaging::{GetGUIThreadInfo, GUITHREADINFO, OBJID_CLIENT};
+
+const IID_IACCESSIBLE: GUID = GUID::from_u128(0x618736e0_3c3d_11cf_810c_00aa00389b71);
+const IID_ISERVICEPROVIDER: GUID = GUID::from_u128(0x6d5140c1_7436_11ce_8034_00aa006009fa);
+const IID_IACCESSIBLETEXT: GUID = GUID::from_u128(0x24fd2ffb_3aad_4a08_8335_a3ad89c0fb4b);
+
+const VT_DISPATCH: u16 = 9;
+/// `get_accFocus` chains through nested containers; real trees are a few deep.
+const MAX_FOCUS_DEPTH: usize = 16;
+
+#[repr(C)]
+struct IUnknownVtbl {
+    query_interface: unsafe extern "system" fn(*mut c_void, *const GUID, *mut *mut c_void) -> HRESULT,
+    _add_ref: unsafe extern "system" fn(*mut c_void) -> u32,
+    release: unsafe extern "system" fn(*mut c_void) -> u32,
+}
+
+#[repr(C)]
+struct IAccessibleVtbl {
+    _unknown: IUnknownVtbl,
+    /// IDispatch's four methods, then `get_accParent` through
+    /// `get_accKeyboardShortcut`.
+    _before_focus: [usize; 15],
+    get_acc_focus: unsafe extern "system" fn(*mut c_void, *mut Variant) -> HRESULT,
+}
+
+#[repr(C)]
+struct IServiceProviderVtbl {
+    _unknown: IUnknownVtbl,
+    query_service: unsafe extern "system" fn(*mut c_void, *const GUID, *const GUID, *mut *mut c_void) -> HRESULT,
+}
+
+#[repr(C)]
+struct IAccessibleTextVtbl {
+    _unknown: IUnknownVtbl,
+    /// `addSelection`, `get_attributes`, `get_caretOffset`, `get_characterExtents`.
+    _before_n_selections: [usize; 4],
+    get_n_selections: unsafe extern "system" fn(*mut c_void, *mut i32) -> HRESULT,
+    _get_offset_at_point: usize,
+    get_selection: unsafe extern "system" fn(*mut c_void, i32, *mut i32, *mut i32) -> HRESULT,
+    get_text: unsafe extern "system" fn(*mut c_void, i32, i32, *mut BSTR) -> HRESULT,
+    /// `get_textBeforeOffset` through `setSelection`.
+    _before_n_characters: [usize; 6],
+    get_n_characters: unsafe extern "system" fn(*mut c_void, *mut i32) -> HRESULT,
+}
+
+/// The part of a VARIANT `get_accFocus` fills in: the type tag and a pointer
+/// or integer payload.
+#[repr(C)]
+struct Variant {
+    vt: u16,
+    _reserved: [u16; 3],
+    value: *mut c_void,
+    _padding: *mut c_void,
+}
+
+/// An owned COM interface pointer, released when dropped.
+struct ComPtr(*mut c_void);
+
+impl ComPtr {
+    unsafe fn vtable<T>(&self) -> &T {
+        &**(self.0 as *const *const T)
+    }
+
+    fn query(&self, iid: &GUID) -> Option<ComPtr> {
+        let mut out = ptr::null_mut();
+        let hr = unsafe { (self.vtable::<IUnknownVtbl>().query_interface)(self.0, iid, &mut out) };
+        (hr >= 0 && !out.is_null()).then_some(ComPtr(out))
+    }
+}
+
+impl Drop for ComPtr {
+    fn drop(&mut self) {
+        unsafe { (self.vtable::<IUnknownVtbl>().release)(self.0) };
+    }
+}
+
+/// Balances a successful `CoInitializeEx`. A thread already initialised in
+/// the other apartment model can still make the calls below.
+struct ComApartment(bool);
+
+impl ComApartment {
+    fn enter() -> Self {
+        ComApartment(unsafe { CoInitializeEx(ptr::null(), COINIT_APARTMENTTHREADED) } >= 0)
+    }
+}
+
+impl Drop for ComApartment {
+    fn drop(&mut self) {
+        if self.0 {
+            unsafe { CoUninitialize() };
+        }
+    }
+}
+
+fn take_bstr(bstr: BSTR) -> Option<String> {
+    if bstr.is_null() {
+        return None;
+    }
+    let text = unsafe { String::from_utf16_lossy(std::slice::from_raw_parts(bstr, SysStringLen(bstr) as usize)) };
+    unsafe { SysFreeString(bstr) };
+    Some(text)
+}
+
+/// The IAccessibleText of the focused object.
+pub(crate) struct AccessibleText {
+    text: ComPtr,
+    _apartment: ComApartment,
+}
+
+impl crate::libreoffice::TextObject for AccessibleText {
+    fn selection_count(&self) -> usize {
+        let mut count = 0;
+        let hr = unsafe { (self.text.vtable::<IAccessibleTextVtbl>().get_n_selections)(self.text.0, &mut count) };
+        if hr < 0 {
+            return 0;
+        }
+        count.max(0) as usize
+    }
+
+    fn selection(&self, index: usize) -> Option<(usize, usize)> {
+        let (mut start, mut end) = (0, 0);
+        let vtable = unsafe { self.text.vtable::<IAccessibleTextVtbl>() };
+        let hr = unsafe { (vtable.get_selection)(self.text.0, index as i32, &mut start, &mut end) };
+        (hr >= 0 && start >= 0 && end >= start).then_some((start as usize, end as usize))
+    }
+
+    fn char_count(&self) -> Option<usize> {
+        let mut count = 0;
+        let hr = unsafe { (self.text.vtable::<IAccessibleTextVtbl>().get_n_characters)(self.text.0, &mut count) };
+        (hr >= 0 && count >= 0).then_some(count as usize)
+    }
+
+    fn text(&self, start: usize, end: usize) -> Option<String> {
+        let mut text: BSTR = ptr::null();
+        let vtable = unsafe { self.text.vtable::<IAccessibleTextVtbl>() };
+        let hr = unsafe { (vtable.get_text)(self.text.0, start as i32, end as i32, &mut text) };
+        if hr < 0 {
+            take_bstr(text);
+            return None;
+        }
+        take_bstr(text)
+    }
+}
+
+/// The IAccessibleText of the object that has the keyboard focus, found by
+/// following `get_accFocus` down from the focused window. `None` if the app
+/// doesn't implement IAccessible2 there.
+pub(crate) fn focused_text() -> Option<AccessibleText> {
+    let apartment = ComApartment::enter();
+    let mut info: GUITHREADINFO = unsafe { std::mem::zeroed() };
+    info.cbSize = std::mem::size_of::<GUITHREADINFO>() as u32;
+    if unsafe { GetGUIThreadInfo(0, &mut info) } == 0 || info.hwndFocus == 0 {
+        return None;
+    }
+    let mut object = ptr::null_mut();
+    let hr =
+        unsafe { AccessibleObjectFromWindow(info.hwndFocus, OBJID_CLIENT as u32, &IID_IACCESSIBLE, &mut object) };
+    if hr < 0 || object.is_null() {
+        return None;
+    }
+    let mut accessible = ComPtr(object);
+    for _ in 0..MAX_FOCUS_DEPTH {
+        let mut focus: Variant = unsafe { std::mem::zeroed() };
+        let hr = unsafe { (accessible.vtable::<IAccessibleVtbl>().get_acc_focus)(accessible.0, &mut focus) };
+        // A child ID or nothing: `accessible` is the focused object itself.
+        if hr < 0 || focus.vt != VT_DISPATCH || focus.value.is_null() {
+            break;
+        }
+        let dispatch = ComPtr(focus.value);
+        if dispatch.0 == accessible.0 {
+            break;
+        }
+        let Some(child) = dispatch.query(&IID_IACCESSIBLE) else {
+            break;
+        };
+        accessible = child;
+    }
+    let provider = accessible.query(&IID_ISERVICEPROVIDER)?;
+    let mut text = ptr::null_mut();
+    let hr = unsafe {
+        (provider.vtable::<IServiceProviderVtbl>().query_service)(
+            provider.0,
+            &IID_IACCESSIBLE,
+            &IID_IACCESSIBLETEXT,
+            &mut text,
+        )
+    };
+    if hr < 0 || text.is_null() {
+        return None;
+    }
+    Some(AccessibleText {
+        text: ComPtr(text),
+        _apartment: apartment,
+    })
+}
@@ -1,76 +1,367 @@
+pub mod advanced;
+#[cfg(feature = "app-info")]
+mod app_info;
+mod apps;
+mod attempt;
+#[cfg(any(target_os = "macos", test))]
+mod ax_tree;
+#[cfg(feature = "browser-bridge")]
+mod browser;
+mod calibrate;
+mod capture;
+mod client;
+#[cfg(feature = "config")]
+mod config;
+mod context;
+#[cfg(feature = "copy-hook")]
+mod copy_hook;
+#[cfg(feature = "drag-hook")]
+mod drag_hook;
+mod env_overrides;
+mod extractor;
+mod formats;
+mod framework;
+mod html;
+mod lifecycle;
+mod memo;
+mod metrics;
+#[cfg(unix)]
+mod neovim;
+mod options;
+pub mod permissions;
+mod process_lock;
+mod queue;
+mod quirks;
+#[cfg(any(feature = "browser-bridge", feature = "server"))]
+mod runtime_dir;
+mod script;
+#[cfg(feature = "segmentation")]
+mod segmentation;
+mod sequence;
+#[cfg(feature = "server")]
+mod server;
+mod session;
+mod side_effects;
+mod strategy;
+mod suppress;
+#[cfg(unix)]
+mod terminal;
 mod utils;
+mod watcher;
 
+#[cfg(all(target_os = "linux", feature = "atspi"))]
+mod atspi;
+#[cfg(target_os = "linux")]
+mod compositor;
+#[cfg(all(target_os = "linux", feature = "daemon"))]
+mod daemon;
+#[cfg(all(target_os = "linux", feature = "gnome"))]
+mod gnome;
+#[cfg(any(target_os = "windows", all(target_os = "linux", feature = "atspi")))]
+mod libreoffice;
 #[cfg(target_os = "linux")]
 mod linux;
 #[cfg(target_os = "macos")]
 mod macos;
+#[cfg(all(target_os = "macos", feature = "services"))]
+mod services;
+#[cfg(target_os = "windows")]
+mod ia2;
+#[cfg(target_os = "linux")]
+mod uinput;
+#[cfg(all(target_os = "windows", feature = "native-clipboard"))]
+mod win_clipboard;
 #[cfg(target_os = "windows")]
 mod windows;
+#[cfg(all(target_os = "windows", feature = "winrt-clipboard"))]
+mod winrt_clipboard;
+#[cfg(all(target_os = "linux", feature = "wayland-data-control"))]
+mod wlr_data_control;
+#[cfg(target_os = "linux")]
+mod x11_selection;
+#[cfg(target_os = "linux")]
+mod xfixes;
+#[cfg(all(target_os = "linux", feature = "ydotool"))]
+mod ydotool;
+
+#[cfg(all(feature = "unstable-raw", any(target_os = "macos", target_os = "windows")))]
+pub mod raw;
 
 #[cfg(target_os = "windows")]
 pub use windows::*;
+#[cfg(target_os = "linux")]
+pub use linux::{
+    get_available_formats, get_primary_selection, get_selected_html, get_selection_formats_bundle, input_backend,
+    set_input_backend, InputBackend,
+};
+#[cfg(all(target_os = "linux", feature = "daemon"))]
+pub use daemon::run_daemon;
+#[cfg(target_os = "macos")]
+pub use macos::{get_available_formats, get_selected_html, get_selected_rtf, get_selection_formats_bundle};
+#[cfg(all(target_os = "macos", feature = "services"))]
+pub use services::register_services_provider;
+
+#[cfg(feature = "app-info")]
+pub use app_info::{AppDetails, AppIcon};
+pub use apps::SourceKind;
+pub use attempt::{Attempt, AttemptLog, FailureReason};
+#[cfg(feature = "browser-bridge")]
+pub use browser::{get_browser_selection, run_native_host, BrowserSelection};
+pub use calibrate::{calibrate, reset_calibration, Calibration};
+pub use capture::{Capture, SelectionBounds, SelectionPosition, SelectionResult};
+pub use client::SelectedTextClient;
+#[cfg(feature = "config")]
+pub use config::{default_config_path, load_config};
+pub use context::{Boundary, Context, ContextConfidence, ContextSize, TokenCounter};
+#[cfg(feature = "copy-hook")]
+pub use copy_hook::{watch_user_copies, CopyEvent, CopyWatch};
+#[cfg(feature = "drag-hook")]
+pub use drag_hook::{watch_selection_drags, DragWatch};
+pub use extractor::{clear_extractors, register_extractor, AppInfo, ExtractContext, Selection, SelectionExtractor};
+pub use formats::{ClipboardFormat, FormatsBundle};
+pub use framework::{detect_framework, focused_framework, Framework};
+pub use html::HtmlFragment;
+pub use lifecycle::{init, is_initialized, shutdown};
+pub use metrics::{metrics, reset_metrics, LatencyPercentiles, Metrics, StrategyCounts};
+pub use options::GetTextOptions;
+pub use quirks::{app_quirks, clear_app_quirks, set_app_quirks, AppQuirks, ContextSupport, CopyShortcut};
+#[cfg(feature = "segmentation")]
+pub use segmentation::{selected_sentences, sentences, Sentence};
+pub use script::{register_script, ScriptExtractor, ScriptLanguage};
+pub use sequence::{input_cooldown, set_input_cooldown};
+#[cfg(feature = "server")]
+pub use server::{serve, ServerOptions};
+pub use side_effects::{track_side_effects, SideEffects};
+pub use strategy::Strategy;
+pub use suppress::{suppress_token, SuppressToken};
+pub use utils::{clipboard_restore_delay, set_clipboard_restore_delay};
 
 #[derive(Debug, thiserror::Error, Clone)]
 pub enum GetTextError {
     #[error("Clipboard error: {0}")]
     Clipboard(String),
+    #[error("Clipboard is held open by another application")]
+    ClipboardBusy,
     #[error("OS specific error: {0}")]
     Os(String),
     #[error("UIA error: {0}")]
     Uia(String),
     #[error("Input simulation error: {0}")]
     Input(String),
+    #[error("No way to simulate input in this session: {0}")]
+    InputUnavailable(String),
+    #[error("Permission denied: {0}")]
+    PermissionDenied(String),
     #[error("Failed to get selected text")]
     NoSelection,
+    #[error("Operation timed out")]
+    Timeout,
+    /// Only a simulated copy could read the selection, and the call was not
+    /// allowed to block. See [`try_get_selected_text`].
+    #[error("Reading the selection would need a simulated copy")]
+    WouldBlock,
+    #[error("Another process is simulating input; timed out waiting for it")]
+    InputBusy,
+    /// A capture was started on a thread that is already capturing, e.g.
+    /// from an extractor or a clipboard listener called during a capture.
+    #[error("A capture was started from inside another capture")]
+    Reentrant,
+    /// A window of the calling process is focused. See
+    /// `GetTextOptions::allow_own_window`.
+    #[error("A window of this process is focused")]
+    OwnWindowFocused,
+    /// The focused app runs in full-screen exclusive mode, e.g. a game.
+    /// Nothing was sent to it.
+    #[error("{app} is running full-screen exclusive; no input was sent")]
+    UnsupportedForegroundApp { app: String },
+    /// The screen is locked, or the session is switched away from or
+    /// disconnected, so simulated input would reach nothing.
+    #[error("The session is locked or inactive")]
+    SessionInactive,
+    #[error("Focus changed from {before} to {after} during capture")]
+    FocusChanged { before: String, after: String },
+    #[error("All strategies failed: {0}")]
+    AllStrategiesFailed(AttemptLog),
     #[error("Failed to find selection in context")]
     NotInContext,
+    /// The app exposes its text to accessibility APIs only with its
+    /// screen-reader mode on. [`GetTextError::hint`] says how to turn it on.
+    #[error("{app} hides its text from accessibility APIs while its screen-reader mode is off")]
+    AccessibilityModeDisabled { app: String },
+    /// The configuration file couldn't be read or has an invalid entry.
+    #[error("Invalid configuration: {0}")]
+    Config(String),
     #[error("Operation not implemented for this platform yet.")]
     Unimplemented,
     #[error("Other error: {0}")]
     Other(String),
 }
 
+/// Recovers the `GetTextError` the platform backends boxed on their way up.
+/// Errors of other types (a script's exit status, a failed conversion) become
+/// `Other` with their message, I/O errors `Os`.
+impl From<Box<dyn std::error::Error>> for GetTextError {
+    fn from(error: Box<dyn std::error::Error>) -> Self {
+        match error.downcast::<GetTextError>() {
+            Ok(error) => *error,
+            Err(error) => match error.downcast::<std::io::Error>() {
+                Ok(error) => (*error).into(),
+                Err(error) => GetTextError::Other(error.to_string()),
+            },
+        }
+    }
+}
+
+impl From<std::io::Error> for GetTextError {
+    fn from(error: std::io::Error) -> Self {
+        GetTextError::Os(error.to_string())
+    }
+}
+
+impl GetTextError {
+    /// What the user can do about this error, when the crate knows.
+    pub fn hint(&self) -> Option<&'static str> {
+        match self {
+            GetTextError::AccessibilityModeDisabled { app } if apps::is_vscode(app) => Some(apps::VSCODE_HINT),
+            GetTextError::AccessibilityModeDisabled { app } if apps::is_jetbrains(app) => Some(apps::JETBRAINS_HINT),
+            _ => None,
+        }
+    }
+}
+
 /// Gets the selected text using clipboard simulation.
 ///
-/// # Arguments
-///
-/// * `cancel_select` - If true, simulates a right arrow click after copying to cancel the text selection.
+/// To deselect afterwards, pass `GetTextOptions::cancel_select(true)` to
+/// [`get_selected_text_with_options`].
 ///
 /// # Errors
 ///
 /// Returns `GetTextError` if clipboard operations fail or other errors occur.
-pub fn get_selected_text() -> Result<String, Box<dyn std::error::Error>> {
-    #[cfg(target_os = "windows")]
-    {
-        let result = windows::get_selected_text();
-        println!("[LIB] Windows get_selected_text_os result: {:?}", result.is_ok());
-        result.map_err(|e| Box::new(e) as Box<dyn std::error::Error>)
-    }
-    #[cfg(target_os = "macos")]
-    {
-        let result = macos::get_selected_text();
-        println!("[LIB] macOS get_selected_text_os result: {:?}", result.is_ok());
-        result
-    }
-    #[cfg(target_os = "linux")]
-    {
-        Err(Box::new(GetTextError::Unimplemented) as Box<dyn std::error::Error>)
-    }
-    #[cfg(not(any(target_os = "windows", target_os = "macos", target_os = "linux")))]
-    {
-        Err(Box::new(GetTextError::Unimplemented) as Box<dyn std::error::Error>)
-    }
+pub fn get_selected_text() -> Result<String, GetTextError> {
+    client::default_client().get_selected_text()
+}
+
+/// Gets the selected text, applying the per-call `options`.
+///
+/// # Errors
+///
+/// Same as [`get_selected_text`].
+pub fn get_selected_text_with_options(options: &GetTextOptions) -> Result<String, GetTextError> {
+    client::default_client().get_selected_text_with_options(options)
+}
+
+/// Gets the selected text like [`get_selected_text`], together with the
+/// focused app, its bundle identifier (macOS), the window title, the
+/// strategy that read the text and the time the capture took, for callers
+/// that treat selections differently depending on where they come from.
+///
+/// # Errors
+///
+/// Same as [`get_selected_text`].
+pub fn get_selection() -> Result<SelectionResult, GetTextError> {
+    client::default_client().get_selection()
+}
+
+/// Captures the selection, its context, the focused app and the selection's
+/// screen bounds in one pass. The clipboard is saved and restored once, where
+/// [`get_selected_text_with_context`] followed by other calls would cycle it
+/// for each copy.
+///
+/// # Errors
+///
+/// Same as [`get_selected_text_with_context`].
+pub fn capture() -> Result<Capture, GetTextError> {
+    client::default_client().capture()
+}
+
+/// Gets the full text of the focused document, independent of any selection,
+/// for tools that summarize or index what's on screen. Reads the UI
+/// Automation TextPattern document range (Windows) or `AXValue`, by range
+/// when it's long (macOS), and falls back to Select All + Copy, which puts
+/// the clipboard and selection back. At most `max_document_chars` characters
+/// are read from UI Automation and AX.
+///
+/// # Errors
+///
+/// `GetTextError::Other` if the document exposes no text and Select All is
+/// skipped for it (see [`advanced::get_context_via_select_all`]), plus the
+/// errors of [`get_selected_text`].
+pub fn get_document_text() -> Result<String, GetTextError> {
+    client::default_client().get_document_text()
+}
+
+/// [`get_document_text`] with explicit options.
+///
+/// # Errors
+///
+/// Same as [`get_document_text`].
+pub fn get_document_text_with_options(options: &GetTextOptions) -> Result<String, GetTextError> {
+    client::default_client().get_document_text_with_options(options)
+}
+
+/// Gets the selected text only from sources that answer at once: AX
+/// (macOS), UI Automation (Windows) or the PRIMARY selection (Linux). Never
+/// simulates input or touches the clipboard, so latency-critical callers can
+/// call it on a hotkey and schedule [`get_selected_text`] elsewhere when it
+/// can't answer.
+///
+/// # Errors
+///
+/// `GetTextError::WouldBlock` if these sources don't have the selection but
+/// a copy might. `GetTextError::NoSelection` if they report that nothing is
+/// selected.
+pub fn try_get_selected_text() -> Result<String, GetTextError> {
+    client::default_client().try_get_selected_text()
+}
+
+/// Blocks until the user selects text anywhere, for flows like "now select
+/// the text you want translated". Returns the first non-empty selection that
+/// differs from the one present when called.
+///
+/// Selections are read like [`try_get_selected_text`] reads them, every
+/// 300 ms (at once on X11), so text that only a copy can read isn't seen.
+///
+/// # Errors
+///
+/// `GetTextError::Timeout` if no new selection appears within `timeout`.
+pub fn wait_for_selection(timeout: std::time::Duration) -> Result<String, GetTextError> {
+    watcher::wait_for_change(timeout).ok_or(GetTextError::Timeout)
+}
+
+/// Gets the selected text trying exactly `strategies`, in this order, instead
+/// of the default chain. Strategies left out never run. App integrations
+/// (browser extension, terminals) still run first.
+///
+/// # Errors
+///
+/// `GetTextError::AllStrategiesFailed` listing every strategy tried, with
+/// `FailureReason::Unsupported` for those that don't exist on this platform.
+pub fn get_selected_text_with_strategies(strategies: &[Strategy]) -> Result<String, GetTextError> {
+    client::default_client().get_selected_text_with_strategies(strategies)
+}
+
+/// Writes the selected text to `writer` in chunks instead of returning one
+/// `String`. See [`SelectedTextClient::get_selected_text_streaming`].
+///
+/// # Errors
+///
+/// Same as [`get_selected_text`], plus `GetTextError::Os` when `writer` fails.
+pub fn get_selected_text_streaming<W: std::io::Write>(writer: W) -> Result<u64, GetTextError> {
+    client::default_client().get_selected_text_streaming(writer)
 }
 
 /// Gets the selected text and its surrounding context.
 ///
 /// This function attempts to retrieve the context using platform-specific methods:
-/// - Windows: Tries UI Automation first, then falls back to simulating Select All + Copy.
-/// - macOS/Linux: Simulates Select All + Copy. (Currently disabled)
+/// - Windows: Tries UI Automation first, then falls back to simulating Select All + Copy
+///   (skipped in editable controls unless `GetTextOptions::allow_select_all_in_editable` is set).
+/// - macOS: Reads AX first, then falls back to a triple-click + Copy of the paragraph.
+/// - Linux: Simulates Select All + Copy.
 ///
-/// # Arguments
+/// [`advanced`] exposes the UIA and Select All steps on their own, for a selection obtained elsewhere.
 ///
-/// * `cancel_select` - If true, simulates a right arrow click after copying to cancel the initial text selection.
+/// `GetTextOptions::cancel_select`, passed to
+/// [`get_selected_text_with_context_opts`], deselects once the context is read.
 ///
 /// # Returns
 ///
@@ -80,22 +371,33 @@ pub fn get_selected_text() -> Result<String, Box<dyn std::error::Error>> {
 /// # Errors
 ///
 /// Returns `GetTextError` if clipboard operations, UIA, or input simulation fail, or if unimplemented.
-pub fn get_selected_text_with_context() -> Result<(String, Option<String>), Box<dyn std::error::Error>> {
-    #[cfg(target_os = "windows")]
-    {
-        windows::get_selected_text_with_context_os()
-    }
-    #[cfg(target_os = "macos")]
-    {
-        macos::get_selected_text_with_context()
-    }
-    #[cfg(target_os = "linux")]
-    {
-        // linux::get_selected_text_with_context_os(_cancel_select) // Temporarily disable
-        Err(Box::new(GetTextError::Unimplemented))
-    }
-    #[cfg(not(any(target_os = "windows", target_os = "macos", target_os = "linux")))]
-    {
-        Err(GetTextError::Unimplemented)
-    }
+/// Returns `GetTextError::AccessibilityModeDisabled` instead of a `None` context for VS Code
+/// and its forks while their screen-reader mode is off.
+pub fn get_selected_text_with_context() -> Result<(String, Option<String>), GetTextError> {
+    client::default_client().get_selected_text_with_context()
+}
+
+/// [`get_selected_text_with_context`] with explicit options.
+///
+/// # Errors
+///
+/// Same as [`get_selected_text_with_context`].
+pub fn get_selected_text_with_context_opts(
+    options: &GetTextOptions,
+) -> Result<(String, Option<String>), GetTextError> {
+    client::default_client().get_selected_text_with_context_opts(options)
+}
+
+/// [`get_selected_text_with_context`], with the context split into the text
+/// before the selection, the selection and the text after it. Where AX, UI
+/// Automation or AT-SPI report where the selection is, the split is made
+/// there, so a selection repeated in its context is still placed right;
+/// otherwise at the first occurrence of the selection. The context is `None`
+/// when there is none or the selection can't be found in it.
+///
+/// # Errors
+///
+/// Same as [`get_selected_text_with_context`].
+pub fn get_selected_text_with_context_parts() -> Result<(String, Option<Context>), GetTextError> {
+    client::default_client().get_selected_text_with_context_parts()
 }
@@ -0,0 +1,115 @@
+//! Selections in LibreOffice, read through its own accessibility objects:
+//! IAccessible2 on Windows, AT-SPI on Linux (`atspi` feature). Writer exposes
+//! one text object per paragraph and Calc one per cell, each with its
+//! selection as offsets, so the selection and its paragraph come back without
+//! a copy, and without the Select All that would otherwise be used for
+//! context.
+
+/// The text interface of one paragraph or cell. Offsets are in the units
+/// the platform API uses, passed through unchanged.
+pub(crate) trait TextObject {
+    fn selection_count(&self) -> usize;
+    fn selection(&self, index: usize) -> Option<(usize, usize)>;
+    fn char_count(&self) -> Option<usize>;
+    fn text(&self, start: usize, end: usize) -> Option<String>;
+}
+
+/// Paragraphs longer than this are left to the copy rather than read whole.
+const MAX_PARAGRAPH_CHARS: usize = 50_000;
+
+/// A selection read from LibreOffice, with its paragraph or cell as context.
+pub(crate) struct OfficeSelection {
+    pub(crate) text: String,
+    pub(crate) context: Option<String>,
+}
+
+/// The selection in the focused paragraph or cell. `None` when nothing is
+/// selected there, and when the selection touches either end of the
+/// paragraph: the focused paragraph only reports its own part of a
+/// selection that runs on into its neighbours, so only a copy gets all of it.
+pub(crate) fn read(object: &impl TextObject) -> Option<OfficeSelection> {
+    if object.selection_count() != 1 {
+        return None;
+    }
+    let (start, end) = object.selection(0)?;
+    let length = object.char_count()?;
+    if start >= end || start == 0 || end >= length || length > MAX_PARAGRAPH_CHARS {
+        return None;
+    }
+    let context = object.text(0, length)?;
+    let text = object.text(start, end)?;
+    crate::context::set_confidence(crate::ContextConfidence::High);
+    Some(OfficeSelection {
+        text,
+        context: Some(context),
+    })
+}
+
+/// The selection in LibreOffice, if it has the focus and its accessibility
+/// objects report one.
+pub(crate) fn selection_for_focused_app() -> Option<OfficeSelection> {
+    let focus = crate::utils::focus_snapshot()?;
+    if !crate::apps::is_libreoffice(focus.app_name()) {
+        return None;
+    }
+    #[cfg(target_os = "windows")]
+    let selection = crate::ia2::focused_text().as_ref().and_then(read);
+    #[cfg(target_os = "linux")]
+    let selection = crate::atspi::focused_text(focus.process_id()).as_ref().and_then(read);
+    selection.filter(|selection| !selection.text.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Paragraph {
+        text: &'static str,
+        selections: Vec<(usize, usize)>,
+    }
+
+    impl TextObject for Paragraph {
+        fn selection_count(&self) -> usize {
+            self.selections.len()
+        }
+
+        fn selection(&self, index: usize) -> Option<(usize, usize)> {
+            self.selections.get(index).copied()
+        }
+
+        fn char_count(&self) -> Option<usize> {
+            Some(self.text.len())
+        }
+
+        fn text(&self, start: usize, end: usize) -> Option<String> {
+            self.text.get(start..end).map(str::to_string)
+        }
+    }
+
+    #[test]
+    fn reads_the_selection_with_its_paragraph() {
+        let paragraph = Paragraph {
+            text: "The quick brown fox jumps.",
+            selections: vec![(4, 9)],
+        };
+        let selection = read(&paragraph).unwrap();
+        assert_eq!(selection.text, "quick");
+        assert_eq!(selection.context.as_deref(), Some("The quick brown fox jumps."));
+    }
+
+    #[test]
+    fn leaves_selections_that_may_span_paragraphs_to_the_copy() {
+        for selection in [(0, 9), (4, 26), (4, 4)] {
+            let paragraph = Paragraph {
+                text: "The quick brown fox jumps.",
+                selections: vec![selection],
+            };
+            assert!(read(&paragraph).is_none(), "{:?}", selection);
+        }
+        let unselected = Paragraph {
+            text: "The quick brown fox jumps.",
+            selections: vec![],
+        };
+        assert!(read(&unselected).is_none());
+    }
+}
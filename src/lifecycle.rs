@@ -0,0 +1,52 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::GetTextError;
+
+static INITIALIZED: AtomicBool = AtomicBool::new(false);
+
+/// Pre-creates the platform backends so the first capture doesn't pay their
+/// cold-start cost.
+///
+/// This creates the input simulator, the UI Automation instance (Windows), the
+/// pasteboard binding (macOS) and a persistent clipboard connection (Linux), and
+/// checks the required permissions. Calling it is optional; without it every
+/// call creates what it needs on demand.
+///
+/// The input simulator is cached per thread (each thread creates its own on
+/// its first capture and keeps it), so call `init()` from the thread that
+/// will trigger captures.
+///
+/// With the `config` feature, this first loads the configuration file at
+/// `default_config_path()` if there is one.
+///
+/// # Errors
+///
+/// Returns `GetTextError::Config` if the configuration file is invalid.
+/// Returns `GetTextError::PermissionDenied` if the accessibility permission is
+/// missing (macOS). The backends are still pre-warmed in that case.
+/// Returns other `GetTextError` variants if a backend cannot be created.
+pub fn init() -> Result<(), GetTextError> {
+    #[cfg(feature = "config")]
+    crate::config::load_default()?;
+    crate::utils::prewarm()?;
+    #[cfg(target_os = "windows")]
+    crate::windows::prewarm()?;
+    INITIALIZED.store(true, Ordering::SeqCst);
+    #[cfg(target_os = "macos")]
+    crate::macos::prewarm()?;
+    Ok(())
+}
+
+/// Releases everything created by [`init`]. Later calls fall back to creating
+/// backends on demand until `init()` is called again.
+pub fn shutdown() {
+    crate::utils::release();
+    #[cfg(target_os = "windows")]
+    crate::windows::release();
+    INITIALIZED.store(false, Ordering::SeqCst);
+}
+
+/// Whether [`init`] has been called without a matching [`shutdown`].
+pub fn is_initialized() -> bool {
+    INITIALIZED.load(Ordering::SeqCst)
+}
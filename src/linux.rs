@@ -1,47 +1,440 @@
-use enigo::*;
-use crate::utils::*{
-    get_selected_text_by_clipboard,
-    get_context_via_select_all,
-};
-use crate::GetTextError;
-use std::{thread, time::Duration};
+use crate::strategy::Strategy;
+use crate::utils::{get_context_via_select_all, get_selected_text_by_clipboard, with_enigo};
+use crate::{AttemptLog, FailureReason, GetTextError, GetTextOptions, SelectedTextClient};
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
 
-pub fn get_selected_text() -> Result<String, Box<dyn std::error::Error>> {
-    let mut enigo = Enigo::new(&Settings::default()).unwrap();
-    crate::utils::get_selected_text_by_clipboard(&mut enigo, false)
-}
+/// How long the owner of PRIMARY gets to hand it over.
+const PRIMARY_TIMEOUT: Duration = Duration::from_millis(500);
+/// How long `try_get_selected_text` waits for it instead.
+const PEEK_TIMEOUT: Duration = Duration::from_millis(50);
+/// How long the clipboard owner gets to list or convert its targets.
+const CLIPBOARD_TIMEOUT: Duration = Duration::from_secs(1);
 
-pub fn get_selected_text_os(cancel_select: bool) -> Result<String, GetTextError> {
-    let mut enigo = Enigo::new(&Settings::default()).map_err(|e| GetTextError::Input(e.to_string()))?;
-    get_selected_text_by_clipboard(&mut enigo, cancel_select)
+pub(crate) fn get_selected_text_with_options(options: &GetTextOptions) -> Result<String, Box<dyn std::error::Error>> {
+    let mut default_order = Vec::new();
+    #[cfg(feature = "atspi")]
+    default_order.push(Strategy::Accessibility);
+    // On X11 the focused app's PRIMARY selection is the selection itself, so
+    // no copy is needed. Wayland doesn't say which app owns PRIMARY, so
+    // there `Strategy::Primary` only runs when listed in the options.
+    if crate::x11_selection::available() {
+        default_order.push(Strategy::Primary);
+    }
+    default_order.push(Strategy::Clipboard);
+    let focus = crate::utils::focus_snapshot();
+    let process_id = focus.as_ref().map(|focus| focus.process_id());
+    let mut order = options.strategies.clone().unwrap_or_else(|| {
+        let app_name = focus.as_ref().map(|focus| focus.app_name()).unwrap_or_default();
+        crate::framework::default_order(app_name, process_id, &default_order)
+    });
+    order.retain(|strategy| !options.disabled_strategies.contains(strategy));
+    let mut found_empty = false;
+    let mut attempts = AttemptLog::default();
+    for current in order {
+        let started = Instant::now();
+        let result = match current {
+            Strategy::Clipboard => {
+                with_enigo(|enigo| get_selected_text_by_clipboard(enigo, options.restore_clipboard))
+                    .map_err(|e| Box::new(e) as Box<dyn std::error::Error>)
+                    .and_then(|result| result)
+            }
+            #[cfg(feature = "atspi")]
+            Strategy::Accessibility => {
+                crate::atspi::selected_text(process_id).map_err(|e| Box::new(e) as Box<dyn std::error::Error>)
+            }
+            Strategy::Primary => {
+                let paste = options.primary_paste && options.allows_input();
+                read_primary(process_id, paste).map_err(|e| Box::new(e) as Box<dyn std::error::Error>)
+            }
+            _ => {
+                attempts.push(current, FailureReason::Unsupported, started.elapsed());
+                continue;
+            }
+        };
+        let succeeded = matches!(&result, Ok(text) if !text.is_empty());
+        crate::metrics::record_strategy(current, succeeded);
+        crate::env_overrides::gst_log!("{:?}: {}", current, crate::env_overrides::describe(&result));
+        let reason = match result {
+            Ok(text) if succeeded => {
+                crate::strategy::note_succeeded(current);
+                return Ok(text);
+            }
+            Ok(_) => {
+                found_empty = true;
+                FailureReason::Empty
+            }
+            Err(e) => FailureReason::from_error(e.as_ref()),
+        };
+        attempts.push(current, reason, started.elapsed());
+    }
+    if found_empty {
+        Ok(String::new())
+    } else {
+        Err(Box::new(GetTextError::AllStrategiesFailed(attempts)))
+    }
 }
 
-pub fn get_selected_text_with_context_os(
-    cancel_select: bool,
-) -> Result<(String, Option<String>), GetTextError> {
-    let mut enigo = Enigo::new(&Settings::default()).map_err(|e| GetTextError::Input(e.to_string()))?;
+/// `Strategy::Primary` for the session type in use.
+fn read_primary(process_id: Option<u64>, paste: bool) -> Result<String, GetTextError> {
+    #[cfg(feature = "wayland-data-control")]
+    if is_wayland() {
+        return crate::wlr_data_control::changed_primary_selection(PRIMARY_TIMEOUT);
+    }
+    crate::x11_selection::read_primary(process_id, PRIMARY_TIMEOUT, paste)
+}
 
-    // 1. Get selected text using standard clipboard method first
-    let selected_text = get_selected_text_by_clipboard(&mut enigo, cancel_select)?;
+/// The focused app's PRIMARY selection, for `try_get_selected_text`: only
+/// where the owner can be told apart from other apps, and only if it
+/// answers within `PEEK_TIMEOUT`.
+pub(crate) fn peek_primary(process_id: Option<u64>) -> Result<String, GetTextError> {
+    // GNOME's Wayland session only hands PRIMARY to the focused client.
+    #[cfg(feature = "gnome")]
+    if is_wayland() && crate::gnome::is_gnome() {
+        return crate::gnome::primary_selection();
+    }
+    if !crate::x11_selection::available() {
+        // Elsewhere Wayland doesn't say which app owns PRIMARY; only a copy can tell.
+        return Err(GetTextError::WouldBlock);
+    }
+    match crate::x11_selection::read_primary(process_id, PEEK_TIMEOUT, false) {
+        Err(GetTextError::Timeout) => Err(GetTextError::WouldBlock),
+        result => result,
+    }
+}
 
+/// Captures the selection, then reads its context through AT-SPI (`atspi`
+/// feature) or, failing that, by Select All + Copy. Without AT-SPI, apps
+/// whose quirks only allow accessibility and calls that may not simulate
+/// input get no context.
+pub(crate) fn get_selected_text_with_context_for(
+    client: &SelectedTextClient,
+) -> Result<(String, Option<String>), Box<dyn std::error::Error>> {
+    let selected_text = client.capture_text(client.options())?;
     if selected_text.is_empty() {
-        // If no text was selected, we can't get context
         return Ok((selected_text, None));
     }
 
-    // 2. On Linux, directly use the fallback: Select All + Copy
-    // Short delay before fallback simulation
-    thread::sleep(Duration::from_millis(100));
-    match get_context_via_select_all(&mut enigo, &selected_text) {
-        Ok(Some(context)) => Ok((selected_text, Some(context))),
-        Ok(None)=> Ok((selected_text, None)), // Should not happen
-        Err(GetTextError::NotInContext) => {
-            eprintln!("Fallback failed: Selected text not found in full text.");
-            Ok((selected_text, None)) 
+    // Reuse a context computed for the same selection moments ago (e.g. hotkey + watcher)
+    let focus = crate::utils::focus_snapshot();
+    let app_name = focus.as_ref().map(|focus| focus.app_name().to_string()).unwrap_or_default();
+    if let Some(context) = client.context_memo.get(&app_name, &selected_text, client.options().context_memo_ttl) {
+        return Ok((selected_text, Some(context)));
+    }
+
+    #[cfg(feature = "atspi")]
+    if let Some(focus) = &focus {
+        let options = client.options();
+        let size = options.context_size.as_ref().unwrap_or(&crate::context::DEFAULT_SIZE);
+        let context = crate::atspi::context(focus.process_id(), &selected_text, size, options.max_document_chars);
+        if let Some(context) = context {
+            client.context_memo.put(&app_name, &selected_text, &context);
+            return Ok((selected_text, Some(context)));
         }
+    }
+
+    if !client.options().allows_input() {
+        return Ok((selected_text, None));
+    }
+    match with_enigo(|enigo| get_context_via_select_all(enigo, &selected_text, client.options()))? {
+        Ok(Some(context)) => {
+            client.context_memo.put(&app_name, &selected_text, &context);
+            Ok((selected_text, Some(context)))
+        }
+        Ok(None) => Ok((selected_text, None)),
+        // The selection was copied; a context that can't be found doesn't fail the call.
         Err(e) => {
-            eprintln!("Fallback context retrieval failed: {}", e);
+            crate::env_overrides::gst_log!("Select All context failed: {}", e);
             Ok((selected_text, None))
         }
     }
 }
+
+/// How simulated key presses reach other apps on Linux.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum InputBackend {
+    /// uinput in a Wayland session when `/dev/uinput` is writable (or
+    /// `ydotoold` is running, with the `ydotool` feature), XTEST otherwise.
+    #[default]
+    Auto,
+    /// The XTEST extension, through enigo. Only reaches X11 and XWayland
+    /// windows.
+    Xtest,
+    /// A virtual keyboard on `/dev/uinput`. Works under X11 and Wayland alike
+    /// but needs write access to the device.
+    Uinput,
+    /// `ydotool`, talking to a running `ydotoold` that owns the uinput device.
+    #[cfg(feature = "ydotool")]
+    Ydotool,
+}
+
+static INPUT_BACKEND: AtomicU8 = AtomicU8::new(0);
+
+/// Selects how key presses are simulated on Linux. Defaults to `Auto`.
+pub fn set_input_backend(backend: InputBackend) {
+    let value = match backend {
+        InputBackend::Auto => 0,
+        InputBackend::Xtest => 1,
+        InputBackend::Uinput => 2,
+        #[cfg(feature = "ydotool")]
+        InputBackend::Ydotool => 3,
+    };
+    INPUT_BACKEND.store(value, Ordering::Relaxed);
+}
+
+pub fn input_backend() -> InputBackend {
+    match INPUT_BACKEND.load(Ordering::Relaxed) {
+        1 => InputBackend::Xtest,
+        2 => InputBackend::Uinput,
+        #[cfg(feature = "ydotool")]
+        3 => InputBackend::Ydotool,
+        _ => InputBackend::Auto,
+    }
+}
+
+/// The backend that will actually be used, never `Auto`.
+///
+/// # Errors
+///
+/// `GetTextError::InputUnavailable` if the selected backend, or with `Auto`
+/// every backend, can't be used in this session.
+pub(crate) fn resolve_input_backend() -> Result<InputBackend, GetTextError> {
+    let x11 = std::env::var_os("DISPLAY").is_some();
+    match input_backend() {
+        InputBackend::Xtest if x11 => Ok(InputBackend::Xtest),
+        InputBackend::Xtest => Err(GetTextError::InputUnavailable(
+            "XTEST needs an X11 display, but DISPLAY is not set".to_string(),
+        )),
+        InputBackend::Uinput if crate::uinput::available() => Ok(InputBackend::Uinput),
+        InputBackend::Uinput => Err(GetTextError::InputUnavailable(
+            "/dev/uinput is not writable; add the user to the `input` group or install a udev rule".to_string(),
+        )),
+        #[cfg(feature = "ydotool")]
+        InputBackend::Ydotool if crate::ydotool::available() => Ok(InputBackend::Ydotool),
+        #[cfg(feature = "ydotool")]
+        InputBackend::Ydotool => Err(GetTextError::InputUnavailable(
+            "no ydotoold socket found; start ydotoold or set YDOTOOL_SOCKET".to_string(),
+        )),
+        _ if is_wayland() && crate::uinput::available() => Ok(InputBackend::Uinput),
+        #[cfg(feature = "ydotool")]
+        _ if is_wayland() && crate::ydotool::available() => Ok(InputBackend::Ydotool),
+        _ if x11 => Ok(InputBackend::Xtest),
+        _ if crate::uinput::available() => Ok(InputBackend::Uinput),
+        #[cfg(feature = "ydotool")]
+        _ if crate::ydotool::available() => Ok(InputBackend::Ydotool),
+        _ => Err(GetTextError::InputUnavailable(
+            "no X11 display for XTEST and /dev/uinput is not writable".to_string(),
+        )),
+    }
+}
+
+/// Presses `modifiers`+`key` (evdev key codes) through uinput or ydotool if
+/// one of them is the resolved backend. Returns false when the caller should send it with enigo
+/// instead.
+pub(crate) fn uinput_shortcut(modifiers: &[u16], key: u16) -> Result<bool, GetTextError> {
+    match resolve_input_backend()? {
+        InputBackend::Uinput => crate::uinput::chord(modifiers, key)?,
+        #[cfg(feature = "ydotool")]
+        InputBackend::Ydotool => crate::ydotool::chord(modifiers, key)?,
+        _ => return Ok(false),
+    }
+    Ok(true)
+}
+
+/// HTML targets, most preferred first.
+const HTML_TARGETS: [&str; 1] = ["text/html"];
+const TEXT_TARGETS: [&str; 4] = ["UTF8_STRING", "text/plain;charset=utf-8", "text/plain", "STRING"];
+const RTF_TARGETS: [&str; 3] = ["text/rtf", "application/rtf", "text/richtext"];
+
+/// Returns the first of `preferred` that the selection owner offers.
+fn negotiate<'a>(offered: &[String], preferred: &[&'a str]) -> Option<&'a str> {
+    preferred
+        .iter()
+        .copied()
+        .find(|target| offered.iter().any(|o| o.eq_ignore_ascii_case(target)))
+}
+
+fn is_wayland() -> bool {
+    std::env::var_os("WAYLAND_DISPLAY").is_some()
+}
+
+/// The X11/Wayland selection a clipboard tool reads from.
+#[derive(Clone, Copy)]
+enum Selection {
+    Clipboard,
+    /// The text currently highlighted, as pasted by middle-click.
+    Primary,
+}
+
+fn run_clipboard_tool(selection: Selection, args: &[&str]) -> Result<Option<Vec<u8>>, GetTextError> {
+    let (program, args) = if is_wayland() {
+        let mut wayland_args = match selection {
+            Selection::Clipboard => vec![],
+            Selection::Primary => vec!["--primary"],
+        };
+        wayland_args.extend_from_slice(args);
+        ("wl-paste", wayland_args)
+    } else {
+        let name = match selection {
+            Selection::Clipboard => "clipboard",
+            Selection::Primary => "primary",
+        };
+        let mut x11_args = vec!["-selection", name, "-o"];
+        x11_args.extend_from_slice(args);
+        ("xclip", x11_args)
+    };
+    let output = std::process::Command::new(program)
+        .args(&args)
+        .output()
+        .map_err(|e| GetTextError::Os(format!("{}: {}", program, e)))?;
+    // Both tools exit non-zero when the clipboard is empty or lacks the target.
+    Ok(output.status.success().then_some(output.stdout))
+}
+
+/// Lists the targets (X11) or MIME types (Wayland) the clipboard owner offers.
+/// On X11 they are asked for over the crate's own connection.
+fn clipboard_targets() -> Result<Vec<String>, GetTextError> {
+    if crate::x11_selection::available() {
+        return crate::x11_selection::clipboard_targets(CLIPBOARD_TIMEOUT);
+    }
+    Ok(run_clipboard_tool(Selection::Clipboard, &["--list-types"])?
+        .map(|out| String::from_utf8_lossy(&out).lines().map(str::to_string).collect())
+        .unwrap_or_default())
+}
+
+fn read_clipboard_target(target: &str) -> Result<Option<Vec<u8>>, GetTextError> {
+    if crate::x11_selection::available() {
+        return crate::x11_selection::read_clipboard_target(target, CLIPBOARD_TIMEOUT);
+    }
+    run_clipboard_tool(Selection::Clipboard, &["--no-newline", "--type", target])
+}
+
+/// Reads the PRIMARY selection, i.e. whatever text is highlighted in any app,
+/// without simulating input and without touching the clipboard.
+///
+/// Some apps answer the conversion request arboard makes but not the one made
+/// by `xclip`/`wl-paste`, or the other way around, so both are tried.
+pub fn get_primary_selection() -> Result<String, GetTextError> {
+    use arboard::{GetExtLinux, LinuxClipboardKind};
+
+    // GNOME's Wayland session only hands PRIMARY to the focused client.
+    #[cfg(feature = "gnome")]
+    if is_wayland() && crate::gnome::is_gnome() {
+        if let Ok(text) = crate::gnome::primary_selection() {
+            return if text.is_empty() { Err(GetTextError::NoSelection) } else { Ok(text) };
+        }
+    }
+
+    #[cfg(feature = "wayland-data-control")]
+    if is_wayland() && crate::wlr_data_control::available() {
+        match crate::wlr_data_control::primary_selection(PRIMARY_TIMEOUT) {
+            Ok(text) if !text.is_empty() => return Ok(text),
+            Ok(_) | Err(GetTextError::NoSelection) => return Err(GetTextError::NoSelection),
+            Err(_) => {}
+        }
+    }
+
+    let direct = crate::utils::open_clipboard().and_then(|mut clipboard| {
+        crate::utils::clipboard_retry(|| clipboard.get().clipboard(LinuxClipboardKind::Primary).text())
+            .map_err(crate::utils::clipboard_error)
+    });
+    let direct_error = match direct {
+        Ok(text) if !text.is_empty() => return Ok(text),
+        Ok(_) => None,
+        Err(e) => Some(e),
+    };
+    let args: &[&str] = if is_wayland() { &["--no-newline"] } else { &[] };
+    match run_clipboard_tool(Selection::Primary, args) {
+        Ok(Some(bytes)) if !bytes.is_empty() => Ok(String::from_utf8_lossy(&bytes).into_owned()),
+        Ok(_) => Err(GetTextError::NoSelection),
+        // Neither tool is installed: report what arboard said.
+        Err(tool_error) => Err(direct_error.unwrap_or(tool_error)),
+    }
+}
+
+
+fn target_format(target: &str) -> Option<crate::ClipboardFormat> {
+    use crate::ClipboardFormat;
+
+    match target {
+        "UTF8_STRING" | "STRING" | "TEXT" | "COMPOUND_TEXT" => Some(ClipboardFormat::PlainText),
+        t if t.starts_with("text/plain") => Some(ClipboardFormat::PlainText),
+        "text/html" => Some(ClipboardFormat::Html),
+        "text/rtf" | "application/rtf" | "text/richtext" => Some(ClipboardFormat::Rtf),
+        t if t.starts_with("image/") => Some(ClipboardFormat::Image),
+        "text/uri-list" | "x-special/gnome-copied-files" => Some(ClipboardFormat::Files),
+        _ => None,
+    }
+}
+
+/// Simulates a copy into an emptied clipboard, hands the result to `read`,
+/// then puts the previous clipboard contents back.
+fn copy_and_read<T>(read: impl FnOnce() -> Result<T, GetTextError>) -> Result<T, GetTextError> {
+    let _sequence = crate::sequence::begin()?;
+    let mut clipboard = crate::utils::ClipboardGuard::save(crate::utils::open_clipboard()?, true);
+    clipboard.clipboard().clear().map_err(crate::utils::clipboard_error)?;
+    crate::side_effects::clipboard_modified();
+    let owner_waiter = crate::xfixes::OwnerWaiter::clipboard().ok();
+    crate::utils::with_enigo(crate::utils::copy)??;
+    let copy_timeout = crate::quirks::copy_wait();
+    match owner_waiter {
+        Some(waiter) => {
+            waiter.wait(copy_timeout);
+        }
+        None => thread::sleep(copy_timeout),
+    }
+
+    let result = read();
+
+    clipboard.restore()?;
+    result
+}
+
+/// Copies the selection and reports which formats the selection owner offers,
+/// restoring the previous clipboard afterwards.
+pub fn get_available_formats() -> Result<Vec<crate::ClipboardFormat>, GetTextError> {
+    copy_and_read(|| {
+        let targets = clipboard_targets()?;
+        Ok(crate::formats::normalize(
+            targets.iter().filter_map(|t| target_format(t)).collect(),
+        ))
+    })
+}
+
+/// Copies the selection and returns its `text/html` representation, if the
+/// selection owner offers one. Requires `wl-paste` on Wayland.
+pub fn get_selected_html() -> Result<Option<crate::HtmlFragment>, GetTextError> {
+    let html = copy_and_read(|| {
+        let targets = clipboard_targets()?;
+        match negotiate(&targets, &HTML_TARGETS) {
+            Some(target) => read_clipboard_target(target),
+            None => Ok(None),
+        }
+    })?;
+    Ok(html.map(|bytes| crate::HtmlFragment {
+        html: String::from_utf8_lossy(&bytes).into_owned(),
+        source_url: None,
+    }))
+}
+
+/// Copies the selection once and returns its plain text, HTML and RTF, as far
+/// as the selection owner offers them, restoring the previous clipboard
+/// afterwards. Requires `wl-paste` on Wayland.
+pub fn get_selection_formats_bundle() -> Result<crate::FormatsBundle, GetTextError> {
+    copy_and_read(|| {
+        let targets = clipboard_targets()?;
+        let read = |preferred: &[&str]| -> Result<Option<String>, GetTextError> {
+            let Some(target) = negotiate(&targets, preferred) else {
+                return Ok(None);
+            };
+            Ok(read_clipboard_target(target)?.map(|bytes| String::from_utf8_lossy(&bytes).into_owned()))
+        };
+        Ok(crate::FormatsBundle {
+            text: read(&TEXT_TARGETS)?,
+            html: read(&HTML_TARGETS)?.map(|html| crate::HtmlFragment { html, source_url: None }),
+            rtf: read(&RTF_TARGETS)?,
+        })
+    })
+}
@@ -1,5 +1,3 @@
-use std::num::NonZeroUsize;
-
 use accessibility_ng::{AXAttribute, AXUIElement, AXUIElementAttributes, AXValue};
 use accessibility_sys_ng::{kAXFocusedUIElementAttribute, kAXSelectedTextAttribute};
 use active_win_pos_rs::get_active_window;
@@ -9,55 +7,312 @@ use core_foundation::number::CFNumber;
 use core_foundation::boolean::CFBoolean;
 use core_foundation::attributed_string::CFAttributedString;
 use core_foundation::array::CFArray;
+use core_foundation_sys::base::CFRange;
 use debug_print::debug_println;
-use lru::LruCache;
-use parking_lot::Mutex;
 use enigo::{
     Button,
     Direction::{self, Click, Press, Release},
-    Enigo, Key, Keyboard, Mouse, Settings,
+    Key, Keyboard, Mouse,
 };
+use std::sync::atomic::{AtomicI64, Ordering};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-static GET_SELECTED_TEXT_METHOD: Mutex<Option<LruCache<String, u8>>> = Mutex::new(None);
+use crate::strategy::{Outcome, Strategy};
+use crate::utils::{with_enigo, SelectionProbe};
+use crate::{AttemptLog, FailureReason, GetTextError, GetTextOptions, SelectedTextClient};
 
-pub fn get_selected_text() -> Result<String, Box<dyn std::error::Error>> {
-    if GET_SELECTED_TEXT_METHOD.lock().is_none() {
-        let cache = LruCache::new(NonZeroUsize::new(100).unwrap());
-        *GET_SELECTED_TEXT_METHOD.lock() = Some(cache);
-    }
-    let mut cache = GET_SELECTED_TEXT_METHOD.lock();
-    let cache = cache.as_mut().unwrap();
-    let app_name = match get_active_window() {
-        Ok(window) => window.app_name,
+pub fn get_selected_text_with_options(
+    client: &SelectedTextClient,
+    options: &GetTextOptions,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let (app_name, process_id) = match get_active_window() {
+        Ok(window) => (window.app_name, window.process_id),
         Err(_) => return Err("No active window found".into()),
     };
     // debug_println!("app_name: {}", app_name);
-    if let Some(method_val) = cache.get(&app_name) {
-        if *method_val == 0 {
+
+    if options.precheck_selection && probe_selection() == SelectionProbe::Absent {
+        debug_println!("[PRECHECK] AX reports no selection in {}, skipping copy.", app_name);
+        return Err(Box::new(GetTextError::NoSelection));
+    }
+
+    // The find pasteboard also holds whatever the user last searched for, in
+    // any app, so it is only a last resort after the copy.
+    const DEFAULT_ORDER: [Strategy; 3] = [Strategy::Accessibility, Strategy::AppleScript, Strategy::FindPasteboard];
+    let default_order = crate::framework::default_order(&app_name, Some(process_id), &DEFAULT_ORDER);
+    let mut order = match &options.strategies {
+        Some(strategies) => strategies.clone(),
+        None if options.bypass_cache => default_order,
+        None => client.strategies.ordered(&app_name, &default_order),
+    };
+    order.retain(|strategy| !options.disabled_strategies.contains(strategy));
+    let jetbrains = crate::apps::is_jetbrains(&app_name);
+    let mut found_empty = false;
+    let mut attempts = AttemptLog::default();
+    for (index, &current) in order.iter().enumerate() {
+        let started = Instant::now();
+        if current == Strategy::Accessibility && jetbrains && focused_exposes_text() == Some(false) {
+            debug_println!("[STRATEGY] {} has screen-reader support off, skipping AX.", app_name);
+            attempts.push(current, FailureReason::AccessibilityModeOff, started.elapsed());
+            continue;
+        }
+        let result = match current {
             // Call the modified get_selected_text_by_ax and extract only the text
-            return get_selected_text_by_ax_robust().map(|(text, _context)| text);
+            Strategy::Accessibility => get_selected_text_by_ax_robust().map(|(text, _context)| text),
+            Strategy::AppleScript => get_selected_text_by_clipboard_using_applescript(),
+            Strategy::FindPasteboard => read_find_pasteboard()
+                .ok_or_else(|| Box::new(GetTextError::NoSelection) as Box<dyn std::error::Error>),
+            Strategy::Clipboard => with_enigo(|enigo| {
+                crate::utils::get_selected_text_by_clipboard(enigo, options.restore_clipboard)
+            })
+                .map_err(|e| Box::new(e) as Box<dyn std::error::Error>)
+                .and_then(|result| result),
+            _ => {
+                attempts.push(current, FailureReason::Unsupported, started.elapsed());
+                continue;
+            }
+        };
+        let succeeded = matches!(&result, Ok(text) if !text.is_empty());
+        let outcome = match &result {
+            Ok(_) if succeeded => Outcome::Succeeded,
+            Ok(_) => Outcome::Empty,
+            Err(_) => Outcome::Failed,
+        };
+        client.strategies.record(&app_name, current, outcome, started.elapsed());
+        crate::metrics::record_strategy(current, succeeded);
+        debug_println!("[STRATEGY] {:?} for {}: success={}", current, app_name, succeeded);
+        crate::env_overrides::gst_log!("{:?} in {}: {}", current, app_name, crate::env_overrides::describe(&result));
+        let reason = match result {
+            Ok(text) if succeeded => {
+                crate::strategy::note_succeeded(current);
+                client.strategies.record_skipped(&app_name, &order[index + 1..]);
+                return Ok(text);
+            }
+            // An empty selection is a valid answer, as long as nothing else finds one.
+            Ok(_) => {
+                found_empty = true;
+                FailureReason::Empty
+            }
+            Err(_) if current == Strategy::Accessibility
+                && !macos_accessibility_client::accessibility::application_is_trusted() =>
+            {
+                FailureReason::PermissionDenied
+            }
+            Err(e) => FailureReason::from_error(e.as_ref()),
+        };
+        attempts.push(current, reason, started.elapsed());
+    }
+    if found_empty {
+        Ok(String::new())
+    } else {
+        Err(Box::new(GetTextError::AllStrategiesFailed(attempts)))
+    }
+}
+
+fn selected_range(element: &AXUIElement) -> Option<CFRange> {
+    element
+        .attribute(&AXAttribute::new(&CFString::from_static_string("AXSelectedTextRange")))
+        .ok()
+        .and_then(|value| value.downcast_into::<AXValue>())
+        .and_then(|value| value.get_value::<CFRange>().ok())
+}
+
+/// The screen rectangle of the AX selection, from `AXBoundsForRange`.
+pub(crate) fn selection_bounds() -> Option<crate::SelectionBounds> {
+    use core_graphics::geometry::CGRect;
+
+    let element = selection_element()?;
+    let range = AXValue::new(&selected_range(&element)?).ok()?;
+    let rect = element
+        .parameterized_attribute(&AXAttribute::new(&CFString::from_static_string("AXBoundsForRange")), &range)
+        .ok()?
+        .downcast_into::<AXValue>()?
+        .get_value::<CGRect>()
+        .ok()?;
+    Some(crate::SelectionBounds {
+        x: rect.origin.x,
+        y: rect.origin.y,
+        width: rect.size.width,
+        height: rect.size.height,
+    })
+}
+
+/// The selection's lines and paragraph, from the control's value, or from
+/// `AXLineForIndex` when the value isn't exposed.
+pub(crate) fn selection_position(limit: usize) -> Option<crate::SelectionPosition> {
+    use crate::ax_tree::AxNode;
+
+    let element = selection_element()?;
+    let range = selected_range(&element)?;
+    let (start, length) = (range.location as usize, range.length as usize);
+    let short_value = char_count(&element).map_or(true, |count| count <= crate::ax_tree::MAX_VALUE_CHARS);
+    if let Some(value) = short_value.then(|| element.string_attribute("AXValue")).flatten() {
+        // AX ranges count UTF-16 code units.
+        let units = value.encode_utf16().collect::<Vec<_>>();
+        if start + length <= units.len() && start <= limit {
+            let prefix = String::from_utf16_lossy(&units[..start]);
+            let selected = String::from_utf16_lossy(&units[start..start + length]);
+            return Some(crate::SelectionPosition::in_text(&prefix, &selected));
         }
-        return get_selected_text_by_clipboard_using_applescript();
     }
 
-    match get_selected_text_by_ax_robust() {
-        Ok((text, _context)) => { // Adapt to new return type
-            if !text.is_empty() {
-                cache.put(app_name.clone(), 0);
-            }
-            Ok(text)
+    let line_for_index = |index: usize| {
+        element
+            .parameterized_attribute(
+                &AXAttribute::new(&CFString::from_static_string("AXLineForIndex")),
+                &CFNumber::from(index as i64),
+            )
+            .ok()?
+            .downcast_into::<CFNumber>()?
+            .to_i64()
+            .map(|line| line as usize + 1)
+    };
+    let first_line = line_for_index(start)?;
+    let last_line = line_for_index((start + length).saturating_sub(1).max(start)).unwrap_or(first_line);
+    Some(crate::SelectionPosition {
+        first_line,
+        last_line,
+        paragraph: None,
+    })
+}
+
+/// The selection of the focused text control, saved so it can be put back
+/// exactly after Select All.
+pub(crate) struct SavedSelection {
+    element: AXUIElement,
+    range: CFRange,
+}
+
+impl SavedSelection {
+    pub(crate) fn save() -> Option<Self> {
+        let element = focused_element()?;
+        let range = selected_range(&element)?;
+        Some(SavedSelection { element, range })
+    }
+
+    /// Returns false if the control doesn't accept a new selection range.
+    pub(crate) fn restore(&self) -> bool {
+        let Ok(value) = AXValue::new(&self.range) else {
+            return false;
+        };
+        self.element
+            .set_attribute(
+                &AXAttribute::new(&CFString::from_static_string("AXSelectedTextRange")),
+                value.as_CFType(),
+            )
+            .is_ok()
+    }
+}
+
+/// Asks AX whether the focused text control has a selection, without sending input.
+pub(crate) fn probe_selection() -> SelectionProbe {
+    let Some(element) = focused_element() else {
+        return SelectionProbe::Unknown;
+    };
+    // Only native text controls report their selection reliably; web areas and
+    // Electron views often claim an empty selection while text is selected.
+    let role = get_element_role(&element);
+    if !matches!(
+        role.as_deref(),
+        Some("AXTextField") | Some("AXTextArea") | Some("AXComboBox") | Some("AXSearchField")
+    ) {
+        return SelectionProbe::Unknown;
+    }
+    match selected_range(&element) {
+        Some(range) if range.length == 0 => SelectionProbe::Absent,
+        Some(_) => SelectionProbe::Present,
+        None => SelectionProbe::Unknown,
+    }
+}
+
+pub(crate) fn prewarm() -> Result<(), GetTextError> {
+    use cocoa::appkit::NSPasteboard;
+    use cocoa::base::nil;
+
+    // Touch the AX system-wide element and the general pasteboard once so the
+    // first capture doesn't pay for loading the frameworks.
+    let _ = AXUIElement::system_wide().role();
+    unsafe {
+        let _ = NSPasteboard::generalPasteboard(nil);
+    }
+    // Records the find pasteboard's state, so older searches aren't taken
+    // for a selection.
+    let _ = read_find_pasteboard();
+    let _ = get_active_window();
+
+    if !macos_accessibility_client::accessibility::application_is_trusted() {
+        return Err(GetTextError::PermissionDenied(
+            "Accessibility access has not been granted to this application".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+pub(crate) fn pasteboard_change_count() -> i64 {
+    use cocoa::appkit::NSPasteboard;
+    use cocoa::base::nil;
+
+    unsafe { NSPasteboard::generalPasteboard(nil).changeCount() as i64 }
+}
+
+/// The find pasteboard's change count when the crate last read it.
+static FIND_PASTEBOARD_SEEN: AtomicI64 = AtomicI64::new(-1);
+
+/// The find pasteboard's text, if it changed since the last call. Sends no
+/// input and leaves the general pasteboard alone. The first call after
+/// startup only records the current state: the text may be a search from
+/// long ago.
+pub(crate) fn read_find_pasteboard() -> Option<String> {
+    use cocoa::appkit::{NSFindPboard, NSPasteboard, NSPasteboardTypeString};
+    use cocoa::base::nil;
+    use cocoa::foundation::NSString;
+
+    unsafe {
+        let pasteboard = NSPasteboard::pasteboardWithName(nil, NSFindPboard);
+        let change_count = pasteboard.changeCount() as i64;
+        let seen = FIND_PASTEBOARD_SEEN.swap(change_count, Ordering::Relaxed);
+        if seen == -1 || seen == change_count {
+            return None;
         }
-        Err(_) => match get_selected_text_by_clipboard_using_applescript() {
-            Ok(text) => {
-                if !text.is_empty() {
-                    cache.put(app_name, 1);
-                }
-                Ok(text)
-            }
-            Err(e) => Err(e),
-        },
+        let string = pasteboard.stringForType(NSPasteboardTypeString);
+        if string == nil {
+            return None;
+        }
+        let text = std::ffi::CStr::from_ptr(string.UTF8String()).to_string_lossy().into_owned();
+        Some(text).filter(|text| !text.is_empty())
+    }
+}
+
+/// The bundle identifier of the app running as `process_id`, e.g.
+/// "com.apple.Safari". `None` for processes outside an app bundle.
+pub(crate) fn bundle_identifier(process_id: u64) -> Option<String> {
+    use cocoa::base::{id, nil};
+    use cocoa::foundation::NSString;
+    use objc::{class, msg_send, sel, sel_impl};
+
+    unsafe {
+        let pid = process_id as i32;
+        let app: id = msg_send![class!(NSRunningApplication), runningApplicationWithProcessIdentifier: pid];
+        if app == nil {
+            return None;
+        }
+        let identifier: id = msg_send![app, bundleIdentifier];
+        if identifier == nil {
+            return None;
+        }
+        let identifier = std::ffi::CStr::from_ptr(identifier.UTF8String()).to_string_lossy().into_owned();
+        Some(identifier).filter(|identifier| !identifier.is_empty())
+    }
+}
+
+/// Records what one of the copy scripts below did. They press Cmd+C and
+/// write the saved clipboard back whenever the pasteboard changed.
+fn record_copy_script(change_count_before: i64) {
+    crate::side_effects::keystrokes_sent();
+    if pasteboard_change_count() != change_count_before {
+        crate::side_effects::clipboard_modified();
+        crate::side_effects::clipboard_restored();
     }
 }
 
@@ -154,7 +409,7 @@ fn try_active_window_approach() -> Result<(String, Option<String>), Box<dyn std:
     debug_println!("[AX_STRATEGY2] No focused element found via application, starting deep traversal...");
     
     // 开始深度遍历寻找包含选中文本的元素
-    if let Some(result) = traverse_ui_tree(&app_element, 0, "App") {
+    if let Some(result) = crate::ax_tree::traverse(&app_element, 0) {
         debug_println!("[AX_STRATEGY2] Found result via deep traversal");
         return Ok(result);
     }
@@ -166,67 +421,91 @@ fn try_active_window_approach() -> Result<(String, Option<String>), Box<dyn std:
     )))
 }
 
-// 深度遍历UI元素树
-fn traverse_ui_tree(element: &AXUIElement, depth: usize, element_name: &str) -> Option<(String, Option<String>)> {
-    const MAX_DEPTH: usize = 6;
-    const MAX_CHILDREN_PER_LEVEL: usize = 15;
+// 获取当前 focused element（先系统级别，再通过活动窗口的应用级别）
+pub(crate) fn focused_element() -> Option<AXUIElement> {
+    let focused_attr = AXAttribute::new(&CFString::from_static_string(kAXFocusedUIElementAttribute));
 
-    if depth > MAX_DEPTH {
-        debug_println!("[UI_TRAVERSE] Reached max depth {}, stopping", depth);
-        return None;
+    if let Some(element) = AXUIElement::system_wide()
+        .attribute(&focused_attr)
+        .ok()
+        .and_then(|element| element.downcast_into::<AXUIElement>())
+    {
+        return Some(element);
     }
 
-    let indent = "  ".repeat(depth);
-    debug_println!("[UI_TRAVERSE] {}Depth {}: Checking {} element", indent, depth, element_name);
-    
-    // 记录当前元素的属性
-    log_element_attributes(element, &format!("{}Depth{}", indent, depth));
-    
-    // 检查当前元素是否有选中文本
-    if let Ok((selected_text, context)) = extract_text_and_context(element) {
-        if !selected_text.is_empty() {
-            debug_println!("[UI_TRAVERSE] {}✓ Found selected text: '{}'", indent, selected_text);
-            return Some((selected_text, context));
+    let active_window = get_active_window().ok()?;
+    AXUIElement::application(active_window.process_id as i32)
+        .attribute(&focused_attr)
+        .ok()
+        .and_then(|element| element.downcast_into::<AXUIElement>())
+}
+
+/// Whether the focused element is a text control AX can read. `None` if
+/// nothing is focused.
+pub(crate) fn focused_exposes_text() -> Option<bool> {
+    let element = focused_element()?;
+    Some(
+        matches!(
+            get_element_role(&element).as_deref(),
+            Some("AXTextField") | Some("AXTextArea") | Some("AXComboBox") | Some("AXSearchField")
+        ) || selected_range(&element).is_some(),
+    )
+}
+
+// 获取真正持有选中文本的元素（focused element 本身，或其应用 UI 树中的某个子元素）
+pub(crate) fn selection_element() -> Option<AXUIElement> {
+    if let Some(element) = focused_element() {
+        if crate::ax_tree::has_selection(&element) {
+            return Some(element);
         }
     }
-    
-    // 尝试获取子元素
-    debug_println!("[UI_TRAVERSE] {}Getting children...", indent);
-    if let Ok(children_attr) = element.attribute(&AXAttribute::new(&CFString::from_static_string("AXChildren"))) {
-        debug_println!("[UI_TRAVERSE] {}Found AXChildren attribute", indent);
-        
-        // 尝试使用更安全的方式获取子元素
-    if let Some(children_count) = get_children_count(element) {
-            debug_println!("[UI_TRAVERSE] {}Found {} children", indent, children_count);
-            
-        let search_limit = children_count.min(MAX_CHILDREN_PER_LEVEL);
-
-        for i in 0..search_limit {
-            if let Some(child) = get_child_at_index(element, i) {
-                    debug_println!("[UI_TRAVERSE] {}Checking child {}/{}", indent, i + 1, search_limit);
-                    
-                    let child_name = get_element_role(&child).unwrap_or_else(|| format!("Child{}", i));
-                    
-                    if let Some(result) = traverse_ui_tree(&child, depth + 1, &child_name) {
-                        return Some(result);
-                    }
-                } else {
-                    debug_println!("[UI_TRAVERSE] {}Failed to get child at index {}", indent, i);
-                }
-            }
-            
-            if children_count > MAX_CHILDREN_PER_LEVEL {
-                debug_println!("[UI_TRAVERSE] {}Limited search to {} children (total: {})", 
-                              indent, MAX_CHILDREN_PER_LEVEL, children_count);
-            }
-        } else {
-            debug_println!("[UI_TRAVERSE] {}Could not determine children count", indent);
-            }
-    } else {
-        debug_println!("[UI_TRAVERSE] {}No AXChildren attribute found", indent);
+
+    let active_window = get_active_window().ok()?;
+    crate::ax_tree::find_selection(&AXUIElement::application(active_window.process_id as i32), 0)
+}
+
+/// The selected text as AX reports it, without sending input. Misses
+/// selections that only a copy can read (web areas, Electron).
+pub(crate) fn peek_selection() -> Option<String> {
+    selection_element()?
+        .attribute(&AXAttribute::new(&CFString::from_static_string(kAXSelectedTextAttribute)))
+        .ok()?
+        .downcast_into::<CFString>()
+        .map(|text| text.to_string())
+}
+
+impl crate::ax_tree::AxNode for AXUIElement {
+    fn string_attribute(&self, name: &str) -> Option<String> {
+        self.attribute(&AXAttribute::new(&CFString::new(name)))
+            .ok()?
+            .downcast_into::<CFString>()
+            .map(|text| text.to_string())
+    }
+
+    fn child_count(&self) -> usize {
+        get_children_count(self).unwrap_or(0)
+    }
+
+    fn child(&self, index: usize) -> Option<Self> {
+        get_child_at_index(self, index)
+    }
+
+    fn char_count(&self) -> Option<usize> {
+        char_count(self)
+    }
+
+    fn selected_range(&self) -> Option<(usize, usize)> {
+        let range = selected_range(self)?;
+        Some((range.location as usize, range.length as usize))
+    }
+
+    fn string_for_range(&self, location: usize, length: usize) -> Option<String> {
+        let range = AXValue::new(&CFRange::init(location as isize, length as isize)).ok()?;
+        self.parameterized_attribute(&AXAttribute::new(&CFString::from_static_string("AXStringForRange")), &range)
+            .ok()?
+            .downcast_into::<CFString>()
+            .map(|text| text.to_string())
     }
-    
-    None
 }
 
 // 记录元素的所有重要属性
@@ -250,7 +529,11 @@ fn log_element_attributes(element: &AXUIElement, prefix: &str) {
         ("AXVisibleCharacterRange", "可见字符范围"),
     ];
     
+    let long_value = char_count(element).is_some_and(|count| count > crate::ax_tree::MAX_VALUE_CHARS);
     for (attr_name, description) in &attributes_to_check {
+        if *attr_name == "AXValue" && long_value {
+            continue;
+        }
         if let Ok(attr_value) = element.attribute(&AXAttribute::new(&CFString::from_static_string(attr_name))) {
             // 尝试不同的类型转换，避免移动所有权
             if let Some(string_val) = attr_value.clone().downcast_into::<CFString>() {
@@ -370,104 +653,14 @@ fn get_current_clipboard_text() -> Result<String, Box<dyn std::error::Error>> {
 
 // 从UI元素提取文本和上下文的通用方法
 fn extract_text_and_context(element: &AXUIElement) -> Result<(String, Option<String>), Box<dyn std::error::Error>> {
-    debug_println!("[AX_EXTRACT] Extracting text and context from element");
-    
-    // 首先尝试获取选中文本
-    let selected_text = match element.attribute(&AXAttribute::new(&CFString::from_static_string(kAXSelectedTextAttribute))) {
-        Ok(selected_text_cfvalue) => {
-            if let Some(selected_text_cfstring) = selected_text_cfvalue.downcast_into::<CFString>() {
-                let text = selected_text_cfstring.to_string();
-                debug_println!("[AX_EXTRACT] Found selected text: '{}'", text);
-                text
-            } else {
-                debug_println!("[AX_EXTRACT] Selected text attribute was not a CFString");
-                String::new()
-            }
-        }
-        Err(e) => {
-            debug_println!("[AX_EXTRACT] Failed to get selected text: {:?}", e);
-            String::new()
-        }
-    };
-    
-    // 如果没有选中文本，返回错误
-    if selected_text.is_empty() {
-        return Err(Box::new(std::io::Error::new(
-                std::io::ErrorKind::NotFound,
-            "No selected text found in element",
-            )));
-    }
-    
-    // 尝试获取上下文
-    let context = get_context_from_element(element);
-    
-    // 针对 WebArea 的特殊处理：如果找到了选中文本但没有上下文，则强制触发 fallback
-    if get_element_role(element).as_deref() == Some("AXWebArea") && context.is_none() {
-        debug_println!("[AX_EXTRACT] Found selected text in WebArea but no AXValue context. Forcing an error to trigger AppleScript fallback.");
-        return Err(Box::new(std::io::Error::new(
-            std::io::ErrorKind::Other,
-            "Found text in WebArea but context requires fallback",
-        )));
-    }
-
-    Ok((selected_text, context))
+    crate::ax_tree::extract(element).ok_or_else(|| {
+        Box::new(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            "No selected text (or no usable WebArea context) in element",
+        )) as Box<dyn std::error::Error>
+    })
 }
 
-// 获取上下文的方法
-fn get_context_from_element(element: &AXUIElement) -> Option<String> {
-    debug_println!("[AX_CONTEXT] Attempting to get context from element");
-    let role = get_element_role(element);
-
-    // Special handling for WebArea: only trust AXValue
-    if role.as_deref() == Some("AXWebArea") {
-        debug_println!("[AX_CONTEXT] Element is a WebArea. Prioritizing AXValue for context.");
-        if let Ok(cf_type_val) = element.value() {
-            if let Some(s) = cf_type_val.downcast_into::<CFString>() {
-                let text = s.to_string();
-                if !text.is_empty() {
-                    debug_println!("[AX_CONTEXT] Found context for WebArea from AXValue (length: {})", text.len());
-                    return Some(text);
-                }
-            }
-        }
-        debug_println!("[AX_CONTEXT] Could not get context from AXValue for WebArea. Returning None to avoid using incorrect fallbacks like title.");
-        return None; // For WebArea, do NOT fall back to title or description
-    }
-    
-    // Fallback logic for other element types
-    // 策略1: 从 AXValue 获取
-    if let Ok(cf_type_val) = element.value() {
-        if let Some(s) = cf_type_val.downcast_into::<CFString>() {
-            let text = s.to_string();
-            if !text.is_empty() && text.len() > 10 { // 确保有足够的内容作为上下文
-                debug_println!("[AX_CONTEXT] Found context from AXValue (length: {})", text.len());
-                return Some(text);
-        }
-        }
-    }
-
-    // 策略2: 从描述获取
-    if let Ok(cf_string) = element.description() {
-            let desc_text = cf_string.to_string();
-        if !desc_text.is_empty() && desc_text.len() > 10 {
-            debug_println!("[AX_CONTEXT] Found context from description (length: {})", desc_text.len());
-            return Some(desc_text);
-        }
-    }
-    
-    // 策略3: 从标题获取
-    if let Ok(cf_string) = element.title() {
-        let title_text = cf_string.to_string();
-        if !title_text.is_empty() && title_text.len() > 10 {
-            debug_println!("[AX_CONTEXT] Found context from title (length: {})", title_text.len());
-            return Some(title_text);
-        }
-        }
-    
-    debug_println!("[AX_CONTEXT] No context found from element attributes");
-            None
-        }
-    
 // 保持原有的 get_selected_text_by_ax 函数以兼容性
 fn get_selected_text_by_ax() -> Result<(String, Option<String>), Box<dyn std::error::Error>> {
     // 直接调用新的健壮版本
@@ -514,10 +707,16 @@ theSelectedText
 fn get_selected_text_by_clipboard_using_applescript() -> Result<String, Box<dyn std::error::Error>>
 {
     // debug_println!("get_selected_text_by_clipboard_using_applescript");
+    let _sequence = crate::sequence::begin()?;
+    let focus_before = crate::utils::focus_snapshot();
+    let change_count = pasteboard_change_count();
     let output = std::process::Command::new("osascript")
         .arg("-e")
         .arg(APPLE_SCRIPT)
         .output()?;
+    record_copy_script(change_count);
+    // The script has already restored the clipboard at this point.
+    crate::utils::ensure_focus_unchanged(focus_before.as_ref())?;
     if output.status.success() {
         let content = String::from_utf8(output.stdout)?;
         let content = content.trim();
@@ -533,17 +732,342 @@ fn get_selected_text_by_clipboard_using_applescript() -> Result<String, Box<dyn
     }
 }
 
-pub fn get_selected_text_with_context() -> Result<(String, Option<String>), Box<dyn std::error::Error>> {
+// Copies the selection and returns the pasteboard contents for the type given
+// as the first argument, decoded as UTF-8. Returns "" when nothing was copied
+// or the source didn't provide that type.
+const COPY_TYPE_SCRIPT: &str = r#"
+use AppleScript version "2.4"
+use scripting additions
+use framework "Foundation"
+use framework "AppKit"
+
+on run argv
+    set theType to item 1 of argv
+    set savedAlertVolume to alert volume of (get volume settings)
+    set savedClipboard to the clipboard
+
+    set thePasteboard to current application's NSPasteboard's generalPasteboard()
+    set theCount to thePasteboard's changeCount()
+
+    tell application "System Events" to set volume alert volume 0
+    tell application "System Events" to keystroke "c" using {command down}
+    delay 0.1
+    tell application "System Events" to set volume alert volume savedAlertVolume
+
+    if thePasteboard's changeCount() is theCount then
+        return ""
+    end if
+
+    set theResult to ""
+    set theData to thePasteboard's dataForType:theType
+    if theData is not missing value then
+        set theString to current application's NSString's alloc()'s initWithData:theData encoding:(current application's NSUTF8StringEncoding)
+        if theString is not missing value then set theResult to theString as text
+    end if
+
+    set the clipboard to savedClipboard
+    return theResult
+end run
+"#;
+
+// Copies the selection and returns the types the source put on the
+// pasteboard, one per line. Returns "" when nothing was copied.
+const COPY_TYPES_SCRIPT: &str = r#"
+use AppleScript version "2.4"
+use scripting additions
+use framework "Foundation"
+use framework "AppKit"
+
+set savedAlertVolume to alert volume of (get volume settings)
+set savedClipboard to the clipboard
+
+set thePasteboard to current application's NSPasteboard's generalPasteboard()
+set theCount to thePasteboard's changeCount()
+
+tell application "System Events" to set volume alert volume 0
+tell application "System Events" to keystroke "c" using {command down}
+delay 0.1
+tell application "System Events" to set volume alert volume savedAlertVolume
+
+if thePasteboard's changeCount() is theCount then
+    return ""
+end if
+
+set theTypes to (thePasteboard's types()'s componentsJoinedByString:linefeed) as text
+
+set the clipboard to savedClipboard
+return theTypes
+"#;
+
+// Copies the selection and returns its plain text, HTML and RTF as three
+// lines of base64, each empty when the source didn't provide that type.
+// Returns "" when nothing was copied.
+const COPY_BUNDLE_SCRIPT: &str = r#"
+use AppleScript version "2.4"
+use scripting additions
+use framework "Foundation"
+use framework "AppKit"
+
+set savedAlertVolume to alert volume of (get volume settings)
+set savedClipboard to the clipboard
+
+set thePasteboard to current application's NSPasteboard's generalPasteboard()
+set theCount to thePasteboard's changeCount()
+
+tell application "System Events" to set volume alert volume 0
+tell application "System Events" to keystroke "c" using {command down}
+delay 0.1
+tell application "System Events" to set volume alert volume savedAlertVolume
+
+if thePasteboard's changeCount() is theCount then
+    return ""
+end if
+
+set theLines to {}
+repeat with theType in {"public.utf8-plain-text", "public.html", "public.rtf"}
+    set theData to (thePasteboard's dataForType:(contents of theType))
+    if theData is missing value then
+        set end of theLines to ""
+    else
+        set end of theLines to (theData's base64EncodedStringWithOptions:0) as text
+    end if
+end repeat
+
+set the clipboard to savedClipboard
+set AppleScript's text item delimiters to linefeed
+return theLines as text
+"#;
+
+fn run_copy_script(script: &str, args: &[&str]) -> Result<Option<String>, GetTextError> {
+    let _sequence = crate::sequence::begin()?;
+    let focus_before = crate::utils::focus_snapshot();
+    let change_count = pasteboard_change_count();
+    let output = std::process::Command::new("osascript")
+        .arg("-e")
+        .arg(script)
+        .args(args)
+        .output()
+        .map_err(|e| GetTextError::Os(e.to_string()))?;
+    record_copy_script(change_count);
+    crate::utils::ensure_focus_unchanged(focus_before.as_ref())?;
+    if !output.status.success() {
+        return Err(GetTextError::Os(String::from_utf8_lossy(&output.stderr).into_owned()));
+    }
+    // osascript appends a newline to the result.
+    let content = String::from_utf8_lossy(&output.stdout);
+    let content = content.strip_suffix('\n').unwrap_or(&content);
+    Ok((!content.is_empty()).then(|| content.to_string()))
+}
+
+fn copy_pasteboard_type(uti: &str) -> Result<Option<String>, GetTextError> {
+    run_copy_script(COPY_TYPE_SCRIPT, &[uti])
+}
+
+fn pasteboard_type_format(uti: &str) -> Option<crate::ClipboardFormat> {
+    use crate::ClipboardFormat;
+
+    match uti {
+        "public.utf8-plain-text" | "public.utf16-plain-text" | "NSStringPboardType" => {
+            Some(ClipboardFormat::PlainText)
+        }
+        "public.html" | "Apple HTML pasteboard type" => Some(ClipboardFormat::Html),
+        "public.rtf" | "com.apple.flat-rtfd" | "NSRTFPboardType" => Some(ClipboardFormat::Rtf),
+        "public.png" | "public.tiff" | "public.jpeg" | "NSTIFFPboardType" => Some(ClipboardFormat::Image),
+        "public.file-url" | "NSFilenamesPboardType" => Some(ClipboardFormat::Files),
+        _ => None,
+    }
+}
+
+/// Copies the selection and reports which formats the source app put on the
+/// pasteboard, restoring the previous clipboard afterwards.
+pub fn get_available_formats() -> Result<Vec<crate::ClipboardFormat>, GetTextError> {
+    let types = run_copy_script(COPY_TYPES_SCRIPT, &[])?.unwrap_or_default();
+    Ok(crate::formats::normalize(
+        types.lines().filter_map(pasteboard_type_format).collect(),
+    ))
+}
+
+/// Copies the selection and returns its `public.html` representation, as
+/// provided by Safari, Pages, Mail and most WebKit/Chromium based apps.
+///
+/// Returns `Ok(None)` when the source only offers plain text. macOS doesn't
+/// report the source document, so `source_url` is always `None`.
+pub fn get_selected_html() -> Result<Option<crate::HtmlFragment>, GetTextError> {
+    Ok(copy_pasteboard_type("public.html")?.map(|html| crate::HtmlFragment {
+        html,
+        source_url: None,
+    }))
+}
+
+/// Copies the selection and returns its `public.rtf` representation, as
+/// provided by TextEdit, Pages, Notes and other Cocoa text views.
+///
+/// Returns `Ok(None)` when the source doesn't offer RTF.
+pub fn get_selected_rtf() -> Result<Option<String>, GetTextError> {
+    copy_pasteboard_type("public.rtf")
+}
+
+/// Copies the selection once and returns its plain text, HTML and RTF, as far
+/// as the source app provided them, restoring the previous clipboard
+/// afterwards.
+pub fn get_selection_formats_bundle() -> Result<crate::FormatsBundle, GetTextError> {
+    use base64::Engine;
+
+    let Some(output) = run_copy_script(COPY_BUNDLE_SCRIPT, &[])? else {
+        return Ok(crate::FormatsBundle::default());
+    };
+    let mut lines = output.lines().map(|line| {
+        let bytes = base64::engine::general_purpose::STANDARD.decode(line.trim()).ok()?;
+        Some(String::from_utf8_lossy(&bytes).into_owned()).filter(|text| !text.is_empty())
+    });
+    let mut next = || lines.next().flatten();
+    Ok(crate::FormatsBundle {
+        text: next(),
+        html: next().map(|html| crate::HtmlFragment { html, source_url: None }),
+        rtf: next(),
+    })
+}
+
+pub fn get_selected_text_with_context(
+    client: &SelectedTextClient,
+) -> Result<(String, Option<String>), Box<dyn std::error::Error>> {
+    let (selected_text, context) = get_selected_text_with_context_ax_first(client)?;
+    if context.is_none() && !selected_text.is_empty() {
+        // Monaco's hidden textarea holds nothing but the selection while its
+        // screen-reader mode is off.
+        let app_name = get_active_window().map(|w| w.app_name).unwrap_or_default();
+        let only_selection = focused_element()
+            .and_then(|element| char_count(&element))
+            .is_some_and(|count| count <= selected_text.encode_utf16().count());
+        if crate::apps::is_vscode(&app_name) && only_selection {
+            return Err(Box::new(GetTextError::AccessibilityModeDisabled { app: app_name }));
+        }
+    }
+    Ok((selected_text, context))
+}
+
+/// The text of the focused element, at most `limit` UTF-16 units of it:
+/// `AXValue`, or a ranged read when the value is long. `None` if it exposes
+/// no text.
+pub(crate) fn document_text(limit: usize) -> Option<String> {
+    use crate::ax_tree::AxNode;
+
+    let element = focused_element()?;
+    let text = match char_count(&element) {
+        Some(count) if count > limit.min(crate::ax_tree::MAX_VALUE_CHARS) => {
+            element.string_for_range(0, count.min(limit))
+        }
+        _ => element.string_attribute("AXValue"),
+    };
+    text.filter(|text| !text.is_empty())
+}
+
+/// How far up from the element under the pointer a message bubble's text is
+/// looked for.
+const POINTER_ANCESTORS: usize = 4;
+
+/// The element at screen point (`x`, `y`), by AX hit testing.
+fn element_at_position(x: f64, y: f64) -> Option<AXUIElement> {
+    use accessibility_sys_ng::{kAXErrorSuccess, AXUIElementCopyElementAtPosition, AXUIElementRef};
+
+    let system_wide = AXUIElement::system_wide();
+    let mut element: AXUIElementRef = std::ptr::null_mut();
+    let error = unsafe {
+        AXUIElementCopyElementAtPosition(system_wide.as_concrete_TypeRef(), x as f32, y as f32, &mut element)
+    };
+    if error != kAXErrorSuccess || element.is_null() {
+        return None;
+    }
+    Some(unsafe { AXUIElement::wrap_under_create_rule(element) })
+}
+
+/// Whether the element at screen point (`x`, `y`) is window chrome (title
+/// bar, scroll bar, button, toolbar) rather than content, so a drag that
+/// starts there moves or scrolls something instead of selecting text.
+/// `None` if AX can't tell.
+#[cfg(feature = "drag-hook")]
+pub(crate) fn is_chrome_at(x: f64, y: f64) -> Option<bool> {
+    let role = element_at_position(x, y)?.role().ok()?.to_string();
+    Some(matches!(
+        role.as_str(),
+        "AXWindow"
+            | "AXScrollBar"
+            | "AXValueIndicator"
+            | "AXSlider"
+            | "AXButton"
+            | "AXImage"
+            | "AXSplitter"
+            | "AXToolbar"
+            | "AXMenuBar"
+            | "AXMenuBarItem"
+            | "AXTabGroup"
+    ))
+}
+
+/// The value, description and title of the element under the mouse pointer
+/// and its nearest ancestors, innermost first. Chat clients expose each
+/// message bubble's text this way while keeping it out of the focused
+/// element.
+pub(crate) fn texts_under_pointer() -> Vec<String> {
+    use core_graphics::event::CGEvent;
+    use core_graphics::event_source::{CGEventSource, CGEventSourceStateID};
+    use crate::ax_tree::AxNode;
+
+    let pointer = CGEventSource::new(CGEventSourceStateID::CombinedSessionState)
+        .and_then(CGEvent::new)
+        .map(|event| event.location());
+    let Some(mut element) = pointer.ok().and_then(|pointer| element_at_position(pointer.x, pointer.y)) else {
+        return Vec::new();
+    };
+    let parent_attribute = AXAttribute::new(&CFString::from_static_string("AXParent"));
+    let mut texts = Vec::new();
+    for _ in 0..POINTER_ANCESTORS {
+        // Skip AXValue of whole documents; a bubble is short.
+        if char_count(&element).map_or(true, |count| count <= crate::ax_tree::MAX_VALUE_CHARS) {
+            texts.extend(element.string_attribute("AXValue"));
+        }
+        texts.extend(element.string_attribute("AXDescription"));
+        texts.extend(element.string_attribute("AXTitle"));
+        let parent = element.attribute(&parent_attribute).ok().and_then(|parent| parent.downcast_into::<AXUIElement>());
+        let Some(parent) = parent else {
+            break;
+        };
+        element = parent;
+    }
+    texts.retain(|text| !text.is_empty());
+    texts
+}
+
+/// `AXNumberOfCharacters` of `element`, in UTF-16 units.
+fn char_count(element: &AXUIElement) -> Option<usize> {
+    element
+        .attribute(&AXAttribute::new(&CFString::from_static_string("AXNumberOfCharacters")))
+        .ok()?
+        .downcast_into::<CFNumber>()?
+        .to_i64()
+        .map(|count| count as usize)
+}
+
+fn get_selected_text_with_context_ax_first(
+    client: &SelectedTextClient,
+) -> Result<(String, Option<String>), Box<dyn std::error::Error>> {
     debug_println!("[CONTEXT_MACOS] Attempting to get selected text and AX description context.");
     // Directly call the enhanced AX function which now returns (String, Option<String>)
     match get_selected_text_by_ax_robust() {
         Ok((selected_text, context_option)) => {
+            if !selected_text.is_empty() {
+                crate::strategy::note_succeeded(Strategy::Accessibility);
+            }
             // If AX was able to get the selected text but not the context,
             // fall back to AppleScript to get both. This can happen in apps
             // like web browsers where AX context is unreliable.
+            if !selected_text.is_empty() && context_option.is_none() && !client.options().allows_input() {
+                debug_println!("[CONTEXT_MACOS] AX got text but no context, and the call may not simulate input.");
+                return Ok((selected_text, None));
+            }
             if !selected_text.is_empty() && context_option.is_none() {
                 debug_println!("[CONTEXT_MACOS] AX got text but no context. Falling back to mouse fallback.");
-                return get_selected_text_with_context_fallback();
+                return get_selected_text_with_context_fallback(client);
             }
 
             if selected_text.is_empty() && context_option.is_none() {
@@ -555,16 +1079,23 @@ pub fn get_selected_text_with_context() -> Result<(String, Option<String>), Box<
             debug_println!("[CONTEXT_MACOS] Selected text: '{}', AX Context: '{:?}'", selected_text, context_option);
             Ok((selected_text, context_option))
         }
+        // The copy and triple click below send input; the strategies the
+        // caller allowed may still find the selection.
+        Err(_) if !client.options().allows_input() => {
+            Ok((client.capture_text(client.options())?, None))
+        }
         Err(e) => {
             debug_println!("[CONTEXT_MACOS] Error in get_selected_text_by_ax_robust: {:?}. Falling back to mouse fallback.", e);
             // 改进的fallback：尝试使用AppleScript获取上下文
-            get_selected_text_with_context_fallback()
+            get_selected_text_with_context_fallback(client)
         }
     }
 }
 
 // Fallback method using mouse simulation to get context
-fn get_selected_text_with_context_fallback() -> Result<(String, Option<String>), Box<dyn std::error::Error>> {
+fn get_selected_text_with_context_fallback(
+    client: &SelectedTextClient,
+) -> Result<(String, Option<String>), Box<dyn std::error::Error>> {
     debug_println!("[CONTEXT_FALLBACK] Attempting to get text and context via fallback.");
     
     // First, get the currently selected text
@@ -574,12 +1105,34 @@ fn get_selected_text_with_context_fallback() -> Result<(String, Option<String>),
         debug_println!("[CONTEXT_FALLBACK] No selected text found via clipboard, aborting.");
         return Ok((String::new(), None));
     }
+    crate::strategy::note_succeeded(Strategy::AppleScript);
+
+    // The triple-click below is disruptive; reuse a recent result for the same selection.
+    let app_name = get_active_window().map(|w| w.app_name).unwrap_or_default();
+    let ttl = client.options().context_memo_ttl;
+    if let Some(context) = client.context_memo.get(&app_name, &selected_text, ttl) {
+        debug_println!("[CONTEXT_FALLBACK] Reusing memoized context.");
+        return Ok((selected_text, Some(context)));
+    }
     
+    let context_support = crate::quirks::app_quirks(&app_name).context;
+    if context_support == crate::ContextSupport::Pointer {
+        let size = client.options().context_size.as_ref().unwrap_or(&crate::context::DEFAULT_SIZE);
+        let context = crate::utils::context_under_pointer(&selected_text, size);
+        return Ok((selected_text, context));
+    }
+    if context_support == crate::ContextSupport::AccessibilityOnly {
+        debug_println!("[CONTEXT_FALLBACK] {} only gives context through AX, skipping the mouse.", app_name);
+        return Ok((selected_text, None));
+    }
+
     // Try to get the surrounding context using the mouse
     match get_context_by_mouse() {
         Ok(context) => {
             if context.contains(&selected_text) {
                 debug_println!("[CONTEXT_FALLBACK] Mouse context contains selected text.");
+                crate::context::set_confidence(crate::ContextConfidence::Medium);
+                client.context_memo.put(&app_name, &selected_text, &context);
                 Ok((selected_text, Some(context)))
             } else {
                 debug_println!("[CONTEXT_FALLBACK] Mouse context does not contain selected text.");
@@ -595,15 +1148,19 @@ fn get_selected_text_with_context_fallback() -> Result<(String, Option<String>),
 
 fn get_context_by_mouse() -> Result<String, Box<dyn std::error::Error>> {
     debug_println!("[CONTEXT_HYBRID] Attempting to get context via mouse click + AppleScript.");
-
-    let mut enigo = Enigo::new(&Settings::default())?;
+    let _sequence = crate::sequence::begin()?;
 
     // 1. Triple-click to select a paragraph using enigo.
-    for _ in 0..3 {
-        enigo.button(Button::Left, Click)?;
-        // A small delay between clicks is necessary for the OS to recognize it as a multi-click.
-        thread::sleep(Duration::from_millis(50));
-    }
+    crate::side_effects::keystrokes_sent();
+    crate::side_effects::selection_changed();
+    with_enigo(|enigo| -> Result<(), enigo::InputError> {
+        for _ in 0..3 {
+            enigo.button(Button::Left, Click)?;
+            // A small delay between clicks is necessary for the OS to recognize it as a multi-click.
+            thread::sleep(Duration::from_millis(50));
+        }
+        Ok(())
+    })??;
     // Give the system a moment to process the selection.
     thread::sleep(Duration::from_millis(150));
 
@@ -654,10 +1211,12 @@ tell application "System Events" to key code 123 -- Arrow Left
 return contextText
 "#;
 
+    let change_count = pasteboard_change_count();
     let output = std::process::Command::new("osascript")
         .arg("-e")
         .arg(COPY_AND_CLEANUP_SCRIPT)
         .output()?;
+    record_copy_script(change_count);
 
     if output.status.success() {
         let content = String::from_utf8(output.stdout)?;
@@ -667,7 +1226,7 @@ return contextText
         let err = String::from_utf8(output.stderr)?;
         debug_println!("[CONTEXT_HYBRID] Script failed: {}", err);
         // If the script fails, try to restore the selection state with enigo as a fallback.
-        enigo.button(Button::Left, Click)?;
+        with_enigo(|enigo| enigo.button(Button::Left, Click))??;
         Err(err.into())
     }
 }
@@ -0,0 +1,66 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::VecDeque;
+use std::hash::{Hash, Hasher};
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+
+use crate::context::ContextConfidence;
+
+const MAX_ENTRIES: usize = 8;
+
+#[derive(Debug)]
+struct Entry {
+    key: u64,
+    context: String,
+    confidence: Option<ContextConfidence>,
+    stored_at: Instant,
+}
+
+/// Recently computed contexts, keyed by a hash of the source app and the
+/// selected text, so that a hotkey and a watcher firing for the same selection
+/// only run the disruptive context retrieval once.
+#[derive(Debug, Default)]
+pub(crate) struct ContextMemo {
+    entries: Mutex<VecDeque<Entry>>,
+}
+
+fn key(app_name: &str, selected_text: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    app_name.hash(&mut hasher);
+    selected_text.hash(&mut hasher);
+    hasher.finish()
+}
+
+impl ContextMemo {
+    /// Returns the context stored for this selection within the last `ttl`,
+    /// noting the confidence it was stored with.
+    pub(crate) fn get(&self, app_name: &str, selected_text: &str, ttl: Duration) -> Option<String> {
+        if ttl.is_zero() {
+            return None;
+        }
+        let key = key(app_name, selected_text);
+        let mut entries = self.entries.lock();
+        entries.retain(|entry| entry.stored_at.elapsed() < ttl);
+        let entry = entries.iter().find(|entry| entry.key == key)?;
+        if let Some(confidence) = entry.confidence {
+            crate::context::set_confidence(confidence);
+        }
+        Some(entry.context.clone())
+    }
+
+    pub(crate) fn put(&self, app_name: &str, selected_text: &str, context: &str) {
+        let key = key(app_name, selected_text);
+        let mut entries = self.entries.lock();
+        entries.retain(|entry| entry.key != key);
+        if entries.len() >= MAX_ENTRIES {
+            entries.pop_front();
+        }
+        entries.push_back(Entry {
+            key,
+            context: context.to_string(),
+            confidence: crate::context::confidence(),
+            stored_at: Instant::now(),
+        });
+    }
+}
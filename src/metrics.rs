@@ -0,0 +1,146 @@
+//! Process-wide counters of capture outcomes, for products that chart the
+//! crate's reliability or look for apps that need a quirk.
+
+use std::collections::HashMap;
+use std::num::NonZeroUsize;
+use std::time::Duration;
+
+use lru::LruCache;
+use parking_lot::Mutex;
+
+use crate::Strategy;
+
+/// Latencies of this many most recent captures make up the percentiles.
+const LATENCY_SAMPLES: usize = 1024;
+const MAX_TRACKED_APPS: usize = 100;
+
+/// How often one strategy produced the selection.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct StrategyCounts {
+    pub successes: u64,
+    /// Attempts that failed or found nothing selected.
+    pub failures: u64,
+}
+
+/// Capture latencies, over the last 1024 captures.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct LatencyPercentiles {
+    pub p50: Duration,
+    pub p90: Duration,
+    pub p99: Duration,
+    pub max: Duration,
+}
+
+/// A snapshot of the counters since the process started or since
+/// [`reset_metrics`].
+#[derive(Debug, Clone, Default)]
+#[non_exhaustive]
+pub struct Metrics {
+    /// Calls to `get_selected_text*` and `get_selected_text_with_context*`.
+    pub captures_total: u64,
+    /// Calls among those that returned an error.
+    pub captures_failed: u64,
+    /// Outcomes of the individual strategies tried during those calls.
+    pub per_strategy_success: HashMap<Strategy, StrategyCounts>,
+    /// `None` until a capture has finished.
+    pub latency_percentiles: Option<LatencyPercentiles>,
+    /// Failed captures by focused app, for the 100 apps that failed most
+    /// recently.
+    pub per_app_failures: HashMap<String, u64>,
+}
+
+struct State {
+    captures_total: u64,
+    captures_failed: u64,
+    per_strategy: HashMap<Strategy, StrategyCounts>,
+    latencies: Vec<Duration>,
+    // Where the next latency goes once `latencies` is full.
+    next_latency: usize,
+    per_app_failures: LruCache<String, u64>,
+}
+
+impl State {
+    fn new() -> Self {
+        State {
+            captures_total: 0,
+            captures_failed: 0,
+            per_strategy: HashMap::new(),
+            latencies: Vec::with_capacity(LATENCY_SAMPLES),
+            next_latency: 0,
+            per_app_failures: LruCache::new(NonZeroUsize::new(MAX_TRACKED_APPS).unwrap()),
+        }
+    }
+}
+
+static STATE: Mutex<Option<State>> = Mutex::new(None);
+
+fn with_state<R>(f: impl FnOnce(&mut State) -> R) -> R {
+    f(STATE.lock().get_or_insert_with(State::new))
+}
+
+/// Records a finished capture, and for a failed one, the focused app.
+pub(crate) fn record_capture(failed: bool, latency: Duration) {
+    // Looked up before taking the lock; it can take a few milliseconds.
+    let failed_app = failed.then(|| {
+        crate::utils::focus_snapshot()
+            .map(|focus| focus.app_name().to_string())
+            .unwrap_or_default()
+    });
+    with_state(|state| {
+        state.captures_total += 1;
+        if state.latencies.len() < LATENCY_SAMPLES {
+            state.latencies.push(latency);
+        } else {
+            state.latencies[state.next_latency] = latency;
+            state.next_latency = (state.next_latency + 1) % LATENCY_SAMPLES;
+        }
+        if let Some(app) = failed_app {
+            state.captures_failed += 1;
+            *state.per_app_failures.get_or_insert_mut(app, || 0) += 1;
+        }
+    });
+}
+
+/// Records one strategy attempt.
+pub(crate) fn record_strategy(strategy: Strategy, succeeded: bool) {
+    with_state(|state| {
+        let counts = state.per_strategy.entry(strategy).or_default();
+        if succeeded {
+            counts.successes += 1;
+        } else {
+            counts.failures += 1;
+        }
+    });
+}
+
+/// The capture counters of this process.
+pub fn metrics() -> Metrics {
+    with_state(|state| {
+        let mut latencies = state.latencies.clone();
+        latencies.sort_unstable();
+        let percentile = |p: usize| latencies[(latencies.len() - 1) * p / 100];
+        Metrics {
+            captures_total: state.captures_total,
+            captures_failed: state.captures_failed,
+            per_strategy_success: state.per_strategy.clone(),
+            latency_percentiles: (!latencies.is_empty()).then(|| LatencyPercentiles {
+                p50: percentile(50),
+                p90: percentile(90),
+                p99: percentile(99),
+                max: percentile(100),
+            }),
+            per_app_failures: state
+                .per_app_failures
+                .iter()
+                .map(|(app, failures)| (app.clone(), *failures))
+                .collect(),
+        }
+    })
+}
+
+/// Sets every counter back to zero.
+pub fn reset_metrics() {
+    *STATE.lock() = None;
+}
@@ -0,0 +1,168 @@
+//! Reads Neovim's visual selection over its msgpack-RPC socket, with the
+//! lines around it as context. Only the handful of msgpack types the one
+//! request and its reply use are encoded and decoded here.
+
+use std::io::{Read, Write};
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde_json::Value;
+
+use crate::terminal::{descends_from, run};
+
+const RPC_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Lines of context kept on each side of the selection.
+const CONTEXT_LINES: usize = 5;
+
+/// Returns JSON `{text, context}`, or nil outside visual mode.
+const VISUAL_SELECTION_LUA: &str = r#"
+local mode = vim.fn.mode()
+if mode ~= 'v' and mode ~= 'V' and mode ~= '\22' then return nil end
+local from, to = vim.fn.getpos('v'), vim.fn.getpos('.')
+local lines = vim.fn.getregion(from, to, { type = mode })
+local first, last = math.min(from[2], to[2]), math.max(from[2], to[2])
+local around = ...
+local context = vim.api.nvim_buf_get_lines(0, math.max(first - 1 - around, 0), last + around, false)
+return vim.json.encode({ text = table.concat(lines, '\n'), context = table.concat(context, '\n') })
+"#;
+
+/// The visual selection of a Neovim running under `root_pid`, and the lines
+/// around it. Needs Neovim 0.10 for `getregion()` and its default server
+/// socket.
+pub(crate) fn visual_selection(root_pid: u64) -> Option<(String, String)> {
+    let pids = run("pgrep", &["-x", "nvim"])?;
+    let socket = pids
+        .lines()
+        .filter_map(|pid| pid.trim().parse::<u64>().ok())
+        .filter(|pid| descends_from(*pid, root_pid))
+        .find_map(server_socket)?;
+
+    let reply = exec_lua(&socket, VISUAL_SELECTION_LUA, CONTEXT_LINES as u64)?;
+    let selection: Value = serde_json::from_str(&reply).ok()?;
+    Some((
+        selection.get("text")?.as_str()?.to_string(),
+        selection.get("context")?.as_str()?.to_string(),
+    ))
+}
+
+/// Where Neovim puts its default `--listen` socket: `stdpath('run')`, which
+/// is `$XDG_RUNTIME_DIR` on Linux and a per-user directory under `$TMPDIR`
+/// otherwise.
+fn server_socket(pid: u64) -> Option<PathBuf> {
+    let name = format!("nvim.{}.0", pid);
+    if let Some(runtime_dir) = std::env::var_os("XDG_RUNTIME_DIR") {
+        let path = Path::new(&runtime_dir).join(&name);
+        if path.exists() {
+            return Some(path);
+        }
+    }
+    let user = std::env::var("USER").ok()?;
+    std::fs::read_dir(std::env::temp_dir().join(format!("nvim.{}", user)))
+        .ok()?
+        .filter_map(Result::ok)
+        .map(|entry| entry.path().join(&name))
+        .find(|path| path.exists())
+}
+
+fn exec_lua(socket: &Path, code: &str, arg: u64) -> Option<String> {
+    let mut stream = UnixStream::connect(socket).ok()?;
+    stream.set_read_timeout(Some(RPC_TIMEOUT)).ok()?;
+    stream.set_write_timeout(Some(RPC_TIMEOUT)).ok()?;
+
+    // [type = request, msgid, method, [code, [arg]]]
+    let mut request = vec![0x94, 0x00, 0x01];
+    write_str(&mut request, "nvim_exec_lua");
+    request.push(0x92);
+    write_str(&mut request, code);
+    request.push(0x91);
+    write_uint(&mut request, arg);
+    stream.write_all(&request).ok()?;
+
+    // [type = response, msgid, error, result]
+    let mut reply = Reply { stream };
+    if reply.array_len()? != 4 || reply.uint()? != 1 || reply.uint()? != 1 || !reply.nil()? {
+        return None;
+    }
+    reply.string()
+}
+
+fn write_str(buffer: &mut Vec<u8>, text: &str) {
+    let len = text.len();
+    match len {
+        0..=31 => buffer.push(0xa0 | len as u8),
+        32..=0xff => buffer.extend_from_slice(&[0xd9, len as u8]),
+        0x100..=0xffff => {
+            buffer.push(0xda);
+            buffer.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        _ => {
+            buffer.push(0xdb);
+            buffer.extend_from_slice(&(len as u32).to_be_bytes());
+        }
+    }
+    buffer.extend_from_slice(text.as_bytes());
+}
+
+fn write_uint(buffer: &mut Vec<u8>, value: u64) {
+    if value < 0x80 {
+        buffer.push(value as u8);
+    } else {
+        buffer.push(0xcf);
+        buffer.extend_from_slice(&value.to_be_bytes());
+    }
+}
+
+struct Reply {
+    stream: UnixStream,
+}
+
+impl Reply {
+    fn bytes<const N: usize>(&mut self) -> Option<[u8; N]> {
+        let mut bytes = [0u8; N];
+        self.stream.read_exact(&mut bytes).ok()?;
+        Some(bytes)
+    }
+
+    fn byte(&mut self) -> Option<u8> {
+        self.bytes::<1>().map(|[byte]| byte)
+    }
+
+    fn array_len(&mut self) -> Option<usize> {
+        match self.byte()? {
+            marker @ 0x90..=0x9f => Some((marker & 0x0f) as usize),
+            0xdc => Some(u16::from_be_bytes(self.bytes()?) as usize),
+            _ => None,
+        }
+    }
+
+    fn uint(&mut self) -> Option<u64> {
+        match self.byte()? {
+            value @ 0x00..=0x7f => Some(value as u64),
+            0xcc => Some(self.byte()? as u64),
+            0xcd => Some(u16::from_be_bytes(self.bytes()?) as u64),
+            0xce => Some(u32::from_be_bytes(self.bytes()?) as u64),
+            0xcf => Some(u64::from_be_bytes(self.bytes()?)),
+            _ => None,
+        }
+    }
+
+    fn nil(&mut self) -> Option<bool> {
+        Some(self.byte()? == 0xc0)
+    }
+
+    /// A string, or `None` for nil and anything else.
+    fn string(&mut self) -> Option<String> {
+        let len = match self.byte()? {
+            marker @ 0xa0..=0xbf => (marker & 0x1f) as usize,
+            0xd9 => self.byte()? as usize,
+            0xda => u16::from_be_bytes(self.bytes()?) as usize,
+            0xdb => u32::from_be_bytes(self.bytes()?) as usize,
+            _ => return None,
+        };
+        let mut text = vec![0u8; len];
+        self.stream.read_exact(&mut text).ok()?;
+        String::from_utf8(text).ok()
+    }
+}
@@ -0,0 +1,239 @@
+use std::time::Duration;
+
+use crate::{ContextSize, Strategy};
+
+/// Per-call settings for the `*_with_options` entry points.
+///
+/// Start from `GetTextOptions::default()` and chain the setters you need.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct GetTextOptions {
+    /// Ignore the strategy order learned for the focused application and try
+    /// every strategy in its default order. The outcomes are still recorded.
+    pub bypass_cache: bool,
+    /// How long a computed context is reused for the same app and selected
+    /// text. Zero disables the reuse.
+    pub context_memo_ttl: Duration,
+    /// Ask UIA/AX whether anything is selected before simulating a copy, and
+    /// fail with `GetTextError::NoSelection` if the focused text control
+    /// definitively has no selection.
+    pub precheck_selection: bool,
+    /// How much of the surrounding text `get_selected_text_with_context`
+    /// returns. `None` keeps each backend's own sizing: 150 characters either
+    /// side for document text, the whole element value or paragraph otherwise.
+    pub context_size: Option<ContextSize>,
+    /// Skip the Select All + Copy context fallback when UIA reports a document
+    /// longer than this many characters, returning the selection without
+    /// context. Copying a whole book freezes the target app and the clipboard.
+    pub max_document_chars: usize,
+    /// Allow the Select All + Copy context fallback in editable controls. Off
+    /// by default: if a stray keystroke lands while everything is selected,
+    /// the user's document is replaced.
+    pub allow_select_all_in_editable: bool,
+    /// How long the Select All + Copy context fallback may take before it
+    /// gives up with `GetTextError::Timeout`.
+    pub select_all_timeout: Duration,
+    /// Put the user's selection back through AX/UIA after Select All + Copy.
+    /// When off, or when the selection can't be saved, Select All is undone
+    /// with Escape and the arrow keys, which leaves the caret at one end of
+    /// the document.
+    pub restore_selection: bool,
+    /// The strategies to try, in this order, instead of the platform's
+    /// default chain. Learned reordering doesn't apply, strategies missing
+    /// from the list never run, and strategies that don't exist on this
+    /// platform fail with `FailureReason::Unsupported`. `None` uses the
+    /// default chain. When none of the strategies left sends input (see
+    /// [`Strategy::sends_input`]), reading the context or the document
+    /// also skips its Select All, copy and click fallbacks.
+    pub strategies: Option<Vec<Strategy>>,
+    /// Save the clipboard before simulating a copy and put it back afterwards.
+    /// Turn it off when the host app manages the clipboard itself, to save
+    /// about 100 ms per call; `SideEffects` then reports the clipboard as
+    /// modified and not restored. Copies driven by AppleScript or PowerShell
+    /// always restore.
+    pub restore_clipboard: bool,
+    /// Capture even when a window of this process is focused. Off by default:
+    /// the simulated shortcuts would land in the host app itself (its search
+    /// box, say), and the call fails with `GetTextError::OwnWindowFocused`.
+    /// Registered extractors still run first, so one matching this process's
+    /// id can read the host's own selection instead.
+    pub allow_own_window: bool,
+    /// Strategies that never run, whatever the order. Applies after
+    /// `strategies` and the learned order.
+    pub disabled_strategies: Vec<Strategy>,
+    /// Press the right arrow after a capture that found text, so the
+    /// selection is collapsed and the caret sits at its end, as after
+    /// clicking away. Off by default. Skipped when the text came from an
+    /// extractor, a terminal integration, LibreOffice or the browser bridge
+    /// rather than a strategy, and when the call may not send input (see
+    /// `strategies`). In read-only views the key may scroll instead.
+    pub cancel_select: bool,
+    /// How long the Clipboard strategy waits for the app to answer the
+    /// simulated copy. `None` uses the app's quirk, else the wait found by
+    /// `calibrate()`. `GST_TIMEOUT_MS` still wins over this.
+    pub copy_wait: Option<Duration>,
+    /// How long a call waits for the captures other threads started before
+    /// it, which run one at a time in the order they were made, before
+    /// failing with `GetTextError::Timeout`.
+    pub queue_timeout: Duration,
+    /// Let a `get_selected_text_with_options` call with the same options as
+    /// one already waiting or running share that call's result instead of
+    /// capturing again. For hosts where several components react to the
+    /// same hotkey.
+    pub coalesce_requests: bool,
+    /// X11: when the focused app owns PRIMARY but doesn't answer the crate's
+    /// request for it, have `Strategy::Primary` middle-click a 1x1 window of
+    /// the crate's own under the pointer and ask again as a paste would. Off
+    /// by default. The click is simulated input, so it only happens when the
+    /// call may send input; with this on, `Strategy::Primary` counts as
+    /// sending it.
+    pub primary_paste: bool,
+}
+
+impl Default for GetTextOptions {
+    /// The crate's defaults, or with the `config` feature, those of the
+    /// configuration file loaded by `init()`.
+    fn default() -> Self {
+        #[cfg(feature = "config")]
+        if let Some(options) = crate::config::default_options() {
+            return options;
+        }
+        Self::builtin()
+    }
+}
+
+impl GetTextOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The crate's own defaults, ignoring any configuration file.
+    pub(crate) fn builtin() -> Self {
+        Self {
+            bypass_cache: false,
+            context_memo_ttl: Duration::from_secs(2),
+            precheck_selection: true,
+            context_size: None,
+            max_document_chars: 2_000_000,
+            allow_select_all_in_editable: false,
+            select_all_timeout: Duration::from_secs(5),
+            restore_selection: true,
+            strategies: None,
+            restore_clipboard: true,
+            allow_own_window: false,
+            disabled_strategies: Vec::new(),
+            cancel_select: false,
+            copy_wait: None,
+            queue_timeout: Duration::from_secs(10),
+            coalesce_requests: false,
+            primary_paste: false,
+        }
+    }
+
+    /// Forces full strategy discovery for this call, e.g. for a "retry with all
+    /// methods" action after the cached strategy started returning nothing.
+    pub fn bypass_cache(mut self, bypass: bool) -> Self {
+        self.bypass_cache = bypass;
+        self
+    }
+
+    pub fn context_memo_ttl(mut self, ttl: Duration) -> Self {
+        self.context_memo_ttl = ttl;
+        self
+    }
+
+    pub fn precheck_selection(mut self, precheck: bool) -> Self {
+        self.precheck_selection = precheck;
+        self
+    }
+
+    /// Sizes the context, e.g. `ContextSize::tokens(512)` for "up to 512
+    /// tokens around the selection" when feeding an LLM prompt.
+    pub fn context_size(mut self, size: ContextSize) -> Self {
+        self.context_size = Some(size);
+        self
+    }
+
+    pub fn max_document_chars(mut self, limit: usize) -> Self {
+        self.max_document_chars = limit;
+        self
+    }
+
+    pub fn allow_select_all_in_editable(mut self, allow: bool) -> Self {
+        self.allow_select_all_in_editable = allow;
+        self
+    }
+
+    pub fn select_all_timeout(mut self, timeout: Duration) -> Self {
+        self.select_all_timeout = timeout;
+        self
+    }
+
+    pub fn restore_selection(mut self, restore: bool) -> Self {
+        self.restore_selection = restore;
+        self
+    }
+
+    /// Restricts the call to `strategies`, tried in this order. Pass
+    /// `&[Strategy::Accessibility, Strategy::Uia]` to never simulate input,
+    /// for the context as well, or a single strategy to use only that one.
+    pub fn strategies(mut self, strategies: &[Strategy]) -> Self {
+        self.strategies = Some(strategies.to_vec());
+        self
+    }
+
+    pub fn restore_clipboard(mut self, restore: bool) -> Self {
+        self.restore_clipboard = restore;
+        self
+    }
+
+    pub fn allow_own_window(mut self, allow: bool) -> Self {
+        self.allow_own_window = allow;
+        self
+    }
+
+    pub fn disable_strategy(mut self, strategy: Strategy) -> Self {
+        if !self.disabled_strategies.contains(&strategy) {
+            self.disabled_strategies.push(strategy);
+        }
+        self
+    }
+
+    pub fn cancel_select(mut self, cancel: bool) -> Self {
+        self.cancel_select = cancel;
+        self
+    }
+
+    pub fn copy_wait(mut self, wait: Duration) -> Self {
+        self.copy_wait = Some(wait);
+        self
+    }
+
+    pub fn queue_timeout(mut self, timeout: Duration) -> Self {
+        self.queue_timeout = timeout;
+        self
+    }
+
+    pub fn coalesce_requests(mut self, coalesce: bool) -> Self {
+        self.coalesce_requests = coalesce;
+        self
+    }
+
+    pub fn primary_paste(mut self, paste: bool) -> Self {
+        self.primary_paste = paste;
+        self
+    }
+
+    /// Whether a call with these options may simulate input: whether some
+    /// strategy it may run sends it.
+    pub(crate) fn allows_input(&self) -> bool {
+        let sends_input = |strategy: &Strategy| {
+            strategy.sends_input() || (self.primary_paste && *strategy == Strategy::Primary)
+        };
+        let allowed = |strategy: &Strategy| sends_input(strategy) && !self.disabled_strategies.contains(strategy);
+        match &self.strategies {
+            Some(strategies) => strategies.iter().any(allowed),
+            None => Strategy::SENDING_INPUT.iter().chain([Strategy::Primary].iter()).any(allowed),
+        }
+    }
+}
@@ -0,0 +1,267 @@
+//! Checks for the OS permissions and services the capture strategies rely on,
+//! so an app can tell its user what to fix instead of reporting empty results.
+//!
+//! Only the capabilities relevant to the current platform are reported.
+
+/// Something a capture strategy needs from the OS.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Capability {
+    /// Reading other apps' UI through AX (macOS) or UI Automation (Windows).
+    Accessibility,
+    /// Sending simulated key presses to the focused app.
+    InputSimulation,
+    /// Reaching apps that run elevated (Windows UIPI).
+    ElevatedTargets,
+    /// Writing to `/dev/uinput`, which works on Wayland where XTEST doesn't.
+    UInput,
+    /// Talking to the AT-SPI accessibility bus.
+    AtSpi,
+    /// The foreground app's own screen-reader support, for apps that expose
+    /// their text to accessibility APIs only when it is on. Reported only
+    /// while such an app is in the foreground.
+    AppAccessibility,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Status {
+    Granted,
+    Denied,
+    /// The check itself couldn't run, e.g. a helper tool is missing.
+    Unknown,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct CapabilityStatus {
+    pub capability: Capability,
+    pub status: Status,
+    /// What the user can do about a `Denied` or `Unknown` status.
+    pub hint: Option<String>,
+}
+
+impl CapabilityStatus {
+    fn new(capability: Capability, status: Status, hint: Option<&str>) -> Self {
+        CapabilityStatus {
+            capability,
+            status,
+            hint: hint.map(str::to_string),
+        }
+    }
+}
+
+/// Runs every check for the current platform. None of them simulate input.
+pub fn check() -> Vec<CapabilityStatus> {
+    let mut statuses = platform_check();
+    statuses.extend(foreground_app_check());
+    statuses
+}
+
+#[cfg(any(target_os = "macos", target_os = "windows"))]
+fn foreground_app_check() -> Option<CapabilityStatus> {
+    let focus = crate::utils::focus_snapshot()?;
+    if !crate::apps::is_jetbrains(focus.app_name()) {
+        return None;
+    }
+    #[cfg(target_os = "macos")]
+    let exposes_text = crate::macos::focused_exposes_text();
+    #[cfg(target_os = "windows")]
+    let exposes_text = crate::windows::focused_exposes_text();
+    Some(match exposes_text {
+        Some(true) => CapabilityStatus::new(Capability::AppAccessibility, Status::Granted, None),
+        Some(false) => CapabilityStatus::new(
+            Capability::AppAccessibility,
+            Status::Denied,
+            Some(crate::apps::JETBRAINS_HINT),
+        ),
+        None => CapabilityStatus::new(Capability::AppAccessibility, Status::Unknown, None),
+    })
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+fn foreground_app_check() -> Option<CapabilityStatus> {
+    None
+}
+
+#[cfg(target_os = "macos")]
+fn platform_check() -> Vec<CapabilityStatus> {
+    let status = if macos_accessibility_client::accessibility::application_is_trusted() {
+        Status::Granted
+    } else {
+        Status::Denied
+    };
+    let hint = (status == Status::Denied).then_some(
+        "Grant access in System Settings > Privacy & Security > Accessibility, then restart the app.",
+    );
+    vec![
+        CapabilityStatus::new(Capability::Accessibility, status, hint),
+        // Synthetic key events are gated by the same trust.
+        CapabilityStatus::new(Capability::InputSimulation, status, hint),
+    ]
+}
+
+#[cfg(target_os = "windows")]
+fn platform_check() -> Vec<CapabilityStatus> {
+    let accessibility = if uiautomation::UIAutomation::new().is_ok() {
+        CapabilityStatus::new(Capability::Accessibility, Status::Granted, None)
+    } else {
+        CapabilityStatus::new(
+            Capability::Accessibility,
+            Status::Denied,
+            Some("UI Automation could not be initialized; check that COM is not blocked for this process."),
+        )
+    };
+
+    let own = windows_token::current();
+    let foreground_elevated = windows_token::foreground_elevated();
+    let elevated_targets = match (own, foreground_elevated) {
+        (Some(own), Some(true)) if !own.elevated && !own.ui_access => CapabilityStatus::new(
+            Capability::ElevatedTargets,
+            Status::Denied,
+            Some(
+                "The foreground app runs as administrator, so UIPI blocks simulated input and UI \
+                 Automation from this process. Run this app elevated, or give it uiAccess=\"true\" \
+                 in its manifest (signed and installed under Program Files).",
+            ),
+        ),
+        (Some(_), Some(_)) => CapabilityStatus::new(Capability::ElevatedTargets, Status::Granted, None),
+        _ => CapabilityStatus::new(Capability::ElevatedTargets, Status::Unknown, None),
+    };
+
+    vec![accessibility, elevated_targets]
+}
+
+#[cfg(target_os = "windows")]
+mod windows_token {
+    use windows_sys::Win32::Foundation::{CloseHandle, GetLastError, ERROR_ACCESS_DENIED, HANDLE};
+    use windows_sys::Win32::Security::{
+        GetTokenInformation, TokenElevation, TokenUIAccess, TOKEN_INFORMATION_CLASS, TOKEN_QUERY,
+    };
+    use windows_sys::Win32::System::Threading::{
+        GetCurrentProcess, OpenProcess, OpenProcessToken, PROCESS_QUERY_LIMITED_INFORMATION,
+    };
+    use windows_sys::Win32::UI::WindowsAndMessaging::{GetForegroundWindow, GetWindowThreadProcessId};
+
+    pub(super) struct TokenFlags {
+        pub(super) elevated: bool,
+        pub(super) ui_access: bool,
+    }
+
+    // TOKEN_ELEVATION and the TokenUIAccess result are both a single DWORD.
+    unsafe fn query_u32(token: HANDLE, class: TOKEN_INFORMATION_CLASS) -> Option<u32> {
+        let mut value = 0u32;
+        let mut len = 0u32;
+        let ok = GetTokenInformation(
+            token,
+            class,
+            &mut value as *mut u32 as *mut _,
+            std::mem::size_of::<u32>() as u32,
+            &mut len,
+        );
+        (ok != 0).then_some(value)
+    }
+
+    unsafe fn flags(process: HANDLE) -> Option<TokenFlags> {
+        let mut token: HANDLE = 0;
+        if OpenProcessToken(process, TOKEN_QUERY, &mut token) == 0 {
+            return None;
+        }
+        let elevated = query_u32(token, TokenElevation);
+        let ui_access = query_u32(token, TokenUIAccess);
+        CloseHandle(token);
+        Some(TokenFlags {
+            elevated: elevated? != 0,
+            ui_access: ui_access.unwrap_or(0) != 0,
+        })
+    }
+
+    pub(super) fn current() -> Option<TokenFlags> {
+        unsafe { flags(GetCurrentProcess()) }
+    }
+
+    pub(super) fn foreground_elevated() -> Option<bool> {
+        unsafe {
+            let window = GetForegroundWindow();
+            if window == 0 {
+                return None;
+            }
+            let mut pid = 0u32;
+            GetWindowThreadProcessId(window, &mut pid);
+            let process = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid);
+            if process == 0 {
+                // A non-elevated process can't even query an elevated one.
+                return (GetLastError() == ERROR_ACCESS_DENIED).then_some(true);
+            }
+            let flags = flags(process);
+            CloseHandle(process);
+            flags.map(|f| f.elevated)
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn platform_check() -> Vec<CapabilityStatus> {
+    let wayland = std::env::var_os("WAYLAND_DISPLAY").is_some();
+    let x11 = std::env::var_os("DISPLAY").is_some();
+    let input = match (wayland, x11) {
+        // The Auto input backend switches to uinput here.
+        (true, _) if crate::uinput::available() => {
+            CapabilityStatus::new(Capability::InputSimulation, Status::Granted, None)
+        }
+        (true, _) => CapabilityStatus::new(
+            Capability::InputSimulation,
+            Status::Denied,
+            Some("This is a Wayland session: XTEST input only reaches XWayland windows. Use uinput instead (see UInput)."),
+        ),
+        (false, true) => CapabilityStatus::new(Capability::InputSimulation, Status::Granted, None),
+        (false, false) => CapabilityStatus::new(
+            Capability::InputSimulation,
+            Status::Denied,
+            Some("Neither WAYLAND_DISPLAY nor DISPLAY is set; run inside the graphical session."),
+        ),
+    };
+
+    let uinput = if crate::uinput::available() {
+        CapabilityStatus::new(Capability::UInput, Status::Granted, None)
+    } else {
+        CapabilityStatus::new(
+            Capability::UInput,
+            Status::Denied,
+            Some("Add the user to the `input` group or install a udev rule granting write access to /dev/uinput."),
+        )
+    };
+
+    let at_spi = match std::process::Command::new("gdbus")
+        .args([
+            "call",
+            "--session",
+            "--dest",
+            "org.a11y.Bus",
+            "--object-path",
+            "/org/a11y/bus",
+            "--method",
+            "org.a11y.Bus.GetAddress",
+        ])
+        .output()
+    {
+        Ok(output) if output.status.success() => CapabilityStatus::new(Capability::AtSpi, Status::Granted, None),
+        Ok(_) => CapabilityStatus::new(
+            Capability::AtSpi,
+            Status::Denied,
+            Some("The AT-SPI bus is not running; install at-spi2-core and enable accessibility in the desktop settings."),
+        ),
+        Err(_) => CapabilityStatus::new(
+            Capability::AtSpi,
+            Status::Unknown,
+            Some("`gdbus` was not found, so the AT-SPI bus could not be checked."),
+        ),
+    };
+
+    vec![input, uinput, at_spi]
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows", target_os = "linux")))]
+fn platform_check() -> Vec<CapabilityStatus> {
+    Vec::new()
+}
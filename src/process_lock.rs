@@ -0,0 +1,109 @@
+//! System-wide lock around simulated input and clipboard sequences, so that two
+//! processes embedding this crate (or two instances of one app) never interleave
+//! their Ctrl+A / Ctrl+C sequences.
+
+use std::time::Duration;
+
+use crate::GetTextError;
+
+/// How long a capture waits for another process to finish its sequence.
+pub(crate) const DEFAULT_WAIT: Duration = Duration::from_secs(2);
+
+const LOCK_NAME: &str = "get-selected-text-input";
+
+#[cfg(unix)]
+pub(crate) struct ProcessLockGuard {
+    file: std::fs::File,
+}
+
+#[cfg(unix)]
+pub(crate) fn acquire(timeout: Duration) -> Result<ProcessLockGuard, GetTextError> {
+    use std::os::unix::io::AsRawFd;
+    use std::time::Instant;
+
+    let path = std::env::temp_dir().join(format!("{}.lock", LOCK_NAME));
+    let file = std::fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&path)
+        .map_err(|e| GetTextError::Os(format!("Failed to open {}: {}", path.display(), e)))?;
+
+    let started = Instant::now();
+    loop {
+        if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } == 0 {
+            return Ok(ProcessLockGuard { file });
+        }
+        if started.elapsed() >= timeout {
+            return Err(GetTextError::InputBusy);
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+}
+
+#[cfg(unix)]
+impl Drop for ProcessLockGuard {
+    fn drop(&mut self) {
+        use std::os::unix::io::AsRawFd;
+        unsafe {
+            libc::flock(self.file.as_raw_fd(), libc::LOCK_UN);
+        }
+    }
+}
+
+#[cfg(windows)]
+pub(crate) struct ProcessLockGuard {
+    handle: windows_sys::Win32::Foundation::HANDLE,
+}
+
+#[cfg(windows)]
+pub(crate) fn acquire(timeout: Duration) -> Result<ProcessLockGuard, GetTextError> {
+    use windows_sys::Win32::Foundation::{CloseHandle, GetLastError, WAIT_ABANDONED, WAIT_OBJECT_0, WAIT_TIMEOUT};
+    use windows_sys::Win32::System::Threading::{CreateMutexW, WaitForSingleObject};
+
+    // "Local\" scopes the mutex to the session, which is where input goes.
+    let name: Vec<u16> = format!("Local\\{}", LOCK_NAME)
+        .encode_utf16()
+        .chain(std::iter::once(0))
+        .collect();
+    let handle = unsafe { CreateMutexW(std::ptr::null(), 0, name.as_ptr()) };
+    if handle == 0 {
+        let code = unsafe { GetLastError() };
+        return Err(GetTextError::Os(format!("CreateMutexW failed: {}", code)));
+    }
+
+    let millis = timeout.as_millis().min(u32::MAX as u128 - 1) as u32;
+    match unsafe { WaitForSingleObject(handle, millis) } {
+        // An abandoned mutex means the previous owner died mid-sequence; we own it now.
+        WAIT_OBJECT_0 | WAIT_ABANDONED => Ok(ProcessLockGuard { handle }),
+        WAIT_TIMEOUT => {
+            unsafe { CloseHandle(handle) };
+            Err(GetTextError::InputBusy)
+        }
+        _ => {
+            let code = unsafe { GetLastError() };
+            unsafe { CloseHandle(handle) };
+            Err(GetTextError::Os(format!("WaitForSingleObject failed: {}", code)))
+        }
+    }
+}
+
+#[cfg(windows)]
+impl Drop for ProcessLockGuard {
+    fn drop(&mut self) {
+        use windows_sys::Win32::Foundation::CloseHandle;
+        use windows_sys::Win32::System::Threading::ReleaseMutex;
+        unsafe {
+            ReleaseMutex(self.handle);
+            CloseHandle(self.handle);
+        }
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+pub(crate) struct ProcessLockGuard;
+
+#[cfg(not(any(unix, windows)))]
+pub(crate) fn acquire(_timeout: Duration) -> Result<ProcessLockGuard, GetTextError> {
+    Ok(ProcessLockGuard)
+}
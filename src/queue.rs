@@ -0,0 +1,133 @@
+//! First-come, first-served admission for captures started on several threads
+//! at once. Without it, callers race for the input lock and the clipboard in
+//! whatever order the OS wakes them, and a busy thread can starve the rest.
+//! Each top-level capture takes a ticket and runs when its number comes up,
+//! or gives up once its deadline has passed.
+
+use std::collections::BTreeSet;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use parking_lot::{Condvar, Mutex};
+
+use crate::GetTextError;
+
+struct Tickets {
+    next: u64,
+    serving: u64,
+    /// Tickets whose callers stopped waiting before their turn.
+    abandoned: BTreeSet<u64>,
+}
+
+impl Tickets {
+    /// Moves on to the next ticket whose caller is still waiting.
+    fn advance(&mut self) {
+        self.serving += 1;
+        while self.abandoned.remove(&self.serving) {
+            self.serving += 1;
+        }
+    }
+}
+
+static TICKETS: Mutex<Tickets> = Mutex::new(Tickets {
+    next: 0,
+    serving: 0,
+    abandoned: BTreeSet::new(),
+});
+static TURN: Condvar = Condvar::new();
+
+fn deadline(timeout: Duration) -> Instant {
+    let now = Instant::now();
+    now.checked_add(timeout).unwrap_or(now + Duration::from_secs(86_400))
+}
+
+/// Held while a capture has its turn. The next caller in line goes when it
+/// is dropped.
+pub(crate) struct Turn(());
+
+impl Drop for Turn {
+    fn drop(&mut self) {
+        TICKETS.lock().advance();
+        TURN.notify_all();
+    }
+}
+
+/// Waits until every capture that asked before this one has finished.
+/// Fails with `GetTextError::Timeout` if that takes longer than `timeout`,
+/// without holding up the callers behind it.
+pub(crate) fn wait_turn(timeout: Duration) -> Result<Turn, GetTextError> {
+    let deadline = deadline(timeout);
+    let mut tickets = TICKETS.lock();
+    let ticket = tickets.next;
+    tickets.next += 1;
+    while tickets.serving != ticket {
+        if TURN.wait_until(&mut tickets, deadline).timed_out() && tickets.serving != ticket {
+            tickets.abandoned.insert(ticket);
+            return Err(GetTextError::Timeout);
+        }
+    }
+    Ok(Turn(()))
+}
+
+/// A capture that later identical requests wait on instead of running their
+/// own.
+struct Shared {
+    result: Mutex<Option<Result<String, GetTextError>>>,
+    done: Condvar,
+}
+
+static IN_FLIGHT: Mutex<Vec<(String, Arc<Shared>)>> = Mutex::new(Vec::new());
+
+/// Runs `capture`, unless a request with the same `key` is already queued or
+/// running, in which case this waits up to `timeout` for that one and
+/// returns a copy of its result.
+pub(crate) fn coalesce(
+    key: String,
+    timeout: Duration,
+    capture: impl FnOnce() -> Result<String, GetTextError>,
+) -> Result<String, GetTextError> {
+    let mut in_flight = IN_FLIGHT.lock();
+    if let Some((_, shared)) = in_flight.iter().find(|(in_flight_key, _)| *in_flight_key == key) {
+        let shared = shared.clone();
+        drop(in_flight);
+        let deadline = deadline(timeout);
+        let mut result = shared.result.lock();
+        while result.is_none() {
+            if shared.done.wait_until(&mut result, deadline).timed_out() && result.is_none() {
+                return Err(GetTextError::Timeout);
+            }
+        }
+        return result.clone().unwrap();
+    }
+    let shared = Arc::new(Shared {
+        result: Mutex::new(None),
+        done: Condvar::new(),
+    });
+    in_flight.push((key, shared.clone()));
+    drop(in_flight);
+
+    let result = capture();
+    IN_FLIGHT.lock().retain(|(_, other)| !Arc::ptr_eq(other, &shared));
+    *shared.result.lock() = Some(result.clone());
+    shared.done.notify_all();
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn advancing_skips_abandoned_tickets() {
+        let mut tickets = Tickets {
+            next: 5,
+            serving: 0,
+            abandoned: BTreeSet::from([1, 2, 4]),
+        };
+        tickets.advance();
+        assert_eq!(tickets.serving, 3);
+        tickets.advance();
+        assert_eq!(tickets.serving, 5);
+        assert!(tickets.abandoned.is_empty());
+    }
+}
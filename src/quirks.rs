@@ -0,0 +1,250 @@
+//! Known quirks of popular apps, so they work without each integrator
+//! rediscovering the same workarounds. Entries match the focused app's name
+//! as a whole, lowercased and without `.exe`, and can be replaced at runtime
+//! with [`set_app_quirks`].
+
+use std::cell::Cell;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use parking_lot::RwLock;
+
+use crate::Strategy;
+
+/// The shortcut that copies the selection in an app. Only applies where the
+/// crate sends key presses itself (Windows, Linux); macOS copies with Cmd+C
+/// through System Events.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum CopyShortcut {
+    /// Ctrl+C.
+    #[default]
+    CtrlC,
+    /// Ctrl+Shift+C, as in terminals where Ctrl+C interrupts the foreground
+    /// program.
+    CtrlShiftC,
+    /// Ctrl+Insert, which Windows consoles and many older apps accept.
+    CtrlInsert,
+}
+
+impl CopyShortcut {
+    /// The evdev key codes, for the uinput and ydotool backends.
+    #[cfg(target_os = "linux")]
+    pub(crate) fn evdev_keys(self) -> (&'static [u16], u16) {
+        use crate::uinput::{KEY_C, KEY_INSERT, KEY_LEFTCTRL, KEY_LEFTSHIFT};
+
+        match self {
+            CopyShortcut::CtrlC => (&[KEY_LEFTCTRL], KEY_C),
+            CopyShortcut::CtrlShiftC => (&[KEY_LEFTCTRL, KEY_LEFTSHIFT], KEY_C),
+            CopyShortcut::CtrlInsert => (&[KEY_LEFTCTRL], KEY_INSERT),
+        }
+    }
+}
+
+/// How the context around a selection may be read in an app.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ContextSupport {
+    /// Through AX/UIA, falling back to changing the selection (Select All or
+    /// a triple-click) and copying it.
+    #[default]
+    Full,
+    /// Only through AX/UIA. For apps where Select All selects something else
+    /// (a terminal's whole scrollback, a chat's input box) or is too slow
+    /// (a whole PDF).
+    AccessibilityOnly,
+    /// Only from the element under the mouse pointer, found by AX/UIA hit
+    /// testing: in chat clients, the message bubble the selection was made
+    /// in. For apps that keep their text out of the focused element.
+    Pointer,
+}
+
+/// What the crate does differently for one app.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct AppQuirks {
+    /// The strategies to start from instead of the platform's default order.
+    /// Learned reordering still applies, and `GetTextOptions::strategies`
+    /// still wins over this.
+    pub strategies: Option<Vec<Strategy>>,
+    pub copy_shortcut: CopyShortcut,
+    pub context: ContextSupport,
+    /// How long to wait for the app to answer the copy, instead of the
+    /// calibrated wait. `GST_TIMEOUT_MS` still wins over this.
+    pub copy_wait: Option<Duration>,
+}
+
+impl AppQuirks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn strategies(mut self, strategies: &[Strategy]) -> Self {
+        self.strategies = Some(strategies.to_vec());
+        self
+    }
+
+    pub fn copy_shortcut(mut self, shortcut: CopyShortcut) -> Self {
+        self.copy_shortcut = shortcut;
+        self
+    }
+
+    pub fn context(mut self, context: ContextSupport) -> Self {
+        self.context = context;
+        self
+    }
+
+    pub fn copy_wait(mut self, wait: Duration) -> Self {
+        self.copy_wait = Some(wait);
+        self
+    }
+}
+
+const CHROMIUM: &[&str] = &[
+    "google chrome", "chrome", "google-chrome", "chromium", "chromium-browser", "microsoft edge", "msedge",
+    "brave browser", "brave",
+];
+
+const WORD: &[&str] = &["microsoft word", "winword"];
+
+const CHAT_CLIENTS: &[&str] = &[
+    "wechat", "weixin", "微信", "qq", "qqnt", "tim", "wxwork", "wecom", "企业微信", "dingtalk", "钉钉", "feishu",
+    "飞书", "lark",
+];
+
+const OUTLOOK: &[&str] = &["outlook", "microsoft outlook"];
+
+const EXPLORER: &[&str] = &["explorer", "file explorer"];
+
+const WINDOWS_TERMINAL: &[&str] = &["windowsterminal", "windows terminal"];
+
+const LINUX_TERMINALS: &[&str] = &[
+    "gnome-terminal-server", "gnome-terminal", "ptyxis", "konsole", "xfce4-terminal", "tilix", "terminator",
+    "alacritty", "kitty", "wezterm-gui", "foot", "xterm",
+];
+
+fn builtin() -> &'static [(&'static [&'static str], AppQuirks)] {
+    static BUILTIN: OnceLock<Vec<(&'static [&'static str], AppQuirks)>> = OnceLock::new();
+    BUILTIN.get_or_init(|| {
+        let copy_strategy = if cfg!(target_os = "macos") { Strategy::AppleScript } else { Strategy::Clipboard };
+        let mut table = vec![
+            // They keep their text out of AX and UIA, or hang reading it, and
+            // Select All selects the input box. Their copy handlers are slow
+            // to fill the clipboard, and the message bubble under the pointer
+            // is the only context hit testing can find.
+            (
+                CHAT_CLIENTS,
+                AppQuirks::new()
+                    .strategies(&[copy_strategy])
+                    .context(ContextSupport::Pointer)
+                    .copy_wait(Duration::from_millis(600)),
+            ),
+            // Its paragraphs are read through IAccessible2 or AT-SPI;
+            // Select All would copy the whole document.
+            (&crate::apps::LIBREOFFICE[..], AppQuirks::new().context(ContextSupport::AccessibilityOnly)),
+            // Select All copies the whole PDF.
+            (&crate::apps::ACROBAT[..], AppQuirks::new().context(ContextSupport::AccessibilityOnly)),
+            // Ctrl+C interrupts the shell when nothing is selected, and Ctrl+A
+            // moves to the start of the line.
+            (
+                WINDOWS_TERMINAL,
+                AppQuirks::new()
+                    .copy_shortcut(CopyShortcut::CtrlShiftC)
+                    .context(ContextSupport::AccessibilityOnly),
+            ),
+            (
+                LINUX_TERMINALS,
+                AppQuirks::new()
+                    .copy_shortcut(CopyShortcut::CtrlShiftC)
+                    .context(ContextSupport::AccessibilityOnly),
+            ),
+            // Copying a large file stalls the editor, and with the screen-reader
+            // mode off there is nothing else to read.
+            (&crate::apps::VSCODE[..], AppQuirks::new().context(ContextSupport::AccessibilityOnly)),
+        ];
+        if cfg!(target_os = "windows") {
+            // Both expose the selection through TextPattern, which is quicker
+            // than a copy and leaves the clipboard alone.
+            table.push((CHROMIUM, AppQuirks::new().strategies(&[Strategy::Uia, Strategy::Clipboard])));
+            table.push((
+                WORD,
+                AppQuirks::new()
+                    .strategies(&[Strategy::Uia, Strategy::Clipboard])
+                    .context(ContextSupport::AccessibilityOnly),
+            ));
+            // Their lists are virtualized, and copying a message or file
+            // doesn't put its text on the clipboard; UIA reads the selected
+            // items' cells instead.
+            table.push((OUTLOOK, AppQuirks::new().strategies(&[Strategy::Uia, Strategy::Clipboard])));
+            table.push((EXPLORER, AppQuirks::new().strategies(&[Strategy::Uia, Strategy::Clipboard])));
+        } else {
+            table.push((WORD, AppQuirks::new().context(ContextSupport::AccessibilityOnly)));
+        }
+        table
+    })
+}
+
+static OVERRIDES: RwLock<Vec<(String, AppQuirks)>> = RwLock::new(Vec::new());
+
+pub(crate) fn normalize(app_name: &str) -> String {
+    let app_name = app_name.to_lowercase();
+    app_name.strip_suffix(".exe").map(str::to_string).unwrap_or(app_name)
+}
+
+/// Replaces the quirks of `app_name` (as the focused app reports it, e.g.
+/// "WINWORD.EXE" or "Microsoft Word") for every capture in this process.
+/// Pass `AppQuirks::new()` to turn the built-in workarounds off.
+pub fn set_app_quirks(app_name: &str, quirks: AppQuirks) {
+    let app_name = normalize(app_name);
+    let mut overrides = OVERRIDES.write();
+    overrides.retain(|(name, _)| *name != app_name);
+    overrides.push((app_name, quirks));
+}
+
+/// Drops every quirk set with [`set_app_quirks`], leaving the built-in ones.
+pub fn clear_app_quirks() {
+    OVERRIDES.write().clear();
+}
+
+/// The quirks applied to `app_name`: its override if any, else its built-in
+/// entry, else none.
+pub fn app_quirks(app_name: &str) -> AppQuirks {
+    let app_name = normalize(app_name);
+    if let Some((_, quirks)) = OVERRIDES.read().iter().find(|(name, _)| *name == app_name) {
+        return quirks.clone();
+    }
+    builtin()
+        .iter()
+        .find(|(names, _)| names.contains(&app_name.as_str()))
+        .map(|(_, quirks)| quirks.clone())
+        .unwrap_or_default()
+}
+
+thread_local! {
+    /// `GetTextOptions::copy_wait` of the call running on this thread.
+    static COPY_WAIT: Cell<Option<Duration>> = const { Cell::new(None) };
+}
+
+/// Runs `f` with the copy wait set by the caller's options, if any.
+pub(crate) fn with_copy_wait<R>(wait: Option<Duration>, f: impl FnOnce() -> R) -> R {
+    let previous = COPY_WAIT.with(|current| current.replace(wait));
+    let result = f();
+    COPY_WAIT.with(|current| current.set(previous));
+    result
+}
+
+/// How long to wait for the focused app to answer a simulated copy: the
+/// call's options, else the app's quirk, else the calibrated wait.
+pub(crate) fn copy_wait() -> Duration {
+    let wait = COPY_WAIT
+        .with(Cell::get)
+        .unwrap_or_else(|| for_focused_app().copy_wait.unwrap_or_else(crate::calibrate::copy_wait));
+    crate::env_overrides::copy_timeout(wait)
+}
+
+/// The quirks of the focused app, or none if it can't be determined.
+pub(crate) fn for_focused_app() -> AppQuirks {
+    crate::utils::focus_snapshot()
+        .map(|focus| app_quirks(focus.app_name()))
+        .unwrap_or_default()
+}
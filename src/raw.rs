@@ -0,0 +1,69 @@
+//! Unstable access to the platform accessibility handles behind a selection.
+//!
+//! Only compiled with the `unstable-raw` feature. The types re-exported here
+//! belong to the underlying platform crates (`accessibility-ng` on macOS,
+//! `uiautomation` on Windows) and may change with any release of this crate,
+//! including patch releases. Use them to run attribute reads or pattern
+//! queries that the crate does not support yet.
+
+use crate::GetTextError;
+
+/// The platform element type: `AXUIElement` on macOS.
+#[cfg(target_os = "macos")]
+pub use accessibility_ng::AXUIElement as RawElement;
+
+/// The platform element type: `UIElement` (wrapping `IUIAutomationElement`) on Windows.
+#[cfg(target_os = "windows")]
+pub use uiautomation::UIElement as RawElement;
+
+/// Returns the element that currently has keyboard focus.
+///
+/// # Errors
+///
+/// Returns `GetTextError::Os`/`GetTextError::Uia` if no focused element can be resolved.
+pub fn focused_element() -> Result<RawElement, GetTextError> {
+    #[cfg(target_os = "macos")]
+    {
+        crate::macos::focused_element()
+            .ok_or_else(|| GetTextError::Os("No focused UI element".to_string()))
+    }
+    #[cfg(target_os = "windows")]
+    {
+        crate::windows::focused_element()
+    }
+}
+
+/// Returns the element that holds the current selection, if any.
+///
+/// This is the element the crate reads the selected text from when the
+/// accessibility strategy succeeds. It may be the focused element itself or
+/// one of its ancestors (Windows) / descendants (macOS).
+///
+/// # Errors
+///
+/// Returns `GetTextError::Uia` if UI Automation cannot be initialised on Windows.
+pub fn selection_element() -> Result<Option<RawElement>, GetTextError> {
+    #[cfg(target_os = "macos")]
+    {
+        Ok(crate::macos::selection_element())
+    }
+    #[cfg(target_os = "windows")]
+    {
+        crate::windows::selection_element()
+    }
+}
+
+/// Records the accessibility tree of the frontmost app as JSON, in the
+/// fixture format the crate's traversal tests replay (see
+/// `tests/fixtures/ax/`). Only the attributes the traversal reads are kept.
+///
+/// # Errors
+///
+/// Returns `GetTextError::Os` if there is no frontmost app.
+#[cfg(target_os = "macos")]
+pub fn record_focused_app_tree() -> Result<String, GetTextError> {
+    let window = active_win_pos_rs::get_active_window()
+        .map_err(|_| GetTextError::Os("No active window found".to_string()))?;
+    let app = RawElement::application(window.process_id as i32);
+    serde_json::to_string_pretty(&crate::ax_tree::record(&app, 0)).map_err(|e| GetTextError::Other(e.to_string()))
+}
@@ -0,0 +1,73 @@
+//! Files only this user can read, for the tokens of the local servers the
+//! crate runs (`browser-bridge` and `server` features).
+
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+/// Created under the temp directory when there is no `$XDG_RUNTIME_DIR`.
+const RUNTIME_DIR: &str = "get-selected-text";
+
+/// A directory only this user can write to: `$XDG_RUNTIME_DIR`, or a
+/// private directory under the temp directory. On macOS and Windows the temp
+/// directory is already per-user.
+pub(crate) fn runtime_dir() -> io::Result<PathBuf> {
+    if let Some(dir) = std::env::var_os("XDG_RUNTIME_DIR") {
+        return Ok(PathBuf::from(dir));
+    }
+    #[cfg(target_os = "linux")]
+    {
+        use std::os::unix::fs::{DirBuilderExt, MetadataExt};
+
+        let uid = unsafe { libc::getuid() };
+        let dir = std::env::temp_dir().join(format!("{}-{}", RUNTIME_DIR, uid));
+        match std::fs::DirBuilder::new().mode(0o700).create(&dir) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {}
+            Err(e) => return Err(e),
+        }
+        // Someone else may have created it first, to read or plant the file.
+        let metadata = std::fs::symlink_metadata(&dir)?;
+        if !metadata.is_dir() || metadata.uid() != uid || metadata.mode() & 0o077 != 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!("{} is not a private directory", dir.display()),
+            ));
+        }
+        Ok(dir)
+    }
+    #[cfg(not(target_os = "linux"))]
+    Ok(std::env::temp_dir().join(RUNTIME_DIR))
+}
+
+/// 128 bits from the OS's random source, as hex.
+pub(crate) fn random_token() -> io::Result<String> {
+    let mut bytes = [0u8; 16];
+    getrandom::getrandom(&mut bytes)?;
+    Ok(bytes.iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
+/// Whether `given` is `token`, in a time that doesn't depend on where they
+/// differ, so the token can't be guessed a byte at a time.
+pub(crate) fn token_matches(given: &str, token: &str) -> bool {
+    given.len() == token.len()
+        && given.bytes().zip(token.bytes()).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+/// Writes `contents` to `name` in [`runtime_dir`], readable by this user only.
+pub(crate) fn write_private_file(name: &str, contents: &str) -> io::Result<PathBuf> {
+    let path = runtime_dir()?.join(name);
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    // A file left by an earlier run keeps its old mode through `mode()`.
+    let _ = std::fs::remove_file(&path);
+    options.open(&path)?.write_all(contents.as_bytes())?;
+    Ok(path)
+}
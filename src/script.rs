@@ -0,0 +1,160 @@
+//! Extractors written as scripts, for one-off app support without a crate
+//! release: an AppleScript that asks the app for its selection, a PowerShell
+//! snippet calling a COM object, a shell command talking to a CLI.
+
+use std::io::Read;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::extractor::{ExtractContext, Selection, SelectionExtractor};
+use crate::{AppInfo, GetTextError};
+
+/// Scripts still running after this long are killed.
+const SCRIPT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How a script is run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ScriptLanguage {
+    /// `osascript` (macOS).
+    AppleScript,
+    /// `osascript -l JavaScript` (macOS).
+    Jxa,
+    /// `powershell -NoProfile -NonInteractive -Command` (Windows).
+    PowerShell,
+    /// `sh -c` (Linux, macOS).
+    Shell,
+}
+
+impl ScriptLanguage {
+    fn command(self, source: &str) -> Result<Command, GetTextError> {
+        let (program, args): (&str, &[&str]) = match self {
+            ScriptLanguage::AppleScript if cfg!(target_os = "macos") => ("osascript", &["-e"]),
+            ScriptLanguage::Jxa if cfg!(target_os = "macos") => ("osascript", &["-l", "JavaScript", "-e"]),
+            ScriptLanguage::PowerShell if cfg!(target_os = "windows") => {
+                ("powershell", &["-NoProfile", "-NonInteractive", "-Command"])
+            }
+            ScriptLanguage::Shell if cfg!(unix) => ("sh", &["-c"]),
+            _ => return Err(GetTextError::Unimplemented),
+        };
+        let mut command = Command::new(program);
+        command.args(args).arg(source);
+        Ok(command)
+    }
+}
+
+/// A [`SelectionExtractor`] that runs a script for one app and reads the
+/// selection from its output.
+///
+/// The script gets the app in `GST_APP_NAME` and `GST_APP_PID`, and
+/// `GST_WANTS_CONTEXT=1` when context was asked for. It prints either the
+/// selected text, or a JSON object `{"text": "...", "context": "..."}`.
+/// Whatever it leaves on the clipboard is replaced by the previous contents
+/// afterwards, so it may copy freely.
+#[derive(Debug, Clone)]
+pub struct ScriptExtractor {
+    app_name: String,
+    language: ScriptLanguage,
+    source: String,
+}
+
+impl ScriptExtractor {
+    /// Runs `source` when `app_name` is focused, matched like
+    /// [`crate::set_app_quirks`] matches it.
+    pub fn new(app_name: &str, language: ScriptLanguage, source: impl Into<String>) -> Self {
+        ScriptExtractor {
+            app_name: crate::quirks::normalize(app_name),
+            language,
+            source: source.into(),
+        }
+    }
+
+    fn run(&self, context: &ExtractContext) -> Result<String, GetTextError> {
+        let mut command = self.language.command(&self.source)?;
+        let mut child = command
+            .env("GST_APP_NAME", &context.app.name)
+            .env("GST_APP_PID", context.app.process_id.to_string())
+            .env("GST_WANTS_CONTEXT", if context.wants_context { "1" } else { "0" })
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| GetTextError::Os(format!("Failed to run the script: {}", e)))?;
+
+        // Read on another thread: a script blocked on a full pipe never exits.
+        let mut stdout = child.stdout.take().expect("stdout is piped");
+        let reader = thread::spawn(move || {
+            let mut output = Vec::new();
+            stdout.read_to_end(&mut output).map(|_| output)
+        });
+        let started = Instant::now();
+        let status = loop {
+            if let Some(status) = child.try_wait().map_err(|e| GetTextError::Os(e.to_string()))? {
+                break status;
+            }
+            if started.elapsed() > SCRIPT_TIMEOUT {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(GetTextError::Timeout);
+            }
+            thread::sleep(Duration::from_millis(10));
+        };
+        let output = reader
+            .join()
+            .map_err(|_| GetTextError::Other("Script output reader panicked".to_string()))?
+            .map_err(|e| GetTextError::Os(e.to_string()))?;
+        if !status.success() {
+            return Err(GetTextError::Os(format!("The script exited with {}", status)));
+        }
+        Ok(String::from_utf8_lossy(&output).into_owned())
+    }
+}
+
+impl SelectionExtractor for ScriptExtractor {
+    fn matches(&self, app: &AppInfo) -> bool {
+        crate::quirks::normalize(&app.name) == self.app_name
+    }
+
+    fn extract(&self, context: &ExtractContext) -> Result<Selection, GetTextError> {
+        let _sequence = crate::sequence::begin()?;
+        let output = with_clipboard_restored(|| self.run(context))??;
+        Ok(parse_output(&output))
+    }
+}
+
+/// Registers a [`ScriptExtractor`] for `app_name`.
+pub fn register_script(app_name: &str, language: ScriptLanguage, source: impl Into<String>) {
+    crate::register_extractor(ScriptExtractor::new(app_name, language, source));
+}
+
+fn parse_output(output: &str) -> Selection {
+    // osascript and most shells end the output with a newline.
+    let output = output.strip_suffix('\n').unwrap_or(output);
+    let output = output.strip_suffix('\r').unwrap_or(output);
+    if let Ok(serde_json::Value::Object(object)) = serde_json::from_str::<serde_json::Value>(output) {
+        if let Some(text) = object.get("text").and_then(|text| text.as_str()) {
+            let selection = Selection::new(text);
+            return match object.get("context").and_then(|context| context.as_str()) {
+                Some(context) => selection.with_context(context),
+                None => selection,
+            };
+        }
+    }
+    Selection::new(output)
+}
+
+/// Runs `f`, then puts the clipboard back if its text changed meanwhile.
+fn with_clipboard_restored<T>(f: impl FnOnce() -> T) -> Result<T, GetTextError> {
+    let mut clipboard = crate::utils::open_clipboard()?;
+    let saved = crate::utils::SavedClipboard::save(&mut clipboard);
+    let result = f();
+
+    let current_text = crate::utils::clipboard_retry(|| clipboard.get_text());
+    if current_text.as_deref().ok() == saved.text() {
+        return Ok(result);
+    }
+    crate::side_effects::clipboard_modified();
+    saved.restore(&mut clipboard)?;
+    Ok(result)
+}
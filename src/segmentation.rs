@@ -0,0 +1,74 @@
+//! Sentence segmentation of a context (`segmentation` feature), so that
+//! translation and dictionary UIs can show the whole sentences around a
+//! selection.
+
+use std::ops::Range;
+
+use unicode_segmentation::UnicodeSegmentation;
+
+/// One sentence of a context.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Sentence {
+    /// The sentence with its trailing whitespace.
+    pub text: String,
+    /// Its byte range in the context.
+    pub range: Range<usize>,
+    /// Whether it overlaps the selection.
+    pub contains_selection: bool,
+}
+
+/// Splits `context` into sentences by the Unicode sentence boundary rules
+/// (UAX #29), marking those that overlap the first occurrence of `selected`.
+/// If `selected` doesn't occur in `context`, no sentence is marked.
+pub fn sentences(context: &str, selected: &str) -> Vec<Sentence> {
+    let selection = context
+        .find(selected)
+        .filter(|_| !selected.is_empty())
+        .map(|start| start..start + selected.len());
+    context
+        .split_sentence_bound_indices()
+        .map(|(start, text)| {
+            let range = start..start + text.len();
+            Sentence {
+                text: text.to_string(),
+                contains_selection: selection
+                    .as_ref()
+                    .is_some_and(|selection| selection.start < range.end && range.start < selection.end),
+                range,
+            }
+        })
+        .collect()
+}
+
+/// The sentences overlapping the selection, joined as they appear in
+/// `context`. `None` if `selected` doesn't occur in it.
+pub fn selected_sentences(context: &str, selected: &str) -> Option<String> {
+    let marked = sentences(context, selected)
+        .into_iter()
+        .filter(|sentence| sentence.contains_selection)
+        .collect::<Vec<_>>();
+    let start = marked.first()?.range.start;
+    let end = marked.last()?.range.end;
+    Some(context[start..end].trim_end().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn marks_sentences_overlapping_the_selection() {
+        let context = "It rained. The quick brown fox jumps. Then it stopped.";
+        let marked = sentences(context, "brown fox")
+            .into_iter()
+            .map(|sentence| sentence.contains_selection)
+            .collect::<Vec<_>>();
+        assert_eq!(marked, [false, true, false]);
+        assert_eq!(
+            selected_sentences(context, "fox jumps. Then").as_deref(),
+            Some("The quick brown fox jumps. Then it stopped.")
+        );
+        assert_eq!(selected_sentences(context, "absent"), None);
+    }
+}
@@ -0,0 +1,65 @@
+//! Bracketing for simulated input sequences: the cross-process lock plus a
+//! cool-down so that a new sequence never starts while the target app is still
+//! processing the keystrokes of the previous one.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+
+use crate::process_lock::{self, ProcessLockGuard};
+use crate::suppress::ClipboardActivity;
+use crate::GetTextError;
+
+const DEFAULT_COOLDOWN_MS: u64 = 100;
+
+static COOLDOWN_MS: AtomicU64 = AtomicU64::new(DEFAULT_COOLDOWN_MS);
+static LAST_SEQUENCE_END: Mutex<Option<Instant>> = Mutex::new(None);
+
+/// Sets the minimum pause between two simulated input sequences, shared by all
+/// threads and clients in the process. Defaults to 100 ms; zero disables it.
+pub fn set_input_cooldown(cooldown: Duration) {
+    COOLDOWN_MS.store(cooldown.as_millis() as u64, Ordering::Relaxed);
+}
+
+/// The current cool-down between simulated input sequences.
+pub fn input_cooldown() -> Duration {
+    Duration::from_millis(COOLDOWN_MS.load(Ordering::Relaxed))
+}
+
+/// Held for the duration of one simulated sequence.
+pub(crate) struct SequenceGuard {
+    // Dropped first, so the activity has ended before the next sequence can start.
+    _activity: ClipboardActivity,
+    _lock: ProcessLockGuard,
+}
+
+impl Drop for SequenceGuard {
+    fn drop(&mut self) {
+        // Runs before `_lock` is released, so the next holder sees this end time.
+        *LAST_SEQUENCE_END.lock() = Some(Instant::now());
+    }
+}
+
+/// Takes the cross-process lock, then waits out the remaining cool-down.
+/// Fails with `GetTextError::SessionInactive` if the session can't take input
+/// by then, and with `GetTextError::UnsupportedForegroundApp` if the focused
+/// app holds the display in full-screen exclusive mode.
+pub(crate) fn begin() -> Result<SequenceGuard, GetTextError> {
+    let lock = process_lock::acquire(process_lock::DEFAULT_WAIT)?;
+    let last_end = *LAST_SEQUENCE_END.lock();
+    if let Some(last_end) = last_end {
+        let remaining = input_cooldown().saturating_sub(last_end.elapsed());
+        if !remaining.is_zero() {
+            std::thread::sleep(remaining);
+        }
+    }
+    crate::session::ensure_active()?;
+    if let Some(app) = crate::utils::exclusive_fullscreen_app() {
+        return Err(GetTextError::UnsupportedForegroundApp { app });
+    }
+    Ok(SequenceGuard {
+        _activity: ClipboardActivity::begin(),
+        _lock: lock,
+    })
+}
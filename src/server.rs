@@ -0,0 +1,257 @@
+//! A localhost WebSocket server (`server` feature), for consumers that can't
+//! link the crate: Electron renderers, Python scripts, editor plugins.
+//!
+//! Every message is a JSON text frame. Requests carry a `method` and an
+//! optional `id`, which the reply echoes:
+//!
+//! ```text
+//! -> {"id": 1, "method": "getSelectedText"}
+//! <- {"id": 1, "result": {"text": "hello"}}
+//! -> {"id": 2, "method": "getSelectedTextWithContext"}
+//! <- {"id": 2, "result": {"text": "hello", "context": "say hello to"}}
+//! -> {"id": 3, "method": "subscribe"}
+//! <- {"id": 3, "result": null}
+//! <- {"event": "selectionChanged", "text": "hello"}
+//! -> {"id": 4, "method": "unsubscribe"}
+//! <- {"id": 4, "result": null}
+//! ```
+//!
+//! Failures are answered with `{"id": ..., "error": "..."}`.
+//! `selectionChanged` events are only polled for while at least one
+//! connection is subscribed.
+//!
+//! Each run picks a random token and writes the port and the token, one per
+//! line, to `get-selected-text-server.port` in `$XDG_RUNTIME_DIR` (or a
+//! private temp directory), readable by this user only. Clients present it
+//! in the handshake URL, `ws://127.0.0.1:<port>/?token=<token>`; without it
+//! any local process, including other users', could read the selection.
+
+use std::io;
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::Duration;
+
+use parking_lot::Mutex;
+use serde_json::{json, Value};
+use tungstenite::handshake::server::{ErrorResponse, Request, Response};
+use tungstenite::{Message, WebSocket};
+
+/// How often a connection stops waiting for requests to flush pending events.
+const EVENT_FLUSH_INTERVAL: Duration = Duration::from_millis(100);
+/// Pause after a failed accept, which usually means the process is out of
+/// file descriptors for the moment.
+const ACCEPT_RETRY_DELAY: Duration = Duration::from_millis(100);
+const PORT_FILE: &str = "get-selected-text-server.port";
+
+/// Configuration for [`serve`].
+#[derive(Debug, Clone, Default)]
+#[non_exhaustive]
+pub struct ServerOptions {
+    /// `Origin` header values allowed to connect. Clients that send no
+    /// `Origin` (anything that isn't a browser) are always allowed; browser
+    /// pages are refused unless listed here, so an arbitrary website can't
+    /// read the selection through the server.
+    pub allowed_origins: Vec<String>,
+}
+
+impl ServerOptions {
+    pub fn allow_origin(mut self, origin: impl Into<String>) -> Self {
+        self.allowed_origins.push(origin.into());
+        self
+    }
+}
+
+type Subscribers = Arc<Mutex<Vec<mpsc::Sender<String>>>>;
+
+/// Listens on `port` of the loopback interface and serves connections
+/// forever, each on its own thread. A connection that fails to be accepted
+/// is logged and skipped.
+///
+/// # Errors
+///
+/// Returns the error from binding the listener, drawing the token or
+/// writing the port file.
+pub fn serve(port: u16, options: ServerOptions) -> io::Result<()> {
+    let listener = TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], port)))?;
+    let token: Arc<str> = crate::runtime_dir::random_token()?.into();
+    crate::runtime_dir::write_private_file(
+        PORT_FILE,
+        &format!("{}\n{}\n", listener.local_addr()?.port(), token),
+    )?;
+    let options = Arc::new(options);
+    let subscribers: Subscribers = Arc::default();
+    {
+        let subscribers = subscribers.clone();
+        thread::spawn(move || watch(subscribers));
+    }
+
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                crate::env_overrides::gst_log!("Accepting a server connection failed: {}", e);
+                thread::sleep(ACCEPT_RETRY_DELAY);
+                continue;
+            }
+        };
+        let options = options.clone();
+        let token = token.clone();
+        let subscribers = subscribers.clone();
+        thread::spawn(move || {
+            let _ = handle_connection(stream, &token, &options, &subscribers);
+        });
+    }
+    Ok(())
+}
+
+fn refuse(status: tungstenite::http::StatusCode, reason: &str) -> ErrorResponse {
+    let mut refusal = ErrorResponse::new(Some(reason.to_string()));
+    *refusal.status_mut() = status;
+    refusal
+}
+
+/// Whether the request URL carries `token=<token>` in its query.
+fn has_token(request: &Request, token: &str) -> bool {
+    request.uri().query().is_some_and(|query| {
+        query
+            .split('&')
+            .filter_map(|pair| pair.strip_prefix("token="))
+            .any(|given| crate::runtime_dir::token_matches(given, token))
+    })
+}
+
+fn check_handshake(
+    request: &Request,
+    response: Response,
+    token: &str,
+    options: &ServerOptions,
+) -> Result<Response, ErrorResponse> {
+    if !has_token(request, token) {
+        return Err(refuse(tungstenite::http::StatusCode::UNAUTHORIZED, "missing or wrong token"));
+    }
+    let Some(origin) = request.headers().get("origin") else {
+        return Ok(response);
+    };
+    let allowed = origin
+        .to_str()
+        .is_ok_and(|origin| options.allowed_origins.iter().any(|allowed| allowed == origin));
+    if allowed {
+        return Ok(response);
+    }
+    Err(refuse(tungstenite::http::StatusCode::FORBIDDEN, "origin not allowed"))
+}
+
+fn handle_connection(
+    stream: TcpStream,
+    token: &str,
+    options: &ServerOptions,
+    subscribers: &Subscribers,
+) -> tungstenite::Result<()> {
+    let mut socket = tungstenite::accept_hdr(stream, |request: &Request, response| {
+        check_handshake(request, response, token, options)
+    })
+    .map_err(|e| match e {
+        tungstenite::HandshakeError::Failure(e) => e,
+        tungstenite::HandshakeError::Interrupted(_) => tungstenite::Error::ConnectionClosed,
+    })?;
+    socket.get_ref().set_read_timeout(Some(EVENT_FLUSH_INTERVAL))?;
+
+    let mut events: Option<mpsc::Receiver<String>> = None;
+    loop {
+        match socket.read() {
+            Ok(Message::Text(text)) => {
+                let reply = handle_request(&text, &mut events, subscribers);
+                socket.send(Message::Text(reply.to_string()))?;
+            }
+            Ok(Message::Close(_)) => return Ok(()),
+            Ok(_) => {}
+            Err(tungstenite::Error::Io(e))
+                if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => {}
+            Err(tungstenite::Error::ConnectionClosed) => return Ok(()),
+            Err(e) => return Err(e),
+        }
+        flush_events(&mut socket, events.as_ref())?;
+    }
+}
+
+fn flush_events(socket: &mut WebSocket<TcpStream>, events: Option<&mpsc::Receiver<String>>) -> tungstenite::Result<()> {
+    let Some(events) = events else {
+        return Ok(());
+    };
+    for text in events.try_iter() {
+        let event = json!({ "event": "selectionChanged", "text": text });
+        socket.send(Message::Text(event.to_string()))?;
+    }
+    Ok(())
+}
+
+fn handle_request(text: &str, events: &mut Option<mpsc::Receiver<String>>, subscribers: &Subscribers) -> Value {
+    let request: Value = match serde_json::from_str(text) {
+        Ok(request) => request,
+        Err(e) => return json!({ "id": null, "error": format!("malformed request: {}", e) }),
+    };
+    let id = request.get("id").cloned().unwrap_or(Value::Null);
+    let result = match request.get("method").and_then(Value::as_str) {
+        Some("getSelectedText") => crate::get_selected_text()
+            .map(|text| json!({ "text": text }))
+            .map_err(|e| e.to_string()),
+        Some("getSelectedTextWithContext") => crate::get_selected_text_with_context()
+            .map(|(text, context)| json!({ "text": text, "context": context }))
+            .map_err(|e| e.to_string()),
+        Some("subscribe") => {
+            if events.is_none() {
+                let (sender, receiver) = mpsc::channel();
+                subscribers.lock().push(sender);
+                *events = Some(receiver);
+            }
+            Ok(Value::Null)
+        }
+        Some("unsubscribe") => {
+            // The watcher drops the sender once it notices the receiver is gone.
+            *events = None;
+            Ok(Value::Null)
+        }
+        Some(method) => Err(format!("unknown method: {}", method)),
+        None => Err("missing method".to_string()),
+    };
+    match result {
+        Ok(result) => json!({ "id": id, "result": result }),
+        Err(error) => json!({ "id": id, "error": error }),
+    }
+}
+
+fn watch(subscribers: Subscribers) {
+    crate::watcher::run(
+        || !subscribers.lock().is_empty(),
+        |text| subscribers.lock().retain(|subscriber| subscriber.send(text.to_string()).is_ok()),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(uri: &str) -> Request {
+        Request::builder().uri(uri).body(()).unwrap()
+    }
+
+    #[test]
+    fn token_must_match_a_whole_query_parameter() {
+        assert!(has_token(&request("/?token=abc"), "abc"));
+        assert!(has_token(&request("/?v=1&token=abc"), "abc"));
+        assert!(!has_token(&request("/"), "abc"));
+        assert!(!has_token(&request("/?token=abcd"), "abc"));
+        assert!(!has_token(&request("/?xtoken=abc"), "abc"));
+        assert!(!has_token(&request("/?token="), "abc"));
+        assert!(has_token(&request("/?token=abd&token=abc"), "abc"));
+    }
+
+    #[test]
+    fn tokens_are_random_hex() {
+        let token = crate::runtime_dir::random_token().unwrap();
+        assert_eq!(token.len(), 32);
+        assert!(token.bytes().all(|byte| byte.is_ascii_hexdigit()));
+        assert_ne!(token, crate::runtime_dir::random_token().unwrap());
+    }
+}
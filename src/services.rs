@@ -0,0 +1,77 @@
+//! A Services menu provider (`services` feature). The user picks "Capture
+//! Selection with <app>" (or its shortcut) and the frontmost app hands over
+//! its selection itself, so this works where simulated copy doesn't: under
+//! Secure Input, or without the accessibility permission.
+//!
+//! The host app declares the service in its Info.plist; see the README.
+
+use std::ffi::CStr;
+use std::os::raw::c_char;
+use std::sync::Once;
+
+use cocoa::appkit::{NSApp, NSPasteboardTypeString};
+use cocoa::base::{id, nil};
+use objc::declare::ClassDecl;
+use objc::runtime::{Class, Object, Sel};
+use objc::{class, msg_send, sel, sel_impl};
+use parking_lot::Mutex;
+
+type Callback = Box<dyn Fn(String) + Send>;
+
+static CALLBACK: Mutex<Option<Callback>> = Mutex::new(None);
+static REGISTER_CLASS: Once = Once::new();
+
+const PROVIDER_CLASS: &str = "GetSelectedTextServicesProvider";
+
+/// Called by AppKit for the `captureSelection` message declared in the
+/// host's NSServices entry.
+extern "C" fn capture_selection(_this: &Object, _cmd: Sel, pasteboard: id, _user_data: id, _error: *mut id) {
+    let text = unsafe {
+        let string: id = msg_send![pasteboard, stringForType: NSPasteboardTypeString];
+        if string == nil {
+            return;
+        }
+        let utf8: *const c_char = msg_send![string, UTF8String];
+        if utf8.is_null() {
+            return;
+        }
+        CStr::from_ptr(utf8).to_string_lossy().into_owned()
+    };
+    if let Some(callback) = CALLBACK.lock().as_ref() {
+        callback(text);
+    }
+}
+
+fn provider_class() -> &'static Class {
+    REGISTER_CLASS.call_once(|| {
+        let mut decl = ClassDecl::new(PROVIDER_CLASS, class!(NSObject)).expect("provider class registered twice");
+        unsafe {
+            decl.add_method(
+                sel!(captureSelection:userData:error:),
+                capture_selection as extern "C" fn(&Object, Sel, id, id, *mut id),
+            );
+        }
+        decl.register();
+    });
+    Class::get(PROVIDER_CLASS).expect("registered above")
+}
+
+/// Makes this app the provider for its `captureSelection` service and
+/// delivers each invocation's text to `callback`, on the main thread.
+/// Registering again replaces the callback.
+///
+/// Call it from the main thread once `NSApp` exists, e.g. at launch.
+pub fn register_services_provider(callback: impl Fn(String) + Send + 'static) {
+    *CALLBACK.lock() = Some(Box::new(callback));
+    unsafe {
+        let provider: id = msg_send![provider_class(), new];
+        let _: () = msg_send![NSApp(), setServicesProvider: provider];
+        // Picks up Info.plist changes without logging out.
+        NSUpdateDynamicServices();
+    }
+}
+
+#[link(name = "AppKit", kind = "framework")]
+extern "C" {
+    fn NSUpdateDynamicServices();
+}
@@ -0,0 +1,98 @@
+//! Whether this process's login session can take input at all. With the
+//! screen locked, or the session switched away from (fast user switching, a
+//! disconnected Remote Desktop session), simulated keys reach nothing and
+//! clipboard reads wait for a copy that never comes.
+
+use crate::GetTextError;
+
+/// Fails with `GetTextError::SessionInactive` when the session is locked or
+/// not in the foreground. Sessions whose state can't be read count as active.
+pub(crate) fn ensure_active() -> Result<(), GetTextError> {
+    match inactive_reason() {
+        Some(reason) => {
+            crate::env_overrides::gst_log!("Session inactive: {}", reason);
+            Err(GetTextError::SessionInactive)
+        }
+        None => Ok(()),
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn inactive_reason() -> Option<&'static str> {
+    use windows_sys::Win32::System::RemoteDesktop::{
+        WTSActive, WTSConnectState, WTSFreeMemory, WTSQuerySessionInformationW, WTS_CONNECTSTATE_CLASS,
+        WTS_CURRENT_SERVER_HANDLE, WTS_CURRENT_SESSION,
+    };
+    use windows_sys::Win32::System::StationsAndDesktops::{CloseDesktop, OpenInputDesktop, DESKTOP_SWITCHDESKTOP};
+
+    let mut buffer = std::ptr::null_mut();
+    let mut bytes = 0;
+    let queried = unsafe {
+        WTSQuerySessionInformationW(
+            WTS_CURRENT_SERVER_HANDLE,
+            WTS_CURRENT_SESSION,
+            WTSConnectState,
+            &mut buffer,
+            &mut bytes,
+        )
+    } != 0;
+    if queried && !buffer.is_null() {
+        let state = unsafe { *buffer.cast::<WTS_CONNECTSTATE_CLASS>() };
+        unsafe { WTSFreeMemory(buffer.cast()) };
+        if state != WTSActive {
+            return Some("the session is disconnected or switched away from");
+        }
+    }
+    // While the workstation is locked the input desktop is Winlogon's, which
+    // a user process can't open.
+    let desktop = unsafe { OpenInputDesktop(0, 0, DESKTOP_SWITCHDESKTOP) };
+    if desktop == 0 {
+        return Some("the workstation is locked");
+    }
+    unsafe { CloseDesktop(desktop) };
+    None
+}
+
+#[cfg(target_os = "macos")]
+fn inactive_reason() -> Option<&'static str> {
+    use core_foundation::base::{CFType, TCFType};
+    use core_foundation::boolean::CFBoolean;
+    use core_foundation::dictionary::{CFDictionary, CFDictionaryRef};
+    use core_foundation::string::CFString;
+
+    #[link(name = "CoreGraphics", kind = "framework")]
+    extern "C" {
+        fn CGSessionCopyCurrentDictionary() -> CFDictionaryRef;
+    }
+
+    let session = unsafe { CGSessionCopyCurrentDictionary() };
+    if session.is_null() {
+        return Some("the process isn't attached to a window server session");
+    }
+    let session: CFDictionary<CFString, CFType> = unsafe { CFDictionary::wrap_under_create_rule(session) };
+    let flag = |key: &'static str| {
+        session
+            .find(CFString::from_static_string(key))
+            .and_then(|value| value.downcast::<CFBoolean>())
+            .map(bool::from)
+    };
+    if flag("CGSSessionScreenIsLocked") == Some(true) {
+        return Some("the screen is locked");
+    }
+    if flag("kCGSSessionOnConsoleKey") == Some(false) {
+        return Some("another user's session is on the console");
+    }
+    None
+}
+
+/// Only a session logind has put in the background is detected; X11 and
+/// Wayland screen lockers take the input themselves.
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+fn inactive_reason() -> Option<&'static str> {
+    let id = std::env::var("XDG_SESSION_ID").ok()?;
+    let state = std::fs::read_to_string(format!("/run/systemd/sessions/{}", id)).ok()?;
+    state
+        .lines()
+        .any(|line| line == "ACTIVE=0")
+        .then_some("the session is in the background")
+}
@@ -0,0 +1,86 @@
+//! What a capture did besides reading, recorded on the calling thread while
+//! [`track_side_effects`] runs.
+
+use std::cell::Cell;
+
+/// What the crate did to the user's environment during a call.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct SideEffects {
+    /// The clipboard was overwritten, by the crate or by a simulated copy.
+    pub clipboard_modified: bool,
+    /// Key presses or mouse clicks were simulated in the focused app.
+    pub keystrokes_sent: bool,
+    /// The selection in the focused app was cancelled or replaced and could
+    /// not be put back exactly.
+    pub selection_changed: bool,
+    /// The previous clipboard contents were written back afterwards. Clipboard
+    /// managers still see both changes.
+    pub clipboard_restored: bool,
+    /// The previous clipboard contents could not be put back: writing them
+    /// failed, or another process kept replacing them. The user's clipboard
+    /// is lost unless the host app saved it.
+    pub clipboard_restore_failed: bool,
+}
+
+impl SideEffects {
+    fn merge(&mut self, other: SideEffects) {
+        self.clipboard_modified |= other.clipboard_modified;
+        self.keystrokes_sent |= other.keystrokes_sent;
+        self.selection_changed |= other.selection_changed;
+        self.clipboard_restored |= other.clipboard_restored;
+        self.clipboard_restore_failed |= other.clipboard_restore_failed;
+    }
+}
+
+thread_local! {
+    static CURRENT: Cell<Option<SideEffects>> = const { Cell::new(None) };
+}
+
+/// Runs `f` and reports the side effects of every capture it made on this
+/// thread, whether or not they succeeded.
+///
+/// ```no_run
+/// let (result, effects) = get_selected_text::track_side_effects(get_selected_text::get_selected_text);
+/// if effects.clipboard_modified {
+///     // Ignore the next clipboard change notification.
+/// }
+/// # let _ = result;
+/// ```
+pub fn track_side_effects<R>(f: impl FnOnce() -> R) -> (R, SideEffects) {
+    let outer = CURRENT.with(|current| current.replace(Some(SideEffects::default())));
+    let result = f();
+    let effects = CURRENT.with(|current| current.replace(outer)).unwrap_or_default();
+    // A nested call's effects also belong to the enclosing one.
+    record(|outer| outer.merge(effects));
+    (result, effects)
+}
+
+fn record(update: impl FnOnce(&mut SideEffects)) {
+    CURRENT.with(|current| {
+        if let Some(mut effects) = current.get() {
+            update(&mut effects);
+            current.set(Some(effects));
+        }
+    });
+}
+
+pub(crate) fn clipboard_modified() {
+    record(|effects| effects.clipboard_modified = true);
+}
+
+pub(crate) fn keystrokes_sent() {
+    record(|effects| effects.keystrokes_sent = true);
+}
+
+pub(crate) fn selection_changed() {
+    record(|effects| effects.selection_changed = true);
+}
+
+pub(crate) fn clipboard_restored() {
+    record(|effects| effects.clipboard_restored = true);
+}
+
+pub(crate) fn clipboard_restore_failed() {
+    record(|effects| effects.clipboard_restore_failed = true);
+}
@@ -0,0 +1,265 @@
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::num::NonZeroUsize;
+use std::time::Duration;
+
+use lru::LruCache;
+use parking_lot::Mutex;
+
+/// A method of obtaining the selected text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Strategy {
+    /// macOS Accessibility API (`AXSelectedText` on the focused element or its
+    /// tree), or on Linux the AT-SPI Text interface of the focused object
+    /// (`atspi` feature).
+    Accessibility,
+    /// Windows UI Automation `TextPattern`.
+    Uia,
+    /// Simulated copy shortcut, read back from the clipboard.
+    Clipboard,
+    /// Copy driven by an AppleScript (`osascript`) on macOS.
+    AppleScript,
+    /// Copy driven by a PowerShell script (`SendKeys`) on Windows, for apps
+    /// that ignore the input enigo sends.
+    PowerShell,
+    /// The macOS find pasteboard, which apps fill with the selection on
+    /// Cmd+E ("Use Selection for Find") and with the term being searched.
+    /// Only used when it changed since the crate last read it, and tried
+    /// after the copy by default: a search typed since then, in any app,
+    /// reads as a selection.
+    FindPasteboard,
+    /// The X11 PRIMARY selection, the text highlighted in the focused app,
+    /// read over the crate's own X connection, only when the focused app
+    /// owns PRIMARY. On Wayland (`wayland-data-control` feature) it is read
+    /// through wlr-data-control, only when listed in the options.
+    Primary,
+}
+
+impl Strategy {
+    /// The strategies that simulate a copy shortcut.
+    pub(crate) const SENDING_INPUT: [Strategy; 3] = [Strategy::Clipboard, Strategy::AppleScript, Strategy::PowerShell];
+
+    /// Whether this strategy simulates key presses. The others only read
+    /// what the OS and apps expose, so a call limited to them never types
+    /// into the focused app.
+    pub fn sends_input(self) -> bool {
+        Strategy::SENDING_INPUT.contains(&self)
+    }
+
+    /// Parses a strategy name as written in configuration, case-insensitively.
+    pub(crate) fn from_name(name: &str) -> Option<Strategy> {
+        match name.to_ascii_lowercase().as_str() {
+            "accessibility" | "ax" => Some(Strategy::Accessibility),
+            "uia" => Some(Strategy::Uia),
+            "clipboard" => Some(Strategy::Clipboard),
+            "applescript" => Some(Strategy::AppleScript),
+            "powershell" => Some(Strategy::PowerShell),
+            "findpasteboard" | "find" => Some(Strategy::FindPasteboard),
+            "primary" => Some(Strategy::Primary),
+            _ => None,
+        }
+    }
+}
+
+thread_local! {
+    static SUCCEEDED: Cell<Option<Strategy>> = const { Cell::new(None) };
+}
+
+/// Notes the strategy that produced the selection about to be returned on
+/// this thread.
+pub(crate) fn note_succeeded(strategy: Strategy) {
+    SUCCEEDED.with(|current| current.set(Some(strategy)));
+}
+
+/// Runs `f` and returns the strategy that produced its selection. `None`
+/// when no strategy did, e.g. when an extractor or the browser bridge
+/// answered.
+pub(crate) fn with_succeeded<R>(f: impl FnOnce() -> R) -> (R, Option<Strategy>) {
+    let outer = SUCCEEDED.with(|current| current.replace(None));
+    let result = f();
+    (result, SUCCEEDED.with(|current| current.replace(outer)))
+}
+
+/// How one strategy attempt ended, for [`StrategyCache::record`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Outcome {
+    Succeeded,
+    Failed,
+    /// The strategy worked but nothing was selected.
+    Empty,
+}
+
+// A strategy that failed this many times in a row for an app is moved to the
+// end of the chain (or skipped when it has no fallback behind it).
+const DEMOTE_AFTER_FAILURES: u32 = 20;
+// Demoted strategies are still probed every this many calls so they can recover
+// after the application is updated.
+const PROBE_INTERVAL: u32 = 10;
+// Below this many attempts the success rate is too noisy to reorder by.
+const MIN_SAMPLES: u32 = 5;
+const MAX_TRACKED_APPS: usize = 100;
+
+#[derive(Debug, Clone, Copy, Default)]
+struct StrategyStats {
+    successes: u32,
+    failures: u32,
+    consecutive_failures: u32,
+    // Calls that passed over this strategy since it was demoted.
+    skips: u32,
+    total_latency: Duration,
+}
+
+impl StrategyStats {
+    fn attempts(&self) -> u32 {
+        self.successes + self.failures
+    }
+
+    fn is_demoted(&self) -> bool {
+        self.consecutive_failures >= DEMOTE_AFTER_FAILURES
+    }
+
+    // Every `PROBE_INTERVAL`th call after being demoted lets the strategy through.
+    fn should_skip(&self) -> bool {
+        self.is_demoted() && self.skips + 1 < PROBE_INTERVAL
+    }
+
+    // Success rate in tenths, so that small differences don't outweigh latency.
+    fn success_bucket(&self) -> u32 {
+        if self.attempts() < MIN_SAMPLES {
+            return 5;
+        }
+        self.successes * 10 / self.attempts()
+    }
+
+    // Mean latency in 50 ms buckets.
+    fn latency_bucket(&self) -> u128 {
+        if self.attempts() < MIN_SAMPLES {
+            return 0;
+        }
+        self.total_latency.as_millis() / self.attempts() as u128 / 50
+    }
+}
+
+type AppStats = HashMap<Strategy, StrategyStats>;
+
+/// Per-app strategy statistics, owned by a `SelectedTextClient`.
+#[derive(Debug, Default)]
+pub(crate) struct StrategyCache {
+    apps: Mutex<Option<LruCache<String, AppStats>>>,
+}
+
+impl StrategyCache {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    fn with_app_stats<R>(&self, app_name: &str, f: impl FnOnce(&mut AppStats) -> R) -> R {
+        let mut guard = self.apps.lock();
+        let cache = guard.get_or_insert_with(|| LruCache::new(NonZeroUsize::new(MAX_TRACKED_APPS).unwrap()));
+        if !cache.contains(app_name) {
+            cache.put(app_name.to_string(), AppStats::new());
+        }
+        f(cache.get_mut(app_name).unwrap())
+    }
+
+    /// Records the outcome of one strategy attempt for `app_name`.
+    pub(crate) fn record(&self, app_name: &str, strategy: Strategy, outcome: Outcome, latency: Duration) {
+        self.with_app_stats(app_name, |stats| {
+            let entry = stats.entry(strategy).or_default();
+            entry.skips = 0;
+            match outcome {
+                Outcome::Succeeded => {
+                    entry.successes += 1;
+                    entry.consecutive_failures = 0;
+                }
+                Outcome::Failed => {
+                    entry.failures += 1;
+                    entry.consecutive_failures += 1;
+                }
+                // Nothing selected says nothing about the strategy.
+                Outcome::Empty => return,
+            }
+            entry.total_latency += latency;
+        });
+    }
+
+    /// Records that a call passed over `strategies` for `app_name` without
+    /// trying them. Only counts for demoted strategies, towards their next probe.
+    pub(crate) fn record_skipped(&self, app_name: &str, strategies: &[Strategy]) {
+        self.with_app_stats(app_name, |stats| {
+            for strategy in strategies {
+                if let Some(entry) = stats.get_mut(strategy).filter(|entry| entry.is_demoted()) {
+                    entry.skips += 1;
+                }
+            }
+        });
+    }
+
+    /// Returns `default_order` reordered by the statistics collected for `app_name`.
+    ///
+    /// Demoted strategies go last, then higher success rates and lower latencies go
+    /// first. Strategies without enough samples keep their default position.
+    pub(crate) fn ordered(&self, app_name: &str, default_order: &[Strategy]) -> Vec<Strategy> {
+        self.with_app_stats(app_name, |stats| {
+            let mut order: Vec<_> = default_order
+                .iter()
+                .enumerate()
+                .map(|(index, strategy)| {
+                    let s = stats.get(strategy).copied().unwrap_or_default();
+                    let key = (s.should_skip(), std::cmp::Reverse(s.success_bucket()), s.latency_bucket(), index);
+                    (key, *strategy)
+                })
+                .collect();
+            order.sort_by_key(|(key, _)| *key);
+            order.into_iter().map(|(_, strategy)| strategy).collect()
+        })
+    }
+
+    /// Whether `strategy` keeps failing for `app_name` and should be skipped on this call.
+    pub(crate) fn is_demoted(&self, app_name: &str, strategy: Strategy) -> bool {
+        self.with_app_stats(app_name, |stats| stats.get(&strategy).is_some_and(StrategyStats::should_skip))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fail(cache: &StrategyCache, times: u32) {
+        for _ in 0..times {
+            cache.record("App", Strategy::Uia, Outcome::Failed, Duration::ZERO);
+        }
+    }
+
+    #[test]
+    fn empty_results_neither_demote_nor_recover() {
+        let cache = StrategyCache::new();
+        fail(&cache, DEMOTE_AFTER_FAILURES - 1);
+        for _ in 0..DEMOTE_AFTER_FAILURES {
+            cache.record("App", Strategy::Uia, Outcome::Empty, Duration::ZERO);
+        }
+        assert!(!cache.is_demoted("App", Strategy::Uia));
+        fail(&cache, 1);
+        assert!(cache.is_demoted("App", Strategy::Uia));
+        cache.record("App", Strategy::Uia, Outcome::Empty, Duration::ZERO);
+        assert!(cache.is_demoted("App", Strategy::Uia));
+    }
+
+    #[test]
+    fn demoted_strategy_is_probed_after_enough_skips() {
+        let cache = StrategyCache::new();
+        fail(&cache, DEMOTE_AFTER_FAILURES);
+        // Asking alone doesn't move towards the probe.
+        for _ in 0..PROBE_INTERVAL {
+            assert!(cache.is_demoted("App", Strategy::Uia));
+        }
+        for _ in 1..PROBE_INTERVAL {
+            assert!(cache.is_demoted("App", Strategy::Uia));
+            cache.record_skipped("App", &[Strategy::Uia]);
+        }
+        assert!(!cache.is_demoted("App", Strategy::Uia));
+        fail(&cache, 1);
+        assert!(cache.is_demoted("App", Strategy::Uia));
+    }
+}
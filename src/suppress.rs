@@ -0,0 +1,108 @@
+//! Telling the crate's own clipboard changes apart from the user's, for host
+//! apps that watch the clipboard, and refusing captures started from inside
+//! another capture.
+
+use std::cell::Cell;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+
+use crate::GetTextError;
+
+/// Clipboard listeners are notified asynchronously; changes reported this
+/// long after a capture still count as the crate's.
+const GRACE_PERIOD: Duration = Duration::from_millis(300);
+
+static ACTIVE: AtomicUsize = AtomicUsize::new(0);
+/// When the last clipboard activity ended, with the clipboard's change count
+/// at that point.
+static LAST_END: Mutex<Option<(Instant, Option<u64>)>> = Mutex::new(None);
+
+#[cfg(target_os = "macos")]
+pub(crate) fn change_count() -> Option<u64> {
+    Some(crate::macos::pasteboard_change_count() as u64)
+}
+
+#[cfg(target_os = "windows")]
+pub(crate) fn change_count() -> Option<u64> {
+    Some(unsafe { windows_sys::Win32::System::DataExchange::GetClipboardSequenceNumber() } as u64)
+}
+
+// X11 and Wayland have no change counter.
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+pub(crate) fn change_count() -> Option<u64> {
+    None
+}
+
+/// Held while the crate may change the clipboard.
+pub(crate) struct ClipboardActivity(());
+
+impl ClipboardActivity {
+    pub(crate) fn begin() -> Self {
+        ACTIVE.fetch_add(1, Ordering::SeqCst);
+        ClipboardActivity(())
+    }
+}
+
+impl Drop for ClipboardActivity {
+    fn drop(&mut self) {
+        // Recorded before the count drops, so there is no gap in between.
+        *LAST_END.lock() = Some((Instant::now(), change_count()));
+        ACTIVE.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Tells a host app's clipboard listener which changes come from this crate.
+///
+/// A capture empties the clipboard, lets the target app copy into it and
+/// puts the previous contents back, and the listener hears about each step.
+/// Keep a token next to the listener and skip changes for which
+/// [`SuppressToken::is_own_change`] is true, so that, for example, a listener
+/// that triggers captures doesn't trigger itself.
+#[derive(Debug, Clone, Copy)]
+pub struct SuppressToken(());
+
+/// Creates a [`SuppressToken`].
+pub fn suppress_token() -> SuppressToken {
+    SuppressToken(())
+}
+
+impl SuppressToken {
+    /// Whether the clipboard change just reported comes from the crate: a
+    /// capture is changing the clipboard right now, ended less than 300 ms
+    /// ago, or left the clipboard as it still is (on macOS and Windows, by
+    /// the pasteboard change count and clipboard sequence number).
+    pub fn is_own_change(&self) -> bool {
+        if ACTIVE.load(Ordering::SeqCst) > 0 {
+            return true;
+        }
+        match *LAST_END.lock() {
+            Some((ended, count)) => ended.elapsed() < GRACE_PERIOD || (count.is_some() && count == change_count()),
+            None => false,
+        }
+    }
+}
+
+thread_local! {
+    static IN_CAPTURE: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Marks this thread as capturing until dropped.
+pub(crate) struct CaptureGuard(());
+
+/// Fails with `GetTextError::Reentrant` when this thread is already capturing,
+/// e.g. when an extractor or a clipboard listener running inside a capture
+/// starts another one.
+pub(crate) fn enter_capture() -> Result<CaptureGuard, GetTextError> {
+    if IN_CAPTURE.with(|in_capture| in_capture.replace(true)) {
+        return Err(GetTextError::Reentrant);
+    }
+    Ok(CaptureGuard(()))
+}
+
+impl Drop for CaptureGuard {
+    fn drop(&mut self) {
+        IN_CAPTURE.with(|in_capture| in_capture.set(false));
+    }
+}
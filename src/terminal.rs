@@ -0,0 +1,183 @@
+//! Selections inside terminals, read from the program that owns them instead
+//! of simulating the terminal's copy shortcut, which is often rebound or
+//! copies only what is visible.
+
+use std::process::Command;
+
+use crate::utils::FocusSnapshot;
+
+/// Terminal emulators, matched against the focused app's name.
+const TERMINALS: [&str; 13] = [
+    "terminal", "iterm", "alacritty", "kitty", "wezterm", "ghostty", "konsole", "xterm", "foot", "tilix",
+    "terminator", "hyper", "warp",
+];
+
+/// Neovim GUIs, which run Neovim as a child process like terminals do.
+const NEOVIM_GUIS: [&str; 3] = ["neovide", "nvim-qt", "goneovim"];
+
+/// A selection read from a program running in the focused terminal.
+pub(crate) struct TerminalSelection {
+    pub(crate) text: String,
+    pub(crate) context: Option<String>,
+}
+
+/// The selection of whatever the focused terminal runs, if it's a terminal
+/// and one of the supported programs has a non-empty selection.
+pub(crate) fn selection_for_focused_app() -> Option<TerminalSelection> {
+    let focus = crate::utils::focus_snapshot()?;
+    let app_name = focus.app_name().to_lowercase();
+    if !TERMINALS.iter().chain(&NEOVIM_GUIS).any(|terminal| app_name.contains(terminal)) {
+        return None;
+    }
+    let pane = tmux_pane(&focus);
+    pane.as_ref()
+        .and_then(tmux_copy_mode)
+        .or_else(|| {
+            // Under tmux, Neovim runs below the tmux server, not the terminal.
+            let root = pane.as_ref().map_or(focus.process_id(), |pane| pane.pid);
+            crate::neovim::visual_selection(root).map(|(text, context)| TerminalSelection {
+                text,
+                context: Some(context),
+            })
+        })
+        .or_else(|| {
+            EXTRACTORS
+                .iter()
+                .filter(|(name, _)| app_name.contains(name))
+                .find_map(|(_, extract)| extract(&focus))
+        })
+        .filter(|selection| !selection.text.is_empty())
+}
+
+type Extractor = fn(&FocusSnapshot) -> Option<TerminalSelection>;
+
+/// Readers for a terminal's own selection, keyed by a substring of its app
+/// name. They run when tmux has no copy-mode selection.
+const EXTRACTORS: &[(&str, Extractor)] = &[
+    #[cfg(target_os = "macos")]
+    ("iterm", iterm2),
+    ("kitty", kitty),
+];
+
+/// Runs `program` and returns its stdout, `None` if it isn't installed or fails.
+pub(crate) fn run(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program).args(args).output().ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[cfg(target_os = "linux")]
+fn parent_pid(pid: u64) -> Option<u64> {
+    // "pid (comm) state ppid ...", where comm may hold spaces and parentheses.
+    let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+    stat.rsplit_once(')')?.1.split_whitespace().nth(1)?.parse().ok()
+}
+
+#[cfg(target_os = "macos")]
+fn parent_pid(pid: u64) -> Option<u64> {
+    let mut info: libc::proc_bsdinfo = unsafe { std::mem::zeroed() };
+    let size = std::mem::size_of::<libc::proc_bsdinfo>() as i32;
+    let written = unsafe {
+        libc::proc_pidinfo(pid as i32, libc::PROC_PIDTBSDINFO, 0, (&mut info as *mut libc::proc_bsdinfo).cast(), size)
+    };
+    (written == size).then_some(u64::from(info.pbi_ppid))
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn parent_pid(pid: u64) -> Option<u64> {
+    run("ps", &["-o", "ppid=", "-p", &pid.to_string()])?.trim().parse().ok()
+}
+
+/// Whether `pid` runs inside the process tree of `ancestor`.
+pub(crate) fn descends_from(mut pid: u64, ancestor: u64) -> bool {
+    // Deeper than any terminal → shell → multiplexer chain.
+    const MAX_DEPTH: usize = 32;
+    for _ in 0..MAX_DEPTH {
+        if pid == ancestor {
+            return true;
+        }
+        match parent_pid(pid) {
+            Some(parent) if parent > 1 => pid = parent,
+            _ => return false,
+        }
+    }
+    false
+}
+
+/// The active pane of the tmux client attached in the focused terminal window.
+struct TmuxPane {
+    id: String,
+    /// The process running in the pane, usually a shell.
+    pid: u64,
+}
+
+fn tmux_pane(focus: &FocusSnapshot) -> Option<TmuxPane> {
+    let clients = run("tmux", &["list-clients", "-F", "#{client_pid}\t#{pane_id}\t#{pane_pid}"])?;
+    clients.lines().find_map(|line| {
+        let mut fields = line.split('\t');
+        let client_pid = fields.next()?.parse().ok()?;
+        let id = fields.next()?.to_string();
+        let pid = fields.next()?.parse().ok()?;
+        descends_from(client_pid, focus.process_id()).then_some(TmuxPane { id, pid })
+    })
+}
+
+/// The pane's copy-mode selection. Outside copy mode the terminal's own
+/// selection applies, so this returns `None`.
+fn tmux_copy_mode(pane: &TmuxPane) -> Option<TerminalSelection> {
+    let state = run("tmux", &["display-message", "-p", "-t", &pane.id, "#{pane_in_mode}\t#{selection_present}"])?;
+    if state.trim() != "1\t1" {
+        return None;
+    }
+    // Copies into a new top buffer without leaving copy mode, then drops it
+    // again. With `set-clipboard` on, tmux would also hand every new buffer
+    // to the terminal's clipboard (OSC 52), replacing the user's; it is
+    // turned off around the copy.
+    let set_clipboard = run("tmux", &["show-options", "-sv", "set-clipboard"]);
+    let set_clipboard = set_clipboard.as_deref().map(str::trim).filter(|value| *value != "off");
+    if set_clipboard.is_some() {
+        run("tmux", &["set-option", "-s", "set-clipboard", "off"])?;
+    }
+    let copied = run("tmux", &["send-keys", "-X", "-t", &pane.id, "copy-selection-no-clear"]);
+    if let Some(value) = set_clipboard {
+        let _ = run("tmux", &["set-option", "-s", "set-clipboard", value]);
+    }
+    copied?;
+    let text = run("tmux", &["show-buffer"]);
+    let _ = run("tmux", &["delete-buffer"]);
+    Some(TerminalSelection { text: text?, context: None })
+}
+
+#[cfg(target_os = "macos")]
+const ITERM2_SELECTION_SCRIPT: &str = r#"tell application "iTerm2" to get selection of current session of current window"#;
+
+/// The selection in iTerm2's active session, through its AppleScript
+/// dictionary. Profiles often bind Cmd+C to something else, and the script
+/// also sees selected text scrolled out of view.
+#[cfg(target_os = "macos")]
+fn iterm2(_focus: &FocusSnapshot) -> Option<TerminalSelection> {
+    let text = run("osascript", &["-e", ITERM2_SELECTION_SCRIPT])?;
+    let text = text.strip_suffix('\n').unwrap_or(&text).to_string();
+    Some(TerminalSelection { text, context: None })
+}
+
+/// The selection in kitty's active window, through remote control. Needs
+/// `allow_remote_control` and a `listen_on` socket in kitty.conf; the socket
+/// is looked up in `KITTY_LISTEN_ON`, then at the paths
+/// `listen_on unix:/tmp/kitty` or `unix:@kitty` produce, with and without
+/// kitty's `-{kitty_pid}` suffix.
+fn kitty(focus: &FocusSnapshot) -> Option<TerminalSelection> {
+    let pid = focus.process_id();
+    let candidates = std::env::var("KITTY_LISTEN_ON").into_iter().chain([
+        format!("unix:/tmp/kitty-{}", pid),
+        format!("unix:@kitty-{}", pid),
+        "unix:/tmp/kitty".to_string(),
+        "unix:@kitty".to_string(),
+    ]);
+    candidates
+        .into_iter()
+        .find_map(|address| run("kitty", &["@", "--to", &address, "get-text", "--extent", "selection"]))
+        .map(|text| TerminalSelection { text, context: None })
+}
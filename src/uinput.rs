@@ -0,0 +1,209 @@
+//! A minimal virtual keyboard on `/dev/uinput`, for sessions where XTEST
+//! can't reach the focused window (Wayland).
+//!
+//! Keys are sent as evdev key codes, i.e. by physical position: on layouts
+//! that move C or A (Dvorak, ...) the shortcut arrives as whatever letter sits
+//! there.
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::io::AsRawFd;
+use std::time::Duration;
+
+use parking_lot::Mutex;
+
+use crate::GetTextError;
+
+const DEVICE: &str = "/dev/uinput";
+
+// From <linux/uinput.h> and <linux/input-event-codes.h>.
+const UI_SET_EVBIT: u64 = 0x4004_5564;
+const UI_SET_KEYBIT: u64 = 0x4004_5565;
+const UI_DEV_CREATE: u64 = 0x5501;
+const UI_DEV_DESTROY: u64 = 0x5502;
+const EV_SYN: u16 = 0x00;
+const EV_KEY: u16 = 0x01;
+const SYN_REPORT: u16 = 0;
+const BUS_VIRTUAL: u16 = 0x06;
+
+pub(crate) const KEY_ESC: u16 = 1;
+pub(crate) const KEY_LEFTCTRL: u16 = 29;
+pub(crate) const KEY_A: u16 = 30;
+pub(crate) const KEY_LEFTSHIFT: u16 = 42;
+pub(crate) const KEY_C: u16 = 46;
+pub(crate) const KEY_LEFT: u16 = 105;
+pub(crate) const KEY_RIGHT: u16 = 106;
+pub(crate) const KEY_INSERT: u16 = 110;
+
+/// Every key the device can send. The kernel silently drops the others.
+const KEYS: [u16; 8] = [KEY_ESC, KEY_LEFTCTRL, KEY_A, KEY_LEFTSHIFT, KEY_C, KEY_LEFT, KEY_RIGHT, KEY_INSERT];
+
+/// How long the compositor needs to pick up a freshly created device before
+/// it routes its events.
+const SETTLE_TIME: Duration = Duration::from_millis(200);
+
+#[repr(C)]
+struct InputId {
+    bustype: u16,
+    vendor: u16,
+    product: u16,
+    version: u16,
+}
+
+#[repr(C)]
+struct UinputUserDev {
+    name: [u8; 80],
+    id: InputId,
+    ff_effects_max: u32,
+    absmax: [i32; 64],
+    absmin: [i32; 64],
+    absfuzz: [i32; 64],
+    absflat: [i32; 64],
+}
+
+#[repr(C)]
+struct InputEvent {
+    time: libc::timeval,
+    kind: u16,
+    code: u16,
+    value: i32,
+}
+
+fn as_bytes<T>(value: &T) -> &[u8] {
+    unsafe { std::slice::from_raw_parts(value as *const T as *const u8, std::mem::size_of::<T>()) }
+}
+
+fn ioctl(file: &File, request: u64, arg: libc::c_int) -> io::Result<()> {
+    if unsafe { libc::ioctl(file.as_raw_fd(), request as _, arg) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+struct VirtualKeyboard {
+    file: File,
+}
+
+impl VirtualKeyboard {
+    fn create() -> io::Result<Self> {
+        let file = OpenOptions::new().write(true).open(DEVICE)?;
+        ioctl(&file, UI_SET_EVBIT, EV_KEY as libc::c_int)?;
+        for key in KEYS {
+            ioctl(&file, UI_SET_KEYBIT, key as libc::c_int)?;
+        }
+
+        let mut name = [0u8; 80];
+        let label = b"get-selected-text virtual keyboard";
+        name[..label.len()].copy_from_slice(label);
+        let device = UinputUserDev {
+            name,
+            id: InputId {
+                bustype: BUS_VIRTUAL,
+                vendor: 0,
+                product: 0,
+                version: 1,
+            },
+            ff_effects_max: 0,
+            absmax: [0; 64],
+            absmin: [0; 64],
+            absfuzz: [0; 64],
+            absflat: [0; 64],
+        };
+        (&file).write_all(as_bytes(&device))?;
+        ioctl(&file, UI_DEV_CREATE, 0)?;
+        std::thread::sleep(SETTLE_TIME);
+        Ok(VirtualKeyboard { file })
+    }
+
+    fn emit(&mut self, kind: u16, code: u16, value: i32) -> io::Result<()> {
+        let event = InputEvent {
+            time: libc::timeval { tv_sec: 0, tv_usec: 0 },
+            kind,
+            code,
+            value,
+        };
+        self.file.write_all(as_bytes(&event))
+    }
+
+    fn key(&mut self, code: u16, pressed: bool) -> io::Result<()> {
+        self.emit(EV_KEY, code, pressed as i32)?;
+        self.emit(EV_SYN, SYN_REPORT, 0)
+    }
+
+    fn chord(&mut self, modifiers: &[u16], key: u16) -> io::Result<()> {
+        for &modifier in modifiers {
+            self.key(modifier, true)?;
+        }
+        self.key(key, true)?;
+        self.key(key, false)?;
+        for &modifier in modifiers.iter().rev() {
+            self.key(modifier, false)?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for VirtualKeyboard {
+    fn drop(&mut self) {
+        let _ = ioctl(&self.file, UI_DEV_DESTROY, 0);
+    }
+}
+
+// Kept between calls: every new device costs SETTLE_TIME.
+static KEYBOARD: Mutex<Option<VirtualKeyboard>> = Mutex::new(None);
+
+/// Whether this process may create a uinput device.
+pub(crate) fn available() -> bool {
+    unsafe { libc::access(b"/dev/uinput\0".as_ptr() as *const libc::c_char, libc::W_OK) == 0 }
+}
+
+/// The first of `modifiers`+`key` the device can't send.
+fn unregistered(modifiers: &[u16], key: u16) -> Option<u16> {
+    modifiers.iter().chain([&key]).copied().find(|code| !KEYS.contains(code))
+}
+
+/// Presses `modifiers`+`key` on the virtual keyboard, creating it on first use.
+///
+/// # Errors
+///
+/// `GetTextError::Input` for a key missing from `KEYS`, which the device
+/// would drop without any error of its own.
+pub(crate) fn chord(modifiers: &[u16], key: u16) -> Result<(), GetTextError> {
+    if let Some(missing) = unregistered(modifiers, key) {
+        return Err(GetTextError::Input(format!("key {} is not registered on the uinput device", missing)));
+    }
+    let mut keyboard = KEYBOARD.lock();
+    if keyboard.is_none() {
+        let created = VirtualKeyboard::create()
+            .map_err(|e| GetTextError::InputUnavailable(format!("Failed to create a uinput device: {}", e)))?;
+        *keyboard = Some(created);
+    }
+    keyboard
+        .as_mut()
+        .expect("created above")
+        .chord(modifiers, key)
+        .map_err(|e| GetTextError::Input(format!("uinput write failed: {}", e)))
+}
+
+pub(crate) fn release() {
+    KEYBOARD.lock().take();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CopyShortcut;
+
+    #[test]
+    fn every_shortcut_sent_is_registered() {
+        for shortcut in [CopyShortcut::CtrlC, CopyShortcut::CtrlShiftC, CopyShortcut::CtrlInsert] {
+            let (modifiers, key) = shortcut.evdev_keys();
+            assert_eq!(unregistered(modifiers, key), None, "{:?}", shortcut);
+        }
+        assert_eq!(unregistered(&[KEY_LEFTCTRL], KEY_A), None);
+        for key in [KEY_ESC, KEY_LEFT, KEY_RIGHT] {
+            assert_eq!(unregistered(&[], key), None);
+        }
+        assert_eq!(unregistered(&[KEY_LEFTCTRL], 200), Some(200));
+    }
+}
@@ -1,177 +1,845 @@
 use debug_print::debug_println;
 use enigo::*;
 use parking_lot::Mutex;
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::{thread, time::Duration};
 
 use crate::GetTextError;
 
-static COPY_PASTE_LOCKER: Mutex<()> = Mutex::new(());
-static INPUT_LOCK_LOCKER: Mutex<()> = Mutex::new(());
-
-const CONTEXT_CHARS_BEFORE: usize = 150;
-const CONTEXT_CHARS_AFTER: usize = 150;
-const CLIPBOARD_OPERATION_TIMEOUT_MS: u64 = 5000; // 5秒超时
-
 // Use debug_print for logging if enabled, otherwise println
 #[cfg(debug_assertions)]
 use debug_print::debug_println as log_println;
 #[cfg(not(debug_assertions))]
 use println as log_println;
 
-pub(crate) fn right_arrow_click(enigo: &mut Enigo, n: usize) {
-    let _guard = INPUT_LOCK_LOCKER.lock();
+thread_local! {
+    // Created on a thread's first simulated input (or by `crate::init()`) and
+    // kept for the thread's lifetime; Enigo is not `Send` on every platform.
+    static WARM_ENIGO: RefCell<Option<Enigo>> = const { RefCell::new(None) };
+    // Set while a `ClipboardSession` owns the save and restore.
+    static IN_SESSION: std::cell::Cell<bool> = const { std::cell::Cell::new(false) };
+    // Whether the app answered this thread's last simulated copy, where the
+    // clipboard has a change count to tell.
+    static COPY_ANSWERED: std::cell::Cell<Option<bool>> = const { std::cell::Cell::new(None) };
+}
 
-    for _ in 0..n {
-        enigo.key(Key::RightArrow, Direction::Click).unwrap();
+/// Whether the focused app changed the clipboard in answer to this thread's
+/// last [`get_selected_text_by_clipboard`], i.e. the copy reached it even if
+/// nothing was selected. `None` where there is no change count (Linux).
+#[cfg(target_os = "windows")]
+pub(crate) fn last_copy_answered() -> Option<bool> {
+    COPY_ANSWERED.with(std::cell::Cell::get)
+}
+
+// Keeping one clipboard handle alive keeps arboard's X11 worker thread and
+// server connection up between calls.
+#[cfg(target_os = "linux")]
+static WARM_CLIPBOARD: Mutex<Option<arboard::Clipboard>> = Mutex::new(None);
+
+pub(crate) fn new_enigo() -> Result<Enigo, GetTextError> {
+    Enigo::new(&Settings::default()).map_err(|e| GetTextError::Input(e.to_string()))
+}
+
+/// Runs `f` with this thread's Enigo, creating it on first use. A nested call
+/// (from inside `f`) gets a fresh one.
+///
+/// Takes no lock: only the input sequences themselves are serialized, by
+/// [`crate::sequence::begin`], so AX/UIA reads on other threads never wait
+/// for a copy in progress.
+pub(crate) fn with_enigo<R>(f: impl FnOnce(&mut Enigo) -> R) -> Result<R, GetTextError> {
+    WARM_ENIGO.with(|cell| {
+        let Ok(mut warm) = cell.try_borrow_mut() else {
+            return Ok(f(&mut new_enigo()?));
+        };
+        if warm.is_none() {
+            *warm = Some(new_enigo()?);
+        }
+        Ok(f(warm.as_mut().unwrap()))
+    })
+}
+
+pub(crate) fn prewarm() -> Result<(), GetTextError> {
+    let enigo = new_enigo()?;
+    WARM_ENIGO.with(|cell| *cell.borrow_mut() = Some(enigo));
+    #[cfg(target_os = "linux")]
+    {
+        let clipboard = open_clipboard()?;
+        *WARM_CLIPBOARD.lock() = Some(clipboard);
+    }
+    Ok(())
+}
+
+pub(crate) fn release() {
+    WARM_ENIGO.with(|cell| cell.borrow_mut().take());
+    #[cfg(target_os = "linux")]
+    {
+        WARM_CLIPBOARD.lock().take();
+        crate::uinput::release();
+    }
+}
+
+const CLIPBOARD_RETRY_ATTEMPTS: u32 = 5;
+const CLIPBOARD_RETRY_BASE_DELAY_MS: u64 = 10;
+
+/// Runs a clipboard operation, retrying with exponential backoff (10, 20, 40,
+/// 80 ms) while another process holds the clipboard open. Other errors,
+/// including "content not available", are returned unchanged on the first try.
+pub(crate) fn clipboard_retry<T>(mut op: impl FnMut() -> Result<T, arboard::Error>) -> Result<T, arboard::Error> {
+    let mut attempt = 0;
+    loop {
+        match op() {
+            Err(arboard::Error::ClipboardOccupied) if attempt + 1 < CLIPBOARD_RETRY_ATTEMPTS => {
+                let delay = CLIPBOARD_RETRY_BASE_DELAY_MS << attempt;
+                crate::env_overrides::gst_log!("Clipboard busy, retrying in {} ms", delay);
+                thread::sleep(Duration::from_millis(delay));
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+pub(crate) fn clipboard_error(error: arboard::Error) -> GetTextError {
+    match error {
+        arboard::Error::ClipboardOccupied => GetTextError::ClipboardBusy,
+        other => GetTextError::Clipboard(other.to_string()),
+    }
+}
+
+/// Opens the clipboard, retrying while it is busy.
+pub(crate) fn open_clipboard() -> Result<arboard::Clipboard, GetTextError> {
+    clipboard_retry(arboard::Clipboard::new).map_err(clipboard_error)
+}
+
+/// Writes the crate's own temporary clipboard content (the placeholder). With
+/// the `winrt-clipboard` or `native-clipboard` feature on Windows these writes
+/// are excluded from clipboard history and cloud sync.
+pub(crate) fn write_temporary_text(clipboard: &mut arboard::Clipboard, text: &str) -> Result<(), GetTextError> {
+    #[cfg(all(target_os = "windows", feature = "winrt-clipboard"))]
+    {
+        let _ = clipboard;
+        crate::winrt_clipboard::set_text(text)
+    }
+    #[cfg(all(target_os = "windows", feature = "native-clipboard", not(feature = "winrt-clipboard")))]
+    {
+        let _ = clipboard;
+        crate::win_clipboard::OpenedClipboard::open()?.set_text(text, true)
+    }
+    #[cfg(not(all(target_os = "windows", any(feature = "native-clipboard", feature = "winrt-clipboard"))))]
+    {
+        clipboard_retry(|| clipboard.set_text(text)).map_err(clipboard_error)
+    }
+}
+
+/// Puts the user's text back on the clipboard. Unlike [`write_temporary_text`]
+/// this is an ordinary entry: it is the user's own, so clipboard history may
+/// keep it.
+fn write_restored_text(clipboard: &mut arboard::Clipboard, text: &str) -> Result<(), GetTextError> {
+    #[cfg(all(target_os = "windows", feature = "native-clipboard"))]
+    {
+        let _ = clipboard;
+        crate::win_clipboard::OpenedClipboard::open()?.set_text(text, false)
+    }
+    #[cfg(not(all(target_os = "windows", feature = "native-clipboard")))]
+    {
+        clipboard_retry(|| clipboard.set_text(text)).map_err(clipboard_error)
+    }
+}
+
+// Another process writing between our restore and its read-back gets this
+// many more attempts, each waiting a little longer.
+const RESTORE_ATTEMPTS: u32 = 3;
+const RESTORE_RETRY_DELAY: Duration = Duration::from_millis(30);
+
+static RESTORE_DELAY_MS: AtomicU64 = AtomicU64::new(0);
+
+/// Delays putting the user's clipboard back after a copy by `delay`, for
+/// target apps that read the clipboard again right after a copy, or users
+/// who paste right away. The restore then runs on a background thread, and
+/// is dropped if something else was copied by then. Process-wide; defaults
+/// to zero (restore before returning).
+///
+/// A delayed restore happens after the call returns, so it isn't reflected
+/// in [`crate::SideEffects`].
+pub fn set_clipboard_restore_delay(delay: Duration) {
+    RESTORE_DELAY_MS.store(delay.as_millis() as u64, Ordering::Relaxed);
+}
+
+/// The current delay before the clipboard is restored after a copy.
+pub fn clipboard_restore_delay() -> Duration {
+    Duration::from_millis(RESTORE_DELAY_MS.load(Ordering::Relaxed))
+}
+
+/// A restore put off by the restore delay. Whichever comes first runs it:
+/// its timer, or the next capture, which must save the user's clipboard and
+/// not the previous capture's copy.
+struct PendingRestore {
+    id: u64,
+    saved: SavedClipboard,
+    /// What the capture's copy left on the clipboard.
+    copied: Option<String>,
+}
+
+static PENDING_RESTORE: Mutex<Option<PendingRestore>> = Mutex::new(None);
+static NEXT_RESTORE_ID: AtomicU64 = AtomicU64::new(0);
+
+impl PendingRestore {
+    fn run(self, clipboard: &mut arboard::Clipboard) {
+        // The user copied something else meanwhile; that wins.
+        if clipboard_retry(|| clipboard.get_text()).ok() != self.copied {
+            crate::env_overrides::gst_log!("Clipboard changed since the copy, dropping the delayed restore");
+            return;
+        }
+        let _ = self.saved.restore(clipboard);
+    }
+}
+
+/// Saves the clipboard once for several copies on this thread, e.g. the
+/// selection and then Select All for its context, and restores it when
+/// dropped. Saves made meanwhile restore nothing themselves.
+pub(crate) struct ClipboardSession {
+    clipboard: arboard::Clipboard,
+    saved: SavedClipboard,
+}
+
+impl ClipboardSession {
+    /// `None` inside another session, which already covers this one.
+    pub(crate) fn begin() -> Result<Option<Self>, GetTextError> {
+        if IN_SESSION.with(|in_session| in_session.get()) {
+            return Ok(None);
+        }
+        let mut clipboard = open_clipboard()?;
+        let saved = SavedClipboard::save(&mut clipboard);
+        IN_SESSION.with(|in_session| in_session.set(true));
+        Ok(Some(ClipboardSession { clipboard, saved }))
+    }
+}
+
+impl Drop for ClipboardSession {
+    fn drop(&mut self) {
+        IN_SESSION.with(|in_session| in_session.set(false));
+        // Only restore what a copy replaced.
+        if !self.saved.is_current(&mut self.clipboard) {
+            let _activity = crate::suppress::ClipboardActivity::begin();
+            let _ = self.saved.restore(&mut self.clipboard);
+        }
+    }
+}
+
+/// The clipboard as it was before a simulated copy, put back when the guard
+/// is dropped: after an error, a timeout or a panic mid-sequence just as on
+/// the normal path, which calls [`ClipboardGuard::restore`] to see its error.
+pub(crate) struct ClipboardGuard {
+    clipboard: arboard::Clipboard,
+    saved: Option<SavedClipboard>,
+}
+
+impl ClipboardGuard {
+    /// Saves the clipboard. With `restore` off nothing is saved or put back;
+    /// only a delayed restore still pending from an earlier capture runs.
+    pub(crate) fn save(mut clipboard: arboard::Clipboard, restore: bool) -> Self {
+        let saved = if restore {
+            Some(SavedClipboard::save(&mut clipboard))
+        } else {
+            run_pending_restore(&mut clipboard);
+            None
+        };
+        ClipboardGuard { clipboard, saved }
+    }
+
+    pub(crate) fn clipboard(&mut self) -> &mut arboard::Clipboard {
+        &mut self.clipboard
+    }
+
+    pub(crate) fn restore(mut self) -> Result<(), GetTextError> {
+        match self.saved.take() {
+            Some(saved) => saved.restore(&mut self.clipboard),
+            None => Ok(()),
+        }
+    }
+
+    /// See [`SavedClipboard::restore_after`].
+    fn restore_after(mut self, delay: Duration, copied: Option<String>) {
+        if let Some(saved) = self.saved.take() {
+            saved.restore_after(delay, copied);
+        }
+    }
+}
+
+impl Drop for ClipboardGuard {
+    fn drop(&mut self) {
+        if let Some(saved) = self.saved.take() {
+            crate::env_overrides::gst_log!("Sequence ended early, restoring the clipboard");
+            let _ = saved.restore(&mut self.clipboard);
+        }
+    }
+}
+
+fn run_pending_restore(clipboard: &mut arboard::Clipboard) {
+    let pending = PENDING_RESTORE.lock().take();
+    if let Some(pending) = pending {
+        pending.run(clipboard);
+    }
+}
+
+/// The clipboard contents before a capture, to put back afterwards.
+pub(crate) struct SavedClipboard {
+    text: Result<String, arboard::Error>,
+    image: Result<arboard::ImageData<'static>, arboard::Error>,
+    // Saved inside a `ClipboardSession`, which restores instead.
+    deferred: bool,
+}
+
+impl SavedClipboard {
+    pub(crate) fn save(clipboard: &mut arboard::Clipboard) -> Self {
+        if IN_SESSION.with(|in_session| in_session.get()) {
+            return SavedClipboard {
+                text: clipboard_retry(|| clipboard.get_text()),
+                image: Err(arboard::Error::ContentNotAvailable),
+                deferred: true,
+            };
+        }
+        run_pending_restore(clipboard);
+        SavedClipboard {
+            text: clipboard_retry(|| clipboard.get_text()),
+            image: clipboard_retry(|| clipboard.get_image()),
+            deferred: false,
+        }
+    }
+
+    pub(crate) fn text(&self) -> Option<&str> {
+        self.text.as_deref().ok()
+    }
+
+    fn write(&self, clipboard: &mut arboard::Clipboard) -> Result<(), GetTextError> {
+        match (&self.text, &self.image) {
+            (Ok(text), _) => write_restored_text(clipboard, text),
+            (_, Ok(image)) => clipboard_retry(|| clipboard.set_image(image.clone())).map_err(clipboard_error),
+            _ => clipboard_retry(|| clipboard.clear()).map_err(clipboard_error),
+        }
+    }
+
+    fn is_current(&self, clipboard: &mut arboard::Clipboard) -> bool {
+        match (&self.text, &self.image) {
+            (Ok(text), _) => clipboard_retry(|| clipboard.get_text()).is_ok_and(|current| current == *text),
+            (_, Ok(image)) => clipboard_retry(|| clipboard.get_image()).is_ok_and(|current| current.bytes == image.bytes),
+            _ => clipboard_retry(|| clipboard.get_text()).is_err(),
+        }
+    }
+
+    /// Writes the saved contents back and reads them back, writing again if
+    /// another process got in between. When they still don't match, the
+    /// side-effect report says so; the capture itself goes on.
+    pub(crate) fn restore(&self, clipboard: &mut arboard::Clipboard) -> Result<(), GetTextError> {
+        if self.deferred {
+            return Ok(());
+        }
+        for attempt in 1..=RESTORE_ATTEMPTS {
+            if let Err(e) = self.write(clipboard) {
+                crate::side_effects::clipboard_restore_failed();
+                return Err(e);
+            }
+            if self.is_current(clipboard) {
+                crate::side_effects::clipboard_restored();
+                return Ok(());
+            }
+            crate::env_overrides::gst_log!("Clipboard changed after restore attempt {}", attempt);
+            thread::sleep(RESTORE_RETRY_DELAY * attempt);
+        }
+        crate::side_effects::clipboard_restore_failed();
+        Ok(())
+    }
+
+    /// Restores like [`SavedClipboard::restore`] once `delay` has passed, on
+    /// another thread, unless the clipboard no longer holds `copied` then.
+    fn restore_after(self, delay: Duration, copied: Option<String>) {
+        if self.deferred {
+            return;
+        }
+        let id = NEXT_RESTORE_ID.fetch_add(1, Ordering::Relaxed);
+        *PENDING_RESTORE.lock() = Some(PendingRestore { id, saved: self, copied });
+        thread::spawn(move || {
+            thread::sleep(delay);
+            let _activity = crate::suppress::ClipboardActivity::begin();
+            let mut pending = PENDING_RESTORE.lock();
+            // Otherwise a capture has run it already.
+            if pending.as_ref().is_some_and(|pending| pending.id == id) {
+                let pending = pending.take().expect("checked above");
+                if let Ok(mut clipboard) = open_clipboard() {
+                    pending.run(&mut clipboard);
+                }
+            }
+        });
+    }
+}
+
+/// Reads the clipboard text after a simulated copy.
+pub(crate) fn read_clipboard_text() -> Result<String, arboard::Error> {
+    #[cfg(all(target_os = "windows", feature = "native-clipboard"))]
+    {
+        crate::win_clipboard::OpenedClipboard::open()
+            .map_err(|_| arboard::Error::ClipboardOccupied)?
+            .get_text()
+            .ok_or(arboard::Error::ContentNotAvailable)
+    }
+    #[cfg(not(all(target_os = "windows", feature = "native-clipboard")))]
+    {
+        clipboard_retry(|| arboard::Clipboard::new()?.get_text())
+    }
+}
+
+/// What the accessibility API says about the selection before any input is simulated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SelectionProbe {
+    Present,
+    /// The focused element is a text control that reports an empty selection.
+    Absent,
+    /// The API can't tell (no text interface, unsupported app, error).
+    Unknown,
+}
+
+/// The foreground window at one point in time.
+pub(crate) struct FocusSnapshot {
+    app_name: String,
+    process_id: u64,
+    window_id: String,
+    title: Option<String>,
+}
+
+impl FocusSnapshot {
+    pub(crate) fn app_name(&self) -> &str {
+        &self.app_name
+    }
+
+    pub(crate) fn process_id(&self) -> u64 {
+        self.process_id
+    }
+
+    /// `None` where the window has no title or it can't be read.
+    pub(crate) fn title(&self) -> Option<&str> {
+        self.title.as_deref().filter(|title| !title.is_empty())
+    }
+}
+
+/// Whether the foreground window belongs to this process.
+pub(crate) fn own_window_focused() -> bool {
+    focus_snapshot().is_some_and(|focus| focus.process_id() == u64::from(std::process::id()))
+}
+
+/// The focused app, if it holds the display in full-screen exclusive mode
+/// (a game, a video player, a D3D presentation), where a simulated Ctrl+C or
+/// Ctrl+A can trigger in-game actions or drop the app out of full screen.
+pub(crate) fn exclusive_fullscreen_app() -> Option<String> {
+    if !exclusive_fullscreen() {
+        return None;
     }
+    Some(focus_snapshot().map(|focus| focus.app_name().to_string()).unwrap_or_default())
 }
 
-pub(crate) fn left_arrow_click(enigo: &mut Enigo, n: usize) {
-    let _guard = INPUT_LOCK_LOCKER.lock();
+#[cfg(target_os = "windows")]
+fn exclusive_fullscreen() -> bool {
+    use windows_sys::Win32::UI::Shell::{SHQueryUserNotificationState, QUNS_RUNNING_D3D_FULL_SCREEN};
+
+    let mut state = 0;
+    unsafe { SHQueryUserNotificationState(&mut state) == 0 && state == QUNS_RUNNING_D3D_FULL_SCREEN }
+}
+
+#[cfg(target_os = "macos")]
+fn exclusive_fullscreen() -> bool {
+    #[link(name = "CoreGraphics", kind = "framework")]
+    extern "C" {
+        fn CGMainDisplayID() -> u32;
+        fn CGDisplayIsCaptured(display: u32) -> u32;
+    }
+    // Games that switch display modes capture the display; ordinary
+    // full-screen windows don't.
+    unsafe { CGDisplayIsCaptured(CGMainDisplayID()) != 0 }
+}
+
+// X11 and Wayland full-screen windows don't take the display exclusively.
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+fn exclusive_fullscreen() -> bool {
+    false
+}
+
+/// `None` when the foreground window can't be determined (e.g. on an
+/// unsupported Wayland compositor), in which case focus changes go undetected.
+pub(crate) fn focus_snapshot() -> Option<FocusSnapshot> {
+    let snapshot = active_win_pos_rs::get_active_window()
+        .ok()
+        .map(|window| FocusSnapshot {
+            app_name: window.app_name,
+            process_id: window.process_id,
+            window_id: window.window_id,
+            title: Some(window.title),
+        });
+    #[cfg(target_os = "linux")]
+    let snapshot = snapshot.or_else(|| {
+        crate::compositor::focused_window().map(|window| FocusSnapshot {
+            app_name: window.app_name,
+            process_id: window.process_id,
+            window_id: window.window_id,
+            title: window.title,
+        })
+    });
+    snapshot
+}
+
+/// Fails with `GetTextError::FocusChanged` if the foreground window is no longer
+/// the one captured in `before`.
+pub(crate) fn ensure_focus_unchanged(before: Option<&FocusSnapshot>) -> Result<(), GetTextError> {
+    let (Some(before), Some(after)) = (before, focus_snapshot()) else {
+        return Ok(());
+    };
+    if before.process_id != after.process_id || before.window_id != after.window_id {
+        crate::env_overrides::gst_log!("Focus changed from {} to {} during the capture", before.app_name, after.app_name);
+        return Err(GetTextError::FocusChanged {
+            before: before.app_name.clone(),
+            after: after.app_name,
+        });
+    }
+    Ok(())
+}
+
+pub(crate) fn right_arrow_click(enigo: &mut Enigo, n: usize) -> Result<(), GetTextError> {
+    crate::side_effects::keystrokes_sent();
 
     for _ in 0..n {
-        enigo.key(Key::LeftArrow, Direction::Click).unwrap();
+        enigo.key(Key::RightArrow, Direction::Click).map_err(input_error)?;
     }
+    Ok(())
+}
+
+/// Collapses the user's selection to its end with the right arrow, for
+/// `GetTextOptions::cancel_select`.
+pub(crate) fn cancel_selection() -> Result<(), GetTextError> {
+    let _sequence = crate::sequence::begin()?;
+    crate::side_effects::keystrokes_sent();
+    crate::side_effects::selection_changed();
+    #[cfg(target_os = "linux")]
+    {
+        if crate::linux::uinput_shortcut(&[], crate::uinput::KEY_RIGHT)? {
+            return Ok(());
+        }
+    }
+    with_enigo(|enigo| right_arrow_click(enigo, 1))?
+}
+
+/// Presses `key` to undo a Select All, through the backend that sent it:
+/// with `evdev`, the uinput or ydotool keyboard, which native Wayland clients
+/// see and XTEST keys from enigo don't reach.
+fn deselect_key(enigo: &mut Enigo, evdev: bool, key: Key) -> Result<(), GetTextError> {
+    crate::side_effects::keystrokes_sent();
+
+    #[cfg(target_os = "linux")]
+    if evdev {
+        let code = match key {
+            Key::Escape => crate::uinput::KEY_ESC,
+            Key::LeftArrow => crate::uinput::KEY_LEFT,
+            Key::RightArrow => crate::uinput::KEY_RIGHT,
+            other => return Err(GetTextError::Input(format!("no evdev code for {:?}", other))),
+        };
+        return crate::linux::uinput_shortcut(&[], code).map(|_| ());
+    }
+    let _ = evdev;
+    enigo.key(key, Direction::Click).map_err(input_error)
 }
 
 pub(crate) fn up_control_keys(enigo: &mut Enigo) {
-    enigo.key(Key::Control, Direction::Release).unwrap();
-    enigo.key(Key::Alt, Direction::Release).unwrap();
-    enigo.key(Key::Shift, Direction::Release).unwrap();
-    enigo.key(Key::Space, Direction::Release).unwrap();
-    enigo.key(Key::Tab, Direction::Release).unwrap();
+    // Releasing a key that isn't down is harmless, and so is failing to.
+    let _ = enigo.key(Key::Control, Direction::Release);
+    let _ = enigo.key(Key::Alt, Direction::Release);
+    let _ = enigo.key(Key::Shift, Direction::Release);
+    let _ = enigo.key(Key::Space, Direction::Release);
+    let _ = enigo.key(Key::Tab, Direction::Release);
     #[cfg(target_os = "macos")]
-    enigo.key(Key::Meta, Direction::Release).unwrap();
+    let _ = enigo.key(Key::Meta, Direction::Release);
+}
+
+fn input_error(error: InputError) -> GetTextError {
+    GetTextError::Input(error.to_string())
+}
+
+/// Modifiers held down for a shortcut. Whatever is still held when the guard
+/// is dropped gets released, so an error or a panic between the press and the
+/// release can't leave the user's keyboard stuck with Ctrl down.
+pub(crate) struct HeldKeys<'a> {
+    enigo: &'a mut Enigo,
+    held: Vec<Key>,
+}
+
+impl<'a> HeldKeys<'a> {
+    pub(crate) fn new(enigo: &'a mut Enigo) -> Self {
+        HeldKeys { enigo, held: Vec::new() }
+    }
+
+    pub(crate) fn press(&mut self, key: Key) -> Result<(), GetTextError> {
+        self.enigo.key(key, Direction::Press).map_err(input_error)?;
+        self.held.push(key);
+        Ok(())
+    }
+
+    pub(crate) fn click(&mut self, key: Key) -> Result<(), GetTextError> {
+        self.enigo.key(key, Direction::Click).map_err(input_error)
+    }
+
+    /// Releases the held keys, last pressed first.
+    pub(crate) fn release(mut self) -> Result<(), GetTextError> {
+        while let Some(key) = self.held.pop() {
+            self.enigo.key(key, Direction::Release).map_err(input_error)?;
+        }
+        Ok(())
+    }
 }
 
-pub(crate) fn copy(enigo: &mut Enigo) {
+impl Drop for HeldKeys<'_> {
+    fn drop(&mut self) {
+        while let Some(key) = self.held.pop() {
+            let _ = self.enigo.key(key, Direction::Release);
+        }
+    }
+}
+
+pub(crate) fn copy(enigo: &mut Enigo) -> Result<(), GetTextError> {
+    crate::side_effects::keystrokes_sent();
+    crate::side_effects::clipboard_modified();
+    #[cfg_attr(target_os = "macos", allow(unused_variables))]
+    let shortcut = crate::quirks::for_focused_app().copy_shortcut;
+    #[cfg(target_os = "linux")]
+    {
+        let (modifiers, key) = shortcut.evdev_keys();
+        if let Ok(true) = crate::linux::uinput_shortcut(modifiers, key) {
+            return Ok(());
+        }
+    }
 
     log_println!("[COPY] Calling up_control_keys...");
     crate::utils::up_control_keys(enigo);
     log_println!("[COPY] up_control_keys finished.");
 
     log_println!("[COPY] Simulating Control Press...");
-    enigo.key(Key::Control, Direction::Press).unwrap();
+    let mut keys = HeldKeys::new(enigo);
+    keys.press(Key::Control)?;
     log_println!("[COPY] Control Press finished.");
 
-    #[cfg(target_os = "windows")]
-    {
-        log_println!("[COPY] Simulating C Click...");
-        enigo.key(Key::C, Direction::Click).unwrap();
-        log_println!("[COPY] C Click finished.");
-    }
-    #[cfg(target_os = "linux")]
+    #[cfg(not(target_os = "macos"))]
     {
-        log_println!("[COPY] Simulating Unicode 'c' Click...");
-        enigo.key(Key::Unicode('c'), Direction::Click).unwrap();
-        log_println!("[COPY] Unicode 'c' Click finished.");
+        use crate::quirks::CopyShortcut;
+
+        if shortcut == CopyShortcut::CtrlShiftC {
+            keys.press(Key::Shift)?;
+        }
+        log_println!("[COPY] Simulating {:?}...", shortcut);
+        match shortcut {
+            CopyShortcut::CtrlInsert => keys.click(Key::Insert)?,
+            #[cfg(target_os = "windows")]
+            _ => keys.click(Key::C)?,
+            #[cfg(target_os = "linux")]
+            _ => keys.click(Key::Unicode('c'))?,
+        }
+        log_println!("[COPY] {:?} finished.", shortcut);
     }
     // No macOS specific key needed here as per original code in utils.rs
 
-    log_println!("[COPY] Simulating Control Release...");
-    enigo.key(Key::Control, Direction::Release).unwrap();
-    log_println!("[COPY] Control Release finished.");
-
-    log_println!("[COPY] Releasing COPY_PASTE_LOCKER...");
-    // _guard goes out of scope here, lock released automatically
-} 
+    log_println!("[COPY] Simulating modifier release...");
+    keys.release()?;
+    log_println!("[COPY] Modifier release finished.");
+    Ok(())
+}
 
+/// Copies the selection into an emptied clipboard and reads it. With
+/// `restore_clipboard` off, the previous contents are neither saved nor put
+/// back, which saves two clipboard round trips.
 pub(crate) fn get_selected_text_by_clipboard(
     enigo: &mut Enigo,
-    cancel_select: bool,
+    restore_clipboard: bool,
 ) -> Result<String, Box<dyn std::error::Error>> {
-    use arboard::Clipboard;
-
-    let old_clipboard = (Clipboard::new()?.get_text(), Clipboard::new()?.get_image());
+    COPY_ANSWERED.with(|answered| answered.set(None));
+    let _sequence = crate::sequence::begin()?;
+    // Fail with a clear error up front rather than copying into the void.
+    #[cfg(target_os = "linux")]
+    crate::linux::resolve_input_backend()?;
 
-    let mut write_clipboard = Clipboard::new()?;
+    // An earlier capture's delayed restore still holds the user's clipboard,
+    // and runs even when this one doesn't restore.
+    let mut clipboard = ClipboardGuard::save(open_clipboard()?, restore_clipboard);
 
     let not_selected_placeholder = "";
 
-    write_clipboard.set_text(not_selected_placeholder)?;
-
-    thread::sleep(Duration::from_millis(50));
+    write_temporary_text(clipboard.clipboard(), not_selected_placeholder)?;
+    crate::side_effects::clipboard_modified();
 
-    copy(enigo);
+    thread::sleep(crate::calibrate::settle_delay());
 
-    if cancel_select {
-        crate::utils::right_arrow_click(enigo, 1);
+    let focus_before = focus_snapshot();
+    let count_before = crate::suppress::change_count();
+    // Started after the placeholder write has settled, so only the copy wakes it.
+    #[cfg(all(target_os = "windows", feature = "winrt-clipboard"))]
+    let change_waiter = crate::winrt_clipboard::ChangeWaiter::new().ok();
+    #[cfg(target_os = "linux")]
+    let owner_waiter = crate::xfixes::OwnerWaiter::clipboard().ok();
+    copy(enigo)?;
+
+    let copy_timeout = crate::quirks::copy_wait();
+    #[cfg(all(target_os = "windows", feature = "winrt-clipboard"))]
+    match change_waiter {
+        Some(waiter) => {
+            waiter.wait(copy_timeout);
+        }
+        None => thread::sleep(copy_timeout),
+    }
+    // The app has answered once it owns CLIPBOARD; the text itself is
+    // converted when read below.
+    #[cfg(target_os = "linux")]
+    match owner_waiter {
+        Some(waiter) => {
+            waiter.wait(copy_timeout);
+        }
+        None => thread::sleep(copy_timeout),
+    }
+    #[cfg(not(any(all(target_os = "windows", feature = "winrt-clipboard"), target_os = "linux")))]
+    thread::sleep(copy_timeout);
+
+    let new_text = read_clipboard_text();
+    let answered = count_before.zip(crate::suppress::change_count()).map(|(before, after)| before != after);
+    COPY_ANSWERED.with(|cell| cell.set(answered));
+    let focus_check = ensure_focus_unchanged(focus_before.as_ref());
+
+    let restore_delay = clipboard_restore_delay();
+    if restore_delay.is_zero() {
+        clipboard.restore()?;
+    } else {
+        clipboard.restore_after(restore_delay, new_text.as_ref().ok().cloned());
     }
 
-    thread::sleep(Duration::from_millis(250));
-
-    let new_text = Clipboard::new()?.get_text();
+    // Only after restoring the clipboard: the copied text belongs to another window.
+    focus_check?;
 
-    match old_clipboard {
-        (Ok(old_text), _) => {
-            // Old Content is Text
-            write_clipboard.set_text(old_text.clone())?;
-            if let Ok(new) = new_text {
-                if new.trim() == not_selected_placeholder.trim() {
-                    Ok(String::new())
-                } else {
-                    Ok(new)
-                }
-            } else {
-                Ok(String::new())
-            }
-        }
-        (_, Ok(image)) => {
-            // Old Content is Image
-            write_clipboard.set_image(image)?;
-            if let Ok(new) = new_text {
-                if new.trim() == not_selected_placeholder.trim() {
-                    Ok(String::new())
-                } else {
-                    Ok(new)
-                }
-            } else {
-                Ok(String::new())
-            }
-        }
+    match new_text {
+        Ok(new) if new.trim() != not_selected_placeholder.trim() => Ok(new),
         _ => {
-            // Old Content is Empty
-            write_clipboard.clear()?;
-            if let Ok(new) = new_text {
-                if new.trim() == not_selected_placeholder.trim() {
-                    Ok(String::new())
-                } else {
-                    Ok(new)
-                }
-            } else {
-                Ok(String::new())
-            }
+            // Nothing was copied because the screen locked meanwhile.
+            crate::session::ensure_active()?;
+            Ok(String::new())
         }
     }
 }
 
+#[cfg(target_os = "macos")]
+use crate::macos::SavedSelection;
+#[cfg(target_os = "windows")]
+use crate::windows::SavedSelection;
+
+/// Linux has no accessibility backend yet, so Select All is undone by the
+/// keyboard heuristics alone.
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+struct SavedSelection;
+
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+impl SavedSelection {
+    fn save() -> Option<Self> {
+        None
+    }
+
+    fn restore(&self) -> bool {
+        false
+    }
+}
+
+/// Context from the element under the mouse pointer or one of its nearest
+/// ancestors, found by hit testing: the first whose text holds the selection.
+pub(crate) fn context_under_pointer(selected_text: &str, size: &crate::ContextSize) -> Option<String> {
+    #[cfg(target_os = "windows")]
+    let texts = crate::windows::texts_under_pointer();
+    #[cfg(target_os = "macos")]
+    let texts = crate::macos::texts_under_pointer();
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    let texts: Vec<String> = Vec::new();
+    let context = texts.iter().find_map(|text| crate::context::around(text, selected_text, size))?;
+    crate::context::set_confidence(crate::ContextConfidence::Medium);
+    Some(context)
+}
+
 pub(crate) fn get_context_via_select_all(
     enigo: &mut Enigo,
     selected_text: &str,
+    options: &crate::GetTextOptions,
 ) -> Result<Option<String>, Box<dyn std::error::Error>> {
-    use arboard::Clipboard;
-    use std::time::{Duration, Instant};
-    
+    let size = options.context_size.as_ref().unwrap_or(&crate::context::DEFAULT_SIZE);
+
     log_println!("[SELECT_ALL] Starting get_context_via_select_all...");
-    
+
     if selected_text.is_empty() {
         // Cannot find context if the original selection was empty
         log_println!("[SELECT_ALL] Selected text is empty, returning None.");
         return Ok(None);
     }
+    if crate::quirks::for_focused_app().context == crate::ContextSupport::Pointer {
+        crate::env_overrides::gst_log!("Reading the element under the pointer instead of Select All");
+        return Ok(context_under_pointer(selected_text, size));
+    }
+    let Some(full_text) = get_text_via_select_all(enigo, options)? else {
+        return Ok(None);
+    };
+
+    log_println!("[SELECT_ALL] Processing full text ({} chars)...", full_text.len());
+    if let Some(context) = crate::context::around(&full_text, selected_text, size) {
+        log_println!("[SELECT_ALL] Context extracted successfully ({} chars).", context.len());
+        crate::context::set_confidence(crate::ContextConfidence::Medium);
+        Ok(Some(context))
+    } else {
+        // Selected text not found in the full text copied via Ctrl+A
+        log_println!("[SELECT_ALL] Selected text not found in full text.");
+        Err(Box::new(GetTextError::NotInContext))
+    }
+}
+
+/// Everything in the focused control, copied with Select All + Copy, after
+/// which the clipboard and (where AX/UIA can) the selection are put back.
+/// `None` where Select All is skipped: apps whose quirks rule it out, and on
+/// Windows editable controls and documents over `max_document_chars`.
+pub(crate) fn get_text_via_select_all(
+    enigo: &mut Enigo,
+    options: &crate::GetTextOptions,
+) -> Result<Option<String>, Box<dyn std::error::Error>> {
+    use std::time::{Duration, Instant};
 
-    let _guard = COPY_PASTE_LOCKER.lock();
-    log_println!("[SELECT_ALL] Acquired COPY_PASTE_LOCKER.");
+    let timeout = options.select_all_timeout;
+
+    if crate::quirks::for_focused_app().context != crate::ContextSupport::Full {
+        crate::env_overrides::gst_log!("Select All doesn't give this app's context");
+        return Ok(None);
+    }
+    if crate::env_overrides::select_all_disabled() {
+        crate::env_overrides::gst_log!("Select All disabled by GST_DISABLE_SELECT_ALL");
+        return Ok(None);
+    }
+    #[cfg(target_os = "windows")]
+    {
+        if !options.allow_select_all_in_editable && crate::windows::focused_is_editable() == Some(true) {
+            crate::env_overrides::gst_log!("Focused control is editable, not risking Select All");
+            return Ok(None);
+        }
+        // Copying a whole book would stall the target app and the clipboard.
+        if crate::windows::document_exceeds(options.max_document_chars) == Some(true) {
+            crate::env_overrides::gst_log!("Document is longer than {} chars, skipping Select All", options.max_document_chars);
+            return Ok(None);
+        }
+    }
+
+    let _sequence = crate::sequence::begin()?;
 
     // 添加总体超时
     let start_time = Instant::now();
     
     // --- Save original clipboard ---  
     log_println!("[SELECT_ALL] Getting original clipboard content...");
-    let mut clipboard = Clipboard::new().map_err(|e| GetTextError::Other(e.to_string()))?;
-    let old_clipboard_text = clipboard.get_text();
-    let old_clipboard_image = clipboard.get_image(); 
+    let clipboard = ClipboardGuard::save(open_clipboard()?, true);
     log_println!("[SELECT_ALL] Original clipboard content retrieved.");
+    let focus_before = focus_snapshot();
+    // Remember the exact selection so it can be put back after Select All.
+    let saved_selection = options.restore_selection.then(SavedSelection::save).flatten();
 
     // --- Perform Select All + Copy --- 
     log_println!("[SELECT_ALL] Releasing modifier keys...");
@@ -179,139 +847,107 @@ pub(crate) fn get_context_via_select_all(
     
     thread::sleep(Duration::from_millis(50)); 
     
-    if start_time.elapsed().as_millis() > CLIPBOARD_OPERATION_TIMEOUT_MS as u128 {
+    if start_time.elapsed() > timeout {
         log_println!("[SELECT_ALL] Timeout before Select All. Abort.");
-        return Err(Box::new(GetTextError::Other("Operation timed out".to_string())));
+        return Err(Box::new(GetTextError::Timeout));
     }
 
     // Simulate Ctrl+A (or Cmd+A on macOS)
     log_println!("[SELECT_ALL] Simulating Select All...");
-    #[cfg(target_os = "macos")]
-    enigo.key(Key::Meta, Direction::Press).unwrap();
-    #[cfg(not(target_os = "macos"))]
-    enigo.key(Key::Command, Direction::Press).unwrap();
-
-    #[cfg(target_os = "windows")]
-    enigo.key(Key::A, Direction::Click).unwrap();
+    crate::side_effects::keystrokes_sent();
     #[cfg(target_os = "linux")]
-    enigo.key(Key::Unicode('a'), Direction::Click).unwrap();
-    #[cfg(target_os = "macos")]
-    enigo.key(Key::Unicode('a'), Direction::Click).unwrap();
-
-    #[cfg(target_os = "macos")]
-    enigo.key(Key::Meta, Direction::Release).unwrap();
-    #[cfg(not(target_os = "macos"))]
-    enigo.key(Key::Control, Direction::Release).unwrap();
+    let sent = crate::linux::uinput_shortcut(&[crate::uinput::KEY_LEFTCTRL], crate::uinput::KEY_A)?;
+    #[cfg(not(target_os = "linux"))]
+    let sent = false;
+    if !sent {
+        let mut keys = HeldKeys::new(enigo);
+        #[cfg(target_os = "macos")]
+        keys.press(Key::Meta)?;
+        #[cfg(not(target_os = "macos"))]
+        keys.press(Key::Control)?;
+
+        #[cfg(target_os = "windows")]
+        keys.click(Key::A)?;
+        #[cfg(not(target_os = "windows"))]
+        keys.click(Key::Unicode('a'))?;
+
+        keys.release()?;
+    }
     
     thread::sleep(Duration::from_millis(50)); 
     
-    if start_time.elapsed().as_millis() > CLIPBOARD_OPERATION_TIMEOUT_MS as u128 {
+    if start_time.elapsed() > timeout {
         log_println!("[SELECT_ALL] Timeout before Copy. Abort.");
-        return Err(Box::new(GetTextError::Other("Operation timed out".to_string())));
+        return Err(Box::new(GetTextError::Timeout));
     }
 
     log_println!("[SELECT_ALL] Simulating Copy...");
-    copy(enigo); // Simulate Ctrl+C (or Cmd+C)
+    copy(enigo)?; // Simulate Ctrl+C (or Cmd+C)
 
     log_println!("[SELECT_ALL] Copy simulation finished.");
 
     thread::sleep(Duration::from_millis(100)); // Wait for clipboard update
 
-    // --- 取消全文选中状态 ---
-    log_println!("[SELECT_ALL] 尝试取消全文选中状态...");
+    // --- Restore the original selection, or at least cancel Select All ---
+    if saved_selection.as_ref().is_some_and(SavedSelection::restore) {
+        crate::env_overrides::gst_log!("Original selection restored after Select All");
+    } else {
+        // --- 取消全文选中状态 ---
+        log_println!("[SELECT_ALL] 尝试取消全文选中状态...");
+        crate::side_effects::selection_changed();
     
-    // 方法1: 先尝试ESC键，这在许多应用中都可以取消选择
-    thread::sleep(Duration::from_millis(50));
-    log_println!("[SELECT_ALL] 方法1：尝试使用ESC键取消选择");
-    enigo.key(Key::Escape, Direction::Click).unwrap();
-    thread::sleep(Duration::from_millis(100));
-    
-    // 方法2: 尝试按左箭头键
-    log_println!("[SELECT_ALL] 方法2：尝试使用左箭头键取消选择");
-    crate::utils::left_arrow_click(enigo, 1);
-    thread::sleep(Duration::from_millis(100));
+        // 方法1: 先尝试ESC键，这在许多应用中都可以取消选择
+        thread::sleep(Duration::from_millis(50));
+        log_println!("[SELECT_ALL] 方法1：尝试使用ESC键取消选择");
+        if let Err(e) = deselect_key(enigo, sent, Key::Escape) {
+            crate::env_overrides::gst_log!("Escape after Select All failed: {}", e);
+        }
+        thread::sleep(Duration::from_millis(100));
     
-    // 方法3: 尝试按右箭头键
-    log_println!("[SELECT_ALL] 方法3：尝试使用右箭头键取消选择");
-    crate::utils::right_arrow_click(enigo, 1);
-    thread::sleep(Duration::from_millis(100));
+        // 方法2: 尝试按左箭头键
+        log_println!("[SELECT_ALL] 方法2：尝试使用左箭头键取消选择");
+        if let Err(e) = deselect_key(enigo, sent, Key::LeftArrow) {
+            crate::env_overrides::gst_log!("Left arrow after Select All failed: {}", e);
+        }
+        thread::sleep(Duration::from_millis(100));
     
-    // 方法4: 尝试单击以取消选择（这在某些应用中有效）
-    log_println!("[SELECT_ALL] 方法4：尝试使用单击操作取消选择");
-    enigo.key(Key::Control, Direction::Release).unwrap(); // 确保没有修饰键被按下
-    enigo.key(Key::Shift, Direction::Release).unwrap();
-    enigo.key(Key::Alt, Direction::Release).unwrap();
-    thread::sleep(Duration::from_millis(50));
-    // 注意：实际点击操作可能需要鼠标位置信息，这里只是确保释放了所有修饰键
+        // 方法3: 尝试按右箭头键
+        log_println!("[SELECT_ALL] 方法3：尝试使用右箭头键取消选择");
+        if let Err(e) = deselect_key(enigo, sent, Key::RightArrow) {
+            crate::env_overrides::gst_log!("Right arrow after Select All failed: {}", e);
+        }
+        thread::sleep(Duration::from_millis(100));
     
-    log_println!("[SELECT_ALL] 完成尝试取消全文选中");
+        // 方法4: 尝试单击以取消选择（这在某些应用中有效）
+        log_println!("[SELECT_ALL] 方法4：尝试使用单击操作取消选择");
+        up_control_keys(enigo); // 确保没有修饰键被按下
+        thread::sleep(Duration::from_millis(50));
+        // 注意：实际点击操作可能需要鼠标位置信息，这里只是确保释放了所有修饰键
+        log_println!("[SELECT_ALL] 完成尝试取消全文选中");
+    }
 
     log_println!("[SELECT_ALL] Sleep finished, attempting to get clipboard content...");
     
 
-    if start_time.elapsed().as_millis() > CLIPBOARD_OPERATION_TIMEOUT_MS as u128 {
+    if start_time.elapsed() > timeout {
         log_println!("[SELECT_ALL] Timeout before getting clipboard content. Abort.");
-        return Err(Box::new(GetTextError::Other("Operation timed out".to_string())));
+        return Err(Box::new(GetTextError::Timeout));
     }
 
     // --- Get Full Text ---  
     log_println!("[SELECT_ALL] Getting clipboard content after Select All + Copy...");
-    let full_text_result = Clipboard::new()
-        .map_err(|e| GetTextError::Other(e.to_string()))?
-        .get_text();
+    let full_text_result = read_clipboard_text();
     log_println!("[SELECT_ALL] Clipboard content retrieved: {}", full_text_result.is_ok());
+    let focus_check = ensure_focus_unchanged(focus_before.as_ref());
 
     // --- Restore original clipboard (important!) ---
     log_println!("[SELECT_ALL] Restoring original clipboard...");
-    match (old_clipboard_text, old_clipboard_image) {
-        (Ok(text), _) => clipboard
-            .set_text(text)
-            .map_err(|e| GetTextError::Other(e.to_string()))?,
-        (_, Ok(image)) => clipboard
-            .set_image(image)
-            .map_err(|e| GetTextError::Other(e.to_string()))?,
-        _ => clipboard
-            .clear()
-            .map_err(|e| GetTextError::Other(e.to_string()))?,
-    }
+    clipboard.restore()?;
     log_println!("[SELECT_ALL] Original clipboard restored.");
+    focus_check?;
     
-    // --- Process Full Text ---  
     match full_text_result {
-        Ok(full_text) => {
-            log_println!("[SELECT_ALL] Processing full text ({} chars)...", full_text.len());
-            if let Some(start_pos) = full_text.find(selected_text) {
-                log_println!("[SELECT_ALL] Selected text found at position {}", start_pos);
-                let end_pos = start_pos + selected_text.len();
-                let context_start = start_pos.saturating_sub(CONTEXT_CHARS_BEFORE);
-                let context_end = (end_pos + CONTEXT_CHARS_AFTER).min(full_text.len());
-                
-                log_println!("[SELECT_ALL] Extracting context from {} to {}", context_start, context_end);
-                // Ensure we are extracting valid UTF-8 boundaries
-                let mut valid_start = context_start;
-                while !full_text.is_char_boundary(valid_start) && valid_start < full_text.len() {
-                    valid_start += 1;
-                }
-                
-                let mut valid_end = context_end;
-                while !full_text.is_char_boundary(valid_end) && valid_end > valid_start {
-                    valid_end -= 1;
-                }
-
-                if valid_start < valid_end {
-                    let context = full_text[valid_start..valid_end].to_string();
-                    log_println!("[SELECT_ALL] Context extracted successfully ({} chars).", context.len());
-                    Ok(Some(context))
-                } else {
-                    log_println!("[SELECT_ALL] Invalid context boundaries. Returning full text.");
-                     Ok(Some(full_text)) // Fallback to full text if boundaries are weird
-                }
-            } else {
-                // Selected text not found in the full text copied via Ctrl+A
-                log_println!("[SELECT_ALL] Selected text not found in full text.");
-                Err(Box::new(GetTextError::NotInContext))
-            }
-        }
+        Ok(full_text) => Ok(Some(full_text)),
         Err(e) => {
             // Failed to get text after Select All + Copy
             log_println!("[SELECT_ALL] Failed to get text from clipboard: {}", e);
@@ -0,0 +1,88 @@
+//! Polls the selection without simulating input, for the change events of
+//! the `server` and `daemon` features and for [`crate::wait_for_selection`].
+//! Reads go through AX, UI Automation or PRIMARY on Linux, so selections only
+//! a copy can read are missed.
+
+use std::thread;
+use std::time::{Duration, Instant};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(300);
+
+fn peek_selection() -> Option<String> {
+    #[cfg(target_os = "macos")]
+    return crate::macos::peek_selection();
+    #[cfg(target_os = "windows")]
+    return crate::windows::peek_selection();
+    #[cfg(target_os = "linux")]
+    return crate::linux::get_primary_selection().ok();
+    #[allow(unreachable_code)]
+    None
+}
+
+/// Sleeps until the next poll. On X11 a new PRIMARY owner wakes it early,
+/// so selections are reported as soon as they are made.
+struct Ticker {
+    #[cfg(target_os = "linux")]
+    owner_waiter: Option<crate::xfixes::OwnerWaiter>,
+}
+
+impl Ticker {
+    fn new() -> Self {
+        Ticker {
+            #[cfg(target_os = "linux")]
+            owner_waiter: crate::xfixes::OwnerWaiter::primary().ok(),
+        }
+    }
+
+    fn wait(&self, interval: Duration) {
+        #[cfg(target_os = "linux")]
+        if let Some(waiter) = &self.owner_waiter {
+            waiter.wait(interval);
+            return;
+        }
+        thread::sleep(interval);
+    }
+}
+
+/// Calls `on_change` with every new non-empty selection, forever. Polling
+/// pauses while `wanted` returns false, and the first selection seen after a
+/// pause is reported even if it didn't change.
+#[cfg(any(feature = "server", feature = "daemon"))]
+pub(crate) fn run(wanted: impl Fn() -> bool, mut on_change: impl FnMut(&str)) {
+    let ticker = Ticker::new();
+    let mut last: Option<String> = None;
+    loop {
+        ticker.wait(POLL_INTERVAL);
+        if !wanted() {
+            last = None;
+            continue;
+        }
+        let current = peek_selection().filter(|text| !text.is_empty());
+        if current == last {
+            continue;
+        }
+        if let Some(text) = &current {
+            on_change(text);
+        }
+        last = current;
+    }
+}
+
+/// The first non-empty selection that differs from the one present when
+/// called, or `None` once `timeout` has elapsed.
+pub(crate) fn wait_for_change(timeout: Duration) -> Option<String> {
+    let started = Instant::now();
+    let ticker = Ticker::new();
+    let initial = peek_selection().filter(|text| !text.is_empty());
+    loop {
+        let remaining = timeout.saturating_sub(started.elapsed());
+        if remaining.is_zero() {
+            return None;
+        }
+        ticker.wait(remaining.min(POLL_INTERVAL));
+        let current = peek_selection().filter(|text| !text.is_empty());
+        if current.is_some() && current != initial {
+            return current;
+        }
+    }
+}
@@ -0,0 +1,225 @@
+//! Direct Win32 clipboard access (`native-clipboard` feature).
+//!
+//! Avoids arboard's per-call allocations, reads formats arboard doesn't expose
+//! (`CF_HDROP` file lists, `HTML Format`), and marks the crate's temporary
+//! writes so they stay out of clipboard history and cloud sync.
+
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::thread;
+use std::time::Duration;
+
+use windows_sys::Win32::Foundation::{GlobalFree, HANDLE};
+use windows_sys::Win32::System::DataExchange::{
+    CloseClipboard, EmptyClipboard, GetClipboardData, IsClipboardFormatAvailable, OpenClipboard,
+    RegisterClipboardFormatW, SetClipboardData,
+};
+use windows_sys::Win32::System::Memory::{GlobalAlloc, GlobalLock, GlobalSize, GlobalUnlock, GMEM_MOVEABLE};
+use windows_sys::Win32::UI::Shell::DragQueryFileW;
+
+use crate::{ClipboardFormat, GetTextError};
+
+const CF_BITMAP: u32 = 2;
+const CF_DIB: u32 = 8;
+const CF_UNICODETEXT: u32 = 13;
+const CF_HDROP: u32 = 15;
+const CF_DIBV5: u32 = 17;
+
+const OPEN_ATTEMPTS: u32 = 5;
+/// Bytes of UTF-8 buffered before each write in `write_text`.
+const STREAM_CHUNK: usize = 64 * 1024;
+const OPEN_BASE_DELAY_MS: u64 = 10;
+
+fn register_format(name: &str) -> u32 {
+    let wide: Vec<u16> = name.encode_utf16().chain(std::iter::once(0)).collect();
+    unsafe { RegisterClipboardFormatW(wide.as_ptr()) }
+}
+
+/// The clipboard, opened for this thread until dropped.
+pub(crate) struct OpenedClipboard(());
+
+impl OpenedClipboard {
+    /// Opens the clipboard, backing off while another process holds it.
+    pub(crate) fn open() -> Result<Self, GetTextError> {
+        for attempt in 0..OPEN_ATTEMPTS {
+            if unsafe { OpenClipboard(0 as _) } != 0 {
+                return Ok(OpenedClipboard(()));
+            }
+            thread::sleep(Duration::from_millis(OPEN_BASE_DELAY_MS << attempt));
+        }
+        Err(GetTextError::ClipboardBusy)
+    }
+
+    fn with_locked<T>(&self, format: u32, read: impl FnOnce(*const u8, usize) -> T) -> Option<T> {
+        unsafe {
+            if IsClipboardFormatAvailable(format) == 0 {
+                return None;
+            }
+            let handle: HANDLE = GetClipboardData(format);
+            if handle as isize == 0 {
+                return None;
+            }
+            let ptr = GlobalLock(handle as _) as *const u8;
+            if ptr.is_null() {
+                return None;
+            }
+            let size = GlobalSize(handle as _);
+            let result = read(ptr, size);
+            GlobalUnlock(handle as _);
+            Some(result)
+        }
+    }
+
+    /// Reads `CF_UNICODETEXT`.
+    pub(crate) fn get_text(&self) -> Option<String> {
+        self.with_locked(CF_UNICODETEXT, |ptr, size| {
+            let units = unsafe { std::slice::from_raw_parts(ptr as *const u16, size / 2) };
+            let len = units.iter().position(|&c| c == 0).unwrap_or(units.len());
+            String::from_utf16_lossy(&units[..len])
+        })
+    }
+
+    /// Writes `CF_UNICODETEXT` to `writer` as UTF-8, converting it in bounded
+    /// chunks rather than into one `String`. The clipboard stays open while
+    /// `writer` runs. Returns the number of bytes written.
+    pub(crate) fn write_text(&self, writer: &mut dyn Write) -> Option<io::Result<u64>> {
+        self.with_locked(CF_UNICODETEXT, |ptr, size| {
+            let units = unsafe { std::slice::from_raw_parts(ptr as *const u16, size / 2) };
+            let len = units.iter().position(|&c| c == 0).unwrap_or(units.len());
+            let mut buffer = String::with_capacity(STREAM_CHUNK + 4);
+            let mut written = 0u64;
+            for c in char::decode_utf16(units[..len].iter().copied()) {
+                buffer.push(c.unwrap_or(char::REPLACEMENT_CHARACTER));
+                if buffer.len() >= STREAM_CHUNK {
+                    writer.write_all(buffer.as_bytes())?;
+                    written += buffer.len() as u64;
+                    buffer.clear();
+                }
+            }
+            writer.write_all(buffer.as_bytes())?;
+            Ok(written + buffer.len() as u64)
+        })
+    }
+
+    /// Reads the raw `HTML Format` (CF_HTML) payload, header included. The
+    /// header offsets are in bytes, so this is not decoded here.
+    pub(crate) fn get_html_raw(&self) -> Option<Vec<u8>> {
+        self.with_locked(register_format("HTML Format"), |ptr, size| {
+            let bytes = unsafe { std::slice::from_raw_parts(ptr, size) };
+            let len = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+            bytes[..len].to_vec()
+        })
+    }
+
+    /// Reads `Rich Text Format`, which is 8-bit RTF markup.
+    pub(crate) fn get_rtf(&self) -> Option<String> {
+        self.with_locked(register_format("Rich Text Format"), |ptr, size| {
+            let bytes = unsafe { std::slice::from_raw_parts(ptr, size) };
+            let len = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+            String::from_utf8_lossy(&bytes[..len]).into_owned()
+        })
+    }
+
+    /// Reads the `CF_HDROP` file list, as put there by Explorer's copy.
+    pub(crate) fn get_files(&self) -> Option<Vec<PathBuf>> {
+        unsafe {
+            if IsClipboardFormatAvailable(CF_HDROP) == 0 {
+                return None;
+            }
+            let hdrop = GetClipboardData(CF_HDROP);
+            if hdrop as isize == 0 {
+                return None;
+            }
+            let count = DragQueryFileW(hdrop as _, u32::MAX, std::ptr::null_mut(), 0);
+            let mut files = Vec::with_capacity(count as usize);
+            for i in 0..count {
+                let len = DragQueryFileW(hdrop as _, i, std::ptr::null_mut(), 0);
+                let mut buffer = vec![0u16; len as usize + 1];
+                DragQueryFileW(hdrop as _, i, buffer.as_mut_ptr(), buffer.len() as u32);
+                files.push(PathBuf::from(String::from_utf16_lossy(&buffer[..len as usize])));
+            }
+            Some(files)
+        }
+    }
+
+    /// Reports which of the formats this crate knows about are present.
+    pub(crate) fn available_formats(&self) -> Vec<ClipboardFormat> {
+        let has = |format: u32| unsafe { IsClipboardFormatAvailable(format) } != 0;
+        let mut formats = Vec::new();
+        if has(CF_UNICODETEXT) {
+            formats.push(ClipboardFormat::PlainText);
+        }
+        if has(register_format("HTML Format")) {
+            formats.push(ClipboardFormat::Html);
+        }
+        if has(register_format("Rich Text Format")) {
+            formats.push(ClipboardFormat::Rtf);
+        }
+        if has(CF_DIB) || has(CF_DIBV5) || has(CF_BITMAP) || has(register_format("PNG")) {
+            formats.push(ClipboardFormat::Image);
+        }
+        if has(CF_HDROP) {
+            formats.push(ClipboardFormat::Files);
+        }
+        formats
+    }
+
+    fn set_data(&self, format: u32, bytes: &[u8]) -> Result<(), GetTextError> {
+        unsafe {
+            let handle = GlobalAlloc(GMEM_MOVEABLE, bytes.len());
+            if handle as isize == 0 {
+                return Err(GetTextError::Clipboard("GlobalAlloc failed".to_string()));
+            }
+            let ptr = GlobalLock(handle as _) as *mut u8;
+            if ptr.is_null() {
+                GlobalFree(handle as _);
+                return Err(GetTextError::Clipboard("GlobalLock failed".to_string()));
+            }
+            std::ptr::copy_nonoverlapping(bytes.as_ptr(), ptr, bytes.len());
+            GlobalUnlock(handle as _);
+            // On success the system owns the memory; otherwise it is still ours.
+            if SetClipboardData(format, handle as _) as isize == 0 {
+                GlobalFree(handle as _);
+                return Err(GetTextError::Clipboard("SetClipboardData failed".to_string()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Replaces the clipboard with `text`. With `exclude_from_history`, the
+    /// entry is kept out of Win+V history, cloud clipboard sync and clipboard
+    /// monitors that honour the exclusion format.
+    pub(crate) fn set_text(&self, text: &str, exclude_from_history: bool) -> Result<(), GetTextError> {
+        if unsafe { EmptyClipboard() } == 0 {
+            return Err(GetTextError::Clipboard("EmptyClipboard failed".to_string()));
+        }
+        let wide: Vec<u8> = text
+            .encode_utf16()
+            .chain(std::iter::once(0))
+            .flat_map(|unit| unit.to_le_bytes())
+            .collect();
+        self.set_data(CF_UNICODETEXT, &wide)?;
+        if exclude_from_history {
+            let zero = 0u32.to_le_bytes();
+            self.set_data(register_format("ExcludeClipboardContentFromMonitorProcessing"), &zero)?;
+            self.set_data(register_format("CanIncludeInClipboardHistory"), &zero)?;
+            self.set_data(register_format("CanUploadToCloudClipboard"), &zero)?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn clear(&self) -> Result<(), GetTextError> {
+        if unsafe { EmptyClipboard() } == 0 {
+            return Err(GetTextError::Clipboard("EmptyClipboard failed".to_string()));
+        }
+        Ok(())
+    }
+}
+
+impl Drop for OpenedClipboard {
+    fn drop(&mut self) {
+        unsafe {
+            CloseClipboard();
+        }
+    }
+}
@@ -1,10 +1,12 @@
 use crate::utils::*;
-use crate::GetTextError;
-use enigo::{Enigo, Settings};
-use uiautomation::UIAutomation;
-use uiautomation::patterns::UITextPattern;
-use uiautomation::types::TextUnit;
-use std::{thread, time::Duration};
+use crate::strategy::{Outcome, Strategy};
+use crate::{AttemptLog, FailureReason, GetTextError, GetTextOptions, SelectedTextClient};
+use active_win_pos_rs::get_active_window;
+use parking_lot::Mutex;
+use uiautomation::{UIAutomation, UIElement, UITreeWalker};
+use uiautomation::patterns::{UITextPattern, UIValuePattern};
+use uiautomation::types::{ControlType, TextUnit};
+use std::{thread, time::{Duration, Instant}};
 
 // Use debug_print for logging if enabled, otherwise println
 #[cfg(debug_assertions)]
@@ -12,20 +14,229 @@ use debug_print::debug_println as log_println;
 #[cfg(not(debug_assertions))]
 use println as log_println;
 
-const CONTEXT_CHARS_BEFORE_UIA_FALLBACK: usize = 150;
-const CONTEXT_CHARS_AFTER_UIA_FALLBACK: usize = 150;
+// UIAutomation::new() joins the multithreaded apartment, and UIA client
+// objects are free-threaded, so one instance serves every thread that calls
+// into the crate.
+struct SharedAutomation {
+    automation: UIAutomation,
+    control_view: UITreeWalker,
+}
+unsafe impl Send for SharedAutomation {}
+
+// Created by `init()` or on first use, and kept until `shutdown()`:
+// CoCreateInstance and the walker lookup cost a few milliseconds per call.
+static AUTOMATION: Mutex<Option<SharedAutomation>> = Mutex::new(None);
+
+thread_local! {
+    static COM_INITIALIZED: std::cell::Cell<bool> = const { std::cell::Cell::new(false) };
+}
+
+/// Joins the multithreaded apartment on a thread that hasn't initialized
+/// COM. Fails harmlessly on a thread that is already in an STA (a UI
+/// thread), where the calls are still valid.
+fn ensure_com_initialized() {
+    use windows_sys::Win32::System::Com::{CoInitializeEx, COINIT_MULTITHREADED};
+
+    if !COM_INITIALIZED.with(|initialized| initialized.replace(true)) {
+        unsafe { CoInitializeEx(std::ptr::null(), COINIT_MULTITHREADED as _) };
+    }
+}
+
+fn shared_automation() -> Result<(UIAutomation, UITreeWalker), GetTextError> {
+    ensure_com_initialized();
+    let mut shared = AUTOMATION.lock();
+    if shared.is_none() {
+        let automation = UIAutomation::new().map_err(|e| GetTextError::Uia(e.to_string()))?;
+        let control_view = automation
+            .get_control_view_walker()
+            .map_err(|e| GetTextError::Uia(format!("Failed to get control view walker: {}", e)))?;
+        *shared = Some(SharedAutomation { automation, control_view });
+    }
+    let shared = shared.as_ref().unwrap();
+    Ok((shared.automation.clone(), shared.control_view.clone()))
+}
+
+pub(crate) fn prewarm() -> Result<(), GetTextError> {
+    let automation = automation()?;
+    // Resolving the focused element once loads the UIA client-side proxies.
+    let _ = automation.get_focused_element();
+    Ok(())
+}
+
+pub(crate) fn release() {
+    AUTOMATION.lock().take();
+}
 
-pub fn get_selected_text() -> Result<String, Box<dyn std::error::Error>> {
-    let mut enigo = Enigo::new(&Settings::default()).unwrap();
-    crate::utils::get_selected_text_by_clipboard(&mut enigo, false)
+fn automation() -> Result<UIAutomation, GetTextError> {
+    shared_automation().map(|(automation, _)| automation)
+}
+
+fn control_view_walker() -> Result<UITreeWalker, GetTextError> {
+    shared_automation().map(|(_, walker)| walker)
+}
+
+/// Asks UIA whether the focused element has a selection, without sending input.
+pub(crate) fn probe_selection() -> SelectionProbe {
+    use uiautomation::types::SupportedTextSelection;
+
+    let Ok(automation) = automation() else {
+        return SelectionProbe::Unknown;
+    };
+    let Ok(element) = automation.get_focused_element() else {
+        return SelectionProbe::Unknown;
+    };
+    let Ok(pattern) = element.get_pattern::<UITextPattern>() else {
+        return SelectionProbe::Unknown;
+    };
+    if matches!(pattern.get_supported_text_selection(), Ok(SupportedTextSelection::None) | Err(_)) {
+        return SelectionProbe::Unknown;
+    }
+    match pattern.get_selection() {
+        // Many controls report a degenerate range at the caret when nothing is selected.
+        Ok(ranges) if ranges.iter().all(|r| r.get_text(-1).map(|t| t.is_empty()).unwrap_or(false)) => {
+            SelectionProbe::Absent
+        }
+        Ok(_) => SelectionProbe::Present,
+        Err(_) => SelectionProbe::Unknown,
+    }
+}
+
+pub(crate) fn get_selected_text_with_options(options: &GetTextOptions) -> Result<String, Box<dyn std::error::Error>> {
+    if options.precheck_selection && probe_selection() == SelectionProbe::Absent {
+        return Err(Box::new(GetTextError::NoSelection));
+    }
+
+    // UIA only reads native text controls, so copying stays the default.
+    // PowerShell is for apps that drop the input enigo sends.
+    const DEFAULT_ORDER: [Strategy; 2] = [Strategy::Clipboard, Strategy::PowerShell];
+    let mut order = options.strategies.clone().unwrap_or_else(|| {
+        let focus = crate::utils::focus_snapshot();
+        let app_name = focus.as_ref().map(|focus| focus.app_name()).unwrap_or_default();
+        crate::framework::default_order(app_name, focus.as_ref().map(|focus| focus.process_id()), &DEFAULT_ORDER)
+    });
+    order.retain(|strategy| !options.disabled_strategies.contains(strategy));
+    let mut found_empty = false;
+    let mut copy_answered = false;
+    let mut attempts = AttemptLog::default();
+    for current in order {
+        let started = Instant::now();
+        // The app took the copy and had nothing selected; spawning
+        // powershell.exe to send it again would find nothing either.
+        if current == Strategy::PowerShell && copy_answered {
+            attempts.push(current, FailureReason::Empty, started.elapsed());
+            continue;
+        }
+        let result = match current {
+            Strategy::Clipboard => with_enigo(|enigo| {
+                crate::utils::get_selected_text_by_clipboard(enigo, options.restore_clipboard)
+            })
+            .map_err(|e| Box::new(e) as Box<dyn std::error::Error>)
+            .and_then(|result| result),
+            Strategy::PowerShell => get_selected_text_by_powershell(),
+            Strategy::Uia => {
+                peek_selection().ok_or_else(|| Box::new(GetTextError::NoSelection) as Box<dyn std::error::Error>)
+            }
+            _ => {
+                attempts.push(current, FailureReason::Unsupported, started.elapsed());
+                continue;
+            }
+        };
+        if current == Strategy::Clipboard {
+            copy_answered = crate::utils::last_copy_answered() == Some(true);
+        }
+        // Not recorded: the UIA statistics drive the context path.
+        let succeeded = matches!(&result, Ok(text) if !text.is_empty());
+        crate::metrics::record_strategy(current, succeeded);
+        crate::env_overrides::gst_log!("{:?}: {}", current, crate::env_overrides::describe(&result));
+        let reason = match result {
+            Ok(text) if succeeded => {
+                crate::strategy::note_succeeded(current);
+                return Ok(text);
+            }
+            Ok(_) => {
+                found_empty = true;
+                FailureReason::Empty
+            }
+            Err(e) => FailureReason::from_error(e.as_ref()),
+        };
+        attempts.push(current, reason, started.elapsed());
+    }
+    if found_empty {
+        Ok(String::new())
+    } else {
+        Err(Box::new(GetTextError::AllStrategiesFailed(attempts)))
+    }
+}
+
+pub fn get_selected_text() -> Result<String, GetTextError> {
+    with_enigo(|enigo| crate::utils::get_selected_text_by_clipboard(enigo, true))?.map_err(GetTextError::from)
+}
+
+// Copies with SendKeys, which some RDP clients and security-wrapped apps
+// accept when they drop SendInput, and puts the previous clipboard text back.
+// Other clipboard formats are lost.
+const POWERSHELL_SCRIPT: &str = r#"
+Add-Type -AssemblyName System.Windows.Forms
+[Console]::OutputEncoding = [System.Text.Encoding]::UTF8
+
+$hadText = [System.Windows.Forms.Clipboard]::ContainsText()
+$savedText = [System.Windows.Forms.Clipboard]::GetText()
+[System.Windows.Forms.Clipboard]::Clear()
+
+[System.Windows.Forms.SendKeys]::SendWait('^c')
+
+$text = ''
+for ($i = 0; $i -lt 20; $i++) {
+    Start-Sleep -Milliseconds 50
+    if ([System.Windows.Forms.Clipboard]::ContainsText()) {
+        $text = [System.Windows.Forms.Clipboard]::GetText()
+        break
+    }
+}
+
+if ($hadText) {
+    [System.Windows.Forms.Clipboard]::SetText($savedText)
+} else {
+    [System.Windows.Forms.Clipboard]::Clear()
+}
+[Console]::Out.Write($text)
+"#;
+
+fn get_selected_text_by_powershell() -> Result<String, Box<dyn std::error::Error>> {
+    use std::os::windows::process::CommandExt;
+
+    // Without a console window, so the keys still go to the focused app.
+    const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+
+    let _sequence = crate::sequence::begin()?;
+    let focus_before = focus_snapshot();
+    let output = std::process::Command::new("powershell")
+        .args(["-NoProfile", "-NonInteractive", "-STA", "-Command", POWERSHELL_SCRIPT])
+        .creation_flags(CREATE_NO_WINDOW)
+        .output()?;
+    crate::side_effects::keystrokes_sent();
+    crate::side_effects::clipboard_modified();
+    crate::side_effects::clipboard_restored();
+    // The script has already restored the clipboard at this point.
+    ensure_focus_unchanged(focus_before.as_ref())?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).into_owned().into());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
 }
 
 pub fn get_selected_text_with_context_os() -> Result<(String, Option<String>), GetTextError> {
+    get_selected_text_with_context_for(crate::client::default_client())
+}
+
+pub(crate) fn get_selected_text_with_context_for(
+    client: &SelectedTextClient,
+) -> Result<(String, Option<String>), GetTextError> {
     log_println!("[CTX_OS] Starting get_selected_text_with_context_os...");
     
     // 1. 调用现有的 get_selected_text 函数获取选中文本
     log_println!("[CTX_OS] Calling get_selected_text...");
-    let selected_text = crate::get_selected_text()?;
+    let selected_text = client.capture_text(client.options())?;
     log_println!("[CTX_OS] Initial selected text: {:?}", selected_text);
 
     if selected_text.is_empty() {
@@ -33,50 +244,221 @@ pub fn get_selected_text_with_context_os() -> Result<(String, Option<String>), G
         return Ok((selected_text, None));
     }
 
-    // 初始化 Enigo，用于后续的上下文获取
-    let mut enigo = Enigo::new(&Settings::default()).map_err(|e| GetTextError::Input(e.to_string()))?;
+    // Reuse a context computed for the same selection moments ago (e.g. hotkey + watcher)
+    let app_name = get_active_window().map(|w| w.app_name).unwrap_or_default();
+    let ttl = client.options().context_memo_ttl;
+    let size = client.options().context_size.as_ref().unwrap_or(&crate::context::DEFAULT_SIZE);
+    if let Some(context) = client.context_memo.get(&app_name, &selected_text, ttl) {
+        crate::env_overrides::gst_log!("Reusing the memoized context");
+        return Ok((selected_text, Some(context)));
+    }
 
-    // 2. Try getting context using UIA
-    log_println!("[CTX_OS] Attempting UIA context retrieval...");
-    match get_context_via_uia(&selected_text) {
-        Ok(Some(context)) => {
-            log_println!("[CTX_OS] UIA context retrieval successful.");
-            return Ok((selected_text, Some(context)))
+    // Monaco's hidden textarea holds nothing but the selection while its
+    // screen-reader mode is off, so UIA has no context to offer.
+    let accessibility_mode_off = crate::apps::is_vscode(&app_name)
+        && document_exceeds(selected_text.encode_utf16().count()) == Some(false);
+    let without_context = |selected_text: String| {
+        if accessibility_mode_off {
+            return Err(GetTextError::AccessibilityModeDisabled { app: app_name.clone() });
         }
-        Ok(None) => {
-            log_println!("[CTX_OS] UIA context retrieval ran but found no context.");
+        Ok((selected_text, None))
+    };
+
+    // Acrobat's focused element has no TextPattern; read the pages instead of
+    // walking up from it.
+    if crate::apps::is_acrobat(&app_name) {
+        crate::env_overrides::gst_log!("Reading Acrobat's page text");
+        match get_context_via_acrobat_pages(&selected_text, size, client.options().max_document_chars) {
+            Ok(Some(context)) => {
+                client.context_memo.put(&app_name, &selected_text, &context);
+                return Ok((selected_text, Some(context)));
+            }
+            Ok(None) => crate::env_overrides::gst_log!("The selection isn't in any of Acrobat's page text"),
+            Err(e) => crate::env_overrides::gst_log!("Reading Acrobat's pages failed: {}", e),
         }
-        Err(e) => {
-            log_println!("[CTX_OS] UIA context retrieval failed: {}, falling back...", e);
+    }
+
+    // 2. Try getting context using UIA, unless it keeps failing for this app
+    if accessibility_mode_off {
+        crate::env_overrides::gst_log!("{} has screen-reader mode off, skipping UIA", app_name);
+    } else if client.strategies.is_demoted(&app_name, Strategy::Uia) {
+        client.strategies.record_skipped(&app_name, &[Strategy::Uia]);
+        crate::env_overrides::gst_log!("UIA has been failing for {}, skipping it", app_name);
+    } else if crate::apps::is_jetbrains(&app_name) && focused_exposes_text() == Some(false) {
+        crate::env_overrides::gst_log!("{} has screen-reader support off, skipping UIA", app_name);
+    } else {
+        log_println!("[CTX_OS] Attempting UIA context retrieval...");
+        let started = Instant::now();
+        let uia_result = get_context_via_uia(&selected_text, size);
+        let outcome = if matches!(uia_result, Ok(Some(_))) { Outcome::Succeeded } else { Outcome::Failed };
+        client.strategies.record(&app_name, Strategy::Uia, outcome, started.elapsed());
+        match uia_result {
+            Ok(Some(context)) => {
+                log_println!("[CTX_OS] UIA context retrieval successful.");
+                client.context_memo.put(&app_name, &selected_text, &context);
+                return Ok((selected_text, Some(context)))
+            }
+            Ok(None) => {
+                log_println!("[CTX_OS] UIA context retrieval ran but found no context.");
+            }
+            Err(e) => {
+                log_println!("[CTX_OS] UIA context retrieval failed: {}, falling back...", e);
+            }
         }
     }
 
-    // 3. Fallback: Try getting context using Select All + Copy
+    // 3. Fallback: Try getting context using Select All + Copy, which skips
+    // editable controls and documents too large to copy
+    if !client.options().allows_input() {
+        crate::env_overrides::gst_log!("The call may not simulate input, skipping Select All");
+        return without_context(selected_text);
+    }
     log_println!("[CTX_OS] Attempting fallback context retrieval (Select All + Copy)...");
     // Short delay before fallback simulation to avoid race conditions
     thread::sleep(Duration::from_millis(100));
-    let fallback_result = get_context_via_select_all(&mut enigo, &selected_text);
+    let fallback_result = with_enigo(|enigo| get_context_via_select_all(enigo, &selected_text, client.options()))?;
     log_println!("[CTX_OS] Fallback result: {:?}", fallback_result.is_ok());
 
     match fallback_result {
-        Ok(Some(context)) => Ok((selected_text, Some(context))), // <--- 返回 Some(context)
+        Ok(Some(context)) => {
+            client.context_memo.put(&app_name, &selected_text, &context);
+            Ok((selected_text, Some(context))) // <--- 返回 Some(context)
+        }
         Ok(None) | Err(GetTextError::NotInContext) => { // 如果 fallback 没找到上下文或选中文本不在其中
              log_println!("[CTX_OS] Fallback did not find context or selection was not in it.");
-            Ok((selected_text, None)) // <--- 返回 None context
+            without_context(selected_text)
         }
         Err(e) => { // 其他 fallback 错误
              log_println!("[CTX_OS] Fallback context retrieval failed: {}", e);
              // 即使 fallback 失败，我们仍然成功获取了 selected_text
-             Ok((selected_text, None)) // <--- 返回 None context，因为上下文获取失败
+             without_context(selected_text)
+        }
+    }
+}
+
+/// Copies `text` the way an app answers Ctrl+C: a hidden edit control of
+/// this process holding it is sent `WM_COPY`. For [`crate::calibrate`].
+pub(crate) fn copy_from_hidden_edit(text: &str) -> Result<(), GetTextError> {
+    use windows_sys::Win32::System::LibraryLoader::GetModuleHandleW;
+    use windows_sys::Win32::UI::WindowsAndMessaging::{
+        CreateWindowExW, DestroyWindow, SendMessageW, EM_SETSEL, WM_COPY, WS_POPUP,
+    };
+
+    let class: Vec<u16> = "EDIT".encode_utf16().chain(std::iter::once(0)).collect();
+    let text: Vec<u16> = text.encode_utf16().chain(std::iter::once(0)).collect();
+    unsafe {
+        let module = GetModuleHandleW(std::ptr::null());
+        // Never shown: WS_VISIBLE isn't set.
+        let edit = CreateWindowExW(
+            0,
+            class.as_ptr(),
+            text.as_ptr(),
+            WS_POPUP,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            module,
+            std::ptr::null(),
+        );
+        if edit == 0 {
+            return Err(GetTextError::Os(format!("CreateWindowExW failed: {}", std::io::Error::last_os_error())));
         }
+        SendMessageW(edit, EM_SETSEL, 0, -1);
+        SendMessageW(edit, WM_COPY, 0, 0);
+        DestroyWindow(edit);
     }
+    Ok(())
+}
+
+/// Simulates a copy into an emptied clipboard, hands the result to `read`,
+/// then puts the previous clipboard text back.
+#[cfg(feature = "native-clipboard")]
+fn copy_and_read<T>(read: impl FnOnce(&crate::win_clipboard::OpenedClipboard) -> T) -> Result<T, GetTextError> {
+    copy_and_read_with(true, read)
+}
+
+/// [`copy_and_read`], leaving the copy on the clipboard unless `restore`.
+fn copy_and_read_with<T>(
+    restore: bool,
+    read: impl FnOnce(&crate::win_clipboard::OpenedClipboard) -> T,
+) -> Result<T, GetTextError> {
+    use crate::win_clipboard::OpenedClipboard;
+
+    let _sequence = crate::sequence::begin()?;
+    // Put back however this returns, including when the copy fails.
+    let clipboard = crate::utils::ClipboardGuard::save(crate::utils::open_clipboard()?, restore);
+    // Each handle is dropped at the end of its statement: the target app must be
+    // able to open the clipboard while the copy is simulated.
+    OpenedClipboard::open()?.clear()?;
+    crate::side_effects::clipboard_modified();
+    with_enigo(copy)??;
+    thread::sleep(crate::quirks::copy_wait());
+    let result = read(&OpenedClipboard::open()?);
+    clipboard.restore()?;
+    Ok(result)
+}
+
+/// Copies the current Explorer (or other shell view) selection and returns it
+/// as a file list, restoring the previous clipboard text afterwards.
+///
+/// Returns an empty list if the selection isn't a set of files.
+#[cfg(feature = "native-clipboard")]
+pub fn get_selected_files() -> Result<Vec<std::path::PathBuf>, GetTextError> {
+    copy_and_read(|clipboard| clipboard.get_files().unwrap_or_default())
+}
+
+/// Copies the selection and returns its HTML fragment and source URL, restoring
+/// the previous clipboard text afterwards.
+///
+/// Returns `Ok(None)` if the source app didn't put HTML on the clipboard.
+#[cfg(feature = "native-clipboard")]
+pub fn get_selected_html() -> Result<Option<crate::HtmlFragment>, GetTextError> {
+    let raw = copy_and_read(|clipboard| clipboard.get_html_raw())?;
+    Ok(raw.and_then(|raw| crate::html::parse_cf_html(&raw)))
+}
+
+/// Copies the selection once and returns its plain text, HTML and RTF, as
+/// far as the source app provided them, restoring the previous clipboard
+/// text afterwards.
+#[cfg(feature = "native-clipboard")]
+pub fn get_selection_formats_bundle() -> Result<crate::FormatsBundle, GetTextError> {
+    copy_and_read(|clipboard| crate::FormatsBundle {
+        text: clipboard.get_text(),
+        html: clipboard.get_html_raw().and_then(|raw| crate::html::parse_cf_html(&raw)),
+        rtf: clipboard.get_rtf(),
+    })
 }
 
-fn get_context_via_uia(selected_text_clipboard: &str) -> Result<Option<String>, GetTextError> {
+/// Copies the selection and streams its text to `writer` straight from the
+/// clipboard memory, restoring the previous clipboard text afterwards if
+/// `restore`.
+#[cfg(feature = "native-clipboard")]
+pub(crate) fn stream_selected_text(
+    writer: &mut dyn std::io::Write,
+    restore: bool,
+) -> Result<u64, Box<dyn std::error::Error>> {
+    let written = copy_and_read_with(restore, |clipboard| clipboard.write_text(writer))?;
+    Ok(written.transpose()?.unwrap_or(0))
+}
+
+/// Copies the selection and reports which formats the source app put on the
+/// clipboard, restoring the previous clipboard text afterwards.
+#[cfg(feature = "native-clipboard")]
+pub fn get_available_formats() -> Result<Vec<crate::ClipboardFormat>, GetTextError> {
+    copy_and_read(|clipboard| clipboard.available_formats())
+}
+
+pub(crate) fn get_context_via_uia(
+    selected_text_clipboard: &str,
+    size: &crate::ContextSize,
+) -> Result<Option<String>, GetTextError> {
     log_println!("[UIA] Starting get_context_via_uia...");
-    let automation = UIAutomation::new().map_err(|e| {
+    let automation = automation().map_err(|e| {
         log_println!("[UIA] Failed to create UIAutomation instance: {}", e);
-        GetTextError::Uia(e.to_string())
+        e
     })?;
     
     log_println!("[UIA] Getting focused element...");
@@ -88,9 +470,9 @@ fn get_context_via_uia(selected_text_clipboard: &str) -> Result<Option<String>,
      log_println!("[UIA] Focused element RuntimeId: {:?}", focused_runtime_id);
 
     log_println!("[UIA] Getting control view walker...");
-    let walker = automation.get_control_view_walker().map_err(|e| {
+    let walker = control_view_walker().map_err(|e| {
         log_println!("[UIA] Failed to get control view walker: {}", e);
-        GetTextError::Uia(format!("Failed to get control view walker: {}", e))
+        e
     })?;
 
     log_println!("[UIA] Starting parent traversal loop...");
@@ -117,7 +499,7 @@ fn get_context_via_uia(selected_text_clipboard: &str) -> Result<Option<String>,
         match current_element.get_pattern::<UITextPattern>() {
             Ok(pattern) => {
                 log_println!("[UIA] Loop #{}: TextPattern found! Processing...", loop_count);
-                match process_text_pattern(&pattern, selected_text_clipboard) {
+                match process_text_pattern(&pattern, selected_text_clipboard, size) {
                     Ok(Some(context)) => {
                         log_println!("[UIA] Loop #{}: Context found via TextPattern!", loop_count);
                         return Ok(Some(context)); // Found context
@@ -137,6 +519,10 @@ fn get_context_via_uia(selected_text_clipboard: &str) -> Result<Option<String>,
                  // Pattern not available for this element
             }
         }
+        if let Some(context) = context_from_value(&current_element, selected_text_clipboard, size) {
+            crate::env_overrides::gst_log!("Context found in the value of ancestor #{}", loop_count);
+            return Ok(Some(context));
+        }
         
         // Navigate to parent using the correct method name
          log_println!("[UIA] Loop #{}: Attempting to get parent element...", loop_count);
@@ -153,11 +539,575 @@ fn get_context_via_uia(selected_text_clipboard: &str) -> Result<Option<String>,
         }
     }
 
+    let embedded = embedded_browser_selection(EmbeddedViews::Activate).and_then(|element| element.get_pattern::<UITextPattern>().ok());
+    if let Some(pattern) = embedded {
+        crate::env_overrides::gst_log!("Trying the embedded browser view's TextPattern");
+        return process_text_pattern(&pattern, selected_text_clipboard, size);
+    }
+
     log_println!("[UIA] Parent traversal loop finished. UIA did not find context.");
     Ok(None)
 }
 
-fn process_text_pattern(pattern: &UITextPattern, selected_text_clipboard: &str) -> Result<Option<String>, GetTextError> {
+/// Context from the text of the pages in the focused Acrobat window, which
+/// its UIA tree exposes per page rather than on the focused element. Pages
+/// are read in tree order up to `limit` characters, and joined so that a
+/// selection or context crossing a page break is still found.
+fn get_context_via_acrobat_pages(
+    selected_text: &str,
+    size: &crate::ContextSize,
+    limit: usize,
+) -> Result<Option<String>, GetTextError> {
+    let automation = automation()?;
+    let walker = control_view_walker()?;
+    let desktop = automation
+        .get_root_element()
+        .map_err(|e| GetTextError::Uia(format!("Failed to get root element: {}", e)))?;
+
+    // The top-level window holding the focus.
+    let mut window = focused_element()?;
+    for _ in 0..20 {
+        match walker.get_parent(&window) {
+            Ok(parent) if !automation.compare_elements(&parent, &desktop).unwrap_or(false) => window = parent,
+            _ => break,
+        }
+    }
+
+    let mut pages = String::new();
+    let mut chars = 0;
+    let visited = walk_descendants(&walker, window, |element| {
+        if chars > limit {
+            return Walk::Stop;
+        }
+        // A page's text already covers its children.
+        let text = element
+            .get_pattern::<UITextPattern>()
+            .and_then(|pattern| pattern.get_document_range())
+            .and_then(|range| range.get_text(-1));
+        match text {
+            Ok(text) if !text.is_empty() => {
+                if !pages.is_empty() {
+                    pages.push('\n');
+                }
+                chars += text.chars().count();
+                pages.push_str(&text);
+                Walk::Skip
+            }
+            _ => Walk::Descend,
+        }
+    });
+    crate::env_overrides::gst_log!("Read {} chars of Acrobat page text from {} elements", pages.len(), visited);
+
+    crate::context::set_confidence(crate::ContextConfidence::Medium);
+    if let Some(context) = crate::context::around(&pages, selected_text, size) {
+        return Ok(Some(context));
+    }
+    // Page text often breaks lines where the copied selection has spaces.
+    let normalized = pages.split_whitespace().collect::<Vec<_>>().join(" ");
+    let selected = selected_text.split_whitespace().collect::<Vec<_>>().join(" ");
+    Ok(crate::context::around(&normalized, &selected, size))
+}
+
+/// What [`walk_descendants`] does after visiting an element.
+enum Walk {
+    Descend,
+    /// Skip the element's children.
+    Skip,
+    Stop,
+}
+
+/// Visits `root` and its descendants depth-first in document order, at most
+/// 12 levels down and 5000 elements in total. Returns how many it visited.
+fn walk_descendants(walker: &UITreeWalker, root: UIElement, visit: impl FnMut(&UIElement) -> Walk) -> usize {
+    walk_descendants_up_to(walker, root, 5000, visit)
+}
+
+/// [`walk_descendants`], visiting at most `max_elements` elements.
+fn walk_descendants_up_to(
+    walker: &UITreeWalker,
+    root: UIElement,
+    max_elements: usize,
+    mut visit: impl FnMut(&UIElement) -> Walk,
+) -> usize {
+    const MAX_DEPTH: usize = 12;
+    let mut visited = 0;
+    let mut stack = vec![(root, 0)];
+    while let Some((element, depth)) = stack.pop() {
+        visited += 1;
+        if visited > max_elements {
+            break;
+        }
+        match visit(&element) {
+            Walk::Stop => break,
+            Walk::Skip => continue,
+            Walk::Descend if depth == MAX_DEPTH => continue,
+            Walk::Descend => {}
+        }
+        // Pushed in reverse, so children are popped in document order.
+        let mut children = Vec::new();
+        let mut child = walker.get_first_child(&element).ok();
+        while let Some(current) = child {
+            child = walker.get_next_sibling(&current).ok();
+            children.push((current, depth + 1));
+        }
+        stack.extend(children.into_iter().rev());
+    }
+    visited
+}
+
+pub(crate) fn focused_element() -> Result<UIElement, GetTextError> {
+    let automation = automation()?;
+    automation
+        .get_focused_element()
+        .map_err(|e| GetTextError::Uia(format!("Failed to get focused element: {}", e)))
+}
+
+/// Walks up from the focused element to the first ancestor whose TextPattern
+/// reports a non-empty selection, mirroring the traversal in `get_context_via_uia`.
+pub(crate) fn selection_element() -> Result<Option<UIElement>, GetTextError> {
+    find_selection_element(EmbeddedViews::Activate)
+}
+
+fn find_selection_element(embedded: EmbeddedViews) -> Result<Option<UIElement>, GetTextError> {
+    let automation = automation()?;
+    let walker = control_view_walker()?;
+    let mut current = automation
+        .get_focused_element()
+        .map_err(|e| GetTextError::Uia(format!("Failed to get focused element: {}", e)))?;
+
+    const MAX_LOOP_COUNT: u32 = 20;
+    for _ in 0..MAX_LOOP_COUNT {
+        if has_selection(&current) {
+            return Ok(Some(current));
+        }
+        match walker.get_parent(&current) {
+            Ok(parent) => current = parent,
+            Err(_) => break,
+        }
+    }
+    // WebView2 and CEF hosts often hold the focus themselves, with the page
+    // in a child window the walk up never reaches.
+    Ok(embedded_browser_selection(embedded))
+}
+
+fn has_selection(element: &UIElement) -> bool {
+    element
+        .get_pattern::<UITextPattern>()
+        .and_then(|pattern| pattern.get_selection())
+        .map(|selection| !selection.is_empty())
+        .unwrap_or(false)
+}
+
+/// The window class of Chromium's page content, in Chrome and Edge as in the
+/// WebView2, CEF and Electron views embedded in apps like Teams, Slack and
+/// Spotify.
+const RENDER_WIDGET_CLASS: &str = "Chrome_RenderWidgetHostHWND";
+
+/// A foreground window's Chromium render widgets.
+struct EmbeddedWidgets {
+    foreground: isize,
+    widgets: Vec<isize>,
+    activated: bool,
+}
+
+/// The widgets of the last foreground window looked at: enumerating child
+/// windows on every poll of the selection adds up.
+static EMBEDDED_WIDGETS: Mutex<Option<EmbeddedWidgets>> = Mutex::new(None);
+
+/// How [`embedded_browser_selection`] treats views whose accessibility tree
+/// may not be built yet.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum EmbeddedViews {
+    /// Turn the trees on and wait for them to be built.
+    Activate,
+    /// Only read trees that are already there, for polls that must not block.
+    Existing,
+}
+
+/// The Chromium render widgets inside the foreground window, and whether
+/// their trees were already activated. Cached per foreground window.
+fn embedded_browser_widgets() -> (Vec<isize>, bool) {
+    use windows_sys::Win32::Foundation::{BOOL, HWND, LPARAM};
+    use windows_sys::Win32::UI::WindowsAndMessaging::{EnumChildWindows, GetClassNameW, GetForegroundWindow};
+
+    unsafe extern "system" fn collect(hwnd: HWND, lparam: LPARAM) -> BOOL {
+        let widgets = &mut *(lparam as *mut Vec<HWND>);
+        let mut class = [0u16; 64];
+        let len = GetClassNameW(hwnd, class.as_mut_ptr(), class.len() as i32);
+        if len > 0 && String::from_utf16_lossy(&class[..len as usize]) == RENDER_WIDGET_CLASS {
+            widgets.push(hwnd);
+        }
+        1
+    }
+
+    let foreground = unsafe { GetForegroundWindow() };
+    if foreground == 0 {
+        return (Vec::new(), false);
+    }
+    let mut cached = EMBEDDED_WIDGETS.lock();
+    if let Some(cached) = cached.as_ref().filter(|cached| cached.foreground == foreground) {
+        return (cached.widgets.clone(), cached.activated);
+    }
+    let mut widgets: Vec<HWND> = Vec::new();
+    unsafe {
+        EnumChildWindows(foreground, Some(collect), &mut widgets as *mut Vec<HWND> as LPARAM);
+    }
+    *cached = Some(EmbeddedWidgets {
+        foreground,
+        widgets: widgets.clone(),
+        activated: false,
+    });
+    (widgets, false)
+}
+
+/// Chromium builds its accessibility tree only once an assistive client
+/// asks for it, with the same message screen readers send.
+fn activate_accessibility(widget: isize) {
+    use windows_sys::Win32::UI::WindowsAndMessaging::{
+        SendMessageTimeoutW, OBJID_CLIENT, SMTO_ABORTIFHUNG, WM_GETOBJECT,
+    };
+
+    let mut result = 0;
+    unsafe {
+        SendMessageTimeoutW(widget, WM_GETOBJECT, 0, OBJID_CLIENT as isize, SMTO_ABORTIFHUNG, 200, &mut result);
+    }
+}
+
+/// The element holding the selection inside a WebView2 or CEF view of the
+/// foreground window. With [`EmbeddedViews::Activate`], turns on Chromium's
+/// accessibility tree the first time a window is looked at and reads it
+/// again once built.
+fn embedded_browser_selection(embedded: EmbeddedViews) -> Option<UIElement> {
+    // Bounds the walk of a large page; a selection further in is read
+    // through a copy instead.
+    const MAX_ELEMENTS: usize = 1500;
+    let (widgets, activated) = embedded_browser_widgets();
+    if widgets.is_empty() {
+        return None;
+    }
+    let automation = automation().ok()?;
+    let walker = control_view_walker().ok()?;
+    let find = || {
+        widgets.iter().find_map(|&widget| {
+            let root = automation.element_from_handle(uiautomation::types::Handle::from(widget)).ok()?;
+            let mut found = None;
+            walk_descendants_up_to(&walker, root, MAX_ELEMENTS, |element| {
+                if has_selection(element) {
+                    found = Some(element.clone());
+                    return Walk::Stop;
+                }
+                Walk::Descend
+            });
+            found
+        })
+    };
+    if let Some(element) = find() {
+        return Some(element);
+    }
+    if activated || embedded == EmbeddedViews::Existing {
+        return None;
+    }
+    // The tree is built asynchronously after the first request.
+    crate::env_overrides::gst_log!("Activating accessibility in {} embedded browser views", widgets.len());
+    for &widget in &widgets {
+        activate_accessibility(widget);
+    }
+    if let Some(cached) = EMBEDDED_WIDGETS.lock().as_mut() {
+        cached.activated = true;
+    }
+    thread::sleep(Duration::from_millis(150));
+    find()
+}
+
+/// The selected text as UIA reports it, without sending input.
+pub(crate) fn peek_selection() -> Option<String> {
+    let Some(element) = find_selection_element(EmbeddedViews::Existing).ok().flatten() else {
+        return selected_items_text();
+    };
+    let ranges = element.get_pattern::<UITextPattern>().ok()?.get_selection().ok()?;
+    ranges.first()?.get_text(-1).ok()
+}
+
+/// The text of the items selected in the focused list, tree or grid, one line
+/// per item. Virtualized views (Outlook's message list, Explorer's details
+/// view) leave most items unrealized, so those are found through
+/// ItemContainerPattern and realized before reading them.
+fn selected_items_text() -> Option<String> {
+    use uiautomation::patterns::{UIItemContainerPattern, UISelectionPattern, UIVirtualizedItemPattern};
+    use uiautomation::types::UIProperty;
+    use uiautomation::variants::Variant;
+
+    const MAX_ITEMS: usize = 100;
+    let walker = control_view_walker().ok()?;
+    // The focus is on an item or on the container itself.
+    let mut container = focused_element().ok()?;
+    let mut selection_pattern = None;
+    for _ in 0..3 {
+        if let Ok(pattern) = container.get_pattern::<UISelectionPattern>() {
+            selection_pattern = Some(pattern);
+            break;
+        }
+        container = walker.get_parent(&container).ok()?;
+    }
+    let selection_pattern = selection_pattern?;
+
+    let mut items = selection_pattern.get_selection().unwrap_or_default();
+    if items.is_empty() {
+        let container_pattern = container.get_pattern::<UIItemContainerPattern>().ok()?;
+        let mut previous = None;
+        while items.len() < MAX_ITEMS {
+            let found = container_pattern.find_item_by_property(
+                previous,
+                UIProperty::SelectionItemIsSelected,
+                Variant::from(true),
+            );
+            let Ok(item) = found else {
+                break;
+            };
+            previous = Some(item.clone());
+            items.push(item);
+        }
+    }
+    items.truncate(MAX_ITEMS);
+
+    let lines = items
+        .iter()
+        .filter_map(|item| {
+            if let Ok(pattern) = item.get_pattern::<UIVirtualizedItemPattern>() {
+                let _ = pattern.realize();
+            }
+            item_text(&walker, item)
+        })
+        .collect::<Vec<_>>();
+    if lines.is_empty() {
+        return None;
+    }
+    Some(lines.join("\n"))
+}
+
+/// An item's cells separated by tabs, as in a details view or a message
+/// list, or else its name.
+fn item_text(walker: &UITreeWalker, item: &UIElement) -> Option<String> {
+    let mut cells = Vec::new();
+    let mut child = walker.get_first_child(item).ok();
+    while let Some(current) = child {
+        if let Ok(name) = current.get_name() {
+            if !name.is_empty() {
+                cells.push(name);
+            }
+        }
+        child = walker.get_next_sibling(&current).ok();
+    }
+    if !cells.is_empty() {
+        return Some(cells.join("\t"));
+    }
+    item.get_name().ok().filter(|name| !name.is_empty())
+}
+
+/// The screen rectangle enclosing the UIA selection.
+pub(crate) fn selection_bounds() -> Option<crate::SelectionBounds> {
+    let element = selection_element().ok()??;
+    let ranges = element.get_pattern::<UITextPattern>().ok()?.get_selection().ok()?;
+    let rects = ranges.first()?.get_bounding_rectangles().ok()?;
+    crate::SelectionBounds::enclosing(rects.iter().map(|rect| {
+        (
+            rect.get_left() as f64,
+            rect.get_top() as f64,
+            rect.get_width() as f64,
+            rect.get_height() as f64,
+        )
+    }))
+}
+
+/// The selection's lines and paragraph, from the text of the document range
+/// up to the selection.
+pub(crate) fn selection_position(limit: usize) -> Option<crate::SelectionPosition> {
+    use uiautomation::types::TextPatternRangeEndpoint;
+
+    let element = selection_element().ok()??;
+    let pattern = element.get_pattern::<UITextPattern>().ok()?;
+    let selection = pattern.get_selection().ok()?.into_iter().next()?;
+    let prefix = pattern.get_document_range().ok()?;
+    prefix
+        .move_endpoint_by_range(TextPatternRangeEndpoint::End, &selection, TextPatternRangeEndpoint::Start)
+        .ok()?;
+    let max_length = limit.min(i32::MAX as usize) as i32;
+    let prefix_text = prefix.get_text(max_length).ok()?;
+    if prefix_text.encode_utf16().count() >= limit {
+        return None;
+    }
+    let selected_text = selection.get_text(-1).ok()?;
+    Some(crate::SelectionPosition::in_text(&prefix_text, &selected_text))
+}
+
+/// The selection of the focused text control, saved so it can be put back
+/// exactly after Select All.
+pub(crate) struct SavedSelection(uiautomation::patterns::UITextRange);
+
+impl SavedSelection {
+    pub(crate) fn save() -> Option<Self> {
+        let element = selection_element().ok()??;
+        let pattern = element.get_pattern::<UITextPattern>().ok()?;
+        let range = pattern.get_selection().ok()?.into_iter().next()?;
+        Some(SavedSelection(range))
+    }
+
+    /// Returns false if the provider doesn't support selecting a range.
+    pub(crate) fn restore(&self) -> bool {
+        self.0.select().is_ok()
+    }
+}
+
+/// Whether the focused element exposes its text through UIA. `None` if
+/// nothing is focused.
+pub(crate) fn focused_exposes_text() -> Option<bool> {
+    let element = focused_element().ok()?;
+    Some(element.get_pattern::<UITextPattern>().is_ok() || element.get_pattern::<UIValuePattern>().is_ok())
+}
+
+/// Whether the element at screen point (`x`, `y`) is window chrome (title
+/// bar, scroll bar, button, toolbar) rather than content, so a drag that
+/// starts there moves or scrolls something instead of selecting text.
+/// `None` if UIA can't tell.
+#[cfg(feature = "drag-hook")]
+pub(crate) fn is_chrome_at(x: i32, y: i32) -> Option<bool> {
+    let element = automation().ok()?.element_from_point(uiautomation::types::Point::new(x, y)).ok()?;
+    Some(matches!(
+        element.get_control_type().ok()?,
+        ControlType::TitleBar
+            | ControlType::ScrollBar
+            | ControlType::Thumb
+            | ControlType::Slider
+            | ControlType::Button
+            | ControlType::Image
+            | ControlType::MenuBar
+            | ControlType::ToolBar
+            | ControlType::Tab
+            | ControlType::TabItem
+    ))
+}
+
+/// How far up from the element under the pointer a message bubble's text is
+/// looked for.
+const POINTER_ANCESTORS: usize = 4;
+
+/// The values and names of the element under the mouse pointer and its
+/// nearest ancestors, innermost first. Chat clients expose each message
+/// bubble's text this way (WeChat as the name of a list item) while keeping
+/// it out of the focused element.
+pub(crate) fn texts_under_pointer() -> Vec<String> {
+    use windows_sys::Win32::Foundation::POINT;
+    use windows_sys::Win32::UI::WindowsAndMessaging::GetCursorPos;
+
+    let mut pointer = POINT { x: 0, y: 0 };
+    if unsafe { GetCursorPos(&mut pointer) } == 0 {
+        return Vec::new();
+    }
+    let (Ok(automation), Ok(walker)) = (automation(), control_view_walker()) else {
+        return Vec::new();
+    };
+    let Ok(mut element) = automation.element_from_point(uiautomation::types::Point::new(pointer.x, pointer.y)) else {
+        return Vec::new();
+    };
+    let mut texts = Vec::new();
+    for _ in 0..POINTER_ANCESTORS {
+        texts.extend(element.get_pattern::<UIValuePattern>().and_then(|pattern| pattern.get_value()).ok());
+        texts.extend(element.get_name().ok());
+        let Ok(parent) = walker.get_parent(&element) else {
+            break;
+        };
+        element = parent;
+    }
+    texts.retain(|text| !text.is_empty());
+    texts
+}
+
+/// Whether the focused control accepts typing. `None` if UIA can't tell.
+pub(crate) fn focused_is_editable() -> Option<bool> {
+    let element = focused_element().ok()?;
+    if let Ok(value) = element.get_pattern::<UIValuePattern>() {
+        return value.is_readonly().ok().map(|read_only| !read_only);
+    }
+    match element.get_control_type().ok()? {
+        ControlType::Edit => Some(true),
+        _ => None,
+    }
+}
+
+/// Whether the document holding the selection is longer than `limit`
+/// characters, reading at most `limit + 1` of them. `None` if UIA can't tell.
+pub(crate) fn document_exceeds(limit: usize) -> Option<bool> {
+    let element = selection_element().ok()??;
+    let pattern = element.get_pattern::<UITextPattern>().ok()?;
+    let max_length = limit.saturating_add(1).min(i32::MAX as usize) as i32;
+    let text = pattern.get_document_range().ok()?.get_text(max_length).ok()?;
+    Some(text.encode_utf16().count() > limit)
+}
+
+/// The text of the document holding the focus, at most `limit` characters of
+/// it: the TextPattern document range of the focused element or its nearest
+/// ancestor that has one, else the focused element's value. `None` if UIA
+/// exposes neither.
+pub(crate) fn document_text(limit: usize) -> Option<String> {
+    let walker = control_view_walker().ok()?;
+    let focused = focused_element().ok()?;
+    let max_length = limit.min(i32::MAX as usize) as i32;
+    let mut element = focused.clone();
+    for _ in 0..20 {
+        if let Ok(pattern) = element.get_pattern::<UITextPattern>() {
+            if let Ok(text) = pattern.get_document_range().and_then(|range| range.get_text(max_length)) {
+                return Some(text);
+            }
+        }
+        let Ok(parent) = walker.get_parent(&element) else {
+            break;
+        };
+        element = parent;
+    }
+    let value = focused.get_pattern::<UIValuePattern>().ok()?.get_value().ok()?;
+    Some(value.chars().take(limit).collect())
+}
+
+/// Context from the value of a control without TextPattern: combo boxes,
+/// search boxes and custom controls often expose their text only through
+/// ValuePattern or, as MSAA controls, LegacyIAccessiblePattern.
+fn context_from_value(element: &UIElement, selected_text: &str, size: &crate::ContextSize) -> Option<String> {
+    use uiautomation::patterns::UILegacyIAccessiblePattern;
+
+    let value = element.get_pattern::<UIValuePattern>().ok().and_then(|pattern| pattern.get_value().ok());
+    if let Some(context) = value.and_then(|value| crate::context::around(&value, selected_text, size)) {
+        crate::context::set_confidence(crate::ContextConfidence::Medium);
+        return Some(context);
+    }
+    let legacy = element.get_pattern::<UILegacyIAccessiblePattern>().ok()?;
+    let legacy_value = legacy.get_value().ok();
+    if let Some(context) = legacy_value.and_then(|value| crate::context::around(&value, selected_text, size)) {
+        crate::context::set_confidence(crate::ContextConfidence::Medium);
+        return Some(context);
+    }
+    let context = crate::context::around(&legacy.get_name().ok()?, selected_text, size)?;
+    crate::context::set_confidence(crate::ContextConfidence::Low);
+    Some(context)
+}
+
+/// Where the selection starts in `range`, in characters, found by cutting
+/// `range` off at it. `range` is unusable afterwards.
+fn selection_offset_in(pattern: &UITextPattern, range: &uiautomation::patterns::UITextRange) -> Option<usize> {
+    use uiautomation::types::TextPatternRangeEndpoint;
+
+    // A fresh range: the one `range` was expanded from may have moved with it.
+    let selection = pattern.get_selection().ok()?.into_iter().next()?;
+    range
+        .move_endpoint_by_range(TextPatternRangeEndpoint::End, &selection, TextPatternRangeEndpoint::Start)
+        .ok()?;
+    Some(range.get_text(-1).ok()?.chars().count())
+}
+
+fn process_text_pattern(
+    pattern: &UITextPattern,
+    selected_text_clipboard: &str,
+    size: &crate::ContextSize,
+) -> Result<Option<String>, GetTextError> {
     log_println!("[UIA_PATTERN] Starting process_text_pattern...");
     
     log_println!("[UIA_PATTERN] Getting selection...");
@@ -190,10 +1140,24 @@ fn process_text_pattern(pattern: &UITextPattern, selected_text_clipboard: &str)
         let expanded_range_para = text_range.clone(); // 移除 mut 关键字，因为变量没有被修改
         if expanded_range_para.expand_to_enclosing_unit(TextUnit::Paragraph).is_ok() {
              log_println!("[UIA_PATTERN] Expanded to Paragraph successfully. Getting text...");
+            if let crate::ContextSize::Paragraphs(more) = size {
+                use uiautomation::types::TextPatternRangeEndpoint;
+
+                // Short of paragraphs near the document's ends, the range just stops there.
+                let more = (*more).min(i32::MAX as usize) as i32;
+                let moves = [(TextPatternRangeEndpoint::Start, -more), (TextPatternRangeEndpoint::End, more)];
+                for (endpoint, count) in moves {
+                    let _ = expanded_range_para.move_endpoint_by_unit(endpoint, TextUnit::Paragraph, count);
+                }
+            }
             if let Ok(paragraph_text) = expanded_range_para.get_text(-1) {
                 log_println!("[UIA_PATTERN] Paragraph text: {:?}", paragraph_text);
                 if paragraph_text.contains(&selected_text_uia) {
                      log_println!("[UIA_PATTERN] Context found via Paragraph expansion.");
+                     if let Some(start) = selection_offset_in(pattern, &expanded_range_para) {
+                         crate::context::note_position(&paragraph_text, &selected_text_uia, start);
+                     }
+                     crate::context::set_confidence(crate::ContextConfidence::High);
                      return Ok(Some(paragraph_text));
                 }
             }
@@ -207,30 +1171,19 @@ fn process_text_pattern(pattern: &UITextPattern, selected_text_clipboard: &str)
             log_println!("[UIA_PATTERN] Getting text from document range...");
             if let Ok(full_text) = doc_range.get_text(-1) {
                  log_println!("[UIA_PATTERN] Full document text length: {}", full_text.len());
-                if let Some(start_pos) = full_text.find(&selected_text_uia) {
-                    log_println!("[UIA_PATTERN] Found UIA selection within full text. Extracting context...");
-                    let end_pos = start_pos + selected_text_uia.len();
-                    let context_start = start_pos.saturating_sub(CONTEXT_CHARS_BEFORE_UIA_FALLBACK);
-                    let context_end = (end_pos + CONTEXT_CHARS_AFTER_UIA_FALLBACK).min(full_text.len());
-                    
-                    // Ensure valid UTF-8 boundaries
-                    let mut valid_start = context_start;
-                    while !full_text.is_char_boundary(valid_start) && valid_start < full_text.len() {
-                         valid_start += 1;
-                    }
-                    let mut valid_end = context_end;
-                    while !full_text.is_char_boundary(valid_end) && valid_end > valid_start {
-                        valid_end -= 1;
-                    }
-                    
-                    if valid_start < valid_end {
-                        let context = full_text[valid_start..valid_end].to_string();
-                         log_println!("[UIA_PATTERN] Context found via document range fallback.");
-                         return Ok(Some(context));
-                    } else {
-                        log_println!("[UIA_PATTERN] Failed to get valid context boundaries from full text. Returning full text.");
-                        return Ok(Some(full_text)); // Return full text if boundaries fail
-                    }
+                let ranged = selection_offset_in(pattern, &doc_range).and_then(|start| {
+                    let end = start + selected_text_uia.chars().count();
+                    crate::context::around_range(&full_text, &selected_text_uia, start, end, size)
+                });
+                if let Some(context) = ranged {
+                    crate::env_overrides::gst_log!("Context found at the selection's position in the document");
+                    crate::context::set_confidence(crate::ContextConfidence::High);
+                    return Ok(Some(context));
+                }
+                if let Some(context) = crate::context::around(&full_text, &selected_text_uia, size) {
+                    log_println!("[UIA_PATTERN] Context found via document range fallback.");
+                    crate::context::set_confidence(crate::ContextConfidence::Medium);
+                    return Ok(Some(context));
                 } else {
                      log_println!("[UIA_PATTERN] UIA selection not found within full document text.");
                 }
@@ -242,6 +1195,7 @@ fn process_text_pattern(pattern: &UITextPattern, selected_text_clipboard: &str)
         }
         
         log_println!("[UIA_PATTERN] All expansion/fallback failed. Returning UIA selection as context.");
+        crate::context::set_confidence(crate::ContextConfidence::Low);
         return Ok(Some(selected_text_uia)); // Return UIA selection as context
     } else {
          log_println!("[UIA_PATTERN] UIA selection did not match clipboard text.");
@@ -0,0 +1,63 @@
+//! Clipboard access through WinRT's `Windows.ApplicationModel.DataTransfer`
+//! (`winrt-clipboard` feature).
+//!
+//! Waits for `ContentChanged` after a simulated copy instead of sleeping a
+//! fixed time, and writes the crate's temporary content with history and
+//! roaming turned off.
+
+use std::sync::mpsc;
+use std::time::Duration;
+
+use ::windows::core::HSTRING;
+use ::windows::ApplicationModel::DataTransfer::{Clipboard, ClipboardContentOptions, DataPackage};
+use ::windows::Foundation::EventHandler;
+
+use crate::GetTextError;
+
+fn winrt_error(error: ::windows::core::Error) -> GetTextError {
+    GetTextError::Clipboard(error.message().to_string())
+}
+
+/// Replaces the clipboard with `text`, kept out of clipboard history and
+/// cloud sync.
+pub(crate) fn set_text(text: &str) -> Result<(), GetTextError> {
+    let package = DataPackage::new().map_err(winrt_error)?;
+    package.SetText(&HSTRING::from(text)).map_err(winrt_error)?;
+    let options = ClipboardContentOptions::new().map_err(winrt_error)?;
+    options.SetIsAllowedInHistory(false).map_err(winrt_error)?;
+    options.SetIsRoamable(false).map_err(winrt_error)?;
+    if !Clipboard::SetContentWithOptions(&package, &options).map_err(winrt_error)? {
+        return Err(GetTextError::ClipboardBusy);
+    }
+    // Renders the package now, so the text outlives this process.
+    Clipboard::Flush().map_err(winrt_error)
+}
+
+/// Signals clipboard changes from its creation until it is dropped.
+pub(crate) struct ChangeWaiter {
+    token: i64,
+    changes: mpsc::Receiver<()>,
+}
+
+impl ChangeWaiter {
+    pub(crate) fn new() -> Result<Self, GetTextError> {
+        let (sender, changes) = mpsc::channel();
+        let handler = EventHandler::new(move |_, _| {
+            let _ = sender.send(());
+            Ok(())
+        });
+        let token = Clipboard::ContentChanged(&handler).map_err(winrt_error)?;
+        Ok(ChangeWaiter { token, changes })
+    }
+
+    /// Whether the clipboard changed within `timeout`.
+    pub(crate) fn wait(&self, timeout: Duration) -> bool {
+        self.changes.recv_timeout(timeout).is_ok()
+    }
+}
+
+impl Drop for ChangeWaiter {
+    fn drop(&mut self) {
+        let _ = Clipboard::RemoveContentChanged(self.token);
+    }
+}
@@ -0,0 +1,211 @@
+//! The PRIMARY selection on Wayland through the wlr-data-control protocol
+//! (`wayland-data-control` feature), which wlroots compositors (Sway,
+//! Hyprland, river...) and KDE offer to clipboard managers. Regular Wayland
+//! clients only see selections while they have the keyboard focus.
+
+use std::collections::HashMap;
+use std::io::Read;
+use std::os::fd::{AsFd, AsRawFd, FromRawFd, OwnedFd};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+use wayland_client::backend::ObjectId;
+use wayland_client::globals::{registry_queue_init, GlobalListContents};
+use wayland_client::protocol::{wl_registry, wl_seat::WlSeat};
+use wayland_client::{event_created_child, Connection, Dispatch, Proxy, QueueHandle};
+use wayland_protocols_wlr::data_control::v1::client::zwlr_data_control_device_v1::{
+    self, ZwlrDataControlDeviceV1,
+};
+use wayland_protocols_wlr::data_control::v1::client::zwlr_data_control_manager_v1::ZwlrDataControlManagerV1;
+use wayland_protocols_wlr::data_control::v1::client::zwlr_data_control_offer_v1::{
+    self, ZwlrDataControlOfferV1,
+};
+
+use crate::GetTextError;
+
+/// Text types in order of preference.
+const TEXT_TYPES: &[&str] = &["text/plain;charset=utf-8", "UTF8_STRING", "text/plain", "STRING", "TEXT"];
+
+/// What the last read returned, to tell a new selection from a stale one.
+static LAST_READ: Mutex<Option<String>> = Mutex::new(None);
+
+fn wayland_error(error: impl std::fmt::Display) -> GetTextError {
+    GetTextError::Os(format!("Wayland: {}", error))
+}
+
+#[derive(Default)]
+struct State {
+    /// The types each offer announced, by offer.
+    offered_types: HashMap<ObjectId, Vec<String>>,
+    primary: Option<ZwlrDataControlOfferV1>,
+}
+
+impl Dispatch<wl_registry::WlRegistry, GlobalListContents> for State {
+    fn event(
+        _: &mut Self,
+        _: &wl_registry::WlRegistry,
+        _: wl_registry::Event,
+        _: &GlobalListContents,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<WlSeat, ()> for State {
+    fn event(_: &mut Self, _: &WlSeat, _: <WlSeat as Proxy>::Event, _: &(), _: &Connection, _: &QueueHandle<Self>) {}
+}
+
+impl Dispatch<ZwlrDataControlManagerV1, ()> for State {
+    fn event(
+        _: &mut Self,
+        _: &ZwlrDataControlManagerV1,
+        _: <ZwlrDataControlManagerV1 as Proxy>::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<ZwlrDataControlDeviceV1, ()> for State {
+    fn event(
+        state: &mut Self,
+        _: &ZwlrDataControlDeviceV1,
+        event: zwlr_data_control_device_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        match event {
+            zwlr_data_control_device_v1::Event::DataOffer { id } => {
+                state.offered_types.insert(id.id(), Vec::new());
+            }
+            zwlr_data_control_device_v1::Event::PrimarySelection { id } => state.primary = id,
+            _ => {}
+        }
+    }
+
+    event_created_child!(State, ZwlrDataControlDeviceV1, [
+        zwlr_data_control_device_v1::EVT_DATA_OFFER_OPCODE => (ZwlrDataControlOfferV1, ()),
+    ]);
+}
+
+impl Dispatch<ZwlrDataControlOfferV1, ()> for State {
+    fn event(
+        state: &mut Self,
+        offer: &ZwlrDataControlOfferV1,
+        event: zwlr_data_control_offer_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        if let zwlr_data_control_offer_v1::Event::Offer { mime_type } = event {
+            state.offered_types.entry(offer.id()).or_default().push(mime_type);
+        }
+    }
+}
+
+/// Whether the compositor offers wlr-data-control with PRIMARY support
+/// (version 2). Checked once per process.
+pub(crate) fn available() -> bool {
+    static AVAILABLE: OnceLock<bool> = OnceLock::new();
+    *AVAILABLE.get_or_init(|| {
+        let Ok(connection) = Connection::connect_to_env() else {
+            return false;
+        };
+        let Ok((globals, queue)) = registry_queue_init::<State>(&connection) else {
+            return false;
+        };
+        globals.bind::<ZwlrDataControlManagerV1, _, _>(&queue.handle(), 2..=2, ()).is_ok()
+    })
+}
+
+/// Reads from `pipe` until the writer closes it or `deadline` passes.
+fn read_pipe(pipe: OwnedFd, deadline: Instant) -> Result<Vec<u8>, GetTextError> {
+    let mut file = std::fs::File::from(pipe);
+    let mut bytes = Vec::new();
+    let mut chunk = [0u8; 8192];
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(GetTextError::Timeout);
+        }
+        let mut poll_fd = libc::pollfd {
+            fd: file.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        let timeout_ms = remaining.as_millis().clamp(1, i32::MAX as u128) as i32;
+        if unsafe { libc::poll(&mut poll_fd, 1, timeout_ms) } < 0 {
+            return Err(wayland_error(std::io::Error::last_os_error()));
+        }
+        if poll_fd.revents == 0 {
+            continue;
+        }
+        match file.read(&mut chunk).map_err(wayland_error)? {
+            0 => return Ok(bytes),
+            read => bytes.extend_from_slice(&chunk[..read]),
+        }
+    }
+}
+
+/// The text of the PRIMARY selection. Fails with `GetTextError::NoSelection`
+/// when there is none or it offers no text.
+pub(crate) fn primary_selection(timeout: Duration) -> Result<String, GetTextError> {
+    let deadline = Instant::now() + timeout;
+    let connection = Connection::connect_to_env().map_err(wayland_error)?;
+    let (globals, mut queue) = registry_queue_init::<State>(&connection).map_err(wayland_error)?;
+    let handle = queue.handle();
+    let manager = globals
+        .bind::<ZwlrDataControlManagerV1, _, _>(&handle, 2..=2, ())
+        .map_err(|_| GetTextError::Unimplemented)?;
+    let seat = globals.bind::<WlSeat, _, _>(&handle, 1..=1, ()).map_err(wayland_error)?;
+    let device = manager.get_data_device(&seat, &handle, ());
+
+    // The device announces the current selections right after it is created.
+    let mut state = State::default();
+    queue.roundtrip(&mut state).map_err(wayland_error)?;
+    let result = (|| {
+        let offer = state.primary.as_ref().ok_or(GetTextError::NoSelection)?;
+        let offered = state.offered_types.get(&offer.id()).cloned().unwrap_or_default();
+        let mime_type = TEXT_TYPES
+            .iter()
+            .find(|wanted| offered.iter().any(|offered| offered == *wanted))
+            .ok_or(GetTextError::NoSelection)?;
+        let mut fds = [0; 2];
+        if unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) } != 0 {
+            return Err(wayland_error(std::io::Error::last_os_error()));
+        }
+        let (read, write) = unsafe { (OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1])) };
+        offer.receive(mime_type.to_string(), write.as_fd());
+        connection.flush().map_err(wayland_error)?;
+        // Only the owner may hold the write end, or the read never ends.
+        drop(write);
+        let bytes = read_pipe(read, deadline)?;
+        Ok(String::from_utf8_lossy(&bytes).into_owned())
+    })();
+    if let Some(offer) = state.primary.take() {
+        offer.destroy();
+    }
+    device.destroy();
+    let _ = connection.flush();
+    result
+}
+
+/// `primary_selection()`, failing with `GetTextError::NoSelection` when it
+/// returns what the previous call did. Unlike on X11, nothing tells which
+/// app owns PRIMARY, and it keeps the last highlighted text after the user
+/// has moved on to another app; unchanged text is more likely stale than
+/// selected again. Changed text may still come from an app other than the
+/// focused one, which is why `Strategy::Primary` is opt-in on Wayland.
+pub(crate) fn changed_primary_selection(timeout: Duration) -> Result<String, GetTextError> {
+    let text = primary_selection(timeout)?;
+    let mut last_read = LAST_READ.lock();
+    if last_read.as_deref() == Some(text.as_str()) {
+        return Err(GetTextError::NoSelection);
+    }
+    *last_read = Some(text.clone());
+    Ok(text)
+}
@@ -0,0 +1,347 @@
+//! Reads X11 selections over a connection of the crate's own, the way a
+//! middle-click paste does: ask the owner to convert it into a property of
+//! a hidden window, then read that property. For PRIMARY nothing is typed
+//! and the clipboard is left alone; for CLIPBOARD this is how the targets
+//! of a simulated copy are listed and read without `xclip`.
+
+use std::os::unix::io::AsRawFd;
+use std::time::{Duration, Instant};
+
+use x11rb::connection::{Connection, RequestConnection};
+use x11rb::protocol::res::{self, ClientIdMask, ClientIdSpec, ConnectionExt as _};
+use x11rb::protocol::xproto::{
+    Atom, AtomEnum, ConnectionExt as _, CreateWindowAux, EventMask, Property, Timestamp, Window, WindowClass,
+};
+use x11rb::protocol::Event;
+use x11rb::rust_connection::RustConnection;
+
+use crate::GetTextError;
+
+/// Incremental transfers stop at this size rather than fill memory.
+const MAX_BYTES: usize = 16 * 1024 * 1024;
+
+fn x11_error(error: impl std::fmt::Display) -> GetTextError {
+    GetTextError::Os(format!("X11: {}", error))
+}
+
+fn intern(connection: &RustConnection, name: &[u8]) -> Result<Atom, GetTextError> {
+    Ok(connection.intern_atom(false, name).map_err(x11_error)?.reply().map_err(x11_error)?.atom)
+}
+
+/// Whether the crate can read selections this way: an X11 session, not
+/// Wayland, where only XWayland apps would be seen.
+pub(crate) fn available() -> bool {
+    std::env::var_os("DISPLAY").is_some() && std::env::var_os("WAYLAND_DISPLAY").is_none()
+}
+
+/// The process of the client that created `window`, through the X-Resource
+/// extension. `None` for remote clients and servers without the extension.
+fn client_process(connection: &RustConnection, window: Window) -> Option<u64> {
+    connection.extension_information(res::X11_EXTENSION_NAME).ok()??;
+    connection.res_query_version(1, 2).ok()?.reply().ok()?;
+    let spec = ClientIdSpec {
+        client: window,
+        mask: ClientIdMask::LOCAL_CLIENT_PID,
+    };
+    let ids = connection.res_query_client_ids(&[spec]).ok()?.reply().ok()?.ids;
+    ids.into_iter().find_map(|id| id.value.first().copied()).map(u64::from)
+}
+
+/// The next event matching `wanted`, waiting until `deadline`.
+fn wait_for<T>(
+    connection: &RustConnection,
+    deadline: Instant,
+    mut wanted: impl FnMut(Event) -> Option<T>,
+) -> Result<T, GetTextError> {
+    loop {
+        while let Some(event) = connection.poll_for_event().map_err(x11_error)? {
+            if let Some(found) = wanted(event) {
+                return Ok(found);
+            }
+        }
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(GetTextError::Timeout);
+        }
+        let mut poll_fd = libc::pollfd {
+            fd: connection.stream().as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        let timeout_ms = remaining.as_millis().clamp(1, i32::MAX as u128) as i32;
+        if unsafe { libc::poll(&mut poll_fd, 1, timeout_ms) } < 0 {
+            return Err(x11_error(std::io::Error::last_os_error()));
+        }
+    }
+}
+
+/// The text of PRIMARY. Fails with `GetTextError::NoSelection` when nothing
+/// owns it, and also when `focused_process` is given and PRIMARY belongs to
+/// another process, or to one X-Resource can't name: what another app
+/// highlighted earlier isn't the selection the caller is after.
+///
+/// An app keeps owning PRIMARY after its selection is cleared, so text the
+/// focused app itself highlighted earlier is still returned.
+///
+/// With `paste`, an owner that refuses or ignores the request is asked again
+/// the way a middle-click paste asks: a window of the crate's own is put
+/// under the pointer and middle-clicked, and PRIMARY is converted with the
+/// click's timestamp. Some toolkits only answer requests made for a real
+/// user event.
+pub(crate) fn read_primary(focused_process: Option<u64>, timeout: Duration, paste: bool) -> Result<String, GetTextError> {
+    if !available() {
+        return Err(GetTextError::Unimplemented);
+    }
+    let deadline = Instant::now() + timeout;
+    let requestor = Requestor::new()?;
+    let connection = &requestor.connection;
+    let owner = connection
+        .get_selection_owner(AtomEnum::PRIMARY.into())
+        .map_err(x11_error)?
+        .reply()
+        .map_err(x11_error)?
+        .owner;
+    if owner == x11rb::NONE {
+        return Err(GetTextError::NoSelection);
+    }
+    if let Some(focused) = focused_process {
+        match client_process(connection, owner) {
+            Some(owner_process) if owner_process == focused => {}
+            Some(owner_process) => {
+                crate::env_overrides::gst_log!("PRIMARY belongs to process {}, not the focused {}", owner_process, focused);
+                return Err(GetTextError::NoSelection);
+            }
+            None => {
+                crate::env_overrides::gst_log!("Couldn't tell which process owns PRIMARY");
+                return Err(GetTextError::NoSelection);
+            }
+        }
+    }
+
+    match primary_text(&requestor, x11rb::CURRENT_TIME, deadline) {
+        Err(GetTextError::NoSelection | GetTextError::Timeout) if paste => {
+            crate::env_overrides::gst_log!("The owner of PRIMARY didn't convert it, pasting it instead");
+            let deadline = Instant::now() + timeout;
+            let time = requestor.middle_click(deadline)?;
+            primary_text(&requestor, time, deadline)
+        }
+        result => result,
+    }
+}
+
+/// PRIMARY as text, converted at `time`.
+fn primary_text(requestor: &Requestor, time: Timestamp, deadline: Instant) -> Result<String, GetTextError> {
+    // Old toolkits only offer Latin-1 STRING.
+    for target in [intern(&requestor.connection, b"UTF8_STRING")?, AtomEnum::STRING.into()] {
+        let Some(bytes) = requestor.convert(AtomEnum::PRIMARY.into(), target, time, deadline)? else {
+            continue;
+        };
+        return Ok(if target == u32::from(AtomEnum::STRING) {
+            bytes.iter().map(|&byte| byte as char).collect()
+        } else {
+            String::from_utf8_lossy(&bytes).into_owned()
+        });
+    }
+    Err(GetTextError::NoSelection)
+}
+
+/// The targets the CLIPBOARD owner offers, by name. Empty when nothing owns
+/// the clipboard.
+pub(crate) fn clipboard_targets(timeout: Duration) -> Result<Vec<String>, GetTextError> {
+    let deadline = Instant::now() + timeout;
+    let requestor = Requestor::new()?;
+    let connection = &requestor.connection;
+    let clipboard = intern(connection, b"CLIPBOARD")?;
+    let targets = intern(connection, b"TARGETS")?;
+    let Some(bytes) = requestor.convert(clipboard, targets, x11rb::CURRENT_TIME, deadline)? else {
+        return Ok(Vec::new());
+    };
+    // A list of atoms, 32 bits each in the server's byte order, which is
+    // also ours since the connection is local.
+    let cookies = bytes
+        .chunks_exact(4)
+        .map(|atom| connection.get_atom_name(u32::from_ne_bytes([atom[0], atom[1], atom[2], atom[3]])))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(x11_error)?;
+    Ok(cookies
+        .into_iter()
+        .filter_map(|cookie| cookie.reply().ok())
+        .map(|reply| String::from_utf8_lossy(&reply.name).into_owned())
+        .collect())
+}
+
+/// The CLIPBOARD contents converted to `target`. `None` when nothing owns
+/// the clipboard or the owner refuses the target.
+pub(crate) fn read_clipboard_target(target: &str, timeout: Duration) -> Result<Option<Vec<u8>>, GetTextError> {
+    let deadline = Instant::now() + timeout;
+    let requestor = Requestor::new()?;
+    let clipboard = intern(&requestor.connection, b"CLIPBOARD")?;
+    let target = intern(&requestor.connection, target.as_bytes())?;
+    requestor.convert(clipboard, target, x11rb::CURRENT_TIME, deadline)
+}
+
+/// A connection of the crate's own with a hidden window that selection
+/// owners convert into.
+struct Requestor {
+    connection: RustConnection,
+    root: Window,
+    window: Window,
+    property: Atom,
+    incr: Atom,
+}
+
+impl Requestor {
+    fn new() -> Result<Self, GetTextError> {
+        let (connection, screen) = x11rb::connect(None).map_err(x11_error)?;
+        let window = connection.generate_id().map_err(x11_error)?;
+        let root = connection.setup().roots[screen].root;
+        connection
+            .create_window(
+                x11rb::COPY_DEPTH_FROM_PARENT,
+                window,
+                root,
+                0,
+                0,
+                1,
+                1,
+                0,
+                WindowClass::INPUT_ONLY,
+                x11rb::COPY_FROM_PARENT,
+                &CreateWindowAux::new().event_mask(EventMask::PROPERTY_CHANGE),
+            )
+            .map_err(x11_error)?;
+        let property = intern(&connection, b"GET_SELECTED_TEXT_SELECTION")?;
+        let incr = intern(&connection, b"INCR")?;
+        Ok(Requestor {
+            connection,
+            root,
+            window,
+            property,
+            incr,
+        })
+    }
+
+    /// Asks the owner of `selection` to convert it to `target` as of `time`
+    /// and reads the result. `None` when there is no owner or it refuses the
+    /// target.
+    fn convert(
+        &self,
+        selection: Atom,
+        target: Atom,
+        time: Timestamp,
+        deadline: Instant,
+    ) -> Result<Option<Vec<u8>>, GetTextError> {
+        let connection = &self.connection;
+        connection
+            .convert_selection(self.window, selection, target, self.property, time)
+            .map_err(x11_error)?;
+        connection.flush().map_err(x11_error)?;
+        let converted = wait_for(connection, deadline, |event| match event {
+            Event::SelectionNotify(notify) if notify.requestor == self.window => Some(notify.property != x11rb::NONE),
+            _ => None,
+        })?;
+        if !converted {
+            return Ok(None);
+        }
+        let reply = connection
+            .get_property(true, self.window, self.property, AtomEnum::ANY, 0, u32::MAX)
+            .map_err(x11_error)?
+            .reply()
+            .map_err(x11_error)?;
+        if reply.type_ == self.incr {
+            read_incremental(connection, self.window, self.property, deadline).map(Some)
+        } else {
+            Ok(Some(reply.value))
+        }
+    }
+
+    /// Middle-clicks a 1x1 window put under the pointer and returns the
+    /// click's timestamp. The window is gone again before this returns.
+    ///
+    /// Fails without clicking if the pointer has left the window by the time
+    /// it is mapped: the click would paste into whatever is under it.
+    fn middle_click(&self, deadline: Instant) -> Result<Timestamp, GetTextError> {
+        use enigo::{Button, Direction, Mouse};
+
+        let _sequence = crate::sequence::begin()?;
+        let connection = &self.connection;
+        let pointer = connection.query_pointer(self.root).map_err(x11_error)?.reply().map_err(x11_error)?;
+        let surface = connection.generate_id().map_err(x11_error)?;
+        connection
+            .create_window(
+                x11rb::COPY_DEPTH_FROM_PARENT,
+                surface,
+                self.root,
+                pointer.root_x,
+                pointer.root_y,
+                1,
+                1,
+                0,
+                WindowClass::INPUT_OUTPUT,
+                x11rb::COPY_FROM_PARENT,
+                // Override-redirect: no window manager frame, focus change or
+                // taskbar entry.
+                &CreateWindowAux::new()
+                    .override_redirect(1)
+                    .event_mask(EventMask::BUTTON_PRESS | EventMask::STRUCTURE_NOTIFY),
+            )
+            .map_err(x11_error)?;
+        let clicked = (|| {
+            connection.map_window(surface).map_err(x11_error)?;
+            connection.flush().map_err(x11_error)?;
+            wait_for(connection, deadline, |event| match event {
+                Event::MapNotify(notify) if notify.window == surface => Some(()),
+                _ => None,
+            })?;
+            let pointer = connection.query_pointer(self.root).map_err(x11_error)?.reply().map_err(x11_error)?;
+            if pointer.child != surface {
+                return Err(GetTextError::Input("the pointer moved off the window to middle-click".into()));
+            }
+            crate::side_effects::keystrokes_sent();
+            crate::utils::with_enigo(|enigo| enigo.button(Button::Middle, Direction::Click))?
+                .map_err(|e| GetTextError::Input(e.to_string()))?;
+            wait_for(connection, deadline, |event| match event {
+                Event::ButtonPress(press) if press.event == surface && press.detail == 2 => Some(press.time),
+                _ => None,
+            })
+        })();
+        let _ = connection.destroy_window(surface);
+        let _ = connection.flush();
+        clicked
+    }
+}
+
+/// Collects a selection sent in chunks (the INCR protocol): the owner
+/// writes each chunk once the previous one was deleted, and an empty one
+/// last.
+fn read_incremental(
+    connection: &RustConnection,
+    window: Window,
+    property: Atom,
+    deadline: Instant,
+) -> Result<Vec<u8>, GetTextError> {
+    let mut bytes = Vec::new();
+    loop {
+        wait_for(connection, deadline, |event| match event {
+            Event::PropertyNotify(notify)
+                if notify.window == window && notify.atom == property && notify.state == Property::NEW_VALUE =>
+            {
+                Some(())
+            }
+            _ => None,
+        })?;
+        let chunk = connection
+            .get_property(true, window, property, AtomEnum::ANY, 0, u32::MAX)
+            .map_err(x11_error)?
+            .reply()
+            .map_err(x11_error)?
+            .value;
+        if chunk.is_empty() {
+            return Ok(bytes);
+        }
+        if bytes.len() + chunk.len() > MAX_BYTES {
+            return Err(GetTextError::Other("The selection is too large".to_string()));
+        }
+        bytes.extend_from_slice(&chunk);
+    }
+}
@@ -0,0 +1,97 @@
+//! X11 selection-ownership events through the XFixes extension.
+//!
+//! An app that copies takes ownership of CLIPBOARD, and one that highlights
+//! text takes PRIMARY. XFixes reports each `SetSelectionOwner` as it
+//! happens, so a capture can read the clipboard the moment the app has
+//! answered the copy instead of after a fixed wait, and the watcher hears
+//! about new selections without polling.
+
+use std::os::unix::io::AsRawFd;
+use std::time::{Duration, Instant};
+
+use x11rb::connection::{Connection, RequestConnection};
+use x11rb::protocol::xfixes::{self, ConnectionExt as _, SelectionEventMask};
+use x11rb::protocol::xproto::{AtomEnum, ConnectionExt as _};
+use x11rb::protocol::Event;
+use x11rb::rust_connection::RustConnection;
+
+use crate::GetTextError;
+
+fn x11_error(error: impl std::fmt::Display) -> GetTextError {
+    GetTextError::Os(format!("X11: {}", error))
+}
+
+/// Whether this is a Wayland session, even when `WAYLAND_DISPLAY` was
+/// cleared for this process and only XWayland's `DISPLAY` is left.
+pub(crate) fn wayland_session() -> bool {
+    std::env::var_os("WAYLAND_DISPLAY").is_some()
+        || std::env::var("XDG_SESSION_TYPE").is_ok_and(|session| session.eq_ignore_ascii_case("wayland"))
+}
+
+/// Signals changes of a selection's owner from its creation until it is
+/// dropped.
+pub(crate) struct OwnerWaiter {
+    connection: RustConnection,
+}
+
+impl OwnerWaiter {
+    /// Watches CLIPBOARD, for a simulated copy.
+    pub(crate) fn clipboard() -> Result<Self, GetTextError> {
+        Self::new(Some(&b"CLIPBOARD"[..]))
+    }
+
+    /// Watches PRIMARY, for the user highlighting text.
+    pub(crate) fn primary() -> Result<Self, GetTextError> {
+        Self::new(None)
+    }
+
+    fn new(selection_name: Option<&[u8]>) -> Result<Self, GetTextError> {
+        // Under Wayland only XWayland apps would be seen.
+        if std::env::var_os("DISPLAY").is_none() || wayland_session() {
+            return Err(GetTextError::Unimplemented);
+        }
+        let (connection, screen) = x11rb::connect(None).map_err(x11_error)?;
+        if connection.extension_information(xfixes::X11_EXTENSION_NAME).map_err(x11_error)?.is_none() {
+            return Err(x11_error("the XFixes extension is missing"));
+        }
+        // XFixes refuses requests from clients that haven't negotiated a version.
+        connection.xfixes_query_version(5, 0).map_err(x11_error)?.reply().map_err(x11_error)?;
+        let selection = match selection_name {
+            Some(name) => connection.intern_atom(false, name).map_err(x11_error)?.reply().map_err(x11_error)?.atom,
+            None => AtomEnum::PRIMARY.into(),
+        };
+        let root = connection.setup().roots[screen].root;
+        connection
+            .xfixes_select_selection_input(root, selection, SelectionEventMask::SET_SELECTION_OWNER)
+            .map_err(x11_error)?
+            .check()
+            .map_err(x11_error)?;
+        Ok(OwnerWaiter { connection })
+    }
+
+    /// Whether the selection got a new owner within `timeout`.
+    pub(crate) fn wait(&self, timeout: Duration) -> bool {
+        let started = Instant::now();
+        loop {
+            match self.connection.poll_for_event() {
+                Ok(Some(Event::XfixesSelectionNotify(_))) => return true,
+                Ok(Some(_)) => continue,
+                Ok(None) => {}
+                Err(_) => return false,
+            }
+            let remaining = timeout.saturating_sub(started.elapsed());
+            if remaining.is_zero() {
+                return false;
+            }
+            let mut poll_fd = libc::pollfd {
+                fd: self.connection.stream().as_raw_fd(),
+                events: libc::POLLIN,
+                revents: 0,
+            };
+            let timeout_ms = remaining.as_millis().clamp(1, i32::MAX as u128) as i32;
+            if unsafe { libc::poll(&mut poll_fd, 1, timeout_ms) } < 0 {
+                return false;
+            }
+        }
+    }
+}
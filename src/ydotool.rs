@@ -0,0 +1,42 @@
+//! Key presses through `ydotoold` (`ydotool` feature), for Wayland sessions
+//! where this process can't open `/dev/uinput` itself but a privileged daemon
+//! already has.
+
+use std::path::PathBuf;
+use std::process::Command;
+
+use crate::GetTextError;
+
+fn socket_path() -> PathBuf {
+    if let Some(path) = std::env::var_os("YDOTOOL_SOCKET") {
+        return PathBuf::from(path);
+    }
+    match std::env::var_os("XDG_RUNTIME_DIR") {
+        Some(dir) => PathBuf::from(dir).join(".ydotool_socket"),
+        None => PathBuf::from("/tmp/.ydotool_socket"),
+    }
+}
+
+/// Whether a `ydotoold` socket is there to talk to.
+pub(crate) fn available() -> bool {
+    socket_path().exists()
+}
+
+/// Presses `modifiers`+`key` (evdev key codes) with `ydotool key`.
+pub(crate) fn chord(modifiers: &[u16], key: u16) -> Result<(), GetTextError> {
+    let presses = modifiers.iter().chain([&key]).map(|code| format!("{}:1", code));
+    let releases = [&key].into_iter().chain(modifiers.iter().rev()).map(|code| format!("{}:0", code));
+    let output = Command::new("ydotool")
+        .env("YDOTOOL_SOCKET", socket_path())
+        .arg("key")
+        .args(presses.chain(releases))
+        .output()
+        .map_err(|e| GetTextError::InputUnavailable(format!("Failed to run ydotool: {}", e)))?;
+    if !output.status.success() {
+        return Err(GetTextError::Input(format!(
+            "ydotool failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(())
+}
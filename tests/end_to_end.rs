@@ -0,0 +1,157 @@
+//! Captures from a real window with a real accessibility tree: each case
+//! opens the test window in a child process with a known selection and checks
+//! what the crate reads from it.
+//!
+//! These need a desktop session and, on macOS, the accessibility permission
+//! for the test binary, so they only run with `GST_E2E=1`:
+//!
+//! ```sh
+//! GST_E2E=1 cargo test --test end_to_end
+//! ```
+//!
+//! On Linux, `--features headless-test` runs them without `GST_E2E` on a
+//! private Xvfb or headless Weston display (see `support/headless.rs`).
+
+mod support;
+
+use std::io::{BufRead, BufReader};
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use get_selected_text::{FailureReason, GetTextError};
+use support::test_window;
+
+const TEXT: &str = "The quick brown fox jumps over the lazy dog.";
+
+/// The test window, killed when dropped.
+struct Window(Child);
+
+impl Window {
+    /// Opens the window with the characters `start..end` of `text` selected,
+    /// and waits until it reports the selection set.
+    fn open(text: &str, start: usize, end: usize) -> Result<Window, String> {
+        let mut child = Command::new(std::env::current_exe().map_err(|e| e.to_string())?)
+            .env(test_window::ROLE_VAR, "1")
+            .env(test_window::TEXT_VAR, text)
+            .env(test_window::SELECT_VAR, format!("{}..{}", start, end))
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("failed to start the test window: {}", e))?;
+        let stdout = child.stdout.take().expect("stdout is piped");
+        let window = Window(child);
+
+        let (ready, wait) = mpsc::channel();
+        thread::spawn(move || {
+            let ready_line = BufReader::new(stdout).lines().map_while(Result::ok).any(|line| line == "ready");
+            let _ = ready.send(ready_line);
+        });
+        match wait.recv_timeout(Duration::from_secs(10)) {
+            Ok(true) => {}
+            _ => return Err("the test window didn't come up".to_string()),
+        }
+        // Let the window manager finish activating it.
+        thread::sleep(Duration::from_millis(500));
+        Ok(window)
+    }
+}
+
+impl Drop for Window {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+fn selected_text() -> Result<(), String> {
+    let _window = Window::open(TEXT, 10, 19)?;
+    let text = get_selected_text::get_selected_text().map_err(|e| e.to_string())?;
+    if text != "brown fox" {
+        return Err(format!("read {:?}", text));
+    }
+    Ok(())
+}
+
+fn context_contains_selection() -> Result<(), String> {
+    let _window = Window::open(TEXT, 4, 9)?;
+    let (text, context) = get_selected_text::get_selected_text_with_context().map_err(|e| e.to_string())?;
+    if text != "quick" {
+        return Err(format!("read {:?}", text));
+    }
+    match context {
+        Some(context) if context.contains("The quick brown") => Ok(()),
+        context => Err(format!("context {:?}", context)),
+    }
+}
+
+fn clipboard_restored() -> Result<(), String> {
+    let mut clipboard = arboard::Clipboard::new().map_err(|e| e.to_string())?;
+    clipboard.set_text("clipboard before capture").map_err(|e| e.to_string())?;
+    let _window = Window::open(TEXT, 0, 3)?;
+    get_selected_text::get_selected_text().map_err(|e| e.to_string())?;
+    match clipboard.get_text() {
+        Ok(text) if text == "clipboard before capture" => Ok(()),
+        other => Err(format!("clipboard holds {:?}", other)),
+    }
+}
+
+fn no_selection() -> Result<(), String> {
+    let _window = Window::open(TEXT, 5, 5)?;
+    match get_selected_text::get_selected_text() {
+        Ok(text) if text.is_empty() => Ok(()),
+        Ok(text) => Err(format!("read {:?}", text)),
+        // Reporting no selection as an error is fine too, as long as it says so.
+        Err(GetTextError::NoSelection) => Ok(()),
+        Err(GetTextError::AllStrategiesFailed(log))
+            if log
+                .attempts()
+                .iter()
+                .all(|attempt| matches!(attempt.reason, FailureReason::Empty | FailureReason::Unsupported)) =>
+        {
+            Ok(())
+        }
+        Err(e) => Err(format!("failed with {:?}", e)),
+    }
+}
+
+fn main() {
+    if std::env::var_os(test_window::ROLE_VAR).is_some() {
+        test_window::run();
+        return;
+    }
+    let headless = cfg!(all(target_os = "linux", feature = "headless-test"));
+    if std::env::var_os("GST_E2E").is_none() && !headless {
+        eprintln!("end_to_end: skipped, set GST_E2E=1 to run against a real window");
+        return;
+    }
+    #[cfg(all(target_os = "linux", feature = "headless-test"))]
+    let _display = match support::headless::start() {
+        Ok(display) => display,
+        Err(message) => {
+            println!("end_to_end: no display: {}", message);
+            std::process::exit(1);
+        }
+    };
+
+    let cases: &[(&str, fn() -> Result<(), String>)] = &[
+        ("selected_text", selected_text),
+        ("context_contains_selection", context_contains_selection),
+        ("clipboard_restored", clipboard_restored),
+        ("no_selection", no_selection),
+    ];
+    let mut failed = 0;
+    for (name, case) in cases {
+        match case() {
+            Ok(()) => println!("test {} ... ok", name),
+            Err(message) => {
+                println!("test {} ... FAILED: {}", name, message);
+                failed += 1;
+            }
+        }
+    }
+    println!("\n{} passed, {} failed", cases.len() - failed, failed);
+    if failed > 0 {
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,73 @@
+//! A private display server for the end-to-end tests (`headless-test`
+//! feature), so they run in CI and containers without a desktop session.
+//!
+//! Starts `Xvfb` when neither `DISPLAY` nor `WAYLAND_DISPLAY` is set, or
+//! headless Weston with `GST_HEADLESS=weston`. Under Weston, key presses go
+//! through uinput, so `/dev/uinput` has to be writable (e.g. passed into the
+//! container with `--device /dev/uinput`).
+
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// The display server, stopped when dropped.
+pub struct Display(Child);
+
+impl Drop for Display {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+fn wait_for(path: PathBuf) -> Result<(), String> {
+    let started = Instant::now();
+    while !path.exists() {
+        if started.elapsed() > Duration::from_secs(10) {
+            return Err(format!("{} didn't appear", path.display()));
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+    Ok(())
+}
+
+fn spawn(program: &str, args: &[&str]) -> Result<Display, String> {
+    Command::new(program)
+        .args(args)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map(Display)
+        .map_err(|e| format!("failed to start {}: {}", program, e))
+}
+
+/// Starts a display server unless the tests already have one, and points
+/// this process and the test windows it spawns at it.
+pub fn start() -> Result<Option<Display>, String> {
+    if std::env::var("GST_HEADLESS").as_deref() == Ok("weston") {
+        let runtime_dir = std::env::var_os("XDG_RUNTIME_DIR")
+            .map(PathBuf::from)
+            .unwrap_or_else(std::env::temp_dir);
+        let display = spawn(
+            "weston",
+            &["--backend=headless-backend.so", "--socket=gst-test", "--idle-time=0"],
+        )?;
+        wait_for(runtime_dir.join("gst-test"))?;
+        std::env::set_var("XDG_RUNTIME_DIR", &runtime_dir);
+        std::env::set_var("WAYLAND_DISPLAY", "gst-test");
+        std::env::remove_var("DISPLAY");
+        return Ok(Some(display));
+    }
+    if std::env::var_os("DISPLAY").is_some() || std::env::var_os("WAYLAND_DISPLAY").is_some() {
+        return Ok(None);
+    }
+    let number = std::env::var("GST_HEADLESS_DISPLAY").unwrap_or_else(|_| "99".to_string());
+    let display = spawn(
+        "Xvfb",
+        &[&format!(":{}", number), "-screen", "0", "1280x800x24", "-nolisten", "tcp"],
+    )?;
+    wait_for(PathBuf::from(format!("/tmp/.X11-unix/X{}", number)))?;
+    std::env::set_var("DISPLAY", format!(":{}", number));
+    Ok(Some(display))
+}
@@ -0,0 +1,3 @@
+#[cfg(all(target_os = "linux", feature = "headless-test"))]
+pub mod headless;
+pub mod test_window;
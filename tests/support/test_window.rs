@@ -0,0 +1,76 @@
+//! A text box whose content and selection the end-to-end tests choose, run in
+//! a child process so that the captures under test come from another app.
+//!
+//! The window takes its text from `GST_TEST_WINDOW_TEXT` and selects the
+//! characters `GST_TEST_WINDOW_SELECT` (`start..end`). It prints `ready` once
+//! the selection is set and the text box is focused.
+
+use std::io::Write;
+
+use eframe::egui;
+use egui::text::{CCursor, CCursorRange};
+
+pub const ROLE_VAR: &str = "GST_TEST_WINDOW";
+pub const TEXT_VAR: &str = "GST_TEST_WINDOW_TEXT";
+pub const SELECT_VAR: &str = "GST_TEST_WINDOW_SELECT";
+
+struct TestWindow {
+    text: String,
+    selection: (usize, usize),
+    ready: bool,
+}
+
+impl eframe::App for TestWindow {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        egui::CentralPanel::default().show(ctx, |ui| {
+            let mut output = egui::TextEdit::multiline(&mut self.text)
+                .id(egui::Id::new("text"))
+                .desired_width(f32::INFINITY)
+                .show(ui);
+            if !self.ready {
+                let (start, end) = self.selection;
+                output
+                    .state
+                    .cursor
+                    .set_char_range(Some(CCursorRange::two(CCursor::new(start), CCursor::new(end))));
+                output.state.store(ctx, output.response.id);
+                output.response.request_focus();
+                self.ready = true;
+                println!("ready");
+                let _ = std::io::stdout().flush();
+            }
+        });
+    }
+}
+
+/// Runs the window until the parent test kills it.
+pub fn run() {
+    let text = std::env::var(TEXT_VAR).unwrap_or_default();
+    let selection = std::env::var(SELECT_VAR)
+        .ok()
+        .and_then(|range| {
+            let (start, end) = range.split_once("..")?;
+            Some((start.parse().ok()?, end.parse().ok()?))
+        })
+        .unwrap_or((0, text.chars().count()));
+    let options = eframe::NativeOptions {
+        viewport: egui::ViewportBuilder::default()
+            .with_title("get-selected-text test window")
+            .with_inner_size([480.0, 240.0])
+            .with_always_on_top()
+            .with_active(true),
+        ..Default::default()
+    };
+    eframe::run_native(
+        "get-selected-text test window",
+        options,
+        Box::new(move |_| {
+            Ok(Box::new(TestWindow {
+                text,
+                selection,
+                ready: false,
+            }))
+        }),
+    )
+    .expect("failed to open the test window");
+}